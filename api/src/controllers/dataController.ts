@@ -9,6 +9,35 @@ import { simulateUserPositionGetter } from '../utils/pairSimulation';
 import { SimulationResult } from '../types/pairTypes';
 import { loadOmnipairIdl } from '../config/idl-loader';
 
+/**
+ * Fee of a swap in its input token. Swaps indexed before the exact fee columns
+ * (migration 020) may have no fee stored; their fee is derived from the pair's
+ * swap_fee_bps, as the indexer used to.
+ */
+const SWAP_INPUT_FEE_SQL = `COALESCE(
+  CASE WHEN s.is_token0_in THEN s.fee_paid0 ELSE s.fee_paid1 END,
+  div(s.amount_in * (SELECT p.swap_fee_bps FROM pools p WHERE p.pair_address = s.pair), 10000)
+)`;
+
+/**
+ * Input token fees of a swap valued in token0 and token1: the fee itself in its
+ * own token, and fee_value_out (or, when missing, the fee converted at the
+ * post-swap reserve ratio) in the other.
+ */
+const SWAP_FEE_VALUES_SQL = `
+  SELECT
+    s.*,
+    CASE WHEN s.is_token0_in THEN f.fee_in ELSE 0 END AS input_fee0,
+    CASE WHEN s.is_token0_in THEN 0 ELSE f.fee_in END AS input_fee1,
+    CASE WHEN s.is_token0_in THEN f.fee_in
+      ELSE COALESCE(s.fee_value_out, div(f.fee_in * s.reserve0, NULLIF(s.reserve1, 0))) END AS fee_value0,
+    CASE WHEN s.is_token0_in
+      THEN COALESCE(s.fee_value_out, div(f.fee_in * s.reserve1, NULLIF(s.reserve0, 0)))
+      ELSE f.fee_in END AS fee_value1
+  FROM swaps s
+  CROSS JOIN LATERAL (SELECT ${SWAP_INPUT_FEE_SQL} AS fee_in) f
+`;
+
 /**
  * Split a position into two separate token positions:
  * 1. Position with collateral0 and debt1 (token0 collateral, token1 debt)
@@ -142,11 +171,11 @@ export class DataController {
     const result = await pool.query(`
       WITH weekly_stats AS (
         SELECT 
-          SUM(fee_paid0::numeric) as weekly_fee0,
-          SUM(fee_paid1::numeric) as weekly_fee1,
+          SUM(input_fee0) as weekly_fee0,
+          SUM(input_fee1) as weekly_fee1,
           AVG(reserve0::numeric) as avg_reserve0,
           AVG(reserve1::numeric) as avg_reserve1
-        FROM swaps 
+        FROM (${SWAP_FEE_VALUES_SQL}) swaps
         WHERE timestamp > to_timestamp($1) 
           AND reserve0 > 0 
           AND reserve1 > 0
@@ -180,8 +209,10 @@ export class DataController {
       const token0APR = avgReserve0 > 0 ? (dailyFee0 / (avgReserve0 * 2)) * 365 * 100 : 0;
       const token1APR = avgReserve1 > 0 ? (dailyFee1 / (avgReserve1 * 2)) * 365 * 100 : 0;
 
+      // Each swap pays its fee in its input token only, so fee0 and fee1 are disjoint
+      // and the pool APR is their sum
       aprData = {
-        apr: (token0APR + token1APR) / 2,
+        apr: token0APR + token1APR,
         apr_breakdown: {
           token0_apr: token0APR,
           token1_apr: token1APR
@@ -196,6 +227,7 @@ export class DataController {
   }
 
   // Helper function to calculate total fees paid for a given pair address and time period
+  // Each total is the value of all swap fees in that token, whichever token they were paid in
   private static async calculateTotalFeesPaid(pairAddress: string, hours?: number): Promise<{
     total_fee_paid_in_token0: string;
     total_fee_paid_in_token1: string;
@@ -219,9 +251,9 @@ export class DataController {
       
       query = `
         SELECT 
-          SUM(fee_paid0::numeric) as total_fee_paid0,
-          SUM(fee_paid1::numeric) as total_fee_paid1
-        FROM swaps 
+          SUM(fee_value0) as total_fee_paid0,
+          SUM(fee_value1) as total_fee_paid1
+        FROM (${SWAP_FEE_VALUES_SQL}) swaps
         WHERE timestamp > to_timestamp($1) AND pair = $2
       `;
       queryParams = [timestamp, pairAddress];
@@ -230,9 +262,9 @@ export class DataController {
       // No time limit - get all fees
       query = `
         SELECT 
-          SUM(fee_paid0::numeric) as total_fee_paid0,
-          SUM(fee_paid1::numeric) as total_fee_paid1
-        FROM swaps 
+          SUM(fee_value0) as total_fee_paid0,
+          SUM(fee_value1) as total_fee_paid1
+        FROM (${SWAP_FEE_VALUES_SQL}) swaps
         WHERE pair = $1
       `;
       queryParams = [pairAddress];
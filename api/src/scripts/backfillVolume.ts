@@ -243,9 +243,10 @@ async function main(): Promise<void> {
         }
       }
 
+      // By row: a transaction can hold several swaps (multi-hop routes)
       await pool.query(
-        'UPDATE swaps SET volume_usd = $1 WHERE tx_sig = $2',
-        [volumeUsd, row.tx_sig]
+        'UPDATE swaps SET volume_usd = $1 WHERE id = $2 AND "timestamp" = $3',
+        [volumeUsd, row.id, row.timestamp]
       );
 
       processed++;
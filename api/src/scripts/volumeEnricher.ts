@@ -4,9 +4,12 @@
  * A long-running process that listens for new swap INSERTs via PostgreSQL NOTIFY,
  * fetches USD token prices from Jupiter Price API V3, and UPDATEs the swap with volume_usd.
  * 
- * The GRPC server holds INSERT notifications waiting for this enrichment (configurable
- * timeout via GRPC_DEDUP_TIMEOUT_SECS, default 5s). Once the UPDATE is done, the GRPC
- * emits the enriched swap to clients.
+ * The indexer now inserts swaps with volume_usd computed from its configured USD prices
+ * ([pricing] in the indexer config), and the GRPC server streams those as inserted. This
+ * sidecar only fills volume_usd of swaps inserted without it, i.e. whose tokens have no
+ * configured USD price. The GRPC server holds such swaps waiting for this enrichment
+ * (configurable timeout via GRPC_DEDUP_TIMEOUT_SECS, default 5s). Its UPDATE is notified
+ * with op 'VOLUME' (migration 022) and the GRPC server emits the enriched swap to clients.
  * 
  * Usage:
  *   npx ts-node api/src/scripts/volumeEnricher.ts
@@ -233,6 +236,7 @@ interface SwapNotification {
   is_token0_in: boolean;
   amount_in: string;
   amount_out: string;
+  timestamp: string;
   tx_sig: string;
   volume_usd: string;
 }
@@ -267,10 +271,10 @@ async function handleSwapNotification(payload: string): Promise<void> {
       notification.amount_out
     );
 
-    // UPDATE the swap with volume_usd
+    // UPDATE this swap only: a transaction can hold several swaps (multi-hop routes)
     await pool.query(
-      'UPDATE swaps SET volume_usd = $1 WHERE tx_sig = $2',
-      [volumeUsd, txSig]
+      'UPDATE swaps SET volume_usd = $1 WHERE id = $2 AND "timestamp" = $3',
+      [volumeUsd, notification.id, notification.timestamp]
     );
 
     console.log(
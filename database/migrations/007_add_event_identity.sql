@@ -0,0 +1,136 @@
+-- ============================================================================
+-- Migration: Identify events per instruction instead of per transaction
+-- ============================================================================
+-- Description: A single transaction can emit the same event more than once
+--              (multi-hop swaps, several liquidations in one transaction).
+--              This migration adds instruction_index and
+--              inner_instruction_index to every event table and makes
+--              (signature, instruction_index, inner_instruction_index) the
+--              idempotency key.
+--
+--              instruction_index       index of the top-level instruction
+--              inner_instruction_index 1-based position within the inner
+--                                      instructions of that top-level
+--                                      instruction, 0 for the top-level
+--                                      instruction itself
+--
+--              Hypertable unique constraints must include the partitioning
+--              column, so "timestamp" stays part of the key there. Event
+--              timestamps are the block time of the transaction, so they are
+--              stable across re-indexing.
+--
+-- Prerequisites:
+--   - Migrations 001 through 006 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 007_add_event_identity.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Swaps
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE swaps
+ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN inner_instruction_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE swaps DROP CONSTRAINT IF EXISTS tx_sig;
+ALTER TABLE swaps
+ADD CONSTRAINT swaps_event_identity_key
+UNIQUE (tx_sig, instruction_index, inner_instruction_index, "timestamp");
+
+-- ----------------------------------------------------------------------------
+-- Adjust liquidity
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE adjust_liquidity
+ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN inner_instruction_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE adjust_liquidity DROP CONSTRAINT IF EXISTS adjust_liquidity_tx_sig_timestamp_key;
+ALTER TABLE adjust_liquidity
+ADD CONSTRAINT adjust_liquidity_event_identity_key
+UNIQUE (tx_sig, instruction_index, inner_instruction_index, "timestamp");
+
+-- ----------------------------------------------------------------------------
+-- User LP position updated events (previously had no transaction reference)
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE user_lp_position_updated_events
+ADD COLUMN tx_sig VARCHAR(88),
+ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN inner_instruction_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE user_lp_position_updated_events
+ADD CONSTRAINT user_lp_position_updated_events_event_identity_key
+UNIQUE (tx_sig, instruction_index, inner_instruction_index, "timestamp");
+
+-- ----------------------------------------------------------------------------
+-- User position updated events
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE user_position_updated_events
+ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN inner_instruction_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE user_position_updated_events DROP CONSTRAINT IF EXISTS user_position_updated_events_transaction_signature_key;
+ALTER TABLE user_position_updated_events
+ADD CONSTRAINT user_position_updated_events_event_identity_key
+UNIQUE (transaction_signature, instruction_index, inner_instruction_index);
+
+-- ----------------------------------------------------------------------------
+-- User position liquidated events
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE user_position_liquidated_events
+ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN inner_instruction_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE user_position_liquidated_events DROP CONSTRAINT IF EXISTS user_position_liquidated_events_transaction_signature_key;
+ALTER TABLE user_position_liquidated_events
+ADD CONSTRAINT user_position_liquidated_events_event_identity_key
+UNIQUE (transaction_signature, instruction_index, inner_instruction_index);
+
+-- ----------------------------------------------------------------------------
+-- Adjust collateral events
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE adjust_collateral_events
+ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN inner_instruction_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE adjust_collateral_events DROP CONSTRAINT IF EXISTS adjust_collateral_events_transaction_signature_key;
+ALTER TABLE adjust_collateral_events
+ADD CONSTRAINT adjust_collateral_events_event_identity_key
+UNIQUE (transaction_signature, instruction_index, inner_instruction_index);
+
+-- ----------------------------------------------------------------------------
+-- Adjust debt events
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE adjust_debt_events
+ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0,
+ADD COLUMN inner_instruction_index INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE adjust_debt_events DROP CONSTRAINT IF EXISTS adjust_debt_events_transaction_signature_key;
+ALTER TABLE adjust_debt_events
+ADD CONSTRAINT adjust_debt_events_event_identity_key
+UNIQUE (transaction_signature, instruction_index, inner_instruction_index);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX IF NOT EXISTS idx_swaps_tx_sig ON swaps USING btree (tx_sig);
+CREATE INDEX IF NOT EXISTS idx_user_lp_position_updated_events_tx_sig ON user_lp_position_updated_events USING btree (tx_sig);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 007 completed successfully';
+    RAISE NOTICE 'Added instruction_index, inner_instruction_index to: swaps, adjust_liquidity, user_lp_position_updated_events, user_position_updated_events, user_position_liquidated_events, adjust_collateral_events, adjust_debt_events';
+    RAISE NOTICE 'Event identity is now (signature, instruction_index, inner_instruction_index)';
+END $$;
@@ -0,0 +1,86 @@
+-- ============================================================================
+-- Migration: Add pair state time series from UpdatePairEvent
+-- ============================================================================
+-- Description: This migration adds the pair_state_updates hypertable, written
+--              from every UpdatePairEvent (EMA prices, rates, accrued interest,
+--              cash reserves and reserves after interest), and the pair_states
+--              table holding the latest state per pair.
+--
+-- Prerequisites:
+--   - Migrations 001 through 007 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 008_add_pair_state_updates.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Pair state updates table (time-series)
+CREATE TABLE pair_state_updates (
+    id BIGSERIAL,
+    pair VARCHAR(44) NOT NULL,
+    signer VARCHAR(44) NOT NULL,
+    price0_ema NUMERIC NOT NULL,
+    price1_ema NUMERIC NOT NULL,
+    rate0 NUMERIC NOT NULL,
+    rate1 NUMERIC NOT NULL,
+    accrued_interest0 NUMERIC NOT NULL,
+    accrued_interest1 NUMERIC NOT NULL,
+    cash_reserve0 NUMERIC NOT NULL,
+    cash_reserve1 NUMERIC NOT NULL,
+    reserve0_after_interest NUMERIC NOT NULL,
+    reserve1_after_interest NUMERIC NOT NULL,
+    tx_sig VARCHAR(88) NOT NULL,
+    instruction_index INTEGER NOT NULL DEFAULT 0,
+    inner_instruction_index INTEGER NOT NULL DEFAULT 0,
+    slot BIGINT NOT NULL,
+    "timestamp" TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (id, "timestamp"),
+    CONSTRAINT pair_state_updates_event_identity_key UNIQUE (tx_sig, instruction_index, inner_instruction_index, "timestamp")
+);
+
+-- Pair states table (latest state per pair)
+CREATE TABLE pair_states (
+    pair VARCHAR(44) PRIMARY KEY,
+    price0_ema NUMERIC NOT NULL,
+    price1_ema NUMERIC NOT NULL,
+    rate0 NUMERIC NOT NULL,
+    rate1 NUMERIC NOT NULL,
+    accrued_interest0 NUMERIC NOT NULL,
+    accrued_interest1 NUMERIC NOT NULL,
+    cash_reserve0 NUMERIC NOT NULL,
+    cash_reserve1 NUMERIC NOT NULL,
+    reserve0_after_interest NUMERIC NOT NULL,
+    reserve1_after_interest NUMERIC NOT NULL,
+    tx_sig VARCHAR(88) NOT NULL,
+    slot BIGINT NOT NULL,
+    event_timestamp TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- ----------------------------------------------------------------------------
+-- Convert tables to TimescaleDB Hypertables
+-- ----------------------------------------------------------------------------
+
+SELECT create_hypertable('pair_state_updates', 'timestamp', chunk_time_interval => INTERVAL '7 days', if_not_exists => TRUE);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX pair_state_updates_timestamp_idx ON pair_state_updates USING btree ("timestamp" DESC);
+CREATE INDEX idx_pair_state_updates_pair_timestamp ON pair_state_updates USING btree (pair, "timestamp" DESC);
+CREATE INDEX idx_pair_state_updates_tx_sig ON pair_state_updates USING btree (tx_sig);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 008 completed successfully';
+    RAISE NOTICE 'Tables created: pair_state_updates, pair_states';
+    RAISE NOTICE 'TimescaleDB hypertables: pair_state_updates';
+END $$;
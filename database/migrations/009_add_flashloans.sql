@@ -0,0 +1,64 @@
+-- ============================================================================
+-- Migration: Add flashloans table
+-- ============================================================================
+-- Description: This migration adds the flashloans table, written from the
+--              Flashloan instruction together with the FlashloanEvent it
+--              emits. receiver_instructions holds the instructions executed by
+--              the receiver program while it held the loan, as a JSON array of
+--              { inner_instruction_index, stack_height, program_id, accounts,
+--              data (base58) }.
+--
+--              fee0/fee1 are NULL when no FlashloanEvent could be decoded for
+--              the instruction.
+--
+-- Prerequisites:
+--   - Migrations 001 through 008 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 009_add_flashloans.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Flashloans table
+CREATE TABLE flashloans (
+    id BIGSERIAL PRIMARY KEY,
+    pair VARCHAR(44) NOT NULL,
+    signer VARCHAR(44) NOT NULL,
+    receiver VARCHAR(44) NOT NULL,
+    receiver_program VARCHAR(44) NOT NULL,
+    amount0 NUMERIC NOT NULL,
+    amount1 NUMERIC NOT NULL,
+    fee0 NUMERIC,
+    fee1 NUMERIC,
+    receiver_instructions JSONB NOT NULL DEFAULT '[]'::jsonb,
+    transaction_signature VARCHAR(88) NOT NULL,
+    instruction_index INTEGER NOT NULL DEFAULT 0,
+    inner_instruction_index INTEGER NOT NULL DEFAULT 0,
+    slot BIGINT NOT NULL,
+    event_timestamp TIMESTAMPTZ NOT NULL,
+    CONSTRAINT flashloans_event_identity_key UNIQUE (transaction_signature, instruction_index, inner_instruction_index)
+);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_flashloans_pair ON flashloans USING btree (pair);
+CREATE INDEX idx_flashloans_signer ON flashloans USING btree (signer);
+CREATE INDEX idx_flashloans_receiver_program ON flashloans USING btree (receiver_program);
+CREATE INDEX idx_flashloans_tx_sig ON flashloans USING btree (transaction_signature);
+CREATE INDEX idx_flashloans_slot ON flashloans USING btree (slot);
+CREATE INDEX idx_flashloans_event_timestamp ON flashloans USING btree (event_timestamp);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 009 completed successfully';
+    RAISE NOTICE 'Tables created: flashloans';
+END $$;
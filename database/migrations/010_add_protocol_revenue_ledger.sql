@@ -0,0 +1,192 @@
+-- ============================================================================
+-- Migration: Add protocol revenue ledger
+-- ============================================================================
+-- Description: This migration adds the protocol revenue ledger:
+--
+--              protocol_fee_claims       one row per ClaimProtocolFeesEvent
+--              protocol_revenue_ledger   one row per claim and recipient
+--                                        (futarchy_treasury, buybacks_vault,
+--                                        team_treasury)
+--              swap_fee_accruals         swap fee of every SwapEvent
+--                                        (amount_in - amount_in_after_fee),
+--                                        denominated in the input token
+--
+--              and the reporting views:
+--
+--              pair_protocol_revenue           claimed totals and swap fees
+--                                              accrued since the last claim
+--                                              (unclaimed) per pair
+--              protocol_revenue_by_recipient   claimed totals per pair and
+--                                              recipient, with the recipient's
+--                                              share of the latest claim
+--
+-- Prerequisites:
+--   - Migrations 001 through 009 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 010_add_protocol_revenue_ledger.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Custom ENUM Types
+-- ----------------------------------------------------------------------------
+
+CREATE TYPE revenue_recipient AS ENUM (
+    'futarchy_treasury',
+    'buybacks_vault',
+    'team_treasury'
+);
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Protocol fee claims table
+CREATE TABLE protocol_fee_claims (
+    id BIGSERIAL PRIMARY KEY,
+    pair VARCHAR(44) NOT NULL,
+    signer VARCHAR(44) NOT NULL,
+    token0 VARCHAR(44) NOT NULL,
+    token1 VARCHAR(44) NOT NULL,
+    futarchy_treasury_amount0 NUMERIC NOT NULL,
+    futarchy_treasury_amount1 NUMERIC NOT NULL,
+    buybacks_vault_amount0 NUMERIC NOT NULL,
+    buybacks_vault_amount1 NUMERIC NOT NULL,
+    team_treasury_amount0 NUMERIC NOT NULL,
+    team_treasury_amount1 NUMERIC NOT NULL,
+    transaction_signature VARCHAR(88) NOT NULL,
+    instruction_index INTEGER NOT NULL DEFAULT 0,
+    inner_instruction_index INTEGER NOT NULL DEFAULT 0,
+    slot BIGINT NOT NULL,
+    event_timestamp TIMESTAMPTZ NOT NULL,
+    CONSTRAINT protocol_fee_claims_event_identity_key UNIQUE (transaction_signature, instruction_index, inner_instruction_index)
+);
+
+-- Protocol revenue ledger table (one entry per claim and recipient)
+CREATE TABLE protocol_revenue_ledger (
+    id BIGSERIAL PRIMARY KEY,
+    pair VARCHAR(44) NOT NULL,
+    recipient revenue_recipient NOT NULL,
+    token0 VARCHAR(44) NOT NULL,
+    token1 VARCHAR(44) NOT NULL,
+    amount0 NUMERIC NOT NULL,
+    amount1 NUMERIC NOT NULL,
+    transaction_signature VARCHAR(88) NOT NULL,
+    instruction_index INTEGER NOT NULL DEFAULT 0,
+    inner_instruction_index INTEGER NOT NULL DEFAULT 0,
+    slot BIGINT NOT NULL,
+    event_timestamp TIMESTAMPTZ NOT NULL,
+    CONSTRAINT protocol_revenue_ledger_entry_key UNIQUE (transaction_signature, instruction_index, inner_instruction_index, recipient)
+);
+
+-- Swap fee accruals table (one entry per swap)
+CREATE TABLE swap_fee_accruals (
+    id BIGSERIAL PRIMARY KEY,
+    pair VARCHAR(44) NOT NULL,
+    fee0 NUMERIC NOT NULL,
+    fee1 NUMERIC NOT NULL,
+    transaction_signature VARCHAR(88) NOT NULL,
+    instruction_index INTEGER NOT NULL DEFAULT 0,
+    inner_instruction_index INTEGER NOT NULL DEFAULT 0,
+    slot BIGINT NOT NULL,
+    event_timestamp TIMESTAMPTZ NOT NULL,
+    CONSTRAINT swap_fee_accruals_event_identity_key UNIQUE (transaction_signature, instruction_index, inner_instruction_index)
+);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_protocol_fee_claims_pair_slot ON protocol_fee_claims USING btree (pair, slot DESC);
+CREATE INDEX idx_protocol_fee_claims_event_timestamp ON protocol_fee_claims USING btree (event_timestamp);
+CREATE INDEX idx_protocol_revenue_ledger_pair_recipient ON protocol_revenue_ledger USING btree (pair, recipient);
+CREATE INDEX idx_protocol_revenue_ledger_event_timestamp ON protocol_revenue_ledger USING btree (event_timestamp);
+CREATE INDEX idx_swap_fee_accruals_pair_slot ON swap_fee_accruals USING btree (pair, slot);
+
+-- ----------------------------------------------------------------------------
+-- Views
+-- ----------------------------------------------------------------------------
+
+-- Claimed vs. unclaimed revenue per pair. Unclaimed swap fees are the fees
+-- accrued by swaps after the pair's latest claim.
+CREATE VIEW pair_protocol_revenue AS
+WITH claims AS (
+    SELECT
+        pair,
+        MAX(slot) AS last_claim_slot,
+        MAX(event_timestamp) AS last_claim_at,
+        COUNT(*) AS claim_count,
+        SUM(futarchy_treasury_amount0 + buybacks_vault_amount0 + team_treasury_amount0) AS claimed_amount0,
+        SUM(futarchy_treasury_amount1 + buybacks_vault_amount1 + team_treasury_amount1) AS claimed_amount1
+    FROM protocol_fee_claims
+    GROUP BY pair
+),
+fees AS (
+    SELECT
+        f.pair,
+        SUM(f.fee0) AS total_swap_fees0,
+        SUM(f.fee1) AS total_swap_fees1,
+        COALESCE(SUM(f.fee0) FILTER (WHERE c.last_claim_slot IS NULL OR f.slot > c.last_claim_slot), 0) AS unclaimed_swap_fees0,
+        COALESCE(SUM(f.fee1) FILTER (WHERE c.last_claim_slot IS NULL OR f.slot > c.last_claim_slot), 0) AS unclaimed_swap_fees1
+    FROM swap_fee_accruals f
+    LEFT JOIN claims c ON c.pair = f.pair
+    GROUP BY f.pair
+)
+SELECT
+    COALESCE(c.pair, f.pair) AS pair,
+    COALESCE(c.claim_count, 0) AS claim_count,
+    COALESCE(c.claimed_amount0, 0) AS claimed_amount0,
+    COALESCE(c.claimed_amount1, 0) AS claimed_amount1,
+    c.last_claim_slot,
+    c.last_claim_at,
+    COALESCE(f.total_swap_fees0, 0) AS total_swap_fees0,
+    COALESCE(f.total_swap_fees1, 0) AS total_swap_fees1,
+    COALESCE(f.unclaimed_swap_fees0, 0) AS unclaimed_swap_fees0,
+    COALESCE(f.unclaimed_swap_fees1, 0) AS unclaimed_swap_fees1
+FROM claims c
+FULL OUTER JOIN fees f ON f.pair = c.pair;
+
+-- Claimed revenue per pair and recipient, with the recipient's share of the
+-- pair's latest claim (used to apportion unclaimed revenue).
+CREATE VIEW protocol_revenue_by_recipient AS
+WITH latest_claims AS (
+    SELECT DISTINCT ON (pair)
+        pair, transaction_signature, instruction_index, inner_instruction_index
+    FROM protocol_fee_claims
+    ORDER BY pair, slot DESC, instruction_index DESC, inner_instruction_index DESC
+),
+latest_shares AS (
+    SELECT
+        l.pair,
+        l.recipient,
+        l.amount0 / NULLIF(SUM(l.amount0) OVER (PARTITION BY l.pair), 0) * 10000 AS latest_share0_bps,
+        l.amount1 / NULLIF(SUM(l.amount1) OVER (PARTITION BY l.pair), 0) * 10000 AS latest_share1_bps
+    FROM protocol_revenue_ledger l
+    JOIN latest_claims c
+        ON c.transaction_signature = l.transaction_signature
+        AND c.instruction_index = l.instruction_index
+        AND c.inner_instruction_index = l.inner_instruction_index
+)
+SELECT
+    l.pair,
+    l.recipient,
+    COUNT(*) AS claim_count,
+    SUM(l.amount0) AS claimed_amount0,
+    SUM(l.amount1) AS claimed_amount1,
+    MAX(l.event_timestamp) AS last_claim_at,
+    s.latest_share0_bps,
+    s.latest_share1_bps
+FROM protocol_revenue_ledger l
+LEFT JOIN latest_shares s ON s.pair = l.pair AND s.recipient = l.recipient
+GROUP BY l.pair, l.recipient, s.latest_share0_bps, s.latest_share1_bps;
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 010 completed successfully';
+    RAISE NOTICE 'Tables created: protocol_fee_claims, protocol_revenue_ledger, swap_fee_accruals';
+    RAISE NOTICE 'Views created: pair_protocol_revenue, protocol_revenue_by_recipient';
+END $$;
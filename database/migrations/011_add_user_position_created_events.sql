@@ -0,0 +1,105 @@
+-- ============================================================================
+-- Migration: Track user position creation
+-- ============================================================================
+-- Description: This migration adds user_position_created_events, written from
+--              UserPositionCreatedEvent, and links user_borrow_positions rows
+--              back to the creation record of their position through
+--              created_event_id. The user_position_lifecycle view combines
+--              creation, latest state and liquidations per position so that
+--              positions created but never funded are visible too.
+--
+-- Prerequisites:
+--   - Migrations 001 through 010 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 011_add_user_position_created_events.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- User position created events table
+CREATE TABLE user_position_created_events (
+    id BIGSERIAL PRIMARY KEY,
+    pair VARCHAR(44) NOT NULL,
+    owner VARCHAR(44) NOT NULL,
+    "position" VARCHAR(44) NOT NULL,
+    transaction_signature VARCHAR(88) NOT NULL,
+    instruction_index INTEGER NOT NULL DEFAULT 0,
+    inner_instruction_index INTEGER NOT NULL DEFAULT 0,
+    slot BIGINT NOT NULL,
+    event_timestamp TIMESTAMPTZ NOT NULL,
+    CONSTRAINT user_position_created_events_event_identity_key UNIQUE (transaction_signature, instruction_index, inner_instruction_index)
+);
+
+-- Link borrow positions to their creation record
+ALTER TABLE user_borrow_positions
+ADD COLUMN created_event_id BIGINT REFERENCES user_position_created_events (id) ON DELETE SET NULL;
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_user_position_created_events_position ON user_position_created_events USING btree ("position", slot DESC);
+CREATE INDEX idx_user_position_created_events_owner ON user_position_created_events USING btree (owner);
+CREATE INDEX idx_user_position_created_events_pair ON user_position_created_events USING btree (pair);
+CREATE INDEX idx_user_borrow_positions_created_event_id ON user_borrow_positions USING btree (created_event_id);
+
+-- ----------------------------------------------------------------------------
+-- Views
+-- ----------------------------------------------------------------------------
+
+-- Lifecycle of every position: opened -> updated -> liquidated/closed
+CREATE VIEW user_position_lifecycle AS
+WITH latest_creations AS (
+    SELECT DISTINCT ON ("position") *
+    FROM user_position_created_events
+    ORDER BY "position", slot DESC, id DESC
+),
+liquidations AS (
+    SELECT
+        "position",
+        COUNT(*) AS liquidation_count,
+        MAX(slot) AS last_liquidated_slot,
+        MAX(event_timestamp) AS last_liquidated_at
+    FROM user_position_liquidated_events
+    GROUP BY "position"
+)
+SELECT
+    COALESCE(c."position", b."position") AS "position",
+    COALESCE(c.pair, b.pair) AS pair,
+    COALESCE(c.owner, b.signer) AS owner,
+    c.id AS created_event_id,
+    c.transaction_signature AS created_tx_sig,
+    c.slot AS created_slot,
+    c.event_timestamp AS created_at,
+    b.collateral0,
+    b.collateral1,
+    b.debt0_shares,
+    b.debt1_shares,
+    b.slot AS last_updated_slot,
+    b.event_timestamp AS last_updated_at,
+    COALESCE(l.liquidation_count, 0) AS liquidation_count,
+    l.last_liquidated_at,
+    CASE
+        WHEN b."position" IS NULL THEN 'created'
+        WHEN l.last_liquidated_slot IS NOT NULL AND l.last_liquidated_slot >= b.slot THEN 'liquidated'
+        WHEN b.collateral0 = 0 AND b.collateral1 = 0 AND b.debt0_shares = 0 AND b.debt1_shares = 0 THEN 'closed'
+        ELSE 'open'
+    END AS status
+FROM latest_creations c
+FULL OUTER JOIN user_borrow_positions b ON b."position" = c."position"
+LEFT JOIN liquidations l ON l."position" = COALESCE(c."position", b."position");
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 011 completed successfully';
+    RAISE NOTICE 'Tables created: user_position_created_events';
+    RAISE NOTICE 'Added created_event_id to: user_borrow_positions';
+    RAISE NOTICE 'Views created: user_position_lifecycle';
+END $$;
@@ -0,0 +1,128 @@
+-- ============================================================================
+-- Migration: Add latest account state tables
+-- ============================================================================
+-- Description: This migration adds one table per Omnipair account type,
+--              holding the latest decoded state of every account received
+--              through account monitoring (RPC program subscribe):
+--
+--              pair_accounts                 Pair
+--              user_position_accounts        UserPosition
+--              rate_model_accounts           RateModel
+--              futarchy_authority_accounts   FutarchyAuthority
+--
+--              Every row carries the slot it was observed at. Upserts only
+--              apply when the incoming slot is not older than the stored one,
+--              so late or replayed updates never overwrite newer state.
+--
+-- Prerequisites:
+--   - Migrations 001 through 011 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 012_add_account_snapshots.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Pair accounts table (latest state per pair account)
+CREATE TABLE pair_accounts (
+    pubkey VARCHAR(44) PRIMARY KEY,
+    token0 VARCHAR(44) NOT NULL,
+    token1 VARCHAR(44) NOT NULL,
+    lp_mint VARCHAR(44) NOT NULL,
+    rate_model VARCHAR(44) NOT NULL,
+    swap_fee_bps INTEGER NOT NULL,
+    half_life BIGINT NOT NULL,
+    fixed_cf_bps INTEGER,
+    reserve0 NUMERIC NOT NULL,
+    reserve1 NUMERIC NOT NULL,
+    cash_reserve0 NUMERIC NOT NULL,
+    cash_reserve1 NUMERIC NOT NULL,
+    last_price0_ema_symmetric NUMERIC NOT NULL,
+    last_price0_ema_directional NUMERIC NOT NULL,
+    last_price1_ema_symmetric NUMERIC NOT NULL,
+    last_price1_ema_directional NUMERIC NOT NULL,
+    last_update NUMERIC NOT NULL,
+    last_rate0 NUMERIC NOT NULL,
+    last_rate1 NUMERIC NOT NULL,
+    total_debt0 NUMERIC NOT NULL,
+    total_debt1 NUMERIC NOT NULL,
+    total_debt0_shares NUMERIC NOT NULL,
+    total_debt1_shares NUMERIC NOT NULL,
+    total_supply NUMERIC NOT NULL,
+    total_collateral0 NUMERIC NOT NULL,
+    total_collateral1 NUMERIC NOT NULL,
+    token0_decimals INTEGER NOT NULL,
+    token1_decimals INTEGER NOT NULL,
+    params_hash BYTEA NOT NULL,
+    version INTEGER NOT NULL,
+    reduce_only BOOLEAN NOT NULL,
+    slot BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- User position accounts table (latest state per position account)
+CREATE TABLE user_position_accounts (
+    pubkey VARCHAR(44) PRIMARY KEY,
+    owner VARCHAR(44) NOT NULL,
+    pair VARCHAR(44) NOT NULL,
+    collateral0_liquidation_cf_bps INTEGER NOT NULL,
+    collateral1_liquidation_cf_bps INTEGER NOT NULL,
+    collateral0 NUMERIC NOT NULL,
+    collateral1 NUMERIC NOT NULL,
+    debt0_shares NUMERIC NOT NULL,
+    debt1_shares NUMERIC NOT NULL,
+    slot BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Rate model accounts table (latest state per rate model account)
+CREATE TABLE rate_model_accounts (
+    pubkey VARCHAR(44) PRIMARY KEY,
+    exp_rate NUMERIC NOT NULL,
+    target_util_start NUMERIC NOT NULL,
+    target_util_end NUMERIC NOT NULL,
+    half_life_ms NUMERIC NOT NULL,
+    min_rate NUMERIC NOT NULL,
+    max_rate NUMERIC NOT NULL,
+    initial_rate NUMERIC NOT NULL,
+    slot BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Futarchy authority accounts table (latest state per futarchy authority account)
+CREATE TABLE futarchy_authority_accounts (
+    pubkey VARCHAR(44) PRIMARY KEY,
+    version INTEGER NOT NULL,
+    authority VARCHAR(44) NOT NULL,
+    futarchy_treasury VARCHAR(44) NOT NULL,
+    buybacks_vault VARCHAR(44) NOT NULL,
+    team_treasury VARCHAR(44) NOT NULL,
+    swap_share_bps INTEGER NOT NULL,
+    interest_share_bps INTEGER NOT NULL,
+    futarchy_treasury_bps INTEGER NOT NULL,
+    buybacks_vault_bps INTEGER NOT NULL,
+    team_treasury_bps INTEGER NOT NULL,
+    global_reduce_only BOOLEAN NOT NULL,
+    slot BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_pair_accounts_rate_model ON pair_accounts USING btree (rate_model);
+CREATE INDEX idx_user_position_accounts_owner ON user_position_accounts USING btree (owner);
+CREATE INDEX idx_user_position_accounts_pair ON user_position_accounts USING btree (pair);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 012 completed successfully';
+    RAISE NOTICE 'Tables created: pair_accounts, user_position_accounts, rate_model_accounts, futarchy_authority_accounts';
+END $$;
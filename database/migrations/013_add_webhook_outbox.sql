@@ -0,0 +1,67 @@
+-- ============================================================================
+-- Migration: Add webhook outbox
+-- ============================================================================
+-- Description: This migration adds the webhook_outbox table used by the
+--              indexer notifier. Processors enqueue one row per event and
+--              configured endpoint; a background worker delivers due rows
+--              with HMAC-signed payloads, retries with exponential backoff
+--              and records the delivery status of every row.
+--
+-- Prerequisites:
+--   - Migrations 001 through 012 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 013_add_webhook_outbox.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Custom ENUM Types
+-- ----------------------------------------------------------------------------
+
+CREATE TYPE webhook_delivery_status AS ENUM (
+    'pending',
+    'delivering',
+    'delivered',
+    'failed'
+);
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Webhook outbox table
+CREATE TABLE webhook_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    -- signature:instruction_index:inner_instruction_index of the source event
+    event_key TEXT NOT NULL,
+    endpoint TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status webhook_delivery_status NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_status_code INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at TIMESTAMPTZ,
+    CONSTRAINT webhook_outbox_event_endpoint_key UNIQUE (event_key, event_type, endpoint)
+);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_webhook_outbox_due ON webhook_outbox USING btree (next_attempt_at, id) WHERE status = 'pending';
+CREATE INDEX idx_webhook_outbox_status ON webhook_outbox USING btree (status);
+CREATE INDEX idx_webhook_outbox_event_type ON webhook_outbox USING btree (event_type);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 013 completed successfully';
+    RAISE NOTICE 'Tables created: webhook_outbox';
+END $$;
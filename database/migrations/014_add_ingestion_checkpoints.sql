@@ -0,0 +1,40 @@
+-- ============================================================================
+-- Migration: Add ingestion checkpoints
+-- ============================================================================
+-- Description: This migration adds ingestion_checkpoints, recording the last
+--              fully processed transaction (slot and signature) per
+--              datasource. On startup the indexer crawls from the checkpoint
+--              of the live datasource up to the chain tip, so transactions
+--              that landed while it was down or reconnecting are not lost.
+--
+--              Checkpoints only move forward: an update with an older slot
+--              than the stored one is ignored.
+--
+-- Prerequisites:
+--   - Migrations 001 through 013 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 014_add_ingestion_checkpoints.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Ingestion checkpoints table (one row per datasource)
+CREATE TABLE ingestion_checkpoints (
+    datasource VARCHAR(64) PRIMARY KEY,
+    slot BIGINT NOT NULL,
+    signature VARCHAR(88) NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 014 completed successfully';
+    RAISE NOTICE 'Tables created: ingestion_checkpoints';
+END $$;
@@ -0,0 +1,47 @@
+-- ============================================================================
+-- Migration: Add processed transactions
+-- ============================================================================
+-- Description: This migration adds processed_transactions, recording every
+--              transaction the indexer has fully processed and the datasource
+--              it came from. When the Helius stream reports a slot gap, the
+--              indexer crawls the program signatures in that slot range and
+--              only fetches the ones missing from this table.
+--
+--              Only recent rows are needed for gap fills; older rows can be
+--              pruned by processed_at.
+--
+-- Prerequisites:
+--   - Migrations 001 through 014 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 015_add_processed_transactions.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Processed transactions table (one row per transaction signature)
+CREATE TABLE processed_transactions (
+    signature VARCHAR(88) PRIMARY KEY,
+    slot BIGINT NOT NULL,
+    datasource VARCHAR(64) NOT NULL,
+    processed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_processed_transactions_slot ON processed_transactions USING btree (slot);
+CREATE INDEX idx_processed_transactions_processed_at ON processed_transactions USING btree (processed_at);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 015 completed successfully';
+    RAISE NOTICE 'Tables created: processed_transactions';
+END $$;
@@ -0,0 +1,180 @@
+-- ============================================================================
+-- Migration: Add commitment status
+-- ============================================================================
+-- Description: This migration adds a commitment column to every table written
+--              by the indexer. Rows are written at 'confirmed' commitment; the
+--              indexer finalizer later promotes them to 'finalized' once their
+--              slot is rooted, or flags them 'rolled_back' when their slot was
+--              skipped.
+--
+--              Rows that already exist are historical and start out as
+--              'finalized'. A trigger resets a row to 'confirmed' whenever its
+--              slot changes, so latest-state tables follow the commitment of
+--              their most recent write.
+--
+--              The finalized_* views expose finalized rows only, for accounting
+--              consumers; the UI keeps reading the base tables for low latency.
+--              pools gains a slot column so pair creations are tracked as well.
+--
+-- Prerequisites:
+--   - Migrations 001 through 015 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 016_add_commitment_status.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Types
+-- ----------------------------------------------------------------------------
+
+CREATE TYPE commitment_status AS ENUM ('confirmed', 'finalized', 'rolled_back');
+
+-- ----------------------------------------------------------------------------
+-- Columns
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE pools ADD COLUMN slot BIGINT;
+
+-- Resets the commitment of rows rewritten at a different slot
+CREATE OR REPLACE FUNCTION reset_commitment_on_slot_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.slot IS DISTINCT FROM OLD.slot THEN
+        NEW.commitment := 'confirmed';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    table_name TEXT;
+BEGIN
+    FOREACH table_name IN ARRAY ARRAY[
+        'pools',
+        'swaps',
+        'adjust_liquidity',
+        'user_liquidity_positions',
+        'user_lp_position_updated_events',
+        'user_borrow_positions',
+        'user_position_updated_events',
+        'user_position_liquidated_events',
+        'adjust_collateral_events',
+        'adjust_debt_events',
+        'leverage_position_created_events',
+        'leverage_position_updated_events',
+        'pair_state_updates',
+        'pair_states',
+        'flashloans',
+        'protocol_fee_claims',
+        'protocol_revenue_ledger',
+        'swap_fee_accruals',
+        'user_position_created_events',
+        'pair_accounts',
+        'user_position_accounts',
+        'rate_model_accounts',
+        'futarchy_authority_accounts',
+        'processed_transactions'
+    ]
+    LOOP
+        -- Existing rows are historical; new rows start out confirmed
+        EXECUTE format('ALTER TABLE %I ADD COLUMN commitment commitment_status NOT NULL DEFAULT ''finalized''', table_name);
+        EXECUTE format('ALTER TABLE %I ALTER COLUMN commitment SET DEFAULT ''confirmed''', table_name);
+
+        -- The finalizer scans pending rows by slot
+        EXECUTE format('CREATE INDEX idx_%s_pending_slot ON %I USING btree (slot) WHERE commitment = ''confirmed''', table_name, table_name);
+
+        EXECUTE format(
+            'CREATE TRIGGER %I BEFORE UPDATE ON %I FOR EACH ROW EXECUTE FUNCTION reset_commitment_on_slot_change()',
+            table_name || '_reset_commitment',
+            table_name
+        );
+    END LOOP;
+END $$;
+
+-- ----------------------------------------------------------------------------
+-- Swap notifications
+-- ----------------------------------------------------------------------------
+
+-- Same payload as migration 002 plus the commitment; commitment-only changes
+-- are reported with op 'COMMITMENT' so listeners can tell them from enrichment
+CREATE OR REPLACE FUNCTION notify_swap_updated()
+RETURNS TRIGGER AS $$
+DECLARE
+    op TEXT := TG_OP;
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW.commitment IS DISTINCT FROM OLD.commitment THEN
+        op := 'COMMITMENT';
+    END IF;
+
+    PERFORM pg_notify(
+        'swap_updates',
+        json_build_object(
+            'op', op,
+            'id', NEW.id::text,
+            'pair', NEW.pair,
+            'user_address', NEW.user_address,
+            'is_token0_in', NEW.is_token0_in,
+            'amount_in', NEW.amount_in::text,
+            'amount_out', NEW.amount_out::text,
+            'reserve0', NEW.reserve0::text,
+            'reserve1', NEW.reserve1::text,
+            'timestamp', to_char(NEW.timestamp AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.MS"Z"'),
+            'tx_sig', NEW.tx_sig,
+            'slot', NEW.slot::text,
+            'fee_paid0', NEW.fee_paid0::text,
+            'fee_paid1', NEW.fee_paid1::text,
+            'ema_price', COALESCE(NEW.ema_price::text, ''),
+            'volume_usd', COALESCE(NEW.volume_usd::text, ''),
+            'commitment', NEW.commitment::text
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ----------------------------------------------------------------------------
+-- Views
+-- ----------------------------------------------------------------------------
+
+CREATE VIEW finalized_swaps AS
+SELECT * FROM swaps WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_adjust_liquidity AS
+SELECT * FROM adjust_liquidity WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_user_position_updated_events AS
+SELECT * FROM user_position_updated_events WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_user_position_liquidated_events AS
+SELECT * FROM user_position_liquidated_events WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_adjust_collateral_events AS
+SELECT * FROM adjust_collateral_events WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_adjust_debt_events AS
+SELECT * FROM adjust_debt_events WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_flashloans AS
+SELECT * FROM flashloans WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_protocol_fee_claims AS
+SELECT * FROM protocol_fee_claims WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_protocol_revenue_ledger AS
+SELECT * FROM protocol_revenue_ledger WHERE commitment = 'finalized';
+
+CREATE VIEW finalized_swap_fee_accruals AS
+SELECT * FROM swap_fee_accruals WHERE commitment = 'finalized';
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 016 completed successfully';
+    RAISE NOTICE 'Types created: commitment_status';
+    RAISE NOTICE 'Added commitment column to all indexed tables, slot column to: pools';
+    RAISE NOTICE 'Views created: finalized_swaps, finalized_adjust_liquidity, finalized_user_position_updated_events, finalized_user_position_liquidated_events, finalized_adjust_collateral_events, finalized_adjust_debt_events, finalized_flashloans, finalized_protocol_fee_claims, finalized_protocol_revenue_ledger, finalized_swap_fee_accruals';
+END $$;
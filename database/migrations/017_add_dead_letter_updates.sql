@@ -0,0 +1,55 @@
+-- ============================================================================
+-- Migration: Add dead-letter updates
+-- ============================================================================
+-- Description: This migration adds dead_letter_updates, keeping every update
+--              (transaction or account) whose processing failed, e.g. during
+--              a database outage. The payload holds the full update so the
+--              indexer `replay-failed` command can run it through the
+--              processors again once the cause is fixed.
+--
+--              Replayed updates are kept with replayed_at set; updates that
+--              fail again have their attempts and last_error updated.
+--
+-- Prerequisites:
+--   - Migrations 001 through 016 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 017_add_dead_letter_updates.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Dead-letter updates table (one row per failed update)
+CREATE TABLE dead_letter_updates (
+    id BIGSERIAL PRIMARY KEY,
+    datasource VARCHAR(64) NOT NULL,
+    update_kind VARCHAR(32) NOT NULL,
+    slot BIGINT NOT NULL,
+    signature VARCHAR(88),
+    pubkey VARCHAR(44),
+    payload JSONB NOT NULL,
+    last_error TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    first_failed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_failed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    replayed_at TIMESTAMPTZ
+);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_dead_letter_updates_pending ON dead_letter_updates USING btree (id) WHERE replayed_at IS NULL;
+CREATE INDEX idx_dead_letter_updates_signature ON dead_letter_updates USING btree (signature);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 017 completed successfully';
+    RAISE NOTICE 'Tables created: dead_letter_updates';
+END $$;
@@ -0,0 +1,71 @@
+-- ============================================================================
+-- Migration: Add swap candles
+-- ============================================================================
+-- Description: This migration adds swap_candles, the OHLCV candles of every
+--              pair at 1m, 5m, 15m, 1h, 4h and 1d resolutions. Prices are the
+--              post-swap reserve ratio reserve1 / reserve0 in raw token units;
+--              volumes are kept in both tokens.
+--
+--              The indexer updates candles in the same transaction as the
+--              swaps it inserts. The open_* and close_* columns record the
+--              trade (slot, signature, instruction) that set open and close,
+--              so swaps written out of order still land correctly. The
+--              indexer `rebuild-candles` command recomputes the candles of a
+--              pair from the swaps table, e.g. after a backfill or reindex.
+--
+-- Prerequisites:
+--   - Migrations 001 through 017 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 018_add_swap_candles.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Swap candles table (one row per pair, resolution and period)
+CREATE TABLE swap_candles (
+    pair VARCHAR(44) NOT NULL,
+    resolution VARCHAR(3) NOT NULL CHECK (resolution IN ('1m', '5m', '15m', '1h', '4h', '1d')),
+    bucket_start TIMESTAMPTZ NOT NULL,
+    open NUMERIC,
+    high NUMERIC,
+    low NUMERIC,
+    close NUMERIC,
+    volume0 NUMERIC NOT NULL DEFAULT 0,
+    volume1 NUMERIC NOT NULL DEFAULT 0,
+    trade_count BIGINT NOT NULL DEFAULT 0,
+    open_slot BIGINT,
+    open_tx_sig VARCHAR(88),
+    open_instruction_index INTEGER,
+    open_inner_instruction_index INTEGER,
+    close_slot BIGINT,
+    close_tx_sig VARCHAR(88),
+    close_instruction_index INTEGER,
+    close_inner_instruction_index INTEGER,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (pair, resolution, bucket_start)
+);
+
+-- ----------------------------------------------------------------------------
+-- Convert tables to TimescaleDB Hypertables
+-- ----------------------------------------------------------------------------
+
+SELECT create_hypertable('swap_candles', 'bucket_start', chunk_time_interval => INTERVAL '30 days', if_not_exists => TRUE);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_swap_candles_resolution_bucket ON swap_candles USING btree (resolution, bucket_start DESC);
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 018 completed successfully';
+    RAISE NOTICE 'Tables created: swap_candles';
+END $$;
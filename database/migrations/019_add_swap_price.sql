@@ -0,0 +1,87 @@
+-- ============================================================================
+-- Migration: Add swap price
+-- ============================================================================
+-- Description: This migration adds a price column to swaps. The indexer now
+--              inserts every swap with its price columns already filled in:
+--                - price: post-swap reserve1 / reserve0, in whole tokens
+--                  (scaled by token0_decimals and token1_decimals)
+--                - ema_price: the pair's on-chain EMA price of token0 at the
+--                  swap, in whole tokens
+--                - volume_usd: USD value of the swap from the indexer's
+--                  configured USD prices (NULL when neither token has one)
+--
+--              Swap notifications carry the price, so listeners no longer
+--              derive it from raw reserves or wait for an enrichment UPDATE.
+--              Rows written before this migration keep a NULL price.
+--
+-- Prerequisites:
+--   - Migrations 001 through 018 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 019_add_swap_price.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Columns
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE swaps ADD COLUMN price NUMERIC;
+
+-- ----------------------------------------------------------------------------
+-- Swap notifications
+-- ----------------------------------------------------------------------------
+
+-- Same payload as migration 016 plus the price
+CREATE OR REPLACE FUNCTION notify_swap_updated()
+RETURNS TRIGGER AS $$
+DECLARE
+    op TEXT := TG_OP;
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW.commitment IS DISTINCT FROM OLD.commitment THEN
+        op := 'COMMITMENT';
+    END IF;
+
+    PERFORM pg_notify(
+        'swap_updates',
+        json_build_object(
+            'op', op,
+            'id', NEW.id::text,
+            'pair', NEW.pair,
+            'user_address', NEW.user_address,
+            'is_token0_in', NEW.is_token0_in,
+            'amount_in', NEW.amount_in::text,
+            'amount_out', NEW.amount_out::text,
+            'reserve0', NEW.reserve0::text,
+            'reserve1', NEW.reserve1::text,
+            'timestamp', to_char(NEW.timestamp AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.MS"Z"'),
+            'tx_sig', NEW.tx_sig,
+            'slot', NEW.slot::text,
+            'fee_paid0', NEW.fee_paid0::text,
+            'fee_paid1', NEW.fee_paid1::text,
+            'price', COALESCE(NEW.price::text, ''),
+            'ema_price', COALESCE(NEW.ema_price::text, ''),
+            'volume_usd', COALESCE(NEW.volume_usd::text, ''),
+            'commitment', NEW.commitment::text
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ----------------------------------------------------------------------------
+-- Views
+-- ----------------------------------------------------------------------------
+
+-- Views expand * when created; recreate it so it exposes the new column
+CREATE OR REPLACE VIEW finalized_swaps AS
+SELECT * FROM swaps WHERE commitment = 'finalized';
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 019 completed successfully';
+    RAISE NOTICE 'Columns added: swaps.price';
+END $$;
@@ -0,0 +1,95 @@
+-- ============================================================================
+-- Migration: Add exact swap fees
+-- ============================================================================
+-- Description: This migration makes the swap fee columns exact. A swap pays
+--              its fee in the input token only:
+--                - fee_paid0 / fee_paid1: amount_in - amount_in_after_fee in
+--                  the column of the input token, 0 in the other
+--                - fee_value_out: that fee valued in the output token at the
+--                  post-swap reserve ratio, rounded down (NULL when the input
+--                  reserve is empty)
+--
+--              Previously the output token column held an approximate
+--              conversion of the fee. Existing rows are rewritten: the input
+--              token fee is kept, and fee_value_out is recomputed from it.
+--
+--              swap_fee_mismatches records the swaps whose fee disagrees with
+--              the swap_fee_bps of their pair, as detected by the indexer.
+--
+-- Prerequisites:
+--   - Migrations 001 through 019 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 020_add_exact_swap_fees.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Columns
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE swaps ADD COLUMN fee_value_out NUMERIC;
+
+-- ----------------------------------------------------------------------------
+-- Existing swaps
+-- ----------------------------------------------------------------------------
+
+-- Rewriting history is not a swap update; do not notify listeners of every row
+ALTER TABLE swaps DISABLE TRIGGER swap_notify;
+
+-- Every SET expression sees the row as it was before the update
+UPDATE swaps SET
+    fee_value_out = CASE
+        WHEN is_token0_in THEN div(fee_paid0 * reserve1, NULLIF(reserve0, 0))
+        ELSE div(fee_paid1 * reserve0, NULLIF(reserve1, 0))
+    END,
+    fee_paid0 = CASE WHEN is_token0_in THEN fee_paid0 ELSE 0 END,
+    fee_paid1 = CASE WHEN is_token0_in THEN 0 ELSE fee_paid1 END
+WHERE fee_paid0 IS NOT NULL AND fee_paid1 IS NOT NULL;
+
+ALTER TABLE swaps ENABLE TRIGGER swap_notify;
+
+-- ----------------------------------------------------------------------------
+-- Tables
+-- ----------------------------------------------------------------------------
+
+-- Swaps whose fee is not amount_in * swap_fee_bps / 10000, rounded either way
+CREATE TABLE swap_fee_mismatches (
+    tx_sig VARCHAR(88) NOT NULL,
+    instruction_index INTEGER NOT NULL,
+    inner_instruction_index INTEGER NOT NULL,
+    pair VARCHAR(44) NOT NULL,
+    slot BIGINT NOT NULL,
+    "timestamp" TIMESTAMPTZ NOT NULL,
+    amount_in NUMERIC NOT NULL,
+    fee_paid NUMERIC NOT NULL,
+    swap_fee_bps INTEGER NOT NULL,
+    expected_fee_min NUMERIC NOT NULL,
+    expected_fee_max NUMERIC NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (tx_sig, instruction_index, inner_instruction_index)
+);
+
+-- ----------------------------------------------------------------------------
+-- Indexes
+-- ----------------------------------------------------------------------------
+
+CREATE INDEX idx_swap_fee_mismatches_pair_timestamp ON swap_fee_mismatches USING btree (pair, "timestamp" DESC);
+
+-- ----------------------------------------------------------------------------
+-- Views
+-- ----------------------------------------------------------------------------
+
+-- Views expand * when created; recreate it so it exposes the new column
+CREATE OR REPLACE VIEW finalized_swaps AS
+SELECT * FROM swaps WHERE commitment = 'finalized';
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 020 completed successfully';
+    RAISE NOTICE 'Columns added: swaps.fee_value_out';
+    RAISE NOTICE 'Tables created: swap_fee_mismatches';
+END $$;
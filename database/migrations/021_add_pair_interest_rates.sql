@@ -0,0 +1,103 @@
+-- ============================================================================
+-- Migration: Add pair interest rates
+-- ============================================================================
+-- Description: This migration adds the lending state of both tokens to every
+--              pair state update and to the latest pair state:
+--                - utilization0/1: total debt over reserve, the debt being the
+--                  part of the reserve lent out (reserve - cash_reserve)
+--                - borrow_apr0/1: the on-chain rate as a fraction (NAD / 1e9)
+--                - supply_apr0/1: borrow APR * utilization, net of the
+--                  protocol's interest share (NULL while it is unknown)
+--
+--              The indexer computes them at each UpdatePairEvent; existing
+--              rows are filled in here with the current interest share.
+--
+--              The rate-model columns of pools (migration 005) are copied
+--              from the RateModel account of each pair, in basis points.
+--
+-- Prerequisites:
+--   - Migrations 001 through 020 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 021_add_pair_interest_rates.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Columns
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE pair_state_updates
+ADD COLUMN utilization0 NUMERIC,
+ADD COLUMN utilization1 NUMERIC,
+ADD COLUMN borrow_apr0 NUMERIC,
+ADD COLUMN borrow_apr1 NUMERIC,
+ADD COLUMN supply_apr0 NUMERIC,
+ADD COLUMN supply_apr1 NUMERIC;
+
+ALTER TABLE pair_states
+ADD COLUMN utilization0 NUMERIC,
+ADD COLUMN utilization1 NUMERIC,
+ADD COLUMN borrow_apr0 NUMERIC,
+ADD COLUMN borrow_apr1 NUMERIC,
+ADD COLUMN supply_apr0 NUMERIC,
+ADD COLUMN supply_apr1 NUMERIC;
+
+-- ----------------------------------------------------------------------------
+-- Existing rows
+-- ----------------------------------------------------------------------------
+
+DO $$
+DECLARE
+    table_name TEXT;
+    lp_share NUMERIC := (
+        SELECT (10000 - interest_share_bps) / 10000.0
+        FROM futarchy_authority_accounts
+        ORDER BY slot DESC
+        LIMIT 1
+    );
+BEGIN
+    FOREACH table_name IN ARRAY ARRAY['pair_state_updates', 'pair_states']
+    LOOP
+        EXECUTE format(
+            'UPDATE %I SET
+                utilization0 = CASE WHEN reserve0_after_interest > 0
+                    THEN trunc(GREATEST(reserve0_after_interest - cash_reserve0, 0) / reserve0_after_interest, 18) ELSE 0 END,
+                utilization1 = CASE WHEN reserve1_after_interest > 0
+                    THEN trunc(GREATEST(reserve1_after_interest - cash_reserve1, 0) / reserve1_after_interest, 18) ELSE 0 END,
+                borrow_apr0 = rate0 / 1000000000,
+                borrow_apr1 = rate1 / 1000000000',
+            table_name
+        );
+
+        EXECUTE format(
+            'UPDATE %I SET
+                supply_apr0 = trunc(borrow_apr0 * utilization0 * $1, 18),
+                supply_apr1 = trunc(borrow_apr1 * utilization1 * $1, 18)',
+            table_name
+        ) USING lp_share;
+    END LOOP;
+END $$;
+
+-- ----------------------------------------------------------------------------
+-- Pool rate models
+-- ----------------------------------------------------------------------------
+
+-- Rate model parameters are NAD-scaled (1e9 = 100%); 1 bps = 100000
+UPDATE pools SET
+    target_util_start_bps = div(r.target_util_start, 100000),
+    target_util_end_bps = div(r.target_util_end, 100000),
+    rate_half_life_ms = r.half_life_ms,
+    min_rate_bps = div(r.min_rate, 100000),
+    max_rate_bps = div(r.max_rate, 100000)
+FROM rate_model_accounts r
+WHERE pools.rate_model = r.pubkey;
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 021 completed successfully';
+    RAISE NOTICE 'Columns added to pair_state_updates and pair_states: utilization0, utilization1, borrow_apr0, borrow_apr1, supply_apr0, supply_apr1';
+END $$;
@@ -0,0 +1,72 @@
+-- ============================================================================
+-- Migration: Add swap volume notifications
+-- ============================================================================
+-- Description: This migration reports the enrichment of a swap's USD volume
+--              in swap notifications. Swaps whose tokens have no configured
+--              USD price are inserted with a NULL volume_usd and filled in
+--              later by the volume enricher (api/src/scripts/volumeEnricher.ts).
+--
+--              An UPDATE that sets volume_usd of a swap that had none is sent
+--              with op 'VOLUME', so listeners can stream the swap again with
+--              its volume. Other updates keep op 'UPDATE', commitment changes
+--              keep op 'COMMITMENT'.
+--
+-- Prerequisites:
+--   - Migrations 001 through 021 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 022_add_swap_volume_notify.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Swap notifications
+-- ----------------------------------------------------------------------------
+
+-- Same payload as migration 019
+CREATE OR REPLACE FUNCTION notify_swap_updated()
+RETURNS TRIGGER AS $$
+DECLARE
+    op TEXT := TG_OP;
+BEGIN
+    IF TG_OP = 'UPDATE' AND NEW.commitment IS DISTINCT FROM OLD.commitment THEN
+        op := 'COMMITMENT';
+    ELSIF TG_OP = 'UPDATE' AND OLD.volume_usd IS NULL AND NEW.volume_usd IS NOT NULL THEN
+        op := 'VOLUME';
+    END IF;
+
+    PERFORM pg_notify(
+        'swap_updates',
+        json_build_object(
+            'op', op,
+            'id', NEW.id::text,
+            'pair', NEW.pair,
+            'user_address', NEW.user_address,
+            'is_token0_in', NEW.is_token0_in,
+            'amount_in', NEW.amount_in::text,
+            'amount_out', NEW.amount_out::text,
+            'reserve0', NEW.reserve0::text,
+            'reserve1', NEW.reserve1::text,
+            'timestamp', to_char(NEW.timestamp AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.MS"Z"'),
+            'tx_sig', NEW.tx_sig,
+            'slot', NEW.slot::text,
+            'fee_paid0', NEW.fee_paid0::text,
+            'fee_paid1', NEW.fee_paid1::text,
+            'price', COALESCE(NEW.price::text, ''),
+            'ema_price', COALESCE(NEW.ema_price::text, ''),
+            'volume_usd', COALESCE(NEW.volume_usd::text, ''),
+            'commitment', NEW.commitment::text
+        )::text
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 022 completed successfully';
+    RAISE NOTICE 'Swap notifications: op VOLUME when volume_usd is first set';
+END $$;
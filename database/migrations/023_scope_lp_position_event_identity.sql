@@ -0,0 +1,41 @@
+-- ============================================================================
+-- Migration: Scope the LP position event identity to indexed transactions
+-- ============================================================================
+-- Description: user_lp_position_updated_events only gained tx_sig in
+--              migration 007. Rows indexed before it have no transaction
+--              reference and cannot be backfilled from the table, so their
+--              tx_sig stays NULL. NULLs never conflict, so the event identity
+--              key did not cover those rows anyway.
+--
+--              This migration replaces the identity constraint with a partial
+--              unique index over the rows that have a tx_sig, which the
+--              indexer always sets. Upserts name the index predicate in their
+--              ON CONFLICT clause.
+--
+-- Prerequisites:
+--   - Migrations 001 through 022 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 023_scope_lp_position_event_identity.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- User LP position updated events
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE user_lp_position_updated_events
+DROP CONSTRAINT IF EXISTS user_lp_position_updated_events_event_identity_key;
+
+CREATE UNIQUE INDEX IF NOT EXISTS user_lp_position_updated_events_event_identity_key
+ON user_lp_position_updated_events (tx_sig, instruction_index, inner_instruction_index, "timestamp")
+WHERE tx_sig IS NOT NULL;
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 023 completed successfully';
+    RAISE NOTICE 'user_lp_position_updated_events identity: unique where tx_sig IS NOT NULL';
+END $$;
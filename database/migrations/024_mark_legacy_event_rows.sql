@@ -0,0 +1,83 @@
+-- ============================================================================
+-- Migration: Mark event rows written before the per-instruction identity
+-- ============================================================================
+-- Description: Migration 007 keyed events by (signature, instruction_index,
+--              inner_instruction_index) with 0/0 defaults for the rows that
+--              already existed. Those rows also keep the insertion time as
+--              "timestamp" instead of the block time, and LP position updates
+--              have no tx_sig at all. Replaying their transactions (backfill,
+--              reindex) never matches them, so their events were stored twice.
+--
+--              The identity of those rows cannot be derived from the database,
+--              so they are marked with legacy_identity. Events are emitted
+--              through CPIs, so rows written by the indexer since migration
+--              007 never have 0/0 indexes. The indexer deletes the legacy rows
+--              of every transaction it writes, before writing its events, and
+--              rebuilds the candles of the days of deleted swaps. Legacy LP
+--              position updates are matched on (pair_address, signer, slot).
+--
+-- Prerequisites:
+--   - Migrations 001 through 023 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 024_mark_legacy_event_rows.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Swaps and liquidity
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE swaps ADD COLUMN legacy_identity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE swaps SET legacy_identity = TRUE
+WHERE instruction_index = 0 AND inner_instruction_index = 0;
+CREATE INDEX IF NOT EXISTS idx_swaps_legacy_tx_sig ON swaps (tx_sig) WHERE legacy_identity;
+
+ALTER TABLE adjust_liquidity ADD COLUMN legacy_identity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE adjust_liquidity SET legacy_identity = TRUE
+WHERE instruction_index = 0 AND inner_instruction_index = 0;
+CREATE INDEX IF NOT EXISTS idx_adjust_liquidity_legacy_tx_sig ON adjust_liquidity (tx_sig) WHERE legacy_identity;
+
+ALTER TABLE user_lp_position_updated_events ADD COLUMN legacy_identity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE user_lp_position_updated_events SET legacy_identity = TRUE
+WHERE tx_sig IS NULL OR (instruction_index = 0 AND inner_instruction_index = 0);
+CREATE INDEX IF NOT EXISTS idx_user_lp_position_updated_events_legacy
+ON user_lp_position_updated_events (pair_address, signer, slot) WHERE legacy_identity;
+
+-- ----------------------------------------------------------------------------
+-- Lending events
+-- ----------------------------------------------------------------------------
+
+ALTER TABLE user_position_updated_events ADD COLUMN legacy_identity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE user_position_updated_events SET legacy_identity = TRUE
+WHERE instruction_index = 0 AND inner_instruction_index = 0;
+CREATE INDEX IF NOT EXISTS idx_user_position_updated_events_legacy_signature
+ON user_position_updated_events (transaction_signature) WHERE legacy_identity;
+
+ALTER TABLE user_position_liquidated_events ADD COLUMN legacy_identity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE user_position_liquidated_events SET legacy_identity = TRUE
+WHERE instruction_index = 0 AND inner_instruction_index = 0;
+CREATE INDEX IF NOT EXISTS idx_user_position_liquidated_events_legacy_signature
+ON user_position_liquidated_events (transaction_signature) WHERE legacy_identity;
+
+ALTER TABLE adjust_collateral_events ADD COLUMN legacy_identity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE adjust_collateral_events SET legacy_identity = TRUE
+WHERE instruction_index = 0 AND inner_instruction_index = 0;
+CREATE INDEX IF NOT EXISTS idx_adjust_collateral_events_legacy_signature
+ON adjust_collateral_events (transaction_signature) WHERE legacy_identity;
+
+ALTER TABLE adjust_debt_events ADD COLUMN legacy_identity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE adjust_debt_events SET legacy_identity = TRUE
+WHERE instruction_index = 0 AND inner_instruction_index = 0;
+CREATE INDEX IF NOT EXISTS idx_adjust_debt_events_legacy_signature
+ON adjust_debt_events (transaction_signature) WHERE legacy_identity;
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 024 completed successfully';
+    RAISE NOTICE 'Added legacy_identity to: swaps, adjust_liquidity, user_lp_position_updated_events, user_position_updated_events, user_position_liquidated_events, adjust_collateral_events, adjust_debt_events';
+    RAISE NOTICE 'Legacy rows are replaced when their transactions are indexed again';
+END $$;
@@ -0,0 +1,129 @@
+-- ============================================================================
+-- Migration: Fix the protocol revenue views
+-- ============================================================================
+-- Description: The views of migration 010 predate the commitment column of
+--              migration 016, so claims, ledger entries and swap fees of
+--              rolled back slots were still counted. They also treated every
+--              swap fee of the latest claim's slot as claimed, including fees
+--              of swaps after the claim in the same transaction.
+--
+--              pair_protocol_revenue           ignores rolled back claims and
+--                                              swap fees; fees are unclaimed
+--                                              when their (slot,
+--                                              instruction_index,
+--                                              inner_instruction_index) is
+--                                              after the latest claim's
+--              protocol_revenue_by_recipient   ignores rolled back ledger
+--                                              entries and claims
+--
+-- Prerequisites:
+--   - Migrations 001 through 024 must be applied
+--
+-- Usage:
+--   psql -U omnipair_user -d omnipair_indexer -f 025_fix_protocol_revenue_views.sql
+-- ============================================================================
+
+-- ----------------------------------------------------------------------------
+-- Views
+-- ----------------------------------------------------------------------------
+
+-- Claimed vs. unclaimed revenue per pair. Unclaimed swap fees are the fees
+-- accrued by swaps after the pair's latest claim.
+CREATE OR REPLACE VIEW pair_protocol_revenue AS
+WITH claims AS (
+    SELECT
+        pair,
+        MAX(slot) AS last_claim_slot,
+        MAX(event_timestamp) AS last_claim_at,
+        COUNT(*) AS claim_count,
+        SUM(futarchy_treasury_amount0 + buybacks_vault_amount0 + team_treasury_amount0) AS claimed_amount0,
+        SUM(futarchy_treasury_amount1 + buybacks_vault_amount1 + team_treasury_amount1) AS claimed_amount1
+    FROM protocol_fee_claims
+    WHERE commitment <> 'rolled_back'
+    GROUP BY pair
+),
+latest_claims AS (
+    SELECT DISTINCT ON (pair)
+        pair, slot, instruction_index, inner_instruction_index
+    FROM protocol_fee_claims
+    WHERE commitment <> 'rolled_back'
+    ORDER BY pair, slot DESC, instruction_index DESC, inner_instruction_index DESC
+),
+fees AS (
+    SELECT
+        f.pair,
+        SUM(f.fee0) AS total_swap_fees0,
+        SUM(f.fee1) AS total_swap_fees1,
+        COALESCE(SUM(f.fee0) FILTER (
+            WHERE l.pair IS NULL
+                OR (f.slot, f.instruction_index, f.inner_instruction_index) > (l.slot, l.instruction_index, l.inner_instruction_index)
+        ), 0) AS unclaimed_swap_fees0,
+        COALESCE(SUM(f.fee1) FILTER (
+            WHERE l.pair IS NULL
+                OR (f.slot, f.instruction_index, f.inner_instruction_index) > (l.slot, l.instruction_index, l.inner_instruction_index)
+        ), 0) AS unclaimed_swap_fees1
+    FROM swap_fee_accruals f
+    LEFT JOIN latest_claims l ON l.pair = f.pair
+    WHERE f.commitment <> 'rolled_back'
+    GROUP BY f.pair
+)
+SELECT
+    COALESCE(c.pair, f.pair) AS pair,
+    COALESCE(c.claim_count, 0) AS claim_count,
+    COALESCE(c.claimed_amount0, 0) AS claimed_amount0,
+    COALESCE(c.claimed_amount1, 0) AS claimed_amount1,
+    c.last_claim_slot,
+    c.last_claim_at,
+    COALESCE(f.total_swap_fees0, 0) AS total_swap_fees0,
+    COALESCE(f.total_swap_fees1, 0) AS total_swap_fees1,
+    COALESCE(f.unclaimed_swap_fees0, 0) AS unclaimed_swap_fees0,
+    COALESCE(f.unclaimed_swap_fees1, 0) AS unclaimed_swap_fees1
+FROM claims c
+FULL OUTER JOIN fees f ON f.pair = c.pair;
+
+-- Claimed revenue per pair and recipient, with the recipient's share of the
+-- pair's latest claim (used to apportion unclaimed revenue).
+CREATE OR REPLACE VIEW protocol_revenue_by_recipient AS
+WITH latest_claims AS (
+    SELECT DISTINCT ON (pair)
+        pair, transaction_signature, instruction_index, inner_instruction_index
+    FROM protocol_fee_claims
+    WHERE commitment <> 'rolled_back'
+    ORDER BY pair, slot DESC, instruction_index DESC, inner_instruction_index DESC
+),
+latest_shares AS (
+    SELECT
+        l.pair,
+        l.recipient,
+        l.amount0 / NULLIF(SUM(l.amount0) OVER (PARTITION BY l.pair), 0) * 10000 AS latest_share0_bps,
+        l.amount1 / NULLIF(SUM(l.amount1) OVER (PARTITION BY l.pair), 0) * 10000 AS latest_share1_bps
+    FROM protocol_revenue_ledger l
+    JOIN latest_claims c
+        ON c.transaction_signature = l.transaction_signature
+        AND c.instruction_index = l.instruction_index
+        AND c.inner_instruction_index = l.inner_instruction_index
+    WHERE l.commitment <> 'rolled_back'
+)
+SELECT
+    l.pair,
+    l.recipient,
+    COUNT(*) AS claim_count,
+    SUM(l.amount0) AS claimed_amount0,
+    SUM(l.amount1) AS claimed_amount1,
+    MAX(l.event_timestamp) AS last_claim_at,
+    s.latest_share0_bps,
+    s.latest_share1_bps
+FROM protocol_revenue_ledger l
+LEFT JOIN latest_shares s ON s.pair = l.pair AND s.recipient = l.recipient
+WHERE l.commitment <> 'rolled_back'
+GROUP BY l.pair, l.recipient, s.latest_share0_bps, s.latest_share1_bps;
+
+-- ----------------------------------------------------------------------------
+-- Done
+-- ----------------------------------------------------------------------------
+
+DO $$
+BEGIN
+    RAISE NOTICE 'Schema migration 025 completed successfully';
+    RAISE NOTICE 'Views replaced: pair_protocol_revenue, protocol_revenue_by_recipient';
+END $$;
@@ -0,0 +1,307 @@
+//! Embedded, versioned schema migrations.
+//!
+//! Every file in `database/migrations` is compiled into the binaries that depend on this
+//! crate. Applied versions are recorded in `_sqlx_migrations` together with the checksum
+//! of their SQL, so a migration edited after it was applied is detected instead of
+//! silently diverging.
+//!
+//! The indexer and the gRPC server call `ensure_schema` on startup: they refuse to start
+//! when the schema is behind, or apply the pending migrations when auto-migrate is enabled.
+
+use std::fmt;
+
+use sqlx::{
+    migrate::{Migrate, MigrateError, Migrator},
+    PgPool,
+};
+
+/// Migrations embedded from `database/migrations`
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// State of one embedded migration in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the embedded SQL no longer matches the recorded checksum
+    Modified,
+    /// Recorded as failed, the database needs manual repair
+    Failed,
+}
+
+impl fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Modified => "modified",
+            MigrationState::Failed => "failed",
+        };
+        f.write_str(state)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+/// Embedded migrations compared with the versions recorded in the database
+#[derive(Debug, Clone)]
+pub struct SchemaStatus {
+    pub migrations: Vec<MigrationStatus>,
+    /// Versions recorded in the database that this build does not know (a newer build
+    /// migrated the database)
+    pub unknown_versions: Vec<i64>,
+    /// The tables exist but no migration is recorded: the schema was created with the
+    /// SQL scripts and needs a baseline
+    pub untracked: bool,
+}
+
+impl SchemaStatus {
+    pub fn versions_in(&self, state: MigrationState) -> Vec<i64> {
+        self.migrations
+            .iter()
+            .filter(|migration| migration.state == state)
+            .map(|migration| migration.version)
+            .collect()
+    }
+
+    /// Whether every embedded migration is applied unchanged
+    pub fn is_current(&self) -> bool {
+        !self.untracked && self.migrations.iter().all(|migration| migration.state == MigrationState::Applied)
+    }
+}
+
+impl fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for migration in &self.migrations {
+            writeln!(f, "{:>4}  {:<9} {}", migration.version, migration.state, migration.description)?;
+        }
+
+        for version in &self.unknown_versions {
+            writeln!(f, "{:>4}  {:<9} (not in this build)", version, "unknown")?;
+        }
+
+        if self.untracked {
+            writeln!(
+                f,
+                "The schema was created outside the migrator; record the migrations already applied with `migrate --baseline <version>`"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reasons the database schema cannot be used by this build
+#[derive(Debug)]
+pub enum SchemaError {
+    Migrate(MigrateError),
+    /// Tables exist but no migration is recorded
+    Untracked,
+    /// Migrations not applied yet
+    Behind(Vec<i64>),
+    /// Applied migrations whose SQL changed since
+    Modified(Vec<i64>),
+    /// A migration recorded as failed
+    Failed(i64),
+    /// Baseline version not embedded in this build
+    UnknownVersion(i64),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Migrate(e) => write!(f, "migration failed: {}", e),
+            SchemaError::Untracked => write!(
+                f,
+                "the schema exists but no migration is recorded; run `migrate --baseline <version>` with the last migration applied by the SQL scripts"
+            ),
+            SchemaError::Behind(versions) => write!(
+                f,
+                "the schema is behind, pending migrations: {}; run the `migrate` command or enable auto-migrate",
+                join_versions(versions)
+            ),
+            SchemaError::Modified(versions) => write!(
+                f,
+                "applied migrations were modified since: {}; migrations must not be edited once applied",
+                join_versions(versions)
+            ),
+            SchemaError::Failed(version) => write!(
+                f,
+                "migration {} is recorded as failed; repair the schema and remove its row from `_sqlx_migrations`",
+                version
+            ),
+            SchemaError::UnknownVersion(version) => write!(f, "migration {} is not embedded in this build", version),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<MigrateError> for SchemaError {
+    fn from(e: MigrateError) -> Self {
+        SchemaError::Migrate(e)
+    }
+}
+
+impl From<sqlx::Error> for SchemaError {
+    fn from(e: sqlx::Error) -> Self {
+        SchemaError::Migrate(MigrateError::Execute(e))
+    }
+}
+
+fn join_versions(versions: &[i64]) -> String {
+    versions.iter().map(|version| version.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Compares the embedded migrations with the database, without modifying it
+pub async fn status(pool: &PgPool) -> Result<SchemaStatus, SchemaError> {
+    let tracked: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+
+    let recorded: Vec<(i64, bool, Vec<u8>)> = if tracked {
+        sqlx::query_as("SELECT version, success, checksum FROM _sqlx_migrations ORDER BY version")
+            .fetch_all(pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let untracked = recorded.is_empty()
+        && sqlx::query_scalar::<_, bool>("SELECT to_regclass('pools') IS NOT NULL")
+            .fetch_one(pool)
+            .await?;
+
+    let migrations = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| {
+            let state = match recorded.iter().find(|(version, _, _)| *version == migration.version) {
+                None => MigrationState::Pending,
+                Some((_, false, _)) => MigrationState::Failed,
+                Some((_, true, checksum)) if checksum.as_slice() != &*migration.checksum => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state,
+            }
+        })
+        .collect();
+
+    let unknown_versions = recorded
+        .iter()
+        .map(|(version, _, _)| *version)
+        .filter(|version| !MIGRATOR.iter().any(|migration| migration.version == *version))
+        .collect();
+
+    Ok(SchemaStatus {
+        migrations,
+        unknown_versions,
+        untracked,
+    })
+}
+
+/// Applies every pending migration, each in its own transaction, and returns their
+/// versions. Concurrent migrators wait on an advisory lock.
+pub async fn migrate(pool: &PgPool) -> Result<Vec<i64>, SchemaError> {
+    let before = status(pool).await?;
+    check_applied(&before)?;
+
+    let pending = before.versions_in(MigrationState::Pending);
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+
+    MIGRATOR.run(pool).await?;
+
+    Ok(pending)
+}
+
+/// Records the embedded migrations up to `version` as applied without running them, for
+/// databases whose schema was created with the SQL scripts. Returns the recorded versions.
+pub async fn baseline(pool: &PgPool, version: i64) -> Result<Vec<i64>, SchemaError> {
+    if !MIGRATOR.iter().any(|migration| migration.version == version) {
+        return Err(SchemaError::UnknownVersion(version));
+    }
+
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let mut recorded = Vec::new();
+    for migration in MIGRATOR.iter().filter(|migration| migration.version <= version) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+            VALUES ($1, $2, TRUE, $3, 0)
+            ON CONFLICT (version) DO NOTHING
+            "#,
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            recorded.push(migration.version);
+        }
+    }
+
+    Ok(recorded)
+}
+
+/// Checks that the schema matches this build before serving. Pending migrations are
+/// applied with `auto_migrate`, otherwise they are an error.
+pub async fn ensure_schema(pool: &PgPool, auto_migrate: bool) -> Result<(), SchemaError> {
+    let status = status(pool).await?;
+    check_applied(&status)?;
+
+    if !status.unknown_versions.is_empty() {
+        log::warn!(
+            "Database has migrations unknown to this build ({}), it was migrated by a newer version",
+            join_versions(&status.unknown_versions)
+        );
+    }
+
+    let pending = status.versions_in(MigrationState::Pending);
+    if pending.is_empty() {
+        log::info!("Database schema is up to date");
+        return Ok(());
+    }
+
+    if !auto_migrate {
+        return Err(SchemaError::Behind(pending));
+    }
+
+    log::info!("Applying pending migrations: {}", join_versions(&pending));
+    let applied = migrate(pool).await?;
+    log::info!("Applied {} migrations", applied.len());
+
+    Ok(())
+}
+
+/// Rejects databases that cannot be migrated automatically
+fn check_applied(status: &SchemaStatus) -> Result<(), SchemaError> {
+    if status.untracked {
+        return Err(SchemaError::Untracked);
+    }
+
+    if let Some(version) = status.versions_in(MigrationState::Failed).first() {
+        return Err(SchemaError::Failed(*version));
+    }
+
+    let modified = status.versions_in(MigrationState::Modified);
+    if !modified.is_empty() {
+        return Err(SchemaError::Modified(modified));
+    }
+
+    Ok(())
+}
@@ -7,6 +7,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         .field_attribute("omnipair.stream.SwapsUpdate.price", "#[serde(default)]")
         .field_attribute("omnipair.stream.SwapsUpdate.volume_usd", "#[serde(default)]")
+        .field_attribute("omnipair.stream.SwapsUpdate.commitment", "#[serde(default)]")
         .file_descriptor_set_path(out_dir.join("stream_descriptor.bin"))
         .compile_protos(&["proto/stream.proto"], &["proto"])?;
     Ok(())
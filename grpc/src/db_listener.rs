@@ -5,7 +5,17 @@ use tokio::time::{Duration, Instant, interval};
 
 use crate::grpc_server::stream::SwapsUpdate;
 
-/// How long to hold an INSERT notification waiting for the enriched UPDATE.
+/// Swap update broadcast to gRPC clients
+#[derive(Clone, Debug)]
+pub struct SwapEvent {
+    pub update: SwapsUpdate,
+    /// Whether this only reports a commitment change (finalized or rolled back) of a
+    /// swap that was already broadcast
+    pub commitment_change: bool,
+}
+
+/// How long to hold an INSERT notification without volume_usd, waiting for the volume
+/// enricher's VOLUME notification.
 /// Configurable via GRPC_DEDUP_TIMEOUT_SECS env var (default: 5).
 fn dedup_timeout() -> Duration {
     let secs = std::env::var("GRPC_DEDUP_TIMEOUT_SECS")
@@ -30,13 +40,16 @@ fn tick_interval_duration() -> Duration {
 struct SwapNotification {
     #[serde(default)]
     op: String,
+    /// Decimal-adjusted price written by the indexer, empty for swaps written before it
+    #[serde(default)]
+    price: String,
     #[serde(flatten)]
     swap: SwapsUpdate,
 }
 
 pub async fn start_db_listener(
     pool: &PgPool,
-    sender: broadcast::Sender<SwapsUpdate>,
+    sender: broadcast::Sender<SwapEvent>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     log::info!("Starting PostgreSQL LISTEN/NOTIFY listener on channel 'swap_updates'");
 
@@ -45,7 +58,8 @@ pub async fn start_db_listener(
 
     log::info!("Successfully connected to PostgreSQL LISTEN channel");
 
-    // Dedup buffer: tx_sig -> (SwapsUpdate, insert_time)
+    // Dedup buffer: swap id -> (SwapsUpdate, insert_time). Swaps inserted without
+    // volume_usd wait here for the enricher, so each swap is streamed once
     let mut buffer: HashMap<String, (SwapsUpdate, Instant)> = HashMap::new();
     let dedup_timeout_dur = dedup_timeout();
     let mut tick = interval(tick_interval_duration());
@@ -65,48 +79,65 @@ pub async fn start_db_listener(
 
                         match serde_json::from_str::<SwapNotification>(notification.payload()) {
                             Ok(mut notif) => {
-                                // Calculate price as reserve1/reserve0 ratio
-                                let reserve0: f64 = notif.swap.reserve0.parse().unwrap_or(0.0);
-                                let reserve1: f64 = notif.swap.reserve1.parse().unwrap_or(0.0);
-                                notif.swap.price = if reserve0 > 0.0 {
-                                    (reserve1 / reserve0) as f32
-                                } else {
-                                    0.0
-                                };
+                                notif.swap.price = notif.price.parse().unwrap_or(0.0);
 
                                 let tx_sig = notif.swap.tx_sig.clone();
                                 let op = notif.op.to_uppercase();
 
-                                if op == "INSERT" {
-                                    // INSERT: hold in buffer, wait for enriched UPDATE
-                                    log::info!(
-                                        "Swap INSERT buffered - Pair: {}, TxSig: {}, waiting for volume enrichment",
+                                if op == "UPDATE" {
+                                    // Any other UPDATE rewrites a swap that was already broadcast
+                                    // (replay, reindex)
+                                    log::debug!(
+                                        "Swap UPDATE skipped - Pair: {}, TxSig: {}",
                                         notif.swap.pair, tx_sig
                                     );
-                                    buffer.insert(tx_sig, (notif.swap, Instant::now()));
-                                } else if op == "UPDATE" {
-                                    // UPDATE: enriched swap arrived
-                                    if buffer.remove(&tx_sig).is_some() {
-                                        // Was in buffer, emit the enriched version
+                                } else if op == "VOLUME" {
+                                    // volume_usd filled in by the volume enricher for a swap the
+                                    // indexer inserted without it
+                                    if buffer.remove(&notif.swap.id).is_some() {
                                         log::info!(
                                             "Swap enriched - Pair: {}, TxSig: {}, VolumeUSD: {}",
                                             notif.swap.pair, tx_sig, notif.swap.volume_usd
                                         );
+                                        emit_swap(&sender, notif.swap);
                                     } else {
-                                        // UPDATE without prior INSERT (e.g., backfill), emit directly
+                                        // Already streamed without volume once its dedup timeout
+                                        // expired (or before the listener started)
+                                        log::debug!(
+                                            "Swap VOLUME skipped (already broadcast) - Pair: {}, TxSig: {}",
+                                            notif.swap.pair, tx_sig
+                                        );
+                                    }
+                                } else if op == "COMMITMENT" {
+                                    // Swap finalized or rolled back by the indexer finalizer
+                                    if notif.swap.commitment == "rolled_back"
+                                        && buffer.remove(&notif.swap.id).is_some()
+                                    {
+                                        // Never streamed, so there is nothing to retract
                                         log::info!(
-                                            "Swap UPDATE (no prior INSERT) - Pair: {}, TxSig: {}, VolumeUSD: {}",
-                                            notif.swap.pair, tx_sig, notif.swap.volume_usd
+                                            "Swap rolled back before broadcast - Pair: {}, TxSig: {}",
+                                            notif.swap.pair, tx_sig
+                                        );
+                                    } else {
+                                        log::info!(
+                                            "Swap {} - Pair: {}, TxSig: {}",
+                                            notif.swap.commitment, notif.swap.pair, tx_sig
                                         );
+                                        emit_commitment_change(&sender, notif.swap);
                                     }
-                                    emit_swap(&sender, notif.swap);
-                                } else {
-                                    // Unknown op or missing op field (backward compat), emit immediately
+                                } else if notif.swap.volume_usd.is_empty() {
+                                    // INSERT without volume_usd: hold in buffer, wait for enrichment
                                     log::info!(
-                                        "Swap notification (op={}) - Pair: {}, TxSig: {}",
+                                        "Swap {} buffered - Pair: {}, TxSig: {}, waiting for volume enrichment",
                                         op, notif.swap.pair, tx_sig
                                     );
-                                    buffer.remove(&tx_sig);
+                                    buffer.insert(notif.swap.id.clone(), (notif.swap, Instant::now()));
+                                } else {
+                                    // INSERT priced by the indexer, or missing op field (backward compat)
+                                    log::info!(
+                                        "Swap {} - Pair: {}, TxSig: {}, Price: {}, VolumeUSD: {}",
+                                        op, notif.swap.pair, tx_sig, notif.swap.price, notif.swap.volume_usd
+                                    );
                                     emit_swap(&sender, notif.swap);
                                 }
                             }
@@ -154,11 +185,11 @@ pub async fn start_db_listener(
                 let timed_out: Vec<String> = buffer
                     .iter()
                     .filter(|(_, (_, inserted_at))| now.duration_since(*inserted_at) >= dedup_timeout_dur)
-                    .map(|(tx_sig, _)| tx_sig.clone())
+                    .map(|(id, _)| id.clone())
                     .collect();
 
-                for tx_sig in timed_out {
-                    if let Some((swap, _)) = buffer.remove(&tx_sig) {
+                for id in timed_out {
+                    if let Some((swap, _)) = buffer.remove(&id) {
                         log::warn!(
                             "Swap dedup timeout ({}s) - emitting without volume_usd - Pair: {}, TxSig: {}",
                             dedup_timeout_dur.as_secs(), swap.pair, swap.tx_sig
@@ -172,8 +203,17 @@ pub async fn start_db_listener(
 }
 
 /// Emit a swap update to all connected GRPC clients
-fn emit_swap(sender: &broadcast::Sender<SwapsUpdate>, swap: SwapsUpdate) {
-    match sender.send(swap) {
+fn emit_swap(sender: &broadcast::Sender<SwapEvent>, swap: SwapsUpdate) {
+    broadcast(sender, SwapEvent { update: swap, commitment_change: false });
+}
+
+/// Emit a commitment change of an already emitted swap to all connected GRPC clients
+fn emit_commitment_change(sender: &broadcast::Sender<SwapEvent>, swap: SwapsUpdate) {
+    broadcast(sender, SwapEvent { update: swap, commitment_change: true });
+}
+
+fn broadcast(sender: &broadcast::Sender<SwapEvent>, event: SwapEvent) {
+    match sender.send(event) {
         Ok(receiver_count) => {
             log::debug!("Broadcasted to {} gRPC clients", receiver_count);
         }
@@ -1,4 +1,5 @@
 use http;
+use metrics::{counter, describe_counter, describe_gauge, gauge};
 use std::time::Duration;
 use tokio::signal;
 use tokio::sync::broadcast;
@@ -20,12 +21,51 @@ use stream::{
 };
 use tonic_web::GrpcWebLayer;
 
+use crate::db_listener::SwapEvent;
+
+/// Clients currently streaming swap updates
+const CONNECTED_CLIENTS: &str = "grpc_connected_clients";
+/// Broadcast messages skipped by clients that fell behind
+const LAGGED_MESSAGES: &str = "grpc_broadcast_lagged_messages_total";
+/// Closed client streams, labelled by `reason` (`closed` or `lagging`)
+const DISCONNECTS: &str = "grpc_client_disconnects_total";
+
+pub fn describe_metrics() {
+    describe_gauge!(CONNECTED_CLIENTS, "Clients currently streaming swap updates");
+    describe_counter!(LAGGED_MESSAGES, "Broadcast messages skipped by clients that fell behind");
+    describe_counter!(DISCONNECTS, "Closed client streams, by reason");
+}
+
+/// Counts a client stream as connected until it is dropped
+struct ConnectedClient {
+    disconnect_reason: &'static str,
+}
+
+impl ConnectedClient {
+    fn new() -> Self {
+        gauge!(CONNECTED_CLIENTS).increment(1.0);
+        Self { disconnect_reason: "closed" }
+    }
+
+    /// Reason recorded once the stream is dropped, instead of `closed`
+    fn disconnecting(&mut self, reason: &'static str) {
+        self.disconnect_reason = reason;
+    }
+}
+
+impl Drop for ConnectedClient {
+    fn drop(&mut self) {
+        gauge!(CONNECTED_CLIENTS).decrement(1.0);
+        counter!(DISCONNECTS, "reason" => self.disconnect_reason).increment(1);
+    }
+}
+
 pub struct SwapStreamServer {
-    broadcast_tx: broadcast::Sender<SwapsUpdate>,
+    broadcast_tx: broadcast::Sender<SwapEvent>,
 }
 
 impl SwapStreamServer {
-    pub fn new(broadcast_tx: broadcast::Sender<SwapsUpdate>) -> Self {
+    pub fn new(broadcast_tx: broadcast::Sender<SwapEvent>) -> Self {
         Self { broadcast_tx }
     }
 
@@ -45,14 +85,20 @@ impl StreamService for SwapStreamServer {
         request: Request<SwapsRequest>,
     ) -> Result<Response<Self::StreamSwapsUpdatesStream>, Status> {
         let peer_addr = request.remote_addr();
-        log::info!("New gRPC stream connection from {:?}", peer_addr);
+        let finalized_only = request.get_ref().finalized_only;
+        log::info!(
+            "New gRPC stream connection from {:?} (finalized_only: {})",
+            peer_addr,
+            finalized_only
+        );
 
         let rx = self.broadcast_tx.subscribe();
+        let mut client = ConnectedClient::new();
         let mut lag_count = 0u64;
         const MAX_LAG_THRESHOLD: u64 = 1000;
 
         let stream = BroadcastStream::new(rx).filter_map(move |result| match result {
-            Ok(swap_update) => {
+            Ok(swap_event) => {
                 if lag_count > 0 {
                     log::warn!(
                         "Client {:?} recovered from {} lag events",
@@ -61,10 +107,15 @@ impl StreamService for SwapStreamServer {
                     );
                     lag_count = 0;
                 }
-                Some(Ok(swap_update))
+                if is_visible(&swap_event, finalized_only) {
+                    Some(Ok(swap_event.update))
+                } else {
+                    None
+                }
             }
             Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(skipped)) => {
                 lag_count += skipped;
+                counter!(LAGGED_MESSAGES).increment(skipped);
                 log::error!(
                     "Client {:?} lagging: skipped {} messages (total lag: {})",
                     peer_addr,
@@ -77,6 +128,7 @@ impl StreamService for SwapStreamServer {
                         "Client {:?} exceeded lag threshold, disconnecting",
                         peer_addr
                     );
+                    client.disconnecting("lagging");
                     Some(Err(Status::resource_exhausted(
                         "Client too slow, connection terminated",
                     )))
@@ -90,6 +142,18 @@ impl StreamService for SwapStreamServer {
     }
 }
 
+/// Finalized-only clients receive swaps once they are finalized. Other clients receive
+/// swaps as soon as they are confirmed, and are told when one is rolled back.
+fn is_visible(swap_event: &SwapEvent, finalized_only: bool) -> bool {
+    let commitment = swap_event.update.commitment.as_str();
+
+    if finalized_only {
+        commitment == "finalized"
+    } else {
+        !swap_event.commitment_change || commitment == "rolled_back"
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -119,7 +183,7 @@ async fn shutdown_signal() {
 }
 
 pub async fn start_grpc_server(
-    broadcast_tx: broadcast::Sender<SwapsUpdate>,
+    broadcast_tx: broadcast::Sender<SwapEvent>,
     port: u16,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let addr = format!("0.0.0.0:{}", port).parse()?;
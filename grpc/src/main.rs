@@ -1,18 +1,48 @@
-use clap::Parser;
+use std::net::SocketAddr;
+
+use carbon_core::metrics::Metrics;
+use carbon_prometheus_metrics::PrometheusMetrics;
+use clap::{Parser, Subcommand};
 use sqlx::PgPool;
 
 mod db_listener;
 mod grpc_server;
 
-use grpc_server::stream::SwapsUpdate;
+use db_listener::SwapEvent;
 
 #[derive(Parser, Debug)]
 #[command(name = "omnipair-grpc-server")]
 #[command(about = "Standalone gRPC streaming server for Omnipair swap updates")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Port for the gRPC server
     #[arg(long, env = "GRPC_PORT", default_value = "50051")]
     grpc_port: u16,
+
+    /// Address of the Prometheus exporter, e.g. 0.0.0.0:9100 (disabled when unset)
+    #[arg(long, env = "METRICS_ADDRESS")]
+    metrics_address: Option<SocketAddr>,
+
+    /// Apply pending schema migrations on startup instead of refusing to start
+    #[arg(long, env = "AUTO_MIGRATE", default_value_t = false)]
+    auto_migrate: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the schema migration status and apply pending migrations, then exit
+    Migrate {
+        /// Only print the migration status, without applying anything
+        #[arg(long, conflicts_with = "baseline")]
+        status: bool,
+
+        /// Record migrations up to this version as applied without running them, for a
+        /// schema created with the SQL scripts
+        #[arg(long, value_name = "VERSION")]
+        baseline: Option<i64>,
+    },
 }
 
 #[tokio::main]
@@ -37,8 +67,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     sqlx::query("SELECT 1").fetch_one(&pool).await?;
     log::info!("Database connection established successfully");
 
+    if let Some(Command::Migrate { status, baseline }) = args.command {
+        return run_migrate(&pool, status, baseline).await;
+    }
+
+    // Refuse to serve from a schema older than the one the listener expects
+    if let Err(e) = omnipair_migrations::ensure_schema(&pool, args.auto_migrate).await {
+        log::error!("Database schema: {}", e);
+        return Err(e.into());
+    }
+
+    if let Some(address) = args.metrics_address {
+        PrometheusMetrics::new_with_address(address).initialize().await?;
+        grpc_server::describe_metrics();
+    }
+
     // Create broadcast channel for swap updates
-    let (broadcast_tx, _broadcast_rx) = tokio::sync::broadcast::channel::<SwapsUpdate>(100);
+    let (broadcast_tx, _broadcast_rx) = tokio::sync::broadcast::channel::<SwapEvent>(100);
 
     // Start DB listener task
     let listener_tx = broadcast_tx.clone();
@@ -56,3 +101,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Prints the migration status and, unless `status_only`, applies pending migrations (or
+/// records the baseline)
+async fn run_migrate(pool: &PgPool, status_only: bool, baseline: Option<i64>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(version) = baseline {
+        let recorded = omnipair_migrations::baseline(pool, version).await?;
+        log::info!("Baseline recorded {} migrations up to version {}", recorded.len(), version);
+    }
+
+    println!("{}", omnipair_migrations::status(pool).await?);
+
+    if status_only || baseline.is_some() {
+        return Ok(());
+    }
+
+    let applied = omnipair_migrations::migrate(pool).await?;
+    if applied.is_empty() {
+        log::info!("No pending migrations");
+    } else {
+        log::info!("Applied {} migrations", applied.len());
+        print!("{}", omnipair_migrations::status(pool).await?);
+    }
+
+    Ok(())
+}
@@ -194,6 +194,12 @@ impl DatasourceId {
     pub fn new_named(name: &str) -> Self {
         Self(name.to_string())
     }
+
+    /// Returns the name or the generated identifier of the datasource, e.g.
+    /// to label its metrics.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
 /// Represents a data update in the `carbon-core` pipeline, encompassing
@@ -0,0 +1,43 @@
+//! A trait for keeping updates that failed processing.
+//!
+//! Without a dead-letter queue, the pipeline logs an update whose processing
+//! failed, counts it in `updates_failed` and drops it. When a
+//! `DeadLetterQueue` is registered with
+//! `PipelineBuilder::dead_letter_queue`, the failed update is handed to it
+//! together with the datasource it came from and the error, so it can be
+//! persisted and replayed once the cause of the failure is fixed.
+//!
+//! ## Replaying
+//!
+//! Replayed updates go through `Pipeline::process`, which runs a single update
+//! through the pipes of a pipeline without starting its datasources.
+//!
+//! ## Implementing the Trait
+//!
+//! `push` is awaited by the pipeline before the next update is processed, so
+//! implementations should persist the update and return quickly. An error
+//! returned by `push` is logged and counted in `updates_dead_letter_failed`;
+//! it does not stop the pipeline.
+
+use {
+    crate::{
+        datasource::{DatasourceId, Update},
+        error::CarbonResult,
+    },
+    async_trait::async_trait,
+};
+
+/// An update whose processing failed, with the datasource it came from.
+#[derive(Debug, Clone)]
+pub struct FailedUpdate {
+    pub update: Update,
+    pub datasource_id: DatasourceId,
+    /// The error returned by the pipes, formatted with `Debug`.
+    pub error: String,
+}
+
+#[async_trait]
+pub trait DeadLetterQueue: Send + Sync {
+    /// Persists an update that failed processing.
+    async fn push(&self, failed_update: FailedUpdate) -> CarbonResult<()>;
+}
@@ -124,6 +124,7 @@ pub mod account_utils;
 mod block_details;
 pub mod collection;
 pub mod datasource;
+pub mod dead_letter;
 pub mod deserialize;
 pub mod error;
 pub mod filter;
@@ -15,6 +15,10 @@
 //!   successful or failed update processing.
 //! - **Histograms**: Measure the distribution of values, such as processing
 //!   times, allowing insights into latency or response times.
+//! - **Labels**: Key-value pairs attached to a metric, such as the datasource
+//!   or the pipe kind, breaking a metric down into separate series. The
+//!   `*_with_labels` methods default to the unlabelled methods, so backends
+//!   without label support still see the aggregate values.
 //!
 //! ## Implementing the Trait
 //!
@@ -26,6 +30,9 @@
 
 use {crate::error::CarbonResult, async_trait::async_trait, std::sync::Arc};
 
+/// A metric label as a `(key, value)` pair.
+pub type Label<'a> = (&'a str, &'a str);
+
 #[async_trait]
 pub trait Metrics: Send + Sync {
     /// Initializes the metrics system, preparing it for data collection.
@@ -61,6 +68,43 @@ pub trait Metrics: Send + Sync {
     /// - `value`: The value to add to the histogram, typically representing
     ///   time or size.
     async fn record_histogram(&self, name: &str, value: f64) -> CarbonResult<()>;
+
+    /// Updates the series of a gauge metric identified by `labels`.
+    ///
+    /// Defaults to `update_gauge`, ignoring the labels.
+    async fn update_gauge_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        _labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        self.update_gauge(name, value).await
+    }
+
+    /// Increments the series of a counter metric identified by `labels`.
+    ///
+    /// Defaults to `increment_counter`, ignoring the labels.
+    async fn increment_counter_with_labels(
+        &self,
+        name: &str,
+        value: u64,
+        _labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        self.increment_counter(name, value).await
+    }
+
+    /// Records a value in the series of a histogram metric identified by
+    /// `labels`.
+    ///
+    /// Defaults to `record_histogram`, ignoring the labels.
+    async fn record_histogram_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        _labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        self.record_histogram(name, value).await
+    }
 }
 
 #[derive(Default)]
@@ -114,4 +158,107 @@ impl MetricsCollection {
         }
         Ok(())
     }
+
+    pub async fn update_gauge_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        for metric in &self.metrics {
+            metric.update_gauge_with_labels(name, value, labels).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn increment_counter_with_labels(
+        &self,
+        name: &str,
+        value: u64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        for metric in &self.metrics {
+            metric
+                .increment_counter_with_labels(name, value, labels)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn record_histogram_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        for metric in &self.metrics {
+            metric
+                .record_histogram_with_labels(name, value, labels)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::Mutex};
+
+    /// Records the unlabelled calls only, relying on the default labelled
+    /// methods
+    #[derive(Default)]
+    struct UnlabelledMetrics {
+        counters: Mutex<Vec<(String, u64)>>,
+    }
+
+    #[async_trait]
+    impl Metrics for UnlabelledMetrics {
+        async fn initialize(&self) -> CarbonResult<()> {
+            Ok(())
+        }
+
+        async fn flush(&self) -> CarbonResult<()> {
+            Ok(())
+        }
+
+        async fn shutdown(&self) -> CarbonResult<()> {
+            Ok(())
+        }
+
+        async fn update_gauge(&self, _name: &str, _value: f64) -> CarbonResult<()> {
+            Ok(())
+        }
+
+        async fn increment_counter(&self, name: &str, value: u64) -> CarbonResult<()> {
+            self.counters
+                .lock()
+                .unwrap()
+                .push((name.to_string(), value));
+            Ok(())
+        }
+
+        async fn record_histogram(&self, _name: &str, _value: f64) -> CarbonResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn labelled_metrics_default_to_unlabelled() {
+        let metrics = Arc::new(UnlabelledMetrics::default());
+        let collection = MetricsCollection::new(vec![metrics.clone()]);
+
+        collection
+            .increment_counter_with_labels(
+                "updates_failed",
+                2,
+                &[("datasource", "helius"), ("pipe", "transaction")],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *metrics.counters.lock().unwrap(),
+            vec![("updates_failed".to_string(), 2)]
+        );
+    }
 }
@@ -52,6 +52,7 @@
 
 use crate::block_details::{BlockDetailsPipe, BlockDetailsPipes};
 use crate::datasource::{BlockDetails, DatasourceId};
+use crate::dead_letter::{DeadLetterQueue, FailedUpdate};
 use crate::filter::Filter;
 use {
     crate::{
@@ -220,6 +221,7 @@ pub struct Pipeline {
     pub datasource_cancellation_token: Option<CancellationToken>,
     pub shutdown_strategy: ShutdownStrategy,
     pub channel_buffer_size: usize,
+    pub dead_letter_queue: Option<Arc<dyn DeadLetterQueue>>,
 }
 
 impl Pipeline {
@@ -266,6 +268,7 @@ impl Pipeline {
             datasource_cancellation_token: None,
             shutdown_strategy: ShutdownStrategy::default(),
             channel_buffer_size: DEFAULT_CHANNEL_BUFFER_SIZE,
+            dead_letter_queue: None,
         }
     }
 
@@ -402,8 +405,11 @@ impl Pipeline {
                 update = update_receiver.recv() => {
                     match update {
                         Some((update, datasource_id)) => {
+                            let pipe = pipe_kind(&update);
+                            let labels = [("datasource", datasource_id.as_str()), ("pipe", pipe)];
+
                             self
-                                .metrics.increment_counter("updates_received", 1)
+                                .metrics.increment_counter_with_labels("updates_received", 1, &labels)
                                 .await?;
 
                             let start = Instant::now();
@@ -413,30 +419,48 @@ impl Pipeline {
 
                             self
                                 .metrics
-                                .record_histogram("updates_process_time_nanoseconds", time_taken_nanoseconds as f64)
+                                .record_histogram_with_labels("updates_process_time_nanoseconds", time_taken_nanoseconds as f64, &labels)
                                 .await?;
 
                             self
                                 .metrics
-                                .record_histogram("updates_process_time_milliseconds", time_taken_milliseconds as f64)
+                                .record_histogram_with_labels("updates_process_time_milliseconds", time_taken_milliseconds as f64, &labels)
                                 .await?;
 
                             match process_result {
                                 Ok(_) => {
                                     self
-                                        .metrics.increment_counter("updates_successful", 1)
+                                        .metrics.increment_counter_with_labels("updates_successful", 1, &labels)
                                         .await?;
 
                                     log::trace!("processed update")
                                 }
                                 Err(error) => {
                                     log::error!("error processing update ({:?}): {:?}", update, error);
-                                    self.metrics.increment_counter("updates_failed", 1).await?;
+                                    self.metrics.increment_counter_with_labels("updates_failed", 1, &labels).await?;
+
+                                    if let Some(dead_letter_queue) = &self.dead_letter_queue {
+                                        let failed_update = FailedUpdate {
+                                            update,
+                                            datasource_id: datasource_id.clone(),
+                                            error: format!("{:?}", error),
+                                        };
+
+                                        match dead_letter_queue.push(failed_update).await {
+                                            Ok(()) => {
+                                                self.metrics.increment_counter_with_labels("updates_dead_lettered", 1, &labels).await?;
+                                            }
+                                            Err(dead_letter_error) => {
+                                                log::error!("error storing failed update in the dead-letter queue: {:?}", dead_letter_error);
+                                                self.metrics.increment_counter_with_labels("updates_dead_letter_failed", 1, &labels).await?;
+                                            }
+                                        }
+                                    }
                                 }
                             };
 
                             self
-                                .metrics.increment_counter("updates_processed", 1)
+                                .metrics.increment_counter_with_labels("updates_processed", 1, &labels)
                                 .await?;
 
                             self
@@ -513,7 +537,10 @@ impl Pipeline {
     /// Returns an error if any of the pipes fail during processing, or if an
     /// issue arises while incrementing counters or updating metrics. Handle
     /// errors gracefully to ensure continuous pipeline operation.
-    async fn process(&mut self, update: Update, datasource_id: DatasourceId) -> CarbonResult<()> {
+    ///
+    /// `process` can also be called outside of `run`, without datasources, to
+    /// replay updates kept by a [`DeadLetterQueue`].
+    pub async fn process(&mut self, update: Update, datasource_id: DatasourceId) -> CarbonResult<()> {
         log::trace!(
             "process(self, update: {:?}, datasource_id: {:?})",
             update,
@@ -544,7 +571,11 @@ impl Pipeline {
                 }
 
                 self.metrics
-                    .increment_counter("account_updates_processed", 1)
+                    .increment_counter_with_labels(
+                        "account_updates_processed",
+                        1,
+                        &[("datasource", datasource_id.as_str())],
+                    )
                     .await?;
             }
             Update::Transaction(transaction_update) => {
@@ -586,7 +617,11 @@ impl Pipeline {
                 }
 
                 self.metrics
-                    .increment_counter("transaction_updates_processed", 1)
+                    .increment_counter_with_labels(
+                        "transaction_updates_processed",
+                        1,
+                        &[("datasource", datasource_id.as_str())],
+                    )
                     .await?;
             }
             Update::AccountDeletion(account_deletion) => {
@@ -600,7 +635,11 @@ impl Pipeline {
                 }
 
                 self.metrics
-                    .increment_counter("account_deletions_processed", 1)
+                    .increment_counter_with_labels(
+                        "account_deletions_processed",
+                        1,
+                        &[("datasource", datasource_id.as_str())],
+                    )
                     .await?;
             }
             Update::BlockDetails(block_details) => {
@@ -616,7 +655,11 @@ impl Pipeline {
                 }
 
                 self.metrics
-                    .increment_counter("block_details_processed", 1)
+                    .increment_counter_with_labels(
+                        "block_details_processed",
+                        1,
+                        &[("datasource", datasource_id.as_str())],
+                    )
                     .await?;
             }
         };
@@ -625,6 +668,17 @@ impl Pipeline {
     }
 }
 
+/// Returns the kind of pipes `update` is routed to, used to label the
+/// pipeline metrics.
+fn pipe_kind(update: &Update) -> &'static str {
+    match update {
+        Update::Account(_) => "account",
+        Update::Transaction(_) => "transaction",
+        Update::AccountDeletion(_) => "account_deletion",
+        Update::BlockDetails(_) => "block_details",
+    }
+}
+
 /// A builder for constructing a `Pipeline` instance with customized data
 /// sources, processing pipes, and metrics.
 ///
@@ -715,6 +769,7 @@ pub struct PipelineBuilder {
     pub datasource_cancellation_token: Option<CancellationToken>,
     pub shutdown_strategy: ShutdownStrategy,
     pub channel_buffer_size: usize,
+    pub dead_letter_queue: Option<Arc<dyn DeadLetterQueue>>,
 }
 
 impl PipelineBuilder {
@@ -1367,6 +1422,23 @@ impl PipelineBuilder {
         self
     }
 
+    /// Sets the dead-letter queue receiving updates that fail processing.
+    ///
+    /// Without a dead-letter queue, a failed update is logged and dropped.
+    /// With one, it is pushed to the queue together with its datasource ID and
+    /// the error, so it can be replayed with [`Pipeline::process`] once the
+    /// cause of the failure is fixed.
+    ///
+    /// # Parameters
+    ///
+    /// - `dead_letter_queue`: An `Arc<dyn DeadLetterQueue>` persisting the
+    ///   failed updates.
+    pub fn dead_letter_queue(mut self, dead_letter_queue: Arc<dyn DeadLetterQueue>) -> Self {
+        log::trace!("dead_letter_queue(self, dead_letter_queue)");
+        self.dead_letter_queue = Some(dead_letter_queue);
+        self
+    }
+
     /// Builds and returns a `Pipeline` configured with the specified
     /// components.
     ///
@@ -1417,6 +1489,7 @@ impl PipelineBuilder {
             metrics_flush_interval: self.metrics_flush_interval,
             datasource_cancellation_token: self.datasource_cancellation_token,
             channel_buffer_size: self.channel_buffer_size,
+            dead_letter_queue: self.dead_letter_queue,
         })
     }
 }
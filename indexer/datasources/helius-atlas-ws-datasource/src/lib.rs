@@ -27,10 +27,16 @@ use {
     std::{
         collections::HashSet,
         str::FromStr,
-        sync::Arc,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
         time::{Duration, Instant},
     },
-    tokio::sync::{mpsc::Sender, RwLock},
+    tokio::sync::{
+        mpsc::{Sender, UnboundedSender},
+        RwLock,
+    },
     tokio_util::sync::CancellationToken,
 };
 
@@ -62,11 +68,21 @@ impl Filters {
     }
 }
 
+/// A slot range in which streamed updates may have been missed, either because the
+/// Clock sysvar jumped or because the websocket had to reconnect. Both bounds are
+/// inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotGap {
+    pub from_slot: u64,
+    pub to_slot: u64,
+}
+
 pub struct HeliusWebsocket {
     pub api_key: String,
     pub filters: Filters,
     pub account_deletions_tracked: Arc<RwLock<HashSet<Pubkey>>>,
     pub cluster: Cluster,
+    pub gap_sender: Option<UnboundedSender<SlotGap>>,
 }
 
 impl HeliusWebsocket {
@@ -81,9 +97,17 @@ impl HeliusWebsocket {
             filters,
             account_deletions_tracked,
             cluster,
+            gap_sender: None,
         }
     }
 
+    /// Reports every detected slot gap on `gap_sender`, so the missed range can be
+    /// filled from another source.
+    pub fn with_gap_sender(mut self, gap_sender: UnboundedSender<SlotGap>) -> Self {
+        self.gap_sender = Some(gap_sender);
+        self
+    }
+
     const fn get_ws_url(cluster: &Cluster) -> &'static str {
         match cluster {
             Cluster::MainnetBeta => MAINNET_WS_URL,
@@ -108,6 +132,9 @@ impl Datasource for HeliusWebsocket {
 
         let id = id.clone();
 
+        // Last slot seen on the Clock sysvar, kept across reconnections to detect gaps
+        let last_seen_slot = Arc::new(AtomicU64::new(0));
+
         loop {
             if cancellation_token.is_cancelled() {
                 log::info!("Cancellation requested, stopping reconnection attempts");
@@ -158,7 +185,9 @@ impl Datasource for HeliusWebsocket {
             let filters = self.filters.clone();
             let sender = sender.clone();
             let helius = Arc::new(helius);
-            let metrics = Arc::clone(&metrics);
+            let iteration_metrics = Arc::clone(&metrics);
+            let gap_sender = self.gap_sender.clone();
+            let last_seen_slot = Arc::clone(&last_seen_slot);
 
             let iteration_cancellation = CancellationToken::new();
             let iteration_cancellation_clone = iteration_cancellation.clone();
@@ -167,6 +196,7 @@ impl Datasource for HeliusWebsocket {
             let id_for_loop = id.clone();
 
             let handle = tokio::spawn(async move {
+                let metrics = iteration_metrics;
                 let mut handles = vec![];
 
                 // Clock subscription
@@ -174,6 +204,8 @@ impl Datasource for HeliusWebsocket {
                 let iteration_cancellation_clock = iteration_cancellation.clone();
                 let helius_clone = Arc::clone(&helius);
                 let metrics_clone = Arc::clone(&metrics);
+                let last_seen_slot = Arc::clone(&last_seen_slot);
+                let gap_sender = gap_sender.clone();
 
                 let handle = tokio::spawn(async move {
                     let ws = match helius_clone.ws() {
@@ -197,9 +229,23 @@ impl Datasource for HeliusWebsocket {
                         }
                     };
 
-                    let mut last_slot = 0u64;
+                    metrics_clone
+                        .update_gauge("helius_atlas_ws_connected", 1.0)
+                        .await
+                        .unwrap_or_else(|value| log::error!("Error recording metric: {}", value));
+
+                    let mut last_slot = last_seen_slot.load(Ordering::Acquire);
+                    let mut first_update = true;
                     let mut last_clock_update = Instant::now();
 
+                    let report_gap = |from_slot: u64, to_slot: u64| {
+                        if let Some(gap_sender) = &gap_sender
+                            && let Err(err) = gap_sender.send(SlotGap { from_slot, to_slot })
+                        {
+                            log::error!("Failed to report slot gap {}..={}: {:?}", from_slot, to_slot, err);
+                        }
+                    };
+
                     loop {
                         tokio::select! {
                             _ = cancellation_token_clock.cancelled() => {
@@ -228,10 +274,30 @@ impl Datasource for HeliusWebsocket {
                                                         "Detected large slot gap: last_slot={}, current_slot={}, gap={}",
                                                         last_slot, current_slot, current_slot - last_slot
                                                     );
+                                                    report_gap(last_slot, current_slot);
+                                                    last_seen_slot.store(current_slot, Ordering::Release);
                                                     iteration_cancellation_clock.cancel();
                                                     return;
                                                 }
+
+                                                // Anything between the last slot before a reconnection and the
+                                                // first slot after it may have been missed
+                                                if first_update && last_slot > 0 && current_slot > last_slot {
+                                                    log::info!(
+                                                        "Reconnected after slot {}, resuming at slot {}",
+                                                        last_slot, current_slot
+                                                    );
+                                                    report_gap(last_slot, current_slot);
+                                                }
+
+                                                first_update = false;
                                                 last_slot = current_slot;
+                                                last_seen_slot.store(current_slot, Ordering::Release);
+
+                                                metrics_clone
+                                                    .update_gauge("helius_atlas_ws_slot", current_slot as f64)
+                                                    .await
+                                                    .unwrap_or_else(|value| log::error!("Error recording metric: {}", value));
 
                                                 metrics_clone
                                                     .record_histogram(
@@ -655,6 +721,16 @@ impl Datasource for HeliusWebsocket {
                 }
             }
 
+            metrics
+                .update_gauge("helius_atlas_ws_connected", 0.0)
+                .await
+                .unwrap_or_else(|value| log::error!("Error recording metric: {}", value));
+
+            metrics
+                .increment_counter("helius_atlas_ws_reconnects", 1)
+                .await
+                .unwrap_or_else(|value| log::error!("Error recording metric: {}", value));
+
             reconnection_attempts = 0;
             tokio::time::sleep(Duration::from_millis(RECONNECTION_DELAY_MS)).await;
         }
@@ -18,6 +18,7 @@ use {
 
 const MAX_RECONNECTION_ATTEMPTS: u32 = 10;
 const RECONNECTION_DELAY_MS: u64 = 3000;
+const DEFAULT_NAME: &str = "program_subscribe";
 
 #[derive(Debug, Clone)]
 pub struct Filters {
@@ -40,15 +41,23 @@ impl Filters {
 pub struct RpcProgramSubscribe {
     pub rpc_ws_url: String,
     pub filters: Filters,
+    /// Reported in the `<name>_connected` gauge, unique per subscription of a pipeline
+    pub name: String,
 }
 
 impl RpcProgramSubscribe {
-    pub const fn new(rpc_ws_url: String, filters: Filters) -> Self {
+    pub fn new(rpc_ws_url: String, filters: Filters) -> Self {
         Self {
             rpc_ws_url,
             filters,
+            name: DEFAULT_NAME.to_string(),
         }
     }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
 }
 
 #[async_trait]
@@ -61,6 +70,7 @@ impl Datasource for RpcProgramSubscribe {
         metrics: Arc<MetricsCollection>,
     ) -> CarbonResult<()> {
         let mut reconnection_attempts = 0;
+        let connected_gauge = format!("{}_connected", self.name);
 
         loop {
             if cancellation_token.is_cancelled() {
@@ -109,6 +119,11 @@ impl Datasource for RpcProgramSubscribe {
 
             reconnection_attempts = 0;
 
+            metrics
+                .update_gauge(&connected_gauge, 1.0)
+                .await
+                .unwrap_or_else(|value| log::error!("Error recording metric: {}", value));
+
             loop {
                 tokio::select! {
                     _ = cancellation_token.cancelled() => {
@@ -165,6 +180,11 @@ impl Datasource for RpcProgramSubscribe {
                 }
             }
 
+            metrics
+                .update_gauge(&connected_gauge, 0.0)
+                .await
+                .unwrap_or_else(|value| log::error!("Error recording metric: {}", value));
+
             tokio::time::sleep(Duration::from_millis(RECONNECTION_DELAY_MS)).await;
         }
 
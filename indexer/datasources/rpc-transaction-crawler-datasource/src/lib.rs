@@ -140,6 +140,7 @@ pub struct RpcTransactionCrawler {
     pub connection_config: ConnectionConfig,
     pub filters: Filters,
     pub commitment: Option<CommitmentConfig>,
+    pub stop_when_exhausted: bool,
 }
 
 impl RpcTransactionCrawler {
@@ -156,8 +157,18 @@ impl RpcTransactionCrawler {
             connection_config,
             filters,
             commitment,
+            stop_when_exhausted: false,
         }
     }
+
+    /// Stops crawling once every signature in the filtered range has been
+    /// fetched, instead of polling for new transactions. The datasource closes
+    /// its update channel afterwards, so a pipeline with no other datasources
+    /// completes on its own.
+    pub const fn with_stop_when_exhausted(mut self, stop_when_exhausted: bool) -> Self {
+        self.stop_when_exhausted = stop_when_exhausted;
+        self
+    }
 }
 
 #[async_trait]
@@ -196,6 +207,7 @@ impl Datasource for RpcTransactionCrawler {
             signature_sender,
             filters.clone(),
             commitment,
+            self.stop_when_exhausted,
             cancellation_token.clone(),
             metrics.clone(),
         );
@@ -244,6 +256,7 @@ fn signature_fetcher(
     signature_sender: Sender<Signature>,
     filters: Filters,
     commitment: Option<CommitmentConfig>,
+    stop_when_exhausted: bool,
     cancellation_token: CancellationToken,
     metrics: Arc<MetricsCollection>,
 ) -> JoinHandle<()> {
@@ -255,7 +268,13 @@ fn signature_fetcher(
         let mut last_fetched_signature = filters.before_signature;
         let mut until_signature = filters.until_signature;
         let mut most_recent_signature: Option<Signature> = None;
+        let mut exhausted = false;
         loop {
+            if exhausted {
+                log::info!("RPC Crawler signature fetcher reached the end of the range");
+                break;
+            }
+
             tokio::select! {
                 _ = cancellation_token.cancelled() => {
                     log::info!("Cancelling RPC Crawler signature fetcher...");
@@ -278,6 +297,11 @@ fn signature_fetcher(
                             Ok(signatures) => {
                                 let start = Instant::now();
 
+                                if signatures.is_empty() && stop_when_exhausted {
+                                    exhausted = true;
+                                    break;
+                                }
+
                                 if signatures.is_empty() {
                                     // no more signatures to fetch, so we've gone through
                                     // all transactions that have been sent up until we started polling for signatures
@@ -490,7 +514,12 @@ fn task_processor(
                     log::info!("Cancelling RPC Crawler task processor...");
                     break;
                 }
-                Some((signature, fetched_transaction)) = transaction_receiver.recv() => {
+                received = transaction_receiver.recv() => {
+                    let Some((signature, fetched_transaction)) = received else {
+                        log::info!("RPC Crawler transaction channel closed, stopping task processor...");
+                        break;
+                    };
+
                     let start = Instant::now();
                     let transaction = fetched_transaction.transaction;
 
@@ -1,6 +1,9 @@
 use {
     async_trait::async_trait,
-    carbon_core::{error::CarbonResult, metrics::Metrics},
+    carbon_core::{
+        error::CarbonResult,
+        metrics::{Label, Metrics},
+    },
     std::{collections::HashMap, time::Instant},
     tokio::sync::RwLock,
 };
@@ -50,6 +53,18 @@ impl LogMetrics {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Name of the series of `name` identified by `labels`, e.g.
+    /// `updates_failed{datasource="helius",pipe="transaction"}`
+    fn series_name(name: &str, labels: &[Label<'_>]) -> String {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, value))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!("{}{{{}}}", name, labels)
+    }
 }
 
 #[async_trait]
@@ -226,4 +241,60 @@ impl Metrics for LogMetrics {
 
         Ok(())
     }
+
+    /// Counts towards the unlabelled total and the labelled series, which is
+    /// printed on flush
+    async fn increment_counter_with_labels(
+        &self,
+        name: &str,
+        value: u64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        self.increment_counter(name, value).await?;
+
+        if !labels.is_empty() {
+            let mut counters = self.counters.write().await;
+            *counters.entry(Self::series_name(name, labels)).or_insert(0) += value;
+        }
+
+        Ok(())
+    }
+
+    async fn update_gauge_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        if labels.is_empty() {
+            return self.update_gauge(name, value).await;
+        }
+
+        // The series of a gauge do not add up, so only the labelled one is set
+        self.gauges
+            .write()
+            .await
+            .insert(Self::series_name(name, labels), value);
+
+        Ok(())
+    }
+
+    async fn record_histogram_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        self.record_histogram(name, value).await?;
+
+        if !labels.is_empty() {
+            let mut histograms = self.histograms.write().await;
+            histograms
+                .entry(Self::series_name(name, labels))
+                .or_insert(Vec::new())
+                .push(value);
+        }
+
+        Ok(())
+    }
 }
@@ -3,72 +3,124 @@ use {
     async_trait::async_trait,
     carbon_core::{
         error::{CarbonResult, Error},
-        metrics::Metrics,
+        metrics::{Label, Metrics},
     },
     metrics::{counter, gauge, histogram},
-    metrics_exporter_prometheus::PrometheusBuilder,
-    std::{collections::HashMap, sync::Once},
+    metrics_exporter_prometheus::{Matcher, PrometheusBuilder},
+    std::{collections::HashMap, sync::Mutex},
     tokio::sync::RwLock,
 };
 
+/// Address of the exporter installed in this process. The `metrics` recorder
+/// is global, so only one exporter can be installed per process.
+static INSTALLED_ADDRESS: Mutex<Option<SocketAddr>> = Mutex::new(None);
+
+/// Exports the pipeline metrics on a Prometheus scrape endpoint. Handles are
+/// kept per series, i.e. per metric name and label set.
+///
+/// Histograms are exported as summaries unless buckets are configured with
+/// `with_buckets` or `with_metric_buckets`.
 pub struct PrometheusMetrics {
     pub counters: RwLock<HashMap<String, metrics::Counter>>,
     pub gauges: RwLock<HashMap<String, metrics::Gauge>>,
     pub histograms: RwLock<HashMap<String, metrics::Histogram>>,
-    pub listen_port: u16,
+    pub listen_address: SocketAddr,
+    /// Buckets of every histogram without buckets of its own
+    pub buckets: Option<Vec<f64>>,
+    /// Buckets of individual histograms, by metric name
+    pub metric_buckets: Vec<(String, Vec<f64>)>,
 }
 
 impl Default for PrometheusMetrics {
     fn default() -> Self {
-        Self {
-            counters: RwLock::new(HashMap::new()),
-            gauges: RwLock::new(HashMap::new()),
-            histograms: RwLock::new(HashMap::new()),
-            listen_port: 9100,
-        }
+        Self::new_with_port(9100)
     }
 }
+
 impl PrometheusMetrics {
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Listens on `127.0.0.1:<listen_port>`
     pub fn new_with_port(listen_port: u16) -> Self {
+        Self::new_with_address(SocketAddr::from(([127, 0, 0, 1], listen_port)))
+    }
+
+    /// Listens on `listen_address`, e.g. `0.0.0.0:9100` to be scraped from
+    /// outside a container
+    pub fn new_with_address(listen_address: SocketAddr) -> Self {
         Self {
             gauges: RwLock::new(HashMap::new()),
             counters: RwLock::new(HashMap::new()),
             histograms: RwLock::new(HashMap::new()),
-            listen_port,
+            listen_address,
+            buckets: None,
+            metric_buckets: Vec::new(),
         }
     }
+
+    /// Exports every histogram with these buckets
+    pub fn with_buckets(mut self, buckets: Vec<f64>) -> Self {
+        self.buckets = Some(buckets);
+        self
+    }
+
+    /// Exports the histogram `name` with these buckets
+    pub fn with_metric_buckets(mut self, name: &str, buckets: Vec<f64>) -> Self {
+        self.metric_buckets.push((name.to_string(), buckets));
+        self
+    }
+
+    fn builder(&self) -> Result<PrometheusBuilder, Error> {
+        let invalid_buckets =
+            |e| Error::Custom(format!("Invalid Prometheus histogram buckets: {}", e));
+
+        let mut builder = PrometheusBuilder::new().with_http_listener(self.listen_address);
+
+        if let Some(buckets) = &self.buckets {
+            builder = builder.set_buckets(buckets).map_err(invalid_buckets)?;
+        }
+
+        for (name, buckets) in &self.metric_buckets {
+            builder = builder
+                .set_buckets_for_metric(Matcher::Full(name.clone()), buckets)
+                .map_err(invalid_buckets)?;
+        }
+
+        Ok(builder)
+    }
 }
 
 #[async_trait]
 impl Metrics for PrometheusMetrics {
+    /// Installs the exporter on the first call. Later calls, e.g. from a
+    /// restarted pipeline, are no-ops as long as they use the same address.
     async fn initialize(&self) -> CarbonResult<()> {
-        static INIT: Once = Once::new();
-
-        let mut result = Ok(());
-        INIT.call_once(|| {
-            let addr = format!("127.0.0.1:{}", self.listen_port)
-                .parse::<SocketAddr>()
-                .expect("Failed to parse address");
-
-            let builder = PrometheusBuilder::new().with_http_listener(addr);
-
-            match builder.install() {
-                Ok(_handle) => {
-                    log::info!("Prometheus exporter installed and listening on {}", addr);
-                }
-                Err(e) => {
-                    result = Err(Error::Custom(format!(
-                        "Failed to install Prometheus exporter: {}",
-                        e
-                    )));
-                }
+        let mut installed = INSTALLED_ADDRESS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match *installed {
+            Some(address) if address == self.listen_address => Ok(()),
+            Some(address) => Err(Error::Custom(format!(
+                "A Prometheus exporter is already listening on {}, cannot listen on {}",
+                address, self.listen_address
+            ))),
+            None => {
+                self.builder()?.install().map_err(|e| {
+                    Error::Custom(format!("Failed to install Prometheus exporter: {}", e))
+                })?;
+
+                log::info!(
+                    "Prometheus exporter installed and listening on {}",
+                    self.listen_address
+                );
+                *installed = Some(self.listen_address);
+
+                Ok(())
             }
-        });
-        result
+        }
     }
 
     async fn flush(&self) -> CarbonResult<()> {
@@ -80,44 +132,93 @@ impl Metrics for PrometheusMetrics {
     }
 
     async fn update_gauge(&self, name: &str, value: f64) -> CarbonResult<()> {
+        self.update_gauge_with_labels(name, value, &[]).await
+    }
+
+    async fn increment_counter(&self, name: &str, value: u64) -> CarbonResult<()> {
+        self.increment_counter_with_labels(name, value, &[]).await
+    }
+
+    async fn record_histogram(&self, name: &str, value: f64) -> CarbonResult<()> {
+        self.record_histogram_with_labels(name, value, &[]).await
+    }
+
+    async fn update_gauge_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        let key = series_key(name, labels);
         let mut gauge = self.gauges.write().await;
 
-        if let Some(gauge) = gauge.get(name) {
+        if let Some(gauge) = gauge.get(&key) {
             gauge.set(value);
         } else {
-            let new_gauge = gauge!(name.to_string());
+            let new_gauge = gauge!(name.to_string(), prometheus_labels(labels));
             new_gauge.set(value);
-            gauge.insert(name.to_string(), new_gauge);
+            gauge.insert(key, new_gauge);
         }
 
         Ok(())
     }
 
-    async fn increment_counter(&self, name: &str, value: u64) -> CarbonResult<()> {
+    async fn increment_counter_with_labels(
+        &self,
+        name: &str,
+        value: u64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        let key = series_key(name, labels);
         let mut counter = self.counters.write().await;
 
-        if let Some(counter) = counter.get(name) {
+        if let Some(counter) = counter.get(&key) {
             counter.increment(value);
         } else {
-            let new_counter = counter!(name.to_string());
+            let new_counter = counter!(name.to_string(), prometheus_labels(labels));
             new_counter.increment(value);
-            counter.insert(name.to_string(), new_counter);
+            counter.insert(key, new_counter);
         }
 
         Ok(())
     }
 
-    async fn record_histogram(&self, name: &str, value: f64) -> CarbonResult<()> {
+    async fn record_histogram_with_labels(
+        &self,
+        name: &str,
+        value: f64,
+        labels: &[Label<'_>],
+    ) -> CarbonResult<()> {
+        let key = series_key(name, labels);
         let mut histogram = self.histograms.write().await;
 
-        if let Some(histogram) = histogram.get(name) {
+        if let Some(histogram) = histogram.get(&key) {
             histogram.record(value);
         } else {
-            let new_histogram = histogram!(name.to_string());
+            let new_histogram = histogram!(name.to_string(), prometheus_labels(labels));
             new_histogram.record(value);
-            histogram.insert(name.to_string(), new_histogram);
+            histogram.insert(key, new_histogram);
         }
 
         Ok(())
     }
 }
+
+/// Key of the series of `name` identified by `labels` in the handle maps
+fn series_key(name: &str, labels: &[Label<'_>]) -> String {
+    let mut key = name.to_string();
+    for (label, value) in labels {
+        key.push('\0');
+        key.push_str(label);
+        key.push('=');
+        key.push_str(value);
+    }
+    key
+}
+
+fn prometheus_labels(labels: &[Label<'_>]) -> Vec<metrics::Label> {
+    labels
+        .iter()
+        .map(|(key, value)| metrics::Label::new(key.to_string(), value.to_string()))
+        .collect()
+}
@@ -0,0 +1,446 @@
+//! Historical backfill.
+//!
+//! A backfill resolves the requested range (slots, signatures or a time range) to the
+//! `before`/`until` signature bounds understood by `getSignaturesForAddress`, then runs
+//! the RPC transaction crawler over exactly that range through the regular
+//! `OmnipairInstructionProcessor`. The crawler stops once the range is exhausted, which
+//! closes the pipeline and ends the command.
+//!
+//! Bounds are resolved once, when the command starts. Without an end bound the range
+//! extends to the chain tip, so transactions landing while the backfill runs are indexed
+//! as well. All writes are idempotent, so overlapping a live indexer is safe.
+//!
+//! Rows are written in batches of `--batch-size` transactions with multi-row inserts.
+
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use carbon_core::{error::CarbonResult, metrics::Metrics, pipeline::Pipeline};
+use carbon_omnipair_decoder::OmnipairDecoder;
+use carbon_rpc_transaction_crawler_datasource::{
+    ConnectionConfig, Filters as TransactionFilters, RetryConfig, RpcTransactionCrawler,
+};
+use chrono::{DateTime, Utc};
+use clap::Args as ClapArgs;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_commitment_config::CommitmentConfig;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+
+use crate::{
+    block_time::BlockTimeResolver,
+    checkpoints::{CheckpointProcessor, BACKFILL_DATASOURCE},
+    event_writer::{EventWriter, WriteMode},
+    notifier::{Notifier, NotifierConfig},
+    pricing::SwapPricer,
+    processors::OmnipairInstructionProcessor,
+    store::EventStore,
+};
+
+/// Maximum page size accepted by `getSignaturesForAddress`
+const SIGNATURE_PAGE_LIMIT: usize = 1000;
+
+/// Longest time committed transactions wait for a full batch
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(5);
+
+/// Range selection for the `backfill` subcommand. Each side of the range is optional
+/// and accepts at most one kind of bound; all bounds are inclusive.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct BackfillArgs {
+    /// First slot to index
+    #[arg(long, conflicts_with_all = ["start_signature", "start_time"])]
+    pub start_slot: Option<u64>,
+
+    /// Last slot to index
+    #[arg(long, conflicts_with_all = ["end_signature", "end_time"])]
+    pub end_slot: Option<u64>,
+
+    /// Oldest transaction signature to index
+    #[arg(long, conflicts_with = "start_time")]
+    pub start_signature: Option<String>,
+
+    /// Newest transaction signature to index
+    #[arg(long, conflicts_with = "end_time")]
+    pub end_signature: Option<String>,
+
+    /// Start of the time range to index (RFC 3339, e.g. 2025-01-01T00:00:00Z)
+    #[arg(long)]
+    pub start_time: Option<DateTime<Utc>>,
+
+    /// End of the time range to index (RFC 3339)
+    #[arg(long)]
+    pub end_time: Option<DateTime<Utc>>,
+
+    /// Number of transactions fetched concurrently
+    #[arg(long, default_value_t = 5)]
+    pub concurrency: usize,
+
+    /// Number of transactions written per database transaction
+    #[arg(long, default_value_t = 500)]
+    pub batch_size: usize,
+}
+
+/// One side of a backfill range
+#[derive(Debug, Clone, PartialEq)]
+enum Bound {
+    Slot(u64),
+    Signature(Signature),
+    Time(DateTime<Utc>),
+}
+
+/// Inclusive backfill range, newest bound first as the crawler walks backwards
+#[derive(Debug, Clone, Default)]
+pub struct BackfillRange {
+    start: Option<Bound>,
+    end: Option<Bound>,
+}
+
+impl BackfillRange {
+    pub fn from_args(args: &BackfillArgs) -> Result<Self, String> {
+        let parse_signature = |signature: &str| {
+            Signature::from_str(signature).map_err(|e| format!("Invalid signature '{}': {}", signature, e))
+        };
+
+        let start = match (args.start_slot, &args.start_signature, args.start_time) {
+            (Some(slot), _, _) => Some(Bound::Slot(slot)),
+            (_, Some(signature), _) => Some(Bound::Signature(parse_signature(signature)?)),
+            (_, _, Some(time)) => Some(Bound::Time(time)),
+            _ => None,
+        };
+        let end = match (args.end_slot, &args.end_signature, args.end_time) {
+            (Some(slot), _, _) => Some(Bound::Slot(slot)),
+            (_, Some(signature), _) => Some(Bound::Signature(parse_signature(signature)?)),
+            (_, _, Some(time)) => Some(Bound::Time(time)),
+            _ => None,
+        };
+
+        match (&start, &end) {
+            (Some(Bound::Slot(start)), Some(Bound::Slot(end))) if start > end => {
+                Err(format!("--start-slot {} is after --end-slot {}", start, end))
+            }
+            (Some(Bound::Time(start)), Some(Bound::Time(end))) if start > end => {
+                Err(format!("--start-time {} is after --end-time {}", start, end))
+            }
+            _ => Ok(Self { start, end }),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let describe = |bound: &Option<Bound>, open: &str| match bound {
+            Some(Bound::Slot(slot)) => format!("slot {}", slot),
+            Some(Bound::Signature(signature)) => format!("signature {}", signature),
+            Some(Bound::Time(time)) => time.to_rfc3339(),
+            None => open.to_string(),
+        };
+        format!("{} -> {}", describe(&self.start, "program genesis"), describe(&self.end, "chain tip"))
+    }
+}
+
+/// Crawler bounds for a resolved range
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedRange {
+    /// Newest signature after the range; the crawl starts right before it
+    pub before: Option<Signature>,
+    /// Newest signature before the range; the crawl stops right after it
+    pub until: Option<Signature>,
+    /// Successful transactions in the range, used for progress reporting
+    pub transactions: u64,
+    /// Slots of the oldest and newest transaction in the range
+    pub slots: Option<(u64, u64)>,
+}
+
+/// Walks the program's signatures from the tip down to the start of the range and
+/// returns the exclusive `before`/`until` signatures enclosing it
+pub async fn resolve_range(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    range: &BackfillRange,
+) -> CarbonResult<ResolvedRange> {
+    let mut resolved = ResolvedRange::default();
+    let mut page_before: Option<Signature> = None;
+    let mut in_range = range.end.is_none();
+    let mut reached_start = false;
+    let mut pages = 0;
+
+    'pages: loop {
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                program_id,
+                GetConfirmedSignaturesForAddress2Config {
+                    before: page_before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_LIMIT),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await
+            .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to fetch signatures: {}", e)))?;
+
+        pages += 1;
+        if page.is_empty() {
+            break;
+        }
+
+        for sig_info in &page {
+            let signature = Signature::from_str(&sig_info.signature).map_err(|e| {
+                carbon_core::error::Error::Custom(format!("Invalid signature {}: {}", sig_info.signature, e))
+            })?;
+
+            if !in_range {
+                if is_after_end(range.end.as_ref(), sig_info, &signature) {
+                    resolved.before = Some(signature);
+                    continue;
+                }
+                in_range = true;
+            }
+
+            if reached_start || is_before_start(range.start.as_ref(), sig_info) {
+                resolved.until = Some(signature);
+                break 'pages;
+            }
+
+            if sig_info.err.is_none() {
+                resolved.transactions += 1;
+            }
+            resolved.slots = Some(match resolved.slots {
+                Some((_, newest)) => (sig_info.slot, newest),
+                None => (sig_info.slot, sig_info.slot),
+            });
+
+            // The start signature is the oldest one in the range
+            reached_start = range.start == Some(Bound::Signature(signature));
+        }
+
+        if pages % 10 == 0 {
+            log::info!(
+                "Resolving backfill range: scanned {} signatures, oldest slot {}",
+                pages * SIGNATURE_PAGE_LIMIT,
+                page.last().map(|s| s.slot).unwrap_or_default()
+            );
+        }
+
+        page_before = page.last().and_then(|s| Signature::from_str(&s.signature).ok());
+    }
+
+    if let Some(Bound::Signature(signature)) = &range.end
+        && !in_range
+    {
+        return Err(carbon_core::error::Error::Custom(format!(
+            "End signature {} not found in program history",
+            signature
+        )));
+    }
+    if let Some(Bound::Signature(signature)) = &range.start
+        && !reached_start
+        && resolved.until.is_none()
+    {
+        return Err(carbon_core::error::Error::Custom(format!(
+            "Start signature {} not found in program history",
+            signature
+        )));
+    }
+
+    Ok(resolved)
+}
+
+fn is_after_end(
+    end: Option<&Bound>,
+    sig_info: &RpcConfirmedTransactionStatusWithSignature,
+    signature: &Signature,
+) -> bool {
+    match end {
+        Some(Bound::Slot(slot)) => sig_info.slot > *slot,
+        Some(Bound::Time(time)) => sig_info.block_time.is_some_and(|t| t > time.timestamp()),
+        Some(Bound::Signature(end)) => signature != end,
+        None => false,
+    }
+}
+
+fn is_before_start(start: Option<&Bound>, sig_info: &RpcConfirmedTransactionStatusWithSignature) -> bool {
+    match start {
+        Some(Bound::Slot(slot)) => sig_info.slot < *slot,
+        Some(Bound::Time(time)) => sig_info.block_time.is_some_and(|t| t < time.timestamp()),
+        // Checked once the start signature itself is reached
+        Some(Bound::Signature(_)) | None => false,
+    }
+}
+
+/// Pipeline metrics reporting backfill progress on every flush
+pub struct BackfillProgress {
+    total: u64,
+    processed: AtomicU64,
+    failed: AtomicU64,
+    started: Mutex<Instant>,
+}
+
+impl BackfillProgress {
+    pub fn new(total: u64) -> Self {
+        Self {
+            total,
+            processed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            started: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    fn report(&self, label: &str) {
+        let processed = self.processed.load(Ordering::Relaxed);
+        let elapsed = self.started.lock().map(|s| s.elapsed()).unwrap_or_default();
+        let rate = processed as f64 / elapsed.as_secs_f64().max(1.0);
+        let percent = if self.total == 0 { 100.0 } else { processed as f64 * 100.0 / self.total as f64 };
+        let eta = if rate > 0.0 && processed < self.total {
+            format!("{}s", ((self.total - processed) as f64 / rate) as u64)
+        } else {
+            "-".to_string()
+        };
+
+        log::info!(
+            "{}: {}/{} transactions ({:.1}%), {} failed, {:.1} tx/s, elapsed {}s, eta {}",
+            label,
+            processed,
+            self.total,
+            percent.min(100.0),
+            self.failed(),
+            rate,
+            elapsed.as_secs(),
+            eta
+        );
+    }
+}
+
+#[async_trait]
+impl Metrics for BackfillProgress {
+    async fn initialize(&self) -> CarbonResult<()> {
+        if let Ok(mut started) = self.started.lock() {
+            *started = Instant::now();
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> CarbonResult<()> {
+        self.report("Backfill progress");
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> CarbonResult<()> {
+        self.report("Backfill finished");
+        Ok(())
+    }
+
+    async fn update_gauge(&self, _name: &str, _value: f64) -> CarbonResult<()> {
+        Ok(())
+    }
+
+    async fn increment_counter(&self, name: &str, value: u64) -> CarbonResult<()> {
+        match name {
+            "updates_processed" => {
+                self.processed.fetch_add(value, Ordering::Relaxed);
+            }
+            "updates_failed" => {
+                self.failed.fetch_add(value, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn record_histogram(&self, _name: &str, _value: f64) -> CarbonResult<()> {
+        Ok(())
+    }
+}
+
+/// Runs a backfill of `program_id` over the requested range and returns once it has been
+/// indexed
+pub async fn run_backfill(
+    http_rpc_url: &str,
+    program_id: Pubkey,
+    args: &BackfillArgs,
+    store: Arc<dyn EventStore>,
+    pricer: Arc<SwapPricer>,
+) -> CarbonResult<()> {
+    let range = BackfillRange::from_args(args).map_err(carbon_core::error::Error::Custom)?;
+
+    log::info!("Resolving backfill range {} for program {}", range.describe(), program_id);
+    let rpc_client = RpcClient::new(http_rpc_url.to_string());
+    let resolved = resolve_range(&rpc_client, &program_id, &range).await?;
+
+    let Some((oldest_slot, newest_slot)) = resolved.slots else {
+        log::info!("No program transactions in range {}, nothing to backfill", range.describe());
+        return Ok(());
+    };
+    log::info!(
+        "Backfilling {} transactions from slot {} to slot {}",
+        resolved.transactions,
+        oldest_slot,
+        newest_slot
+    );
+
+    let crawler = RpcTransactionCrawler::new(
+        http_rpc_url.to_string(),
+        program_id,
+        ConnectionConfig::new(
+            SIGNATURE_PAGE_LIMIT,
+            Duration::from_secs(5),
+            args.concurrency.max(1),
+            RetryConfig::default(),
+            None,
+            None,
+            true, // blocking_send: never drop transactions of the range
+        ),
+        TransactionFilters::new(None, resolved.before, resolved.until),
+        Some(CommitmentConfig::confirmed()),
+    )
+    .with_stop_when_exhausted(true);
+
+    // Historical events must not trigger webhooks, so the notifier has no endpoints
+    let block_times = Arc::new(BlockTimeResolver::new(http_rpc_url.to_string()));
+    let notifier = Arc::new(Notifier::new(NotifierConfig::default()));
+    let progress = Arc::new(BackfillProgress::new(resolved.transactions));
+    let writer = Arc::new(EventWriter::new(store, WriteMode::Batched {
+        max_transactions: args.batch_size.max(1),
+        max_delay: MAX_BATCH_DELAY,
+    }));
+
+    let mut pipeline = Pipeline::builder()
+        .datasource(crawler)
+        .metrics(progress.clone())
+        .metrics_flush_interval(3)
+        .instruction(
+            OmnipairDecoder,
+            OmnipairInstructionProcessor::new(block_times, notifier, writer.clone())
+                .with_programs([program_id])
+                .with_pricing(pricer),
+        )
+        .instruction(OmnipairDecoder, CheckpointProcessor::without_checkpoint(writer.clone(), BACKFILL_DATASOURCE))
+        .shutdown_strategy(carbon_core::pipeline::ShutdownStrategy::ProcessPending)
+        .build()?;
+
+    pipeline.run().await?;
+
+    // Write the last, partial batch
+    writer.flush().await?;
+
+    if progress.failed() > 0 {
+        return Err(carbon_core::error::Error::Custom(format!(
+            "Backfill completed with {} failed transactions",
+            progress.failed()
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+use carbon_core::{error::CarbonResult, transaction::TransactionMetadata};
+use chrono::{DateTime, TimeZone, Utc};
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+/// Maximum number of slot -> block time entries kept in memory
+const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+
+/// Number of attempts made against the RPC before giving up on a slot
+const MAX_RESOLVE_ATTEMPTS: u32 = 5;
+
+/// Initial delay between attempts; recent slots can take a moment to become available
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(400);
+
+/// Resolves slots to their on-chain block time.
+///
+/// Datasources such as the RPC transaction crawler already provide `block_time` on each
+/// transaction. Others (e.g. Helius Atlas WS) only provide the slot, in which case the
+/// block time is fetched with `getBlockTime` and cached, so that every event in the same
+/// slot costs a single RPC call.
+pub struct BlockTimeResolver {
+    rpc_client: RpcClient,
+    cache: Mutex<BTreeMap<u64, i64>>,
+    capacity: usize,
+}
+
+impl BlockTimeResolver {
+    pub fn new(rpc_url: String) -> Self {
+        Self::with_capacity(rpc_url, DEFAULT_CACHE_CAPACITY)
+    }
+
+    pub fn with_capacity(rpc_url: String, capacity: usize) -> Self {
+        Self {
+            rpc_client: RpcClient::new(rpc_url),
+            cache: Mutex::new(BTreeMap::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Returns the timestamp to persist for events of the given transaction.
+    ///
+    /// Uses the block time carried by the transaction when present and falls back to
+    /// resolving it from the slot.
+    pub async fn event_timestamp(
+        &self,
+        transaction_metadata: &TransactionMetadata,
+    ) -> CarbonResult<DateTime<Utc>> {
+        let block_time = match transaction_metadata.block_time {
+            Some(block_time) => {
+                self.remember(transaction_metadata.slot, block_time);
+                block_time
+            }
+            None => self.resolve(transaction_metadata.slot).await?,
+        };
+
+        to_datetime(block_time, transaction_metadata.slot)
+    }
+
+    /// Resolves the Unix block time of a slot, using the cache when possible.
+    pub async fn resolve(&self, slot: u64) -> CarbonResult<i64> {
+        if let Some(block_time) = self.cached(slot) {
+            return Ok(block_time);
+        }
+
+        let mut delay = INITIAL_RETRY_DELAY;
+        let mut attempt = 1;
+
+        loop {
+            match self.rpc_client.get_block_time(slot).await {
+                Ok(block_time) => {
+                    self.remember(slot, block_time);
+                    return Ok(block_time);
+                }
+                Err(e) if attempt < MAX_RESOLVE_ATTEMPTS => {
+                    log::warn!(
+                        "Failed to fetch block time for slot {} (attempt {}/{}), retrying in {}ms: {}",
+                        slot,
+                        attempt,
+                        MAX_RESOLVE_ATTEMPTS,
+                        delay.as_millis(),
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(carbon_core::error::Error::Custom(format!(
+                        "Failed to resolve block time for slot {} after {} attempts: {}",
+                        slot, MAX_RESOLVE_ATTEMPTS, e
+                    )));
+                }
+            }
+        }
+    }
+
+    fn cached(&self, slot: u64) -> Option<i64> {
+        self.cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&slot).copied())
+    }
+
+    fn remember(&self, slot: u64, block_time: i64) {
+        let Ok(mut cache) = self.cache.lock() else {
+            return;
+        };
+
+        cache.insert(slot, block_time);
+
+        // Evict the oldest slots first; live indexing only moves forward
+        while cache.len() > self.capacity {
+            cache.pop_first();
+        }
+    }
+}
+
+fn to_datetime(block_time: i64, slot: u64) -> CarbonResult<DateTime<Utc>> {
+    Utc.timestamp_opt(block_time, 0).single().ok_or_else(|| {
+        carbon_core::error::Error::Custom(format!(
+            "Invalid block time {} for slot {}",
+            block_time, slot
+        ))
+    })
+}
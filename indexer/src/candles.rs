@@ -0,0 +1,485 @@
+//! OHLCV candles.
+//!
+//! Every swap is folded into one candle per resolution (1m, 5m, 15m, 1h, 4h and 1d,
+//! aligned on UTC epoch boundaries) in `swap_candles`. Prices are the post-swap reserve
+//! ratio `reserve1 / reserve0` in raw token units; volumes are kept in both tokens.
+//!
+//! Candles are updated in the same database transaction as the swaps, from the swaps
+//! that transaction actually inserted, so replays and overlapping datasources never count
+//! a swap twice. Open and close follow the trade order `(slot, signature, instruction
+//! index, inner instruction index)`, which makes out-of-order writes (backfills, gap
+//! fills) land correctly.
+//!
+//! Swaps that are rewritten in place (`reindex`) are not reflected incrementally: the
+//! `rebuild-candles` command recomputes the candles of a pair from the `swaps` table, one
+//! UTC day at a time. The finalizer rebuilds the days of rolled back swaps the same way.
+
+use std::collections::BTreeMap;
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use carbon_core::error::{CarbonResult, Error};
+use carbon_omnipair_decoder::instructions::swap_event::SwapEvent;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use clap::Args as ClapArgs;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+
+use crate::{
+    database::{get_db_pool, INSERT_CHUNK_ROWS},
+    event_context::EventContext,
+};
+
+/// Decimal places of stored prices
+pub const PRICE_SCALE: i64 = 18;
+
+/// Options of the `rebuild-candles` subcommand
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct RebuildCandlesArgs {
+    /// Pair whose candles are rebuilt
+    #[arg(long)]
+    pub pair: String,
+
+    /// Start of the range to rebuild (RFC 3339); defaults to the pair's first swap
+    #[arg(long)]
+    pub start_time: Option<DateTime<Utc>>,
+
+    /// End of the range to rebuild (RFC 3339); defaults to the pair's last swap
+    #[arg(long)]
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// Candle resolutions, stored by label in `swap_candles.resolution`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Resolution {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    FourHours,
+    OneDay,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 6] = [
+        Resolution::OneMinute,
+        Resolution::FiveMinutes,
+        Resolution::FifteenMinutes,
+        Resolution::OneHour,
+        Resolution::FourHours,
+        Resolution::OneDay,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Resolution::OneMinute => "1m",
+            Resolution::FiveMinutes => "5m",
+            Resolution::FifteenMinutes => "15m",
+            Resolution::OneHour => "1h",
+            Resolution::FourHours => "4h",
+            Resolution::OneDay => "1d",
+        }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Resolution::OneMinute => 60,
+            Resolution::FiveMinutes => 5 * 60,
+            Resolution::FifteenMinutes => 15 * 60,
+            Resolution::OneHour => 60 * 60,
+            Resolution::FourHours => 4 * 60 * 60,
+            Resolution::OneDay => 24 * 60 * 60,
+        }
+    }
+
+    /// Start of the candle containing `timestamp`
+    pub fn bucket_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let seconds = timestamp.timestamp();
+        Utc.timestamp_opt(seconds - seconds.rem_euclid(self.seconds()), 0)
+            .single()
+            .unwrap_or(timestamp)
+    }
+}
+
+/// Position of a swap in the chain: (slot, signature, instruction_index, inner_instruction_index)
+pub type TradeKey = (i64, String, i32, i32);
+
+/// The fields of a swap that make up candles
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandleSwap {
+    pub pair: String,
+    pub is_token0_in: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub reserve0: u64,
+    pub reserve1: u64,
+    pub timestamp: DateTime<Utc>,
+    pub key: TradeKey,
+}
+
+impl CandleSwap {
+    pub fn from_event(event: &SwapEvent, ctx: &EventContext) -> Self {
+        Self {
+            pair: event.metadata.pair.to_string(),
+            is_token0_in: event.is_token0_in,
+            amount_in: event.amount_in,
+            amount_out: event.amount_out,
+            reserve0: event.reserve0,
+            reserve1: event.reserve1,
+            timestamp: ctx.timestamp,
+            key: (ctx.slot, ctx.signature.clone(), ctx.instruction_index, ctx.inner_instruction_index),
+        }
+    }
+
+    /// Post-swap price of token0 in token1, or `None` when reserve0 is empty
+    pub fn price(&self) -> Option<BigDecimal> {
+        reserve_price(self.reserve0, self.reserve1)
+    }
+
+    /// Traded amounts as (token0, token1)
+    pub fn volumes(&self) -> (u64, u64) {
+        if self.is_token0_in {
+            (self.amount_in, self.amount_out)
+        } else {
+            (self.amount_out, self.amount_in)
+        }
+    }
+}
+
+/// `reserve1 / reserve0`, truncated to `PRICE_SCALE` decimals with integer arithmetic so
+/// that incremental updates and rebuilds store identical prices
+pub fn reserve_price(reserve0: u64, reserve1: u64) -> Option<BigDecimal> {
+    if reserve0 == 0 {
+        return None;
+    }
+
+    // reserve1 * 10^18 stays below u128::MAX for any u64 reserve
+    let scaled = reserve1 as u128 * 10u128.pow(PRICE_SCALE as u32) / reserve0 as u128;
+    Some(BigDecimal::new(BigInt::from(scaled), PRICE_SCALE))
+}
+
+/// A price at a position in the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricePoint {
+    pub key: TradeKey,
+    pub price: BigDecimal,
+}
+
+/// Aggregated swaps of one pair over one candle period. Price fields are `None` until a
+/// swap with a price is folded in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Candle {
+    pub open: Option<PricePoint>,
+    pub high: Option<BigDecimal>,
+    pub low: Option<BigDecimal>,
+    pub close: Option<PricePoint>,
+    pub volume0: u128,
+    pub volume1: u128,
+    pub trade_count: i64,
+}
+
+impl Candle {
+    /// Folds a swap into the candle, in any order
+    pub fn apply(&mut self, swap: &CandleSwap) {
+        let (volume0, volume1) = swap.volumes();
+        self.volume0 += volume0 as u128;
+        self.volume1 += volume1 as u128;
+        self.trade_count += 1;
+
+        let Some(price) = swap.price() else {
+            return;
+        };
+
+        if self.open.as_ref().is_none_or(|open| swap.key < open.key) {
+            self.open = Some(PricePoint { key: swap.key.clone(), price: price.clone() });
+        }
+        if self.close.as_ref().is_none_or(|close| swap.key > close.key) {
+            self.close = Some(PricePoint { key: swap.key.clone(), price: price.clone() });
+        }
+        if self.high.as_ref().is_none_or(|high| price > *high) {
+            self.high = Some(price.clone());
+        }
+        if self.low.as_ref().is_none_or(|low| price < *low) {
+            self.low = Some(price);
+        }
+    }
+}
+
+/// Candle identity: (pair, resolution, bucket start)
+pub type CandleKey = (String, Resolution, DateTime<Utc>);
+
+/// Folds swaps into their candle of every resolution
+pub fn aggregate<'a>(swaps: impl IntoIterator<Item = &'a CandleSwap>) -> BTreeMap<CandleKey, Candle> {
+    let mut candles: BTreeMap<CandleKey, Candle> = BTreeMap::new();
+
+    for swap in swaps {
+        for resolution in Resolution::ALL {
+            candles
+                .entry((swap.pair.clone(), resolution, resolution.bucket_start(swap.timestamp)))
+                .or_default()
+                .apply(swap);
+        }
+    }
+
+    candles
+}
+
+/// Serializes candle writes per pair between the indexer pipelines and rebuilds, so a
+/// rebuild never misses a swap committed while it runs
+async fn lock_pairs(conn: &mut PgConnection, pairs: &[&str]) -> CarbonResult<()> {
+    // Sorted so concurrent writers take the locks in the same order
+    let mut pairs = pairs.to_vec();
+    pairs.sort_unstable();
+    pairs.dedup();
+
+    for pair in pairs {
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended('swap_candles:' || $1, 0))")
+            .bind(pair)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| Error::Custom(format!("Failed to lock candles of pair {}: {}", pair, e)))?;
+    }
+
+    Ok(())
+}
+
+/// The incoming open trade precedes the stored one (or there is none yet)
+const OPEN_IS_EARLIER: &str = "(swap_candles.open_slot IS NULL OR \
+    ROW(EXCLUDED.open_slot, EXCLUDED.open_tx_sig, EXCLUDED.open_instruction_index, EXCLUDED.open_inner_instruction_index) < \
+    ROW(swap_candles.open_slot, swap_candles.open_tx_sig, swap_candles.open_instruction_index, swap_candles.open_inner_instruction_index))";
+
+/// The incoming close trade follows the stored one (or there is none yet)
+const CLOSE_IS_LATER: &str = "(swap_candles.close_slot IS NULL OR \
+    ROW(EXCLUDED.close_slot, EXCLUDED.close_tx_sig, EXCLUDED.close_instruction_index, EXCLUDED.close_inner_instruction_index) > \
+    ROW(swap_candles.close_slot, swap_candles.close_tx_sig, swap_candles.close_instruction_index, swap_candles.close_inner_instruction_index))";
+
+/// Merges `candles` into `swap_candles`: volumes and trade counts add up, high and low
+/// widen, and open and close are replaced by earlier and later trades respectively
+async fn merge_candles(conn: &mut PgConnection, candles: &BTreeMap<CandleKey, Candle>) -> CarbonResult<()> {
+    let rows: Vec<_> = candles.iter().collect();
+
+    for chunk in rows.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO swap_candles (
+                pair, resolution, bucket_start, open, high, low, close, volume0, volume1, trade_count,
+                open_slot, open_tx_sig, open_instruction_index, open_inner_instruction_index,
+                close_slot, close_tx_sig, close_instruction_index, close_inner_instruction_index,
+                updated_at
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, ((pair, resolution, bucket_start), candle)| {
+            let open_key = candle.open.as_ref().map(|open| &open.key);
+            let close_key = candle.close.as_ref().map(|close| &close.key);
+
+            row.push_bind(pair.clone())
+                .push_bind(resolution.label())
+                .push_bind(*bucket_start)
+                .push_bind(candle.open.as_ref().map(|open| open.price.clone()))
+                .push_bind(candle.high.clone())
+                .push_bind(candle.low.clone())
+                .push_bind(candle.close.as_ref().map(|close| close.price.clone()))
+                .push_bind(BigDecimal::from(BigInt::from(candle.volume0)))
+                .push_bind(BigDecimal::from(BigInt::from(candle.volume1)))
+                .push_bind(candle.trade_count)
+                .push_bind(open_key.map(|key| key.0))
+                .push_bind(open_key.map(|key| key.1.clone()))
+                .push_bind(open_key.map(|key| key.2))
+                .push_bind(open_key.map(|key| key.3))
+                .push_bind(close_key.map(|key| key.0))
+                .push_bind(close_key.map(|key| key.1.clone()))
+                .push_bind(close_key.map(|key| key.2))
+                .push_bind(close_key.map(|key| key.3))
+                .push("now()");
+        });
+
+        // Every SET expression sees the row as it was before the update
+        query.push(format!(
+            r#"
+            ON CONFLICT (pair, resolution, bucket_start) DO UPDATE SET
+                open = CASE WHEN {earlier} THEN EXCLUDED.open ELSE swap_candles.open END,
+                open_slot = CASE WHEN {earlier} THEN EXCLUDED.open_slot ELSE swap_candles.open_slot END,
+                open_tx_sig = CASE WHEN {earlier} THEN EXCLUDED.open_tx_sig ELSE swap_candles.open_tx_sig END,
+                open_instruction_index = CASE WHEN {earlier}
+                    THEN EXCLUDED.open_instruction_index ELSE swap_candles.open_instruction_index END,
+                open_inner_instruction_index = CASE WHEN {earlier}
+                    THEN EXCLUDED.open_inner_instruction_index ELSE swap_candles.open_inner_instruction_index END,
+                close = CASE WHEN {later} THEN EXCLUDED.close ELSE swap_candles.close END,
+                close_slot = CASE WHEN {later} THEN EXCLUDED.close_slot ELSE swap_candles.close_slot END,
+                close_tx_sig = CASE WHEN {later} THEN EXCLUDED.close_tx_sig ELSE swap_candles.close_tx_sig END,
+                close_instruction_index = CASE WHEN {later}
+                    THEN EXCLUDED.close_instruction_index ELSE swap_candles.close_instruction_index END,
+                close_inner_instruction_index = CASE WHEN {later}
+                    THEN EXCLUDED.close_inner_instruction_index ELSE swap_candles.close_inner_instruction_index END,
+                high = GREATEST(swap_candles.high, EXCLUDED.high),
+                low = LEAST(swap_candles.low, EXCLUDED.low),
+                volume0 = swap_candles.volume0 + EXCLUDED.volume0,
+                volume1 = swap_candles.volume1 + EXCLUDED.volume1,
+                trade_count = swap_candles.trade_count + EXCLUDED.trade_count,
+                updated_at = now()
+            "#,
+            earlier = OPEN_IS_EARLIER,
+            later = CLOSE_IS_LATER,
+        ));
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into swap_candles table: {}", e);
+            return Err(Error::Custom(format!("Failed to update candles: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Folds newly inserted swaps into their candles. Swaps that were already stored must
+/// not be passed again, or they would be counted twice.
+pub async fn record_swaps(conn: &mut PgConnection, swaps: &[&(SwapEvent, EventContext)]) -> CarbonResult<()> {
+    if swaps.is_empty() {
+        return Ok(());
+    }
+
+    let swaps: Vec<CandleSwap> = swaps.iter().map(|(event, ctx)| CandleSwap::from_event(event, ctx)).collect();
+    let pairs: Vec<&str> = swaps.iter().map(|swap| swap.pair.as_str()).collect();
+
+    lock_pairs(conn, &pairs).await?;
+    merge_candles(conn, &aggregate(&swaps)).await
+}
+
+/// Loads the swaps of `pair` in `[start, end)`, leaving out rolled back ones
+async fn load_swaps(
+    conn: &mut PgConnection,
+    pair: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> CarbonResult<Vec<CandleSwap>> {
+    type SwapRow = (
+        Option<bool>,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+        DateTime<Utc>,
+        Option<i64>,
+        Option<String>,
+        i32,
+        i32,
+    );
+
+    let rows = sqlx::query_as::<_, SwapRow>(
+        r#"
+        SELECT is_token0_in, amount_in::TEXT, amount_out::TEXT, reserve0::TEXT, reserve1::TEXT,
+            "timestamp", slot::BIGINT, tx_sig, instruction_index, inner_instruction_index
+        FROM swaps
+        WHERE pair = $1 AND "timestamp" >= $2 AND "timestamp" < $3 AND commitment <> 'rolled_back'
+        "#
+    )
+    .bind(pair)
+    .bind(start)
+    .bind(end)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| Error::Custom(format!("Failed to load swaps of pair {}: {}", pair, e)))?;
+
+    let parse = |value: Option<String>| value.and_then(|value| value.parse::<u64>().ok());
+
+    let mut swaps = Vec::with_capacity(rows.len());
+    for (is_token0_in, amount_in, amount_out, reserve0, reserve1, timestamp, slot, tx_sig, ix, inner_ix) in rows {
+        let swap = (|| {
+            Some(CandleSwap {
+                pair: pair.to_string(),
+                is_token0_in: is_token0_in?,
+                amount_in: parse(amount_in)?,
+                amount_out: parse(amount_out)?,
+                reserve0: parse(reserve0)?,
+                reserve1: parse(reserve1)?,
+                timestamp,
+                key: (slot?, tx_sig.clone()?, ix, inner_ix),
+            })
+        })();
+
+        match swap {
+            Some(swap) => swaps.push(swap),
+            None => log::warn!("Skipping incomplete swap {:?} of pair {} at {}", tx_sig, pair, timestamp),
+        }
+    }
+
+    Ok(swaps)
+}
+
+/// Recomputes every candle of `pair` starting within the UTC day at `day` on `conn`,
+/// replacing the stored ones. Returns the number of swaps folded in.
+pub async fn replace_day_candles(conn: &mut PgConnection, pair: &str, day: DateTime<Utc>) -> CarbonResult<usize> {
+    let day_end = day + Duration::days(1);
+
+    lock_pairs(conn, &[pair]).await?;
+
+    sqlx::query("DELETE FROM swap_candles WHERE pair = $1 AND bucket_start >= $2 AND bucket_start < $3")
+        .bind(pair)
+        .bind(day)
+        .bind(day_end)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to delete candles of pair {}: {}", pair, e)))?;
+
+    let swaps = load_swaps(conn, pair, day, day_end).await?;
+    merge_candles(conn, &aggregate(&swaps)).await?;
+
+    Ok(swaps.len())
+}
+
+/// Rebuilds the candles of `pair` within the UTC day at `day` in its own transaction
+async fn rebuild_day(pair: &str, day: DateTime<Utc>) -> CarbonResult<usize> {
+    let pool = get_db_pool()?;
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        Error::Custom(format!("Failed to begin candle rebuild transaction: {}", e))
+    })?;
+
+    let swaps = replace_day_candles(&mut tx, pair, day).await?;
+
+    tx.commit().await.map_err(|e| {
+        Error::Custom(format!("Failed to commit candle rebuild: {}", e))
+    })?;
+
+    Ok(swaps)
+}
+
+/// Rebuilds the candles of a pair over the requested range, widened to whole UTC days so
+/// every resolution is recomputed from complete periods
+pub async fn rebuild_candles(args: &RebuildCandlesArgs) -> CarbonResult<()> {
+    let pool = get_db_pool()?;
+    let pair = args.pair.as_str();
+
+    let (first_swap, last_swap) = sqlx::query_as::<_, (Option<DateTime<Utc>>, Option<DateTime<Utc>>)>(
+        r#"SELECT MIN("timestamp"), MAX("timestamp") FROM swaps WHERE pair = $1"#
+    )
+    .bind(pair)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| Error::Custom(format!("Failed to load swap range of pair {}: {}", pair, e)))?;
+
+    let (Some(start), Some(end)) = (args.start_time.or(first_swap), args.end_time.or(last_swap)) else {
+        log::info!("Pair {} has no swaps, nothing to rebuild", pair);
+        return Ok(());
+    };
+    if start > end {
+        return Err(Error::Custom(format!("--start-time {} is after --end-time {}", start, end)));
+    }
+
+    let mut day = Resolution::OneDay.bucket_start(start);
+    let last_day = Resolution::OneDay.bucket_start(end);
+    log::info!(
+        "Rebuilding candles of pair {} from {} to {}",
+        pair,
+        day.to_rfc3339(),
+        (last_day + Duration::days(1)).to_rfc3339()
+    );
+
+    let mut swaps = 0;
+    while day <= last_day {
+        swaps += rebuild_day(pair, day).await?;
+        day += Duration::days(1);
+    }
+
+    log::info!("Rebuilt candles of pair {} from {} swaps", pair, swaps);
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! Ingestion checkpoints.
+//!
+//! The live pipeline records the last fully processed transaction of its datasource in
+//! `ingestion_checkpoints`. On startup a catch-up crawl covers everything between that
+//! checkpoint and the chain tip while the live stream is already running, so restarts
+//! and deploys do not leave gaps.
+//!
+//! The checkpoint only advances once the catch-up has completed: if the process stops
+//! mid-way, the next start crawls again from the same checkpoint.
+//!
+//! Every pipeline also records its fully processed transactions in
+//! `processed_transactions`, which gap fills use to skip what was already indexed. Both
+//! are written in the same database transaction as the rows of the Solana transaction.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use carbon_core::{
+    error::CarbonResult,
+    instruction::{DecodedInstruction, InstructionMetadata, NestedInstructions},
+    metrics::MetricsCollection,
+    processor::Processor,
+};
+use carbon_omnipair_decoder::instructions::OmnipairInstruction;
+
+use crate::{event_writer::EventWriter, health::HealthState};
+
+/// Datasource name of the live Helius Atlas WebSocket stream
+pub const LIVE_DATASOURCE: &str = "helius_atlas";
+
+/// Datasource name of the startup catch-up crawl
+pub const CATCH_UP_DATASOURCE: &str = "rpc_catch_up";
+
+/// Datasource name of slot gap fills
+pub const GAP_FILL_DATASOURCE: &str = "rpc_gap_fill";
+
+/// Datasource name of the `backfill` command
+pub const BACKFILL_DATASOURCE: &str = "rpc_backfill";
+
+/// Datasource name of the `replay-failed` command
+pub const REPLAY_DATASOURCE: &str = "dead_letter_replay";
+
+/// Datasource name of the `reindex` command
+pub const REINDEX_DATASOURCE: &str = "rpc_reindex";
+
+/// Commits each processed transaction of a datasource on its `EventWriter` and, for the
+/// live datasource, advances its checkpoint.
+///
+/// Registered as an instruction pipe after `OmnipairInstructionProcessor`: the pipeline
+/// runs each pipe over all instructions of a transaction in turn and stops on the first
+/// error, so reaching this processor means every instruction of the transaction was
+/// processed and staged.
+pub struct CheckpointProcessor {
+    writer: Arc<EventWriter>,
+    datasource: &'static str,
+    caught_up: Option<Arc<AtomicBool>>,
+    health: Option<Arc<HealthState>>,
+    /// Signature and slot of the last committed transaction; a transaction rolled back and
+    /// landed again in another slot is committed again
+    last_transaction: Option<(String, i64)>,
+}
+
+impl CheckpointProcessor {
+    /// Records processed transactions and advances the checkpoint once `caught_up` is set
+    pub fn new(writer: Arc<EventWriter>, datasource: &'static str, caught_up: Arc<AtomicBool>) -> Self {
+        Self {
+            writer,
+            datasource,
+            caught_up: Some(caught_up),
+            health: None,
+            last_transaction: None,
+        }
+    }
+
+    /// Only records processed transactions, without keeping a checkpoint
+    pub fn without_checkpoint(writer: Arc<EventWriter>, datasource: &'static str) -> Self {
+        Self {
+            writer,
+            datasource,
+            caught_up: None,
+            health: None,
+            last_transaction: None,
+        }
+    }
+
+    /// Reports each committed transaction as the last processed one to `health`
+    pub fn with_health(mut self, health: Arc<HealthState>) -> Self {
+        self.health = Some(health);
+        self
+    }
+}
+
+#[async_trait]
+impl Processor for CheckpointProcessor {
+    type InputType = (
+        InstructionMetadata,
+        DecodedInstruction<OmnipairInstruction>,
+        NestedInstructions,
+        solana_instruction::Instruction,
+    );
+
+    async fn process(
+        &mut self,
+        (metadata, _instruction, _nested_instructions, _raw_instruction): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        // Called once per Omnipair instruction; write once per transaction
+        let signature = metadata.transaction_metadata.signature.to_string();
+        let slot = metadata.transaction_metadata.slot as i64;
+        if self.last_transaction.as_ref().is_some_and(|(last, last_slot)| *last == signature && *last_slot == slot) {
+            return Ok(());
+        }
+
+        let advance_checkpoint = self
+            .caught_up
+            .as_ref()
+            .is_some_and(|caught_up| caught_up.load(Ordering::Acquire));
+
+        self.writer.commit(&signature, slot, self.datasource, advance_checkpoint).await?;
+
+        if advance_checkpoint {
+            log::debug!("Checkpoint {} advanced to slot {} ({})", self.datasource, slot, signature);
+        }
+
+        if let Some(health) = &self.health {
+            health.record_transaction(slot as u64, &signature);
+        }
+
+        self.last_transaction = Some((signature, slot));
+
+        Ok(())
+    }
+}
@@ -1,14 +1,51 @@
-use std::env;
-use clap::Parser;
+//! Indexer configuration.
+//!
+//! Settings are resolved from, in increasing order of precedence: built-in defaults, an
+//! optional TOML file (`--config` or `INDEXER_CONFIG`), environment variables and CLI
+//! flags. The file is the only way to configure several programs or datasource filters;
+//! see `indexer/config.example.toml`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use bigdecimal::BigDecimal;
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use solana_pubkey::Pubkey;
+
+use crate::{
+    backfill::BackfillArgs, candles::RebuildCandlesArgs, dead_letter::ReplayArgs, notifier::NotifierConfig,
+    reindex::ReindexArgs, schema::MigrateArgs,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about = "Omnipair Indexer Daemon")]
 pub struct Args {
-    /// RPC HTTP URL (falls back to RPC_HTTP_URL env) - used for historical data
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// TOML configuration file (falls back to INDEXER_CONFIG env)
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Solana cluster: mainnet-beta, devnet, testnet or localnet (falls back to CLUSTER env)
+    #[arg(long)]
+    pub cluster: Option<String>,
+
+    /// Program ID to index, repeatable (falls back to OMNIPAIR_PROGRAM_ID env)
+    #[arg(long = "program-id")]
+    pub program_ids: Vec<String>,
+
+    /// RPC HTTP URL (falls back to HTTP_RPC_URL env) - used for historical data
     #[arg(long)]
     pub http_rpc_url: Option<String>,
 
-    /// Start block for historical crawling (falls back to START_BLOCK env)
+    /// Start block for the first catch-up crawl when no checkpoint exists yet (falls back to START_BLOCK env)
     #[arg(long)]
     pub start_block: Option<u64>,
 
@@ -24,72 +61,792 @@ pub struct Args {
     #[arg(long, default_value_t = false)]
     pub enable_account_monitoring: bool,
 
-    /// Health check port (0 disables /health endpoint)
-    #[arg(long, default_value_t = 8080)]
-    pub health_port: u16,
+    /// Health check port, 0 disables the /health endpoints (falls back to HEALTH_PORT env, default: 8080)
+    #[arg(long)]
+    pub health_port: Option<u16>,
+
+    /// Prometheus exporter address (falls back to METRICS_ADDRESS env, default: 0.0.0.0:9100)
+    #[arg(long)]
+    pub metrics_address: Option<String>,
+
+    /// Webhook endpoint as <event_type>=<url>, repeatable (falls back to comma-separated WEBHOOK_ENDPOINTS env)
+    #[arg(long = "webhook")]
+    pub webhook_endpoints: Vec<String>,
+
+    /// Secret used to HMAC-sign webhook payloads (falls back to WEBHOOK_SECRET env)
+    #[arg(long)]
+    pub webhook_secret: Option<String>,
+
+    /// Database URL (falls back to DATABASE_URL env)
+    #[arg(long)]
+    pub database_url: Option<String>,
+
+    /// Apply pending schema migrations on startup instead of refusing to start (falls back to AUTO_MIGRATE env)
+    #[arg(long, default_value_t = false)]
+    pub auto_migrate: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Index a historical range with the RPC transaction crawler, then exit
+    Backfill(BackfillArgs),
+    /// Print the schema migration status and apply pending migrations, then exit
+    Migrate(MigrateArgs),
+    /// Run the updates kept in the dead-letter queue through the processors again, then exit
+    ReplayFailed(ReplayArgs),
+    /// Fetch the given transactions from RPC and index them again with upserts, then exit
+    Reindex(ReindexArgs),
+    /// Recompute the OHLCV candles of a pair from its stored swaps, then exit
+    RebuildCandles(RebuildCandlesArgs),
+}
+
+/// Solana cluster the indexer runs against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cluster {
+    MainnetBeta,
+    Devnet,
+    Testnet,
+    Localnet,
+}
+
+impl Cluster {
+    pub const NAMES: [&'static str; 4] = ["mainnet-beta", "devnet", "testnet", "localnet"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cluster::MainnetBeta => "mainnet-beta",
+            Cluster::Devnet => "devnet",
+            Cluster::Testnet => "testnet",
+            Cluster::Localnet => "localnet",
+        }
+    }
+
+    pub fn default_http_rpc_url(&self) -> &'static str {
+        match self {
+            Cluster::MainnetBeta => "https://api.mainnet-beta.solana.com/",
+            Cluster::Devnet => "https://api.devnet.solana.com/",
+            Cluster::Testnet => "https://api.testnet.solana.com/",
+            Cluster::Localnet => "http://127.0.0.1:8899/",
+        }
+    }
+
+    pub fn default_ws_rpc_url(&self) -> &'static str {
+        match self {
+            Cluster::MainnetBeta => "wss://api.mainnet-beta.solana.com/",
+            Cluster::Devnet => "wss://api.devnet.solana.com/",
+            Cluster::Testnet => "wss://api.testnet.solana.com/",
+            Cluster::Localnet => "ws://127.0.0.1:8900/",
+        }
+    }
+
+    /// Helius Atlas only serves mainnet-beta and devnet
+    pub fn helius_cluster(&self) -> Option<helius::types::Cluster> {
+        match self {
+            Cluster::MainnetBeta => Some(helius::types::Cluster::MainnetBeta),
+            Cluster::Devnet => Some(helius::types::Cluster::Devnet),
+            Cluster::Testnet | Cluster::Localnet => None,
+        }
+    }
+}
+
+impl FromStr for Cluster {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "mainnet-beta" | "mainnet" => Ok(Cluster::MainnetBeta),
+            "devnet" => Ok(Cluster::Devnet),
+            "testnet" => Ok(Cluster::Testnet),
+            "localnet" | "localhost" => Ok(Cluster::Localnet),
+            _ => Err(format!("unknown cluster '{}', expected one of: {}", value, Cluster::NAMES.join(", "))),
+        }
+    }
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A program whose transactions and accounts are indexed
+#[derive(Debug, Clone)]
+pub struct ProgramConfig {
+    pub name: String,
+    pub program_id: Pubkey,
+    /// Bounds the first catch-up crawl when no checkpoint exists yet (0: no bound)
+    pub start_block: u64,
+}
+
+/// Helius Atlas WebSocket stream of program transactions
+#[derive(Debug, Clone, Default)]
+pub struct HeliusConfig {
+    pub api_key: Option<String>,
+    /// Also stream failed transactions
+    pub include_failed: bool,
+    /// Only stream transactions that reference all of these accounts
+    pub account_required: Vec<Pubkey>,
+    /// Never stream transactions that reference any of these accounts
+    pub account_exclude: Vec<Pubkey>,
+}
+
+/// RPC program subscribe stream of program account updates
+#[derive(Debug, Clone)]
+pub struct ProgramSubscribeConfig {
+    pub ws_url: String,
+    /// Only stream accounts with one of these data sizes (empty: all accounts)
+    pub data_sizes: Vec<u64>,
+    /// Seed the account tables with a getProgramAccounts snapshot on startup
+    pub seed_snapshot: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub auto_migrate: bool,
+}
+
+/// Health server and the staleness thresholds that flip readiness
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// 0 disables the health server
+    pub port: u16,
+    /// Slots the live stream may trail the chain tip (0: unchecked)
+    pub max_slot_lag: u64,
+    /// Longest time without a processed update (zero: unchecked)
+    pub max_update_age: Duration,
+}
+
+/// Prometheus exporter of the pipeline and indexer metrics
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub address: SocketAddr,
+    /// Buckets of every histogram; empty exports histograms as summaries
+    pub buckets: Vec<f64>,
+    /// Buckets of individual histograms, by metric name
+    pub metric_buckets: BTreeMap<String, Vec<f64>>,
+}
+
+/// Dead-letter queue of updates that failed processing
+#[derive(Debug, Clone)]
+pub struct DeadLetterConfig {
+    pub enabled: bool,
+    /// JSON lines file receiving failed updates while the database is unreachable
+    pub fallback_path: Option<PathBuf>,
+}
+
+/// Swap pricing
+#[derive(Debug, Clone, Default)]
+pub struct PricingConfig {
+    /// Static USD price of one whole token, by mint
+    pub usd_prices: HashMap<Pubkey, BigDecimal>,
+}
+
+/// Which processors the live pipeline runs
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessorsConfig {
+    /// Program events from transactions (live stream, catch-up and gap fills)
+    pub events: bool,
+    /// Latest program account state
+    pub accounts: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Config {
+    pub cluster: Cluster,
+    pub programs: Vec<ProgramConfig>,
     pub http_rpc_url: String,
-    pub start_block: u64,
-    pub helius_api_key: Option<String>,
-    pub rpc_ws_url: Option<String>,
-    pub health_port: u16,
+    pub helius: HeliusConfig,
+    pub program_subscribe: ProgramSubscribeConfig,
+    pub database: DatabaseConfig,
+    pub processors: ProcessorsConfig,
+    pub notifier: NotifierConfig,
+    pub health: HealthConfig,
+    pub metrics: MetricsConfig,
+    pub dead_letter: DeadLetterConfig,
+    pub pricing: PricingConfig,
+}
+
+/// Layout of the TOML configuration file; every setting is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    cluster: Option<String>,
+    #[serde(default)]
+    programs: Vec<FileProgram>,
+    #[serde(default)]
+    datasources: FileDatasources,
+    #[serde(default)]
+    database: FileDatabase,
+    #[serde(default)]
+    processors: FileProcessors,
+    #[serde(default)]
+    notifier: FileNotifier,
+    #[serde(default)]
+    health: FileHealth,
+    #[serde(default)]
+    metrics: FileMetrics,
+    #[serde(default)]
+    dead_letter: FileDeadLetter,
+    #[serde(default)]
+    pricing: FilePricing,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileProgram {
+    name: Option<String>,
+    program_id: String,
+    start_block: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileDatasources {
+    #[serde(default)]
+    rpc: FileRpc,
+    #[serde(default)]
+    helius: FileHelius,
+    #[serde(default)]
+    program_subscribe: FileProgramSubscribe,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileRpc {
+    http_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileHelius {
+    api_key: Option<String>,
+    include_failed: Option<bool>,
+    #[serde(default)]
+    account_required: Vec<String>,
+    #[serde(default)]
+    account_exclude: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileProgramSubscribe {
+    ws_url: Option<String>,
+    #[serde(default)]
+    data_sizes: Vec<u64>,
+    seed_snapshot: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileDatabase {
+    url: Option<String>,
+    max_connections: Option<u32>,
+    auto_migrate: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileProcessors {
+    events: Option<bool>,
+    accounts: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileNotifier {
+    #[serde(default)]
+    endpoints: HashMap<String, Vec<String>>,
+    secret: Option<String>,
+    max_attempts: Option<i32>,
+    initial_backoff_secs: Option<u64>,
+    poll_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileHealth {
+    port: Option<u16>,
+    max_slot_lag: Option<u64>,
+    max_update_age_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileMetrics {
+    enabled: Option<bool>,
+    address: Option<String>,
+    #[serde(default)]
+    buckets: Vec<f64>,
+    #[serde(default)]
+    metric_buckets: BTreeMap<String, Vec<f64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileDeadLetter {
+    enabled: Option<bool>,
+    fallback_path: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FilePricing {
+    usd_prices_file: Option<String>,
+    #[serde(default)]
+    usd_prices: BTreeMap<String, f64>,
+}
+
+/// Validation errors, each prefixed with the setting it refers to
+#[derive(Debug, Default)]
+struct Errors(Vec<String>);
+
+impl Errors {
+    fn push(&mut self, setting: &str, message: impl fmt::Display) {
+        self.0.push(format!("{}: {}", setting, message));
+    }
+
+    fn into_result(self) -> Result<(), String> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!("Invalid configuration:\n  - {}", self.0.join("\n  - ")))
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"))
+}
+
+fn parse_pubkey(errors: &mut Errors, setting: &str, value: &str) -> Option<Pubkey> {
+    match Pubkey::from_str(value.trim()) {
+        Ok(pubkey) => Some(pubkey),
+        Err(_) => {
+            errors.push(setting, format!("'{}' is not a valid base58 public key", value));
+            None
+        }
+    }
+}
+
+fn check_url(errors: &mut Errors, setting: &str, url: &str, schemes: &[&str]) {
+    if !schemes.iter().any(|scheme| url.starts_with(&format!("{}://", scheme))) {
+        errors.push(setting, format!("'{}' must be a {} URL", url, schemes.join("/")));
+    }
+}
+
+/// Histogram buckets must be finite and strictly increasing
+fn check_buckets(errors: &mut Errors, setting: &str, buckets: &[f64]) {
+    if buckets.iter().any(|bound| !bound.is_finite()) || buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+        errors.push(setting, "buckets must be finite and strictly increasing");
+    }
+}
+
+/// Reads a TOML file of `<mint> = <USD price>` entries
+fn read_usd_prices_file(path: &str) -> Result<BTreeMap<String, f64>, String> {
+    let contents = std::fs::read_to_string(Path::new(path))
+        .map_err(|e| format!("Failed to read USD prices file {}: {}", path, e))?;
+
+    toml::from_str(&contents).map_err(|e| format!("Invalid USD prices file {}: {}", path, e))
+}
+
+fn read_file_config(path: &str) -> Result<FileConfig, String> {
+    let contents = std::fs::read_to_string(Path::new(path))
+        .map_err(|e| format!("Failed to read config file {}: {}", path, e))?;
+
+    toml::from_str(&contents).map_err(|e| format!("Invalid config file {}: {}", path, e))
 }
 
 impl Config {
-    pub fn from_args(args: Args) -> Self {
-        let helius_api_key = args.helius_api_key.or_else(|| env::var("HELIUS_API_KEY").ok());
-        let http_rpc_url = args.http_rpc_url.or_else(|| env::var("HTTP_RPC_URL").ok())
-            .unwrap_or_else(|| "https://api.mainnet-beta.solana.com/".to_string());
-        let start_block = args.start_block.or_else(|| {
-            env::var("START_BLOCK").ok().and_then(|s| s.parse().ok())
-        }).unwrap_or(0);
-        
-        let rpc_ws_url = if args.enable_account_monitoring {
-            Some(args.rpc_ws_url.unwrap_or_else(|| {
-                env::var("RPC_WS_URL").unwrap_or_else(|_| "wss://api.mainnet-beta.solana.com/".to_string())
-            }))
+    /// Resolves the configuration from the file, environment and CLI flags
+    pub fn load(args: Args) -> Result<Self, String> {
+        let file = match args.config.clone().or_else(|| env_var("INDEXER_CONFIG")) {
+            Some(path) => read_file_config(&path)?,
+            None => FileConfig::default(),
+        };
+
+        Self::resolve(args, file)
+    }
+
+    fn resolve(args: Args, file: FileConfig) -> Result<Self, String> {
+        let mut errors = Errors::default();
+
+        let cluster = match args.cluster.clone().or_else(|| env_var("CLUSTER")).or(file.cluster) {
+            Some(name) => Cluster::from_str(&name).unwrap_or_else(|e| {
+                errors.push("cluster", e);
+                Cluster::MainnetBeta
+            }),
+            None => Cluster::MainnetBeta,
+        };
+
+        // Programs: CLI, then env, then file, then the decoder's default program
+        let start_block_override = args.start_block.or_else(|| env_var("START_BLOCK").and_then(|s| s.parse().ok()));
+        // Each program with the setting it comes from, for precise duplicate errors
+        let mut programs: Vec<(String, ProgramConfig)> = Vec::new();
+        let override_ids = if args.program_ids.is_empty() {
+            env_var("OMNIPAIR_PROGRAM_ID").map(|id| vec![id]).unwrap_or_default()
         } else {
-            None
+            args.program_ids.clone()
         };
 
-        Self {
-            http_rpc_url,
-            start_block,
-            helius_api_key,
-            rpc_ws_url,
-            health_port: args.health_port,
+        if !override_ids.is_empty() {
+            for (index, id) in override_ids.iter().enumerate() {
+                let setting = format!("program-id[{}]", index);
+                if let Some(program_id) = parse_pubkey(&mut errors, &setting, id) {
+                    programs.push((setting, ProgramConfig {
+                        name: if index == 0 { "omnipair".to_string() } else { format!("omnipair-{}", index) },
+                        program_id,
+                        start_block: start_block_override.unwrap_or(0),
+                    }));
+                }
+            }
+        } else if !file.programs.is_empty() {
+            for (index, program) in file.programs.iter().enumerate() {
+                let setting = format!("programs[{}]", index);
+                let name = program.name.clone().unwrap_or_else(|| format!("program-{}", index));
+                if name.trim().is_empty() {
+                    errors.push(&format!("{}.name", setting), "must not be empty");
+                }
+
+                if let Some(program_id) = parse_pubkey(&mut errors, &format!("{}.program_id", setting), &program.program_id) {
+                    programs.push((setting, ProgramConfig {
+                        name,
+                        program_id,
+                        start_block: start_block_override.or(program.start_block).unwrap_or(0),
+                    }));
+                }
+            }
+        } else {
+            programs.push(("programs".to_string(), ProgramConfig {
+                name: "omnipair".to_string(),
+                program_id: *carbon_omnipair_decoder::PROGRAM_ID,
+                start_block: start_block_override.unwrap_or(0),
+            }));
+        }
+
+        for (index, (setting, program)) in programs.iter().enumerate() {
+            let earlier = &programs[..index];
+            if let Some((first, _)) = earlier.iter().find(|(_, other)| other.program_id == program.program_id) {
+                errors.push(&format!("{}.program_id", setting), format!("{} is already listed in {}", program.program_id, first));
+            }
+            if let Some((first, _)) = earlier.iter().find(|(_, other)| other.name == program.name) {
+                errors.push(&format!("{}.name", setting), format!("'{}' is already used by {}", program.name, first));
+            }
+        }
+        let programs: Vec<ProgramConfig> = programs.into_iter().map(|(_, program)| program).collect();
+
+        let http_rpc_url = args
+            .http_rpc_url
+            .clone()
+            .or_else(|| env_var("HTTP_RPC_URL"))
+            .or(file.datasources.rpc.http_url)
+            .unwrap_or_else(|| cluster.default_http_rpc_url().to_string());
+        check_url(&mut errors, "datasources.rpc.http_url", &http_rpc_url, &["http", "https"]);
+
+        let helius_file = file.datasources.helius;
+        let mut account_filter = |setting: &str, values: &[String]| -> Vec<Pubkey> {
+            values
+                .iter()
+                .enumerate()
+                .filter_map(|(index, value)| parse_pubkey(&mut errors, &format!("{}[{}]", setting, index), value))
+                .collect()
+        };
+        let helius = HeliusConfig {
+            api_key: args.helius_api_key.clone().or_else(|| env_var("HELIUS_API_KEY")).or(helius_file.api_key),
+            include_failed: helius_file.include_failed.unwrap_or(false),
+            account_required: account_filter("datasources.helius.account_required", &helius_file.account_required),
+            account_exclude: account_filter("datasources.helius.account_exclude", &helius_file.account_exclude),
+        };
+
+        let subscribe_file = file.datasources.program_subscribe;
+        let program_subscribe = ProgramSubscribeConfig {
+            ws_url: args
+                .rpc_ws_url
+                .clone()
+                .or_else(|| env_var("RPC_WS_URL"))
+                .or(subscribe_file.ws_url)
+                .unwrap_or_else(|| cluster.default_ws_rpc_url().to_string()),
+            data_sizes: subscribe_file.data_sizes,
+            seed_snapshot: subscribe_file.seed_snapshot.unwrap_or(true),
+        };
+        check_url(&mut errors, "datasources.program_subscribe.ws_url", &program_subscribe.ws_url, &["ws", "wss"]);
+        for (index, size) in program_subscribe.data_sizes.iter().enumerate() {
+            if *size == 0 {
+                errors.push(&format!("datasources.program_subscribe.data_sizes[{}]", index), "must be greater than 0");
+            }
+        }
+
+        let database = DatabaseConfig {
+            url: args.database_url.clone().or_else(|| env_var("DATABASE_URL")).or(file.database.url),
+            max_connections: file.database.max_connections.unwrap_or(10),
+            auto_migrate: args.auto_migrate || env_flag("AUTO_MIGRATE") || file.database.auto_migrate.unwrap_or(false),
+        };
+        if database.max_connections == 0 {
+            errors.push("database.max_connections", "must be at least 1");
+        }
+        if let Some(url) = &database.url {
+            check_url(&mut errors, "database.url", url, &["postgres", "postgresql"]);
         }
+
+        let processors = ProcessorsConfig {
+            events: file.processors.events.unwrap_or(true),
+            accounts: args.enable_account_monitoring || file.processors.accounts.unwrap_or(false),
+        };
+        if !processors.events && !processors.accounts {
+            errors.push("processors", "at least one of `events` and `accounts` must be enabled");
+        }
+
+        // Webhooks: CLI or env endpoint specs replace the file's endpoints
+        let endpoint_specs = if !args.webhook_endpoints.is_empty() {
+            Some(args.webhook_endpoints.clone())
+        } else {
+            env_var("WEBHOOK_ENDPOINTS").map(|value| value.split(',').map(|spec| spec.trim().to_string()).collect())
+        };
+        let endpoints = match endpoint_specs {
+            Some(specs) => NotifierConfig::parse_endpoints(&specs).unwrap_or_else(|e| {
+                errors.push("notifier.endpoints", e);
+                HashMap::new()
+            }),
+            None => file.notifier.endpoints,
+        };
+        let defaults = NotifierConfig::default();
+        let notifier = NotifierConfig {
+            endpoints,
+            hmac_secret: args.webhook_secret.clone().or_else(|| env_var("WEBHOOK_SECRET")).or(file.notifier.secret),
+            max_attempts: file.notifier.max_attempts.unwrap_or(defaults.max_attempts),
+            initial_backoff: file.notifier.initial_backoff_secs.map(Duration::from_secs).unwrap_or(defaults.initial_backoff),
+            poll_interval: file.notifier.poll_interval_secs.map(Duration::from_secs).unwrap_or(defaults.poll_interval),
+        };
+        if let Err(e) = notifier.validate() {
+            errors.push("notifier", e);
+        }
+        if notifier.initial_backoff.is_zero() {
+            errors.push("notifier.initial_backoff_secs", "must be greater than 0");
+        }
+        if notifier.poll_interval.is_zero() {
+            errors.push("notifier.poll_interval_secs", "must be greater than 0");
+        }
+
+        let env_number = |errors: &mut Errors, setting: &str, name: &str| -> Option<u64> {
+            let value = env_var(name)?;
+            value.trim().parse().map_err(|_| errors.push(setting, format!("{}='{}' is not a number", name, value))).ok()
+        };
+        let health = HealthConfig {
+            port: match args.health_port {
+                Some(port) => port,
+                None => match env_number(&mut errors, "health.port", "HEALTH_PORT") {
+                    Some(port) => u16::try_from(port).unwrap_or_else(|_| {
+                        errors.push("health.port", format!("{} is not a valid port", port));
+                        0
+                    }),
+                    None => file.health.port.unwrap_or(8080),
+                },
+            },
+            max_slot_lag: env_number(&mut errors, "health.max_slot_lag", "HEALTH_MAX_SLOT_LAG")
+                .or(file.health.max_slot_lag)
+                .unwrap_or(150),
+            max_update_age: Duration::from_secs(
+                env_number(&mut errors, "health.max_update_age_secs", "HEALTH_MAX_UPDATE_AGE_SECS")
+                    .or(file.health.max_update_age_secs)
+                    .unwrap_or(0),
+            ),
+        };
+
+        let metrics_address = args
+            .metrics_address
+            .clone()
+            .or_else(|| env_var("METRICS_ADDRESS"))
+            .or(file.metrics.address)
+            .unwrap_or_else(|| "0.0.0.0:9100".to_string());
+        let metrics = MetricsConfig {
+            enabled: match env_var("METRICS_ENABLED") {
+                Some(value) => value.trim().parse().unwrap_or_else(|_| {
+                    errors.push("metrics.enabled", format!("METRICS_ENABLED='{}' is not true or false", value));
+                    false
+                }),
+                None => file.metrics.enabled.unwrap_or(true),
+            },
+            address: metrics_address.parse().unwrap_or_else(|_| {
+                errors.push(
+                    "metrics.address",
+                    format!("'{}' is not a socket address such as 0.0.0.0:9100", metrics_address),
+                );
+                SocketAddr::from(([0, 0, 0, 0], 9100))
+            }),
+            buckets: file.metrics.buckets,
+            metric_buckets: file.metrics.metric_buckets,
+        };
+        check_buckets(&mut errors, "metrics.buckets", &metrics.buckets);
+        for (name, buckets) in &metrics.metric_buckets {
+            if buckets.is_empty() {
+                errors.push(&format!("metrics.metric_buckets.{}", name), "must not be empty");
+            }
+            check_buckets(&mut errors, &format!("metrics.metric_buckets.{}", name), buckets);
+        }
+        if metrics.enabled && health.port != 0 && metrics.address.port() == health.port {
+            errors.push("metrics.address", format!("port {} is already used by the health server", health.port));
+        }
+
+        // An empty fallback path disables the fallback file
+        let fallback_path = env_var("DEAD_LETTER_FALLBACK_PATH")
+            .or(file.dead_letter.fallback_path)
+            .unwrap_or_else(|| "dead-letters.jsonl".to_string());
+        let dead_letter = DeadLetterConfig {
+            enabled: file.dead_letter.enabled.unwrap_or(true),
+            fallback_path: (!fallback_path.trim().is_empty()).then(|| PathBuf::from(fallback_path.trim())),
+        };
+
+        // Prices listed in the configuration file override those of the prices file
+        let mut usd_prices = BTreeMap::new();
+        if let Some(path) = env_var("USD_PRICES_FILE").or(file.pricing.usd_prices_file) {
+            match read_usd_prices_file(&path) {
+                Ok(prices) => usd_prices.extend(prices),
+                Err(e) => errors.push("pricing.usd_prices_file", e),
+            }
+        }
+        usd_prices.extend(file.pricing.usd_prices);
+
+        let mut pricing = PricingConfig::default();
+        for (mint, price) in usd_prices {
+            let setting = format!("pricing.usd_prices.{}", mint);
+            let Some(mint) = parse_pubkey(&mut errors, &setting, &mint) else {
+                continue;
+            };
+            match BigDecimal::from_str(&price.to_string()) {
+                Ok(price) if price > BigDecimal::from(0) => {
+                    pricing.usd_prices.insert(mint, price);
+                }
+                _ => errors.push(&setting, format!("{} is not a positive price", price)),
+            }
+        }
+
+        errors.into_result()?;
+
+        Ok(Self {
+            cluster,
+            programs,
+            http_rpc_url,
+            helius,
+            program_subscribe,
+            database,
+            processors,
+            notifier,
+            health,
+            metrics,
+            dead_letter,
+            pricing,
+        })
+    }
+
+    pub fn program_ids(&self) -> Vec<Pubkey> {
+        self.programs.iter().map(|program| program.program_id).collect()
     }
 
+    /// Program subscriptions of account monitoring: one per program and data size, as RPC
+    /// filters cannot express alternatives
+    pub fn program_subscriptions(&self) -> Vec<(Pubkey, Option<u64>)> {
+        let data_sizes: Vec<Option<u64>> = if self.program_subscribe.data_sizes.is_empty() {
+            vec![None]
+        } else {
+            self.program_subscribe.data_sizes.iter().copied().map(Some).collect()
+        };
+
+        self.program_ids()
+            .into_iter()
+            .flat_map(|program_id| data_sizes.iter().map(move |data_size| (program_id, *data_size)))
+            .collect()
+    }
+
+    /// Checks the settings the daemon needs on top of those checked by `load`
     pub fn validate(&self) -> Result<(), String> {
-        if self.helius_api_key.is_none() {
-            return Err("HELIUS_API_KEY is required for transaction monitoring".to_string());
+        let mut errors = Errors::default();
+
+        if self.processors.events {
+            if self.helius.api_key.is_none() {
+                errors.push(
+                    "datasources.helius.api_key",
+                    "HELIUS_API_KEY is required for transaction monitoring",
+                );
+            }
+            if self.cluster.helius_cluster().is_none() {
+                errors.push(
+                    "cluster",
+                    format!("Helius Atlas does not serve {}, disable processors.events", self.cluster),
+                );
+            }
         }
-        Ok(())
+
+        errors.into_result()
     }
 
     pub fn log_configuration(&self) {
         log::info!("Configuration:");
-        
-        if self.helius_api_key.is_some() {
+        log::info!("  Cluster: {}", self.cluster);
+
+        for program in &self.programs {
+            log::info!("  Program {}: {} (start block {})", program.name, program.program_id, program.start_block);
+        }
+
+        if !self.processors.events {
+            log::info!("  Transaction monitoring: Disabled");
+        } else if self.helius.api_key.is_some() {
             log::info!("  Transaction monitoring: Helius Atlas WebSocket");
         } else {
             log::error!("  HELIUS_API_KEY is required for transaction monitoring");
         }
-        
-        if let Some(ref url) = self.rpc_ws_url {
-            log::info!("  Account monitoring: Enabled via {}", url);
+
+        if self.processors.accounts {
+            log::info!("  Account monitoring: Enabled via {}", self.program_subscribe.ws_url);
         } else {
             log::info!("  Account monitoring: Disabled");
         }
 
-        if self.health_port != 0 {
-            log::info!("  Health check server: Port {}", self.health_port);
+        if self.notifier.is_enabled() {
+            let mut event_types: Vec<_> = self.notifier.endpoints.keys().cloned().collect();
+            event_types.sort();
+            log::info!(
+                "  Webhooks: {} ({})",
+                event_types.join(", "),
+                if self.notifier.hmac_secret.is_some() { "signed" } else { "unsigned" }
+            );
+        } else {
+            log::info!("  Webhooks: Disabled");
+        }
+
+        log::info!(
+            "  Schema migrations: {}",
+            if self.database.auto_migrate { "applied on startup" } else { "required up to date" }
+        );
+
+        if self.health.port != 0 {
+            log::info!(
+                "  Health check server: Port {} (max slot lag {}, max update age {}s)",
+                self.health.port,
+                self.health.max_slot_lag,
+                self.health.max_update_age.as_secs()
+            );
         } else {
             log::info!("  Health check server: Disabled");
         }
+
+        match (self.dead_letter.enabled, &self.dead_letter.fallback_path) {
+            (false, _) => log::info!("  Dead-letter queue: Disabled"),
+            (true, Some(path)) => log::info!("  Dead-letter queue: dead_letter_updates (fallback file {})", path.display()),
+            (true, None) => log::info!("  Dead-letter queue: dead_letter_updates"),
+        }
+
+        if self.metrics.enabled {
+            log::info!("  Prometheus exporter: http://{}/metrics", self.metrics.address);
+        } else {
+            log::info!("  Prometheus exporter: Disabled");
+        }
+
+        log::info!("  Static USD prices: {} mints", self.pricing.usd_prices.len());
     }
 }
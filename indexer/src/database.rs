@@ -9,18 +9,49 @@ use carbon_omnipair_decoder::instructions::{
     user_position_liquidated_event::UserPositionLiquidatedEvent,
     user_liquidity_position_updated_event::UserLiquidityPositionUpdatedEvent,
     pair_created_event::PairCreatedEvent,
+    update_pair_event::UpdatePairEvent,
+    user_position_created_event::UserPositionCreatedEvent,
+};
+use carbon_omnipair_decoder::accounts::{
+    futarchy_authority::FutarchyAuthority,
+    pair::Pair,
+    rate_model::RateModel,
+    user_position::UserPosition,
+};
+use chrono::{DateTime, Utc};
+use solana_pubkey::Pubkey;
+use sqlx::{postgres::PgPoolOptions, PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    candles,
+    config::DatabaseConfig,
+    dead_letter::{DeadLetter, StoredDeadLetter},
+    event_context::EventContext,
+    event_writer::{EventBatch, IngestionCheckpoint, ProcessedTransaction},
+    fees,
+    flashloans::FlashloanRecord,
+    interest::{self, RateModelParams},
+    notifier,
+    pricing::SwapPricing,
+    revenue,
+    store::EventKey,
 };
-use sqlx::PgPool;
 use tokio::sync::OnceCell;
 
 static DB_POOL: OnceCell<PgPool> = OnceCell::const_new();
 
 /// Initialize the database connection pool
-pub async fn init_db_pool() -> CarbonResult<()> {
-    let database_url = std::env::var("DATABASE_URL")
-        .map_err(|_| carbon_core::error::Error::Custom("DATABASE_URL environment variable not set".to_string()))?;
+pub async fn init_db_pool(config: &DatabaseConfig) -> CarbonResult<()> {
+    let database_url = config.url.as_ref()
+        .ok_or_else(|| carbon_core::error::Error::Custom(
+            "Missing database URL: set DATABASE_URL, --database-url or database.url in the config file".to_string(),
+        ))?;
     
-    let pool = PgPool::connect(&database_url).await
+    let pool = PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .connect(database_url)
+        .await
         .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to connect to database: {}", e)))?;
     
     // Test the connection
@@ -42,533 +73,1563 @@ pub fn get_db_pool() -> CarbonResult<&'static PgPool> {
         .ok_or_else(|| carbon_core::error::Error::Custom("Database pool not initialized. Call init_db_pool() first".to_string()))
 }
 
-/// Upsert a swap event into the database (handles duplicate tx_sig)
-pub async fn upsert_swap_event(
-    swap_event: &SwapEvent,
-    tx_signature: &str,
-    slot: i64,
-) -> CarbonResult<()> {
+/// Checks that the database answers a trivial query
+pub async fn check_connection() -> CarbonResult<()> {
     let pool = get_db_pool()?;
-    
-    // Calculate fee amounts
-    let fee_amount = swap_event.amount_in - swap_event.amount_in_after_fee;
-    let fee_paid0 = if swap_event.is_token0_in {
-        fee_amount
-    } else {
-        // Convert token1 fee to token0 equivalent using reserve ratio
-        (swap_event.reserve0 as f64 / swap_event.reserve1 as f64 * fee_amount as f64) as u64
-    };
-    let fee_paid1 = if swap_event.is_token0_in {
-        // Convert token0 fee to token1 equivalent using reserve ratio  
-        (swap_event.reserve1 as f64 / swap_event.reserve0 as f64 * fee_amount as f64) as u64
-    } else {
-        fee_amount
-    };
-    
-    let upsert_result = sqlx::query(
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .map_err(|e| carbon_core::error::Error::Custom(format!("Database connection check failed: {}", e)))?;
+    Ok(())
+}
+
+/// Maximum rows per multi-row INSERT, keeping every statement well below the
+/// 65535 bind parameter limit of the Postgres protocol
+pub const INSERT_CHUNK_ROWS: usize = 1000;
+
+/// Write every row of `batch` in a single database transaction: either all events of
+/// the batched Solana transactions are persisted, or none is.
+///
+/// Rows are grouped per table into multi-row inserts. Creation events go first so the
+/// rows referencing them can link to them within the same batch.
+pub async fn write_event_batch(batch: &EventBatch) -> CarbonResult<()> {
+    let pool = get_db_pool()?;
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        carbon_core::error::Error::Custom(format!("Failed to begin event batch transaction: {}", e))
+    })?;
+
+    let legacy_swap_days = delete_legacy_rows(&mut tx, batch).await?;
+    upsert_pair_created_events(&mut tx, &batch.pairs_created).await?;
+    upsert_user_position_created_events(&mut tx, &batch.user_positions_created).await?;
+    let inserted_swaps = upsert_swap_events(&mut tx, &batch.swaps, &batch.swap_pricing).await?;
+    let new_swaps: Vec<_> = batch
+        .swaps
+        .iter()
+        .filter(|(_, ctx)| {
+            inserted_swaps.contains(&(ctx.signature.clone(), ctx.instruction_index, ctx.inner_instruction_index))
+        })
+        .collect();
+    candles::record_swaps(&mut tx, &new_swaps).await?;
+    for (pair, day) in &legacy_swap_days {
+        candles::replace_day_candles(&mut tx, pair, *day).await?;
+    }
+    fees::validate_swap_fees(&mut tx, &new_swaps).await?;
+    revenue::accrue_swap_fees(&mut tx, &batch.swaps).await?;
+    upsert_liquidity_events(&mut tx, &batch.mints, &batch.burns).await?;
+    upsert_adjust_collateral_events(&mut tx, &batch.collateral_adjustments).await?;
+    upsert_adjust_debt_events(&mut tx, &batch.debt_adjustments).await?;
+    upsert_user_position_updated_events(&mut tx, &batch.user_position_updates).await?;
+    upsert_user_position_liquidated_events(&mut tx, &batch.user_position_liquidations).await?;
+    upsert_user_liquidity_position_updated_events(&mut tx, &batch.user_liquidity_position_updates).await?;
+    upsert_update_pair_events(&mut tx, &batch.pair_updates).await?;
+    upsert_flashloans(&mut tx, &batch.flashloans).await?;
+    revenue::record_protocol_fee_claims(&mut tx, &batch.protocol_fee_claims).await?;
+    notifier::enqueue_outbox_entries(&mut tx, &batch.notifications).await?;
+    insert_processed_transactions(&mut tx, &batch.processed_transactions).await?;
+
+    if let Some(checkpoint) = &batch.checkpoint {
+        upsert_ingestion_checkpoint(&mut tx, checkpoint).await?;
+    }
+
+    tx.commit().await.map_err(|e| {
+        carbon_core::error::Error::Custom(format!("Failed to commit event batch: {}", e))
+    })?;
+
+    Ok(())
+}
+
+/// Event tables with rows written before migration 007 (`legacy_identity`, migration 024)
+/// and their signature column
+const LEGACY_IDENTITY_TABLES: [(&str, &str); 5] = [
+    ("adjust_liquidity", "tx_sig"),
+    ("user_position_updated_events", "transaction_signature"),
+    ("user_position_liquidated_events", "transaction_signature"),
+    ("adjust_collateral_events", "transaction_signature"),
+    ("adjust_debt_events", "transaction_signature"),
+];
+
+/// Deletes the legacy rows of the batch's transactions, which the batch writes again
+/// under their per-instruction identity. Legacy LP position updates have no signature
+/// and are matched on their pair, signer and slot. Returns the pair and UTC day of every
+/// deleted swap, whose candles must be rebuilt.
+async fn delete_legacy_rows(
+    conn: &mut PgConnection,
+    batch: &EventBatch,
+) -> CarbonResult<HashSet<(String, DateTime<Utc>)>> {
+    let signatures: Vec<String> = batch
+        .processed_transactions
+        .iter()
+        .map(|transaction| transaction.signature.clone())
+        .collect();
+    if signatures.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let swap_days: Vec<(Option<String>, DateTime<Utc>)> = sqlx::query_as(
         r#"
-        INSERT INTO swaps (
-            pair, user_address, is_token0_in, amount_in, amount_out, 
-            reserve0, reserve1, timestamp, tx_sig, slot, fee_paid0, fee_paid1
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
-        ON CONFLICT (tx_sig, timestamp) DO UPDATE SET
-            pair = EXCLUDED.pair,
-            user_address = EXCLUDED.user_address,
-            is_token0_in = EXCLUDED.is_token0_in,
-            amount_in = EXCLUDED.amount_in,
-            amount_out = EXCLUDED.amount_out,
-            reserve0 = EXCLUDED.reserve0,
-            reserve1 = EXCLUDED.reserve1,
-            timestamp = EXCLUDED.timestamp,
-            slot = EXCLUDED.slot,
-            fee_paid0 = EXCLUDED.fee_paid0,
-            fee_paid1 = EXCLUDED.fee_paid1
-        "#
+        DELETE FROM swaps WHERE legacy_identity AND tx_sig = ANY($1)
+        RETURNING pair, date_trunc('day', "timestamp" AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+        "#,
     )
-    .bind(swap_event.metadata.pair.to_string())
-    .bind(swap_event.metadata.signer.to_string())
-    .bind(swap_event.is_token0_in)
-    .bind(bigdecimal::BigDecimal::from(swap_event.amount_in))
-    .bind(bigdecimal::BigDecimal::from(swap_event.amount_out))
-    .bind(bigdecimal::BigDecimal::from(swap_event.reserve0))
-    .bind(bigdecimal::BigDecimal::from(swap_event.reserve1))
-    .bind(chrono::Utc::now())
-    .bind(tx_signature)
-    .bind(bigdecimal::BigDecimal::from(slot))
-    .bind(bigdecimal::BigDecimal::from(fee_paid0))
-    .bind(bigdecimal::BigDecimal::from(fee_paid1))
-    .execute(pool)
-    .await;
-    
-    if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into swaps table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert swap: {}", e)));
+    .bind(&signatures)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to delete legacy swaps: {}", e)))?;
+
+    for (table, signature_column) in LEGACY_IDENTITY_TABLES {
+        sqlx::query(&format!("DELETE FROM {} WHERE legacy_identity AND {} = ANY($1)", table, signature_column))
+            .bind(&signatures)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to delete legacy rows of {}: {}", table, e)))?;
     }
-    
+
+    if !batch.user_liquidity_position_updates.is_empty() {
+        let (pairs, (signers, slots)): (Vec<String>, (Vec<String>, Vec<bigdecimal::BigDecimal>)) = batch
+            .user_liquidity_position_updates
+            .iter()
+            .map(|(event, _)| {
+                (
+                    event.metadata.pair.to_string(),
+                    (event.metadata.signer.to_string(), bigdecimal::BigDecimal::from(event.metadata.slot)),
+                )
+            })
+            .unzip();
+
+        sqlx::query(
+            r#"
+            DELETE FROM user_lp_position_updated_events e
+            USING UNNEST($1::text[], $2::text[], $3::numeric[]) AS n(pair_address, signer, slot)
+            WHERE e.legacy_identity
+                AND e.pair_address = n.pair_address
+                AND e.signer = n.signer
+                AND e.slot = n.slot
+            "#,
+        )
+        .bind(&pairs)
+        .bind(&signers)
+        .bind(&slots)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to delete legacy LP position updates: {}", e)))?;
+    }
+
+    Ok(swap_days
+        .into_iter()
+        .filter_map(|(pair, day)| Some((pair?, day)))
+        .collect())
+}
+
+/// Keeps the last row per key, where a later row wins unless it has a lower slot.
+/// Latest-state tables are upserted per key, and Postgres rejects a multi-row upsert
+/// that touches the same row twice.
+fn latest_per_key<T, K: Eq + std::hash::Hash>(
+    rows: &[(T, EventContext)],
+    key: impl Fn(&T) -> K,
+) -> Vec<&(T, EventContext)> {
+    let mut latest: HashMap<K, usize> = HashMap::new();
+
+    for (index, (row, ctx)) in rows.iter().enumerate() {
+        match latest.entry(key(row)) {
+            std::collections::hash_map::Entry::Occupied(mut entry) => {
+                if rows[*entry.get()].1.slot <= ctx.slot {
+                    entry.insert(index);
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(index);
+            }
+        }
+    }
+
+    let mut indexes: Vec<usize> = latest.into_values().collect();
+    indexes.sort_unstable();
+    indexes.into_iter().map(|index| &rows[index]).collect()
+}
+
+/// Upsert swap events into the swaps table (handles duplicate tx_sig), together with their
+/// price columns. Returns the keys of the swaps that were inserted rather than updated.
+pub async fn upsert_swap_events(
+    conn: &mut PgConnection,
+    swaps: &[(SwapEvent, EventContext)],
+    pricing: &[(SwapPricing, EventContext)],
+) -> CarbonResult<HashSet<EventKey>> {
+    let pricing_by_key: HashMap<EventKey, &SwapPricing> = pricing
+        .iter()
+        .map(|(pricing, ctx)| ((ctx.signature.clone(), ctx.instruction_index, ctx.inner_instruction_index), pricing))
+        .collect();
+    let mut inserted = HashSet::new();
+
+    for chunk in swaps.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO swaps (
+                pair, user_address, is_token0_in, amount_in, amount_out, 
+                reserve0, reserve1, timestamp, tx_sig, slot, fee_paid0, fee_paid1, fee_value_out,
+                instruction_index, inner_instruction_index, price, ema_price, volume_usd
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (swap_event, ctx)| {
+            let (fee_paid0, fee_paid1) = fees::fees_paid(swap_event);
+            let fee_value_out = fees::fee_value_out(swap_event)
+                .map(|value| bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(value)));
+            let key = (ctx.signature.clone(), ctx.instruction_index, ctx.inner_instruction_index);
+            let pricing = pricing_by_key.get(&key).map(|pricing| (*pricing).clone()).unwrap_or_default();
+
+            row.push_bind(swap_event.metadata.pair.to_string())
+                .push_bind(swap_event.metadata.signer.to_string())
+                .push_bind(swap_event.is_token0_in)
+                .push_bind(bigdecimal::BigDecimal::from(swap_event.amount_in))
+                .push_bind(bigdecimal::BigDecimal::from(swap_event.amount_out))
+                .push_bind(bigdecimal::BigDecimal::from(swap_event.reserve0))
+                .push_bind(bigdecimal::BigDecimal::from(swap_event.reserve1))
+                .push_bind(ctx.timestamp)
+                .push_bind(ctx.signature.clone())
+                .push_bind(bigdecimal::BigDecimal::from(ctx.slot))
+                .push_bind(bigdecimal::BigDecimal::from(fee_paid0))
+                .push_bind(bigdecimal::BigDecimal::from(fee_paid1))
+                .push_bind(fee_value_out)
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index)
+                .push_bind(pricing.price)
+                .push_bind(pricing.ema_price)
+                .push_bind(pricing.volume_usd);
+        });
+
+        // Price columns are only replaced by known values, so rewriting a swap without a
+        // pricer or a USD price keeps the ones it was written with
+        query.push(
+            r#"
+            ON CONFLICT (tx_sig, instruction_index, inner_instruction_index, timestamp) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                user_address = EXCLUDED.user_address,
+                is_token0_in = EXCLUDED.is_token0_in,
+                amount_in = EXCLUDED.amount_in,
+                amount_out = EXCLUDED.amount_out,
+                reserve0 = EXCLUDED.reserve0,
+                reserve1 = EXCLUDED.reserve1,
+                timestamp = EXCLUDED.timestamp,
+                slot = EXCLUDED.slot,
+                fee_paid0 = EXCLUDED.fee_paid0,
+                fee_paid1 = EXCLUDED.fee_paid1,
+                fee_value_out = EXCLUDED.fee_value_out,
+                price = COALESCE(EXCLUDED.price, swaps.price),
+                ema_price = COALESCE(EXCLUDED.ema_price, swaps.ema_price),
+                volume_usd = COALESCE(EXCLUDED.volume_usd, swaps.volume_usd)
+            RETURNING tx_sig, instruction_index, inner_instruction_index, (xmax = 0) AS inserted
+            "#
+        );
+
+        let rows = match query.build_query_as::<(String, i32, i32, bool)>().fetch_all(&mut *conn).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                log::error!("Failed to upsert into swaps table: {}", e);
+                return Err(carbon_core::error::Error::Custom(format!("Failed to upsert swaps: {}", e)));
+            }
+        };
+
+        inserted.extend(
+            rows.into_iter()
+                .filter(|(_, _, _, inserted)| *inserted)
+                .map(|(signature, instruction_index, inner_instruction_index, _)| {
+                    (signature, instruction_index, inner_instruction_index)
+                }),
+        );
+    }
+
+    Ok(inserted)
+}
+
+/// Upsert mint ("add") and burn ("remove") events into the adjust_liquidity table
+pub async fn upsert_liquidity_events(
+    conn: &mut PgConnection,
+    mints: &[(MintEvent, EventContext)],
+    burns: &[(BurnEvent, EventContext)],
+) -> CarbonResult<()> {
+    let rows: Vec<_> = mints
+        .iter()
+        .map(|(event, ctx)| ("add", event.amount0, event.amount1, event.liquidity, &event.metadata, ctx))
+        .chain(
+            burns
+                .iter()
+                .map(|(event, ctx)| ("remove", event.amount0, event.amount1, event.liquidity, &event.metadata, ctx)),
+        )
+        .collect();
+
+    for chunk in rows.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO adjust_liquidity (
+                pair, user_address, amount0, amount1, liquidity, tx_sig, timestamp, event_type, slot,
+                instruction_index, inner_instruction_index
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event_type, amount0, amount1, liquidity, metadata, ctx)| {
+            row.push_bind(metadata.pair.to_string())
+                .push_bind(metadata.signer.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(*amount0))
+                .push_bind(bigdecimal::BigDecimal::from(*amount1))
+                .push_bind(bigdecimal::BigDecimal::from(*liquidity))
+                .push_bind(ctx.signature.clone())
+                .push_bind(ctx.timestamp)
+                .push_bind(*event_type)
+                .push_unseparated("::liquidity_event_type")
+                .push_bind(bigdecimal::BigDecimal::from(metadata.slot))
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (tx_sig, instruction_index, inner_instruction_index, timestamp) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                user_address = EXCLUDED.user_address,
+                amount0 = EXCLUDED.amount0,
+                amount1 = EXCLUDED.amount1,
+                liquidity = EXCLUDED.liquidity,
+                timestamp = EXCLUDED.timestamp,
+                event_type = EXCLUDED.event_type,
+                slot = EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into adjust_liquidity table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert liquidity events: {}", e)));
+        }
+    }
+
     Ok(())
 }
 
-/// Upsert a mint event into the adjust_liquidity table
-pub async fn upsert_mint_event(
-    event: &MintEvent,
-    tx_signature: &str,
-    _slot: i64,
+/// Upsert AdjustCollateralEvents into the database
+pub async fn upsert_adjust_collateral_events(
+    conn: &mut PgConnection,
+    events: &[(AdjustCollateralEvent, EventContext)],
 ) -> CarbonResult<()> {
-    let pool = get_db_pool()?;
-    
-    let upsert_result = sqlx::query(
-        r#"
-        INSERT INTO adjust_liquidity (
-            pair, user_address, amount0, amount1, liquidity, tx_sig, timestamp, event_type, slot
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8::liquidity_event_type, $9)
-        ON CONFLICT (tx_sig, timestamp) DO UPDATE SET
-            pair = EXCLUDED.pair,
-            user_address = EXCLUDED.user_address,
-            amount0 = EXCLUDED.amount0,
-            amount1 = EXCLUDED.amount1,
-            liquidity = EXCLUDED.liquidity,
-            timestamp = EXCLUDED.timestamp,
-            event_type = EXCLUDED.event_type,
-            slot = EXCLUDED.slot
-        "#
-    )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.amount0))
-    .bind(bigdecimal::BigDecimal::from(event.amount1))
-    .bind(bigdecimal::BigDecimal::from(event.liquidity))
-    .bind(tx_signature)
-    .bind(chrono::Utc::now())
-    .bind("add") // MintEvent = "add" liquidity
-    .bind(bigdecimal::BigDecimal::from(event.metadata.slot))
-    .execute(pool)
-    .await;
-    
-    if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into adjust_liquidity table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert mint event: {}", e)));
+    for chunk in events.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO adjust_collateral_events (
+                pair, signer, amount0, amount1, transaction_signature, slot, event_timestamp,
+                instruction_index, inner_instruction_index
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.amount0))
+                .push_bind(bigdecimal::BigDecimal::from(event.amount1))
+                .push_bind(ctx.signature.clone())
+                .push_bind(bigdecimal::BigDecimal::from(ctx.slot))
+                .push_bind(ctx.timestamp)
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                signer = EXCLUDED.signer,
+                amount0 = EXCLUDED.amount0,
+                amount1 = EXCLUDED.amount1,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into adjust_collateral_events table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert adjust collateral events: {}", e)));
+        }
     }
-    
+
     Ok(())
 }
 
-/// Upsert a burn event into the adjust_liquidity table
-pub async fn upsert_burn_event(
-    event: &BurnEvent,
-    tx_signature: &str,
-    _slot: i64,
+/// Upsert AdjustDebtEvents into the database
+pub async fn upsert_adjust_debt_events(
+    conn: &mut PgConnection,
+    events: &[(AdjustDebtEvent, EventContext)],
 ) -> CarbonResult<()> {
-    let pool = get_db_pool()?;
-    
-    let upsert_result = sqlx::query(
-        r#"
-        INSERT INTO adjust_liquidity (
-            pair, user_address, amount0, amount1, liquidity, tx_sig, timestamp, event_type, slot
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8::liquidity_event_type, $9)
-        ON CONFLICT (tx_sig, timestamp) DO UPDATE SET
-            pair = EXCLUDED.pair,
-            user_address = EXCLUDED.user_address,
-            amount0 = EXCLUDED.amount0,
-            amount1 = EXCLUDED.amount1,
-            liquidity = EXCLUDED.liquidity,
-            timestamp = EXCLUDED.timestamp,
-            event_type = EXCLUDED.event_type,
-            slot = EXCLUDED.slot
-        "#
-    )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.amount0))
-    .bind(bigdecimal::BigDecimal::from(event.amount1))
-    .bind(bigdecimal::BigDecimal::from(event.liquidity))
-    .bind(tx_signature)
-    .bind(chrono::Utc::now())
-    .bind("remove") // BurnEvent = "remove" liquidity
-    .bind(bigdecimal::BigDecimal::from(event.metadata.slot))
-    .execute(pool)
-    .await;
-    
-    if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into adjust_liquidity table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert burn event: {}", e)));
+    for chunk in events.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO adjust_debt_events (
+                pair, signer, amount0, amount1, transaction_signature, slot, event_timestamp,
+                instruction_index, inner_instruction_index
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.amount0))
+                .push_bind(bigdecimal::BigDecimal::from(event.amount1))
+                .push_bind(ctx.signature.clone())
+                .push_bind(bigdecimal::BigDecimal::from(ctx.slot))
+                .push_bind(ctx.timestamp)
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                signer = EXCLUDED.signer,
+                amount0 = EXCLUDED.amount0,
+                amount1 = EXCLUDED.amount1,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into adjust_debt_events table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert adjust debt events: {}", e)));
+        }
     }
-    
+
     Ok(())
 }
 
-/// Upsert an AdjustCollateralEvent into the database
-pub async fn upsert_adjust_collateral_event(
-    event: &AdjustCollateralEvent,
-    tx_signature: &str,
-    slot: i64,
+/// Upsert UserPositionUpdatedEvents and refresh the latest borrow position per pair per signer
+pub async fn upsert_user_position_updated_events(
+    conn: &mut PgConnection,
+    events: &[(UserPositionUpdatedEvent, EventContext)],
 ) -> CarbonResult<()> {
-    let pool = get_db_pool()?;
-    
-    let upsert_result = sqlx::query(
-        r#"
-        INSERT INTO adjust_collateral_events (
-            pair, signer, amount0, amount1, transaction_signature, slot, event_timestamp
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7)
-        ON CONFLICT (transaction_signature) DO UPDATE SET
-            pair = EXCLUDED.pair,
-            signer = EXCLUDED.signer,
-            amount0 = EXCLUDED.amount0,
-            amount1 = EXCLUDED.amount1,
-            slot = EXCLUDED.slot,
-            event_timestamp = EXCLUDED.event_timestamp
-        "#
-    )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.amount0))
-    .bind(bigdecimal::BigDecimal::from(event.amount1))
-    .bind(tx_signature)
-    .bind(bigdecimal::BigDecimal::from(slot))
-    .bind(chrono::Utc::now())
-    .execute(pool)
-    .await;
-    
-    if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into adjust_collateral_events table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert adjust collateral event: {}", e)));
+    for chunk in events.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO user_position_updated_events (
+                pair, signer, position, collateral0, collateral1, debt0_shares, debt1_shares,
+                collateral0_liquidation_cf_bps, collateral1_liquidation_cf_bps,
+                collateral0_max_cf_bps, collateral1_max_cf_bps,
+                transaction_signature, slot, event_timestamp,
+                instruction_index, inner_instruction_index
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(event.position.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.collateral0))
+                .push_bind(bigdecimal::BigDecimal::from(event.collateral1))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt0_shares)))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt1_shares)))
+                .push_bind(event.collateral0_liquidation_cf_bps as i32)
+                .push_bind(event.collateral1_liquidation_cf_bps as i32)
+                .push_bind(event.collateral0_max_cf_bps as i32)
+                .push_bind(event.collateral1_max_cf_bps as i32)
+                .push_bind(ctx.signature.clone())
+                .push_bind(bigdecimal::BigDecimal::from(ctx.slot))
+                .push_bind(ctx.timestamp)
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                signer = EXCLUDED.signer,
+                position = EXCLUDED.position,
+                collateral0 = EXCLUDED.collateral0,
+                collateral1 = EXCLUDED.collateral1,
+                debt0_shares = EXCLUDED.debt0_shares,
+                debt1_shares = EXCLUDED.debt1_shares,
+                collateral0_liquidation_cf_bps = EXCLUDED.collateral0_liquidation_cf_bps,
+                collateral1_liquidation_cf_bps = EXCLUDED.collateral1_liquidation_cf_bps,
+                collateral0_max_cf_bps = EXCLUDED.collateral0_max_cf_bps,
+                collateral1_max_cf_bps = EXCLUDED.collateral1_max_cf_bps,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into user_position_updated_events table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user position updated events: {}", e)));
+        }
     }
-    
+
+    // Also upsert into user_borrow_positions table (latest position per pair per signer).
+    // Older slots never overwrite newer state, so catch-up crawls, backfills and replays
+    // can run alongside live indexing.
+    let latest = latest_per_key(events, |event| (event.metadata.pair, event.metadata.signer));
+
+    for chunk in latest.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO user_borrow_positions (
+                pair, signer, position, collateral0, collateral1, debt0_shares, debt1_shares,
+                collateral0_liquidation_cf_bps, collateral1_liquidation_cf_bps,
+                collateral0_max_cf_bps, collateral1_max_cf_bps,
+                slot, event_timestamp, updated_at, created_event_id
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(event.position.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.collateral0))
+                .push_bind(bigdecimal::BigDecimal::from(event.collateral1))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt0_shares)))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt1_shares)))
+                .push_bind(event.collateral0_liquidation_cf_bps as i32)
+                .push_bind(event.collateral1_liquidation_cf_bps as i32)
+                .push_bind(event.collateral0_max_cf_bps as i32)
+                .push_bind(event.collateral1_max_cf_bps as i32)
+                .push_bind(bigdecimal::BigDecimal::from(ctx.slot))
+                .push_bind(ctx.timestamp)
+                .push_bind(chrono::Utc::now())
+                .push(r#"(SELECT id FROM user_position_created_events WHERE "position" = "#)
+                .push_bind_unseparated(event.position.to_string())
+                .push_unseparated(" AND slot <= ")
+                .push_bind_unseparated(ctx.slot)
+                .push_unseparated(" ORDER BY slot DESC, id DESC LIMIT 1)");
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (pair, signer) DO UPDATE SET
+                position = EXCLUDED.position,
+                collateral0 = EXCLUDED.collateral0,
+                collateral1 = EXCLUDED.collateral1,
+                debt0_shares = EXCLUDED.debt0_shares,
+                debt1_shares = EXCLUDED.debt1_shares,
+                collateral0_liquidation_cf_bps = EXCLUDED.collateral0_liquidation_cf_bps,
+                collateral1_liquidation_cf_bps = EXCLUDED.collateral1_liquidation_cf_bps,
+                collateral0_max_cf_bps = EXCLUDED.collateral0_max_cf_bps,
+                collateral1_max_cf_bps = EXCLUDED.collateral1_max_cf_bps,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp,
+                updated_at = now(),
+                created_event_id = COALESCE(EXCLUDED.created_event_id, user_borrow_positions.created_event_id)
+            WHERE user_borrow_positions.slot <= EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into user_borrow_positions table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user borrow positions: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Upsert UserPositionLiquidatedEvents into the database
+pub async fn upsert_user_position_liquidated_events(
+    conn: &mut PgConnection,
+    events: &[(UserPositionLiquidatedEvent, EventContext)],
+) -> CarbonResult<()> {
+    for chunk in events.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO user_position_liquidated_events (
+                pair, signer, position, liquidator, collateral0_liquidated, collateral1_liquidated,
+                debt0_liquidated, debt1_liquidated, collateral_price, shortfall, liquidation_bonus_applied,
+                k0, k1, transaction_signature, slot, event_timestamp,
+                instruction_index, inner_instruction_index
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(event.position.to_string())
+                .push_bind(event.liquidator.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.collateral0_liquidated))
+                .push_bind(bigdecimal::BigDecimal::from(event.collateral1_liquidated))
+                .push_bind(bigdecimal::BigDecimal::from(event.debt0_liquidated))
+                .push_bind(bigdecimal::BigDecimal::from(event.debt1_liquidated))
+                .push_bind(bigdecimal::BigDecimal::from(event.collateral_price))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.shortfall)))
+                .push_bind(bigdecimal::BigDecimal::from(event.liquidation_bonus_applied))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.k0)))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.k1)))
+                .push_bind(ctx.signature.clone())
+                .push_bind(bigdecimal::BigDecimal::from(ctx.slot))
+                .push_bind(ctx.timestamp)
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                signer = EXCLUDED.signer,
+                position = EXCLUDED.position,
+                liquidator = EXCLUDED.liquidator,
+                collateral0_liquidated = EXCLUDED.collateral0_liquidated,
+                collateral1_liquidated = EXCLUDED.collateral1_liquidated,
+                debt0_liquidated = EXCLUDED.debt0_liquidated,
+                debt1_liquidated = EXCLUDED.debt1_liquidated,
+                collateral_price = EXCLUDED.collateral_price,
+                shortfall = EXCLUDED.shortfall,
+                liquidation_bonus_applied = EXCLUDED.liquidation_bonus_applied,
+                k0 = EXCLUDED.k0,
+                k1 = EXCLUDED.k1,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into user_position_liquidated_events table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user position liquidated events: {}", e)));
+        }
+    }
+
     Ok(())
 }
 
-/// Upsert an AdjustDebtEvent into the database
-pub async fn upsert_adjust_debt_event(
-    event: &AdjustDebtEvent,
-    tx_signature: &str,
+/// Upsert PairCreatedEvents into the pools table, unless it holds a creation from a later slot
+/// (pools created before migration 016 have no slot and are always updated)
+pub async fn upsert_pair_created_events(
+    conn: &mut PgConnection,
+    events: &[(PairCreatedEvent, EventContext)],
+) -> CarbonResult<()> {
+    let latest = latest_per_key(events, |event| event.metadata.pair);
+
+    for chunk in latest.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO pools (
+                pair_address, token0, token1, lp_mint, rate_model, swap_fee_bps, half_life, fixed_cf_bps, params_hash, version,
+                target_util_start_bps, target_util_end_bps, rate_half_life_ms, min_rate_bps, max_rate_bps, slot
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.token0.to_string())
+                .push_bind(event.token1.to_string())
+                .push_bind(event.lp_mint.to_string())
+                .push_bind(event.rate_model.to_string())
+                .push_bind(event.swap_fee_bps as i32)
+                .push_bind(event.half_life as i64)
+                .push_bind(event.fixed_cf_bps.map(|bps| bps as i32))
+                .push_bind(event.params_hash)
+                .push_bind(event.version as i32)
+                .push_bind(event.target_util_start_bps as i64)
+                .push_bind(event.target_util_end_bps as i64)
+                .push_bind(event.rate_half_life_ms as i64)
+                .push_bind(event.min_rate_bps as i64)
+                .push_bind(event.max_rate_bps as i64)
+                .push_bind(ctx.slot);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (pair_address) DO UPDATE SET
+                token0 = EXCLUDED.token0,
+                token1 = EXCLUDED.token1,
+                lp_mint = EXCLUDED.lp_mint,
+                rate_model = EXCLUDED.rate_model,
+                swap_fee_bps = EXCLUDED.swap_fee_bps,
+                half_life = EXCLUDED.half_life,
+                fixed_cf_bps = EXCLUDED.fixed_cf_bps,
+                params_hash = EXCLUDED.params_hash,
+                version = EXCLUDED.version,
+                target_util_start_bps = EXCLUDED.target_util_start_bps,
+                target_util_end_bps = EXCLUDED.target_util_end_bps,
+                rate_half_life_ms = EXCLUDED.rate_half_life_ms,
+                min_rate_bps = EXCLUDED.min_rate_bps,
+                max_rate_bps = EXCLUDED.max_rate_bps,
+                slot = EXCLUDED.slot
+            WHERE pools.slot IS NULL OR pools.slot <= EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into pools table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert pair created events: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Upsert UserLiquidityPositionUpdatedEvents and refresh the latest LP position per pair per signer
+pub async fn upsert_user_liquidity_position_updated_events(
+    conn: &mut PgConnection,
+    events: &[(UserLiquidityPositionUpdatedEvent, EventContext)],
+) -> CarbonResult<()> {
+    // Upsert into user_lp_position_updated_events table (one row per emitted event). The
+    // identity is a partial unique index over rows with a tx_sig (migration 023), which is
+    // always bound here; only rows indexed before migration 007 lack one.
+    for chunk in events.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO user_lp_position_updated_events (
+                pair_address, lp_amount, amount0, amount1, signer, timestamp, slot,
+                tx_sig, instruction_index, inner_instruction_index
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.lp_amount))
+                .push_bind(bigdecimal::BigDecimal::from(event.token0_amount))
+                .push_bind(bigdecimal::BigDecimal::from(event.token1_amount))
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(ctx.timestamp)
+                .push_bind(bigdecimal::BigDecimal::from(event.metadata.slot))
+                .push_bind(ctx.signature.clone())
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (tx_sig, instruction_index, inner_instruction_index, timestamp) WHERE tx_sig IS NOT NULL DO UPDATE SET
+                pair_address = EXCLUDED.pair_address,
+                lp_amount = EXCLUDED.lp_amount,
+                amount0 = EXCLUDED.amount0,
+                amount1 = EXCLUDED.amount1,
+                signer = EXCLUDED.signer,
+                slot = EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to insert into user_lp_position_updated_events table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to insert user liquidity position updated events: {}", e)));
+        }
+    }
+
+    // Upsert into user_liquidity_positions table (latest position per pair per signer)
+    // Uses ON CONFLICT with unique constraint on (pair, signer); older slots never
+    // overwrite newer state. Legacy rows without a slot are always replaced.
+    let latest = latest_per_key(events, |event| (event.metadata.pair, event.metadata.signer));
+
+    for chunk in latest.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO user_liquidity_positions (
+                pair, signer, token0_mint, token1_mint, amount0, amount1, lp_mint, lp_amount, slot
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(event.token0_mint.to_string())
+                .push_bind(event.token1_mint.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.token0_amount))
+                .push_bind(bigdecimal::BigDecimal::from(event.token1_amount))
+                .push_bind(event.lp_mint.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.lp_amount))
+                .push_bind(ctx.slot);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (pair, signer) DO UPDATE SET
+                token0_mint = EXCLUDED.token0_mint,
+                token1_mint = EXCLUDED.token1_mint,
+                amount0 = EXCLUDED.amount0,
+                amount1 = EXCLUDED.amount1,
+                lp_mint = EXCLUDED.lp_mint,
+                lp_amount = EXCLUDED.lp_amount,
+                updated_at = now(),
+                slot = EXCLUDED.slot
+            WHERE user_liquidity_positions.slot IS NULL OR user_liquidity_positions.slot <= EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into user_liquidity_positions table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user liquidity positions: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Upsert UpdatePairEvents into pair_state_updates and refresh the latest pair state,
+/// along with the utilization and APRs of both tokens
+pub async fn upsert_update_pair_events(
+    conn: &mut PgConnection,
+    events: &[(UpdatePairEvent, EventContext)],
+) -> CarbonResult<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let interest_share_bps = interest::protocol_interest_share(conn).await?;
+
+    for chunk in events.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO pair_state_updates (
+                pair, signer, price0_ema, price1_ema, rate0, rate1,
+                accrued_interest0, accrued_interest1, cash_reserve0, cash_reserve1,
+                reserve0_after_interest, reserve1_after_interest,
+                utilization0, utilization1, borrow_apr0, borrow_apr1, supply_apr0, supply_apr1,
+                tx_sig, instruction_index, inner_instruction_index, slot, timestamp
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            let (rates0, rates1) = interest::pair_rates(event, interest_share_bps);
+
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(event.metadata.signer.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.price0_ema))
+                .push_bind(bigdecimal::BigDecimal::from(event.price1_ema))
+                .push_bind(bigdecimal::BigDecimal::from(event.rate0))
+                .push_bind(bigdecimal::BigDecimal::from(event.rate1))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.accrued_interest0)))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.accrued_interest1)))
+                .push_bind(bigdecimal::BigDecimal::from(event.cash_reserve0))
+                .push_bind(bigdecimal::BigDecimal::from(event.cash_reserve1))
+                .push_bind(bigdecimal::BigDecimal::from(event.reserve0_after_interest))
+                .push_bind(bigdecimal::BigDecimal::from(event.reserve1_after_interest))
+                .push_bind(rates0.utilization)
+                .push_bind(rates1.utilization)
+                .push_bind(rates0.borrow_apr)
+                .push_bind(rates1.borrow_apr)
+                .push_bind(rates0.supply_apr)
+                .push_bind(rates1.supply_apr)
+                .push_bind(ctx.signature.clone())
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index)
+                .push_bind(ctx.slot)
+                .push_bind(ctx.timestamp);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (tx_sig, instruction_index, inner_instruction_index, timestamp) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                signer = EXCLUDED.signer,
+                price0_ema = EXCLUDED.price0_ema,
+                price1_ema = EXCLUDED.price1_ema,
+                rate0 = EXCLUDED.rate0,
+                rate1 = EXCLUDED.rate1,
+                accrued_interest0 = EXCLUDED.accrued_interest0,
+                accrued_interest1 = EXCLUDED.accrued_interest1,
+                cash_reserve0 = EXCLUDED.cash_reserve0,
+                cash_reserve1 = EXCLUDED.cash_reserve1,
+                reserve0_after_interest = EXCLUDED.reserve0_after_interest,
+                reserve1_after_interest = EXCLUDED.reserve1_after_interest,
+                utilization0 = EXCLUDED.utilization0,
+                utilization1 = EXCLUDED.utilization1,
+                borrow_apr0 = EXCLUDED.borrow_apr0,
+                borrow_apr1 = EXCLUDED.borrow_apr1,
+                supply_apr0 = EXCLUDED.supply_apr0,
+                supply_apr1 = EXCLUDED.supply_apr1,
+                slot = EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into pair_state_updates table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert pair state updates: {}", e)));
+        }
+    }
+
+    // Also upsert into pair_states table (latest state per pair).
+    // Older slots never overwrite newer state, so backfills can run alongside live indexing.
+    let latest = latest_per_key(events, |event| event.metadata.pair);
+
+    for chunk in latest.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO pair_states (
+                pair, price0_ema, price1_ema, rate0, rate1,
+                accrued_interest0, accrued_interest1, cash_reserve0, cash_reserve1,
+                reserve0_after_interest, reserve1_after_interest,
+                utilization0, utilization1, borrow_apr0, borrow_apr1, supply_apr0, supply_apr1,
+                tx_sig, slot, event_timestamp, updated_at
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (event, ctx)| {
+            let (rates0, rates1) = interest::pair_rates(event, interest_share_bps);
+
+            row.push_bind(event.metadata.pair.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(event.price0_ema))
+                .push_bind(bigdecimal::BigDecimal::from(event.price1_ema))
+                .push_bind(bigdecimal::BigDecimal::from(event.rate0))
+                .push_bind(bigdecimal::BigDecimal::from(event.rate1))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.accrued_interest0)))
+                .push_bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.accrued_interest1)))
+                .push_bind(bigdecimal::BigDecimal::from(event.cash_reserve0))
+                .push_bind(bigdecimal::BigDecimal::from(event.cash_reserve1))
+                .push_bind(bigdecimal::BigDecimal::from(event.reserve0_after_interest))
+                .push_bind(bigdecimal::BigDecimal::from(event.reserve1_after_interest))
+                .push_bind(rates0.utilization)
+                .push_bind(rates1.utilization)
+                .push_bind(rates0.borrow_apr)
+                .push_bind(rates1.borrow_apr)
+                .push_bind(rates0.supply_apr)
+                .push_bind(rates1.supply_apr)
+                .push_bind(ctx.signature.clone())
+                .push_bind(ctx.slot)
+                .push_bind(ctx.timestamp)
+                .push("now()");
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (pair) DO UPDATE SET
+                price0_ema = EXCLUDED.price0_ema,
+                price1_ema = EXCLUDED.price1_ema,
+                rate0 = EXCLUDED.rate0,
+                rate1 = EXCLUDED.rate1,
+                accrued_interest0 = EXCLUDED.accrued_interest0,
+                accrued_interest1 = EXCLUDED.accrued_interest1,
+                cash_reserve0 = EXCLUDED.cash_reserve0,
+                cash_reserve1 = EXCLUDED.cash_reserve1,
+                reserve0_after_interest = EXCLUDED.reserve0_after_interest,
+                reserve1_after_interest = EXCLUDED.reserve1_after_interest,
+                utilization0 = EXCLUDED.utilization0,
+                utilization1 = EXCLUDED.utilization1,
+                borrow_apr0 = EXCLUDED.borrow_apr0,
+                borrow_apr1 = EXCLUDED.borrow_apr1,
+                supply_apr0 = EXCLUDED.supply_apr0,
+                supply_apr1 = EXCLUDED.supply_apr1,
+                tx_sig = EXCLUDED.tx_sig,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp,
+                updated_at = now()
+            WHERE pair_states.slot <= EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into pair_states table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert pair states: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Upsert flashloans (instruction, event and receiver program activity) into the flashloans table
+pub async fn upsert_flashloans(
+    conn: &mut PgConnection,
+    flashloans: &[(FlashloanRecord, EventContext)],
+) -> CarbonResult<()> {
+    let receiver_instructions = flashloans
+        .iter()
+        .map(|(flashloan, _)| serde_json::to_value(&flashloan.receiver_instructions))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to serialize receiver instructions: {}", e)))?;
+
+    let rows: Vec<_> = flashloans.iter().zip(receiver_instructions).collect();
+
+    for chunk in rows.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO flashloans (
+                pair, signer, receiver, receiver_program, amount0, amount1, fee0, fee1,
+                receiver_instructions, transaction_signature, instruction_index, inner_instruction_index,
+                slot, event_timestamp
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, ((flashloan, ctx), receiver_instructions)| {
+            row.push_bind(flashloan.pair.to_string())
+                .push_bind(flashloan.signer.to_string())
+                .push_bind(flashloan.receiver.to_string())
+                .push_bind(flashloan.receiver_program.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(flashloan.amount0))
+                .push_bind(bigdecimal::BigDecimal::from(flashloan.amount1))
+                .push_bind(flashloan.fee0.map(bigdecimal::BigDecimal::from))
+                .push_bind(flashloan.fee1.map(bigdecimal::BigDecimal::from))
+                .push_bind(receiver_instructions.clone())
+                .push_bind(ctx.signature.clone())
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index)
+                .push_bind(ctx.slot)
+                .push_bind(ctx.timestamp);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                signer = EXCLUDED.signer,
+                receiver = EXCLUDED.receiver,
+                receiver_program = EXCLUDED.receiver_program,
+                amount0 = EXCLUDED.amount0,
+                amount1 = EXCLUDED.amount1,
+                fee0 = EXCLUDED.fee0,
+                fee1 = EXCLUDED.fee1,
+                receiver_instructions = EXCLUDED.receiver_instructions,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into flashloans table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert flashloans: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Upsert UserPositionCreatedEvents and link existing borrow position rows to them.
+///
+/// Written row by row: each creation links the borrow positions updated after it, which
+/// depends on the creations written before it. Position creations are rare enough that
+/// this does not matter for throughput.
+pub async fn upsert_user_position_created_events(
+    conn: &mut PgConnection,
+    events: &[(UserPositionCreatedEvent, EventContext)],
+) -> CarbonResult<()> {
+    for (event, ctx) in events {
+        let upsert_result = sqlx::query_scalar::<_, i64>(
+            r#"
+            INSERT INTO user_position_created_events (
+                pair, owner, position, transaction_signature, instruction_index, inner_instruction_index,
+                slot, event_timestamp
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                owner = EXCLUDED.owner,
+                position = EXCLUDED.position,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            RETURNING id
+            "#
+        )
+        .bind(event.metadata.pair.to_string())
+        .bind(event.metadata.signer.to_string())
+        .bind(event.position.to_string())
+        .bind(&ctx.signature)
+        .bind(ctx.instruction_index)
+        .bind(ctx.inner_instruction_index)
+        .bind(ctx.slot)
+        .bind(ctx.timestamp)
+        .fetch_one(&mut *conn)
+        .await;
+
+        let created_event_id = match upsert_result {
+            Ok(id) => id,
+            Err(e) => {
+                log::error!("Failed to upsert into user_position_created_events table: {}", e);
+                return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user position created event: {}", e)));
+            }
+        };
+
+        // Link borrow positions that were updated after this creation (events may arrive out of order)
+        let link_result = sqlx::query(
+            r#"
+            UPDATE user_borrow_positions
+            SET created_event_id = $1
+            WHERE position = $2
+                AND slot >= $3
+                AND (
+                    created_event_id IS NULL
+                    OR created_event_id IN (
+                        SELECT id FROM user_position_created_events WHERE position = $2 AND slot < $3
+                    )
+                )
+            "#
+        )
+        .bind(created_event_id)
+        .bind(event.position.to_string())
+        .bind(ctx.slot)
+        .execute(&mut *conn)
+        .await;
+
+        if let Err(e) = link_result {
+            log::error!("Failed to link user_borrow_positions to creation record: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to link user borrow position: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Upsert the latest state of a Pair account (older slots never overwrite newer state)
+pub async fn upsert_pair_account(
+    pubkey: &Pubkey,
+    pair: &Pair,
     slot: i64,
 ) -> CarbonResult<()> {
     let pool = get_db_pool()?;
     
     let upsert_result = sqlx::query(
         r#"
-        INSERT INTO adjust_debt_events (
-            pair, signer, amount0, amount1, transaction_signature, slot, event_timestamp
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7)
-        ON CONFLICT (transaction_signature) DO UPDATE SET
-            pair = EXCLUDED.pair,
-            signer = EXCLUDED.signer,
-            amount0 = EXCLUDED.amount0,
-            amount1 = EXCLUDED.amount1,
+        INSERT INTO pair_accounts (
+            pubkey, token0, token1, lp_mint, rate_model, swap_fee_bps, half_life, fixed_cf_bps,
+            reserve0, reserve1, cash_reserve0, cash_reserve1,
+            last_price0_ema_symmetric, last_price0_ema_directional,
+            last_price1_ema_symmetric, last_price1_ema_directional,
+            last_update, last_rate0, last_rate1,
+            total_debt0, total_debt1, total_debt0_shares, total_debt1_shares,
+            total_supply, total_collateral0, total_collateral1,
+            token0_decimals, token1_decimals, params_hash, version, reduce_only,
+            slot, updated_at
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16,
+            $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31,
+            $32, now()
+        )
+        ON CONFLICT (pubkey) DO UPDATE SET
+            token0 = EXCLUDED.token0,
+            token1 = EXCLUDED.token1,
+            lp_mint = EXCLUDED.lp_mint,
+            rate_model = EXCLUDED.rate_model,
+            swap_fee_bps = EXCLUDED.swap_fee_bps,
+            half_life = EXCLUDED.half_life,
+            fixed_cf_bps = EXCLUDED.fixed_cf_bps,
+            reserve0 = EXCLUDED.reserve0,
+            reserve1 = EXCLUDED.reserve1,
+            cash_reserve0 = EXCLUDED.cash_reserve0,
+            cash_reserve1 = EXCLUDED.cash_reserve1,
+            last_price0_ema_symmetric = EXCLUDED.last_price0_ema_symmetric,
+            last_price0_ema_directional = EXCLUDED.last_price0_ema_directional,
+            last_price1_ema_symmetric = EXCLUDED.last_price1_ema_symmetric,
+            last_price1_ema_directional = EXCLUDED.last_price1_ema_directional,
+            last_update = EXCLUDED.last_update,
+            last_rate0 = EXCLUDED.last_rate0,
+            last_rate1 = EXCLUDED.last_rate1,
+            total_debt0 = EXCLUDED.total_debt0,
+            total_debt1 = EXCLUDED.total_debt1,
+            total_debt0_shares = EXCLUDED.total_debt0_shares,
+            total_debt1_shares = EXCLUDED.total_debt1_shares,
+            total_supply = EXCLUDED.total_supply,
+            total_collateral0 = EXCLUDED.total_collateral0,
+            total_collateral1 = EXCLUDED.total_collateral1,
+            token0_decimals = EXCLUDED.token0_decimals,
+            token1_decimals = EXCLUDED.token1_decimals,
+            params_hash = EXCLUDED.params_hash,
+            version = EXCLUDED.version,
+            reduce_only = EXCLUDED.reduce_only,
             slot = EXCLUDED.slot,
-            event_timestamp = EXCLUDED.event_timestamp
+            updated_at = now()
+        WHERE pair_accounts.slot <= EXCLUDED.slot
         "#
     )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.amount0))
-    .bind(bigdecimal::BigDecimal::from(event.amount1))
-    .bind(tx_signature)
-    .bind(bigdecimal::BigDecimal::from(slot))
-    .bind(chrono::Utc::now())
+    .bind(pubkey.to_string())
+    .bind(pair.token0.to_string())
+    .bind(pair.token1.to_string())
+    .bind(pair.lp_mint.to_string())
+    .bind(pair.rate_model.to_string())
+    .bind(pair.swap_fee_bps as i32)
+    .bind(pair.half_life as i64)
+    .bind(pair.fixed_cf_bps.map(|bps| bps as i32))
+    .bind(bigdecimal::BigDecimal::from(pair.reserve0))
+    .bind(bigdecimal::BigDecimal::from(pair.reserve1))
+    .bind(bigdecimal::BigDecimal::from(pair.cash_reserve0))
+    .bind(bigdecimal::BigDecimal::from(pair.cash_reserve1))
+    .bind(bigdecimal::BigDecimal::from(pair.last_price0_ema.symmetric))
+    .bind(bigdecimal::BigDecimal::from(pair.last_price0_ema.directional))
+    .bind(bigdecimal::BigDecimal::from(pair.last_price1_ema.symmetric))
+    .bind(bigdecimal::BigDecimal::from(pair.last_price1_ema.directional))
+    .bind(bigdecimal::BigDecimal::from(pair.last_update))
+    .bind(bigdecimal::BigDecimal::from(pair.last_rate0))
+    .bind(bigdecimal::BigDecimal::from(pair.last_rate1))
+    .bind(bigdecimal::BigDecimal::from(pair.total_debt0))
+    .bind(bigdecimal::BigDecimal::from(pair.total_debt1))
+    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(pair.total_debt0_shares)))
+    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(pair.total_debt1_shares)))
+    .bind(bigdecimal::BigDecimal::from(pair.total_supply))
+    .bind(bigdecimal::BigDecimal::from(pair.total_collateral0))
+    .bind(bigdecimal::BigDecimal::from(pair.total_collateral1))
+    .bind(pair.token0_decimals as i32)
+    .bind(pair.token1_decimals as i32)
+    .bind(pair.params_hash)
+    .bind(pair.version as i32)
+    .bind(pair.reduce_only)
+    .bind(slot)
     .execute(pool)
     .await;
     
     if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into adjust_debt_events table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert adjust debt event: {}", e)));
+        log::error!("Failed to upsert into pair_accounts table: {}", e);
+        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert pair account: {}", e)));
     }
     
     Ok(())
 }
 
-/// Upsert a UserPositionUpdatedEvent into the database
-pub async fn upsert_user_position_updated_event(
-    event: &UserPositionUpdatedEvent,
-    tx_signature: &str,
+/// Upsert the latest state of a UserPosition account (older slots never overwrite newer state)
+pub async fn upsert_user_position_account(
+    pubkey: &Pubkey,
+    position: &UserPosition,
     slot: i64,
 ) -> CarbonResult<()> {
     let pool = get_db_pool()?;
     
-    // Compute event_timestamp once for reuse
-    let event_timestamp = chrono::Utc::now();
-    
     let upsert_result = sqlx::query(
         r#"
-        INSERT INTO user_position_updated_events (
-            pair, signer, position, collateral0, collateral1, debt0_shares, debt1_shares,
-            collateral0_liquidation_cf_bps, collateral1_liquidation_cf_bps,
-            collateral0_max_cf_bps, collateral1_max_cf_bps,
-            transaction_signature, slot, event_timestamp
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
-        ON CONFLICT (transaction_signature) DO UPDATE SET
+        INSERT INTO user_position_accounts (
+            pubkey, owner, pair, collateral0_liquidation_cf_bps, collateral1_liquidation_cf_bps,
+            collateral0, collateral1, debt0_shares, debt1_shares, slot, updated_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, now())
+        ON CONFLICT (pubkey) DO UPDATE SET
+            owner = EXCLUDED.owner,
             pair = EXCLUDED.pair,
-            signer = EXCLUDED.signer,
-            position = EXCLUDED.position,
+            collateral0_liquidation_cf_bps = EXCLUDED.collateral0_liquidation_cf_bps,
+            collateral1_liquidation_cf_bps = EXCLUDED.collateral1_liquidation_cf_bps,
             collateral0 = EXCLUDED.collateral0,
             collateral1 = EXCLUDED.collateral1,
             debt0_shares = EXCLUDED.debt0_shares,
             debt1_shares = EXCLUDED.debt1_shares,
-            collateral0_liquidation_cf_bps = EXCLUDED.collateral0_liquidation_cf_bps,
-            collateral1_liquidation_cf_bps = EXCLUDED.collateral1_liquidation_cf_bps,
-            collateral0_max_cf_bps = EXCLUDED.collateral0_max_cf_bps,
-            collateral1_max_cf_bps = EXCLUDED.collateral1_max_cf_bps,
             slot = EXCLUDED.slot,
-            event_timestamp = EXCLUDED.event_timestamp
+            updated_at = now()
+        WHERE user_position_accounts.slot <= EXCLUDED.slot
         "#
     )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(event.position.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.collateral0))
-    .bind(bigdecimal::BigDecimal::from(event.collateral1))
-    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt0_shares)))
-    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt1_shares)))
-    .bind(event.collateral0_liquidation_cf_bps as i32)
-    .bind(event.collateral1_liquidation_cf_bps as i32)
-    .bind(event.collateral0_max_cf_bps as i32)
-    .bind(event.collateral1_max_cf_bps as i32)
-    .bind(tx_signature)
-    .bind(bigdecimal::BigDecimal::from(slot))
-    .bind(event_timestamp)
+    .bind(pubkey.to_string())
+    .bind(position.owner.to_string())
+    .bind(position.pair.to_string())
+    .bind(position.collateral0_liquidation_cf_bps as i32)
+    .bind(position.collateral1_liquidation_cf_bps as i32)
+    .bind(bigdecimal::BigDecimal::from(position.collateral0))
+    .bind(bigdecimal::BigDecimal::from(position.collateral1))
+    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(position.debt0_shares)))
+    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(position.debt1_shares)))
+    .bind(slot)
     .execute(pool)
     .await;
     
     if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into user_position_updated_events table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user position updated event: {}", e)));
+        log::error!("Failed to upsert into user_position_accounts table: {}", e);
+        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user position account: {}", e)));
     }
     
-    // Also upsert into user_borrow_positions table (latest position per pair per signer)
+    Ok(())
+}
+
+/// Upsert the latest state of a RateModel account (older slots never overwrite newer state)
+/// and copy its parameters onto the pools using it
+pub async fn upsert_rate_model_account(
+    pubkey: &Pubkey,
+    rate_model: &RateModel,
+    slot: i64,
+) -> CarbonResult<()> {
+    let pool = get_db_pool()?;
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        carbon_core::error::Error::Custom(format!("Failed to begin rate model transaction: {}", e))
+    })?;
     
-    let upsert_latest_result = sqlx::query(
+    let upsert_result = sqlx::query(
         r#"
-        INSERT INTO user_borrow_positions (
-            pair, signer, position, collateral0, collateral1, debt0_shares, debt1_shares,
-            collateral0_liquidation_cf_bps, collateral1_liquidation_cf_bps,
-            collateral0_max_cf_bps, collateral1_max_cf_bps,
-            slot, event_timestamp, updated_at
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
-        ON CONFLICT (pair, signer) DO UPDATE SET
-            position = EXCLUDED.position,
-            collateral0 = EXCLUDED.collateral0,
-            collateral1 = EXCLUDED.collateral1,
-            debt0_shares = EXCLUDED.debt0_shares,
-            debt1_shares = EXCLUDED.debt1_shares,
-            collateral0_liquidation_cf_bps = EXCLUDED.collateral0_liquidation_cf_bps,
-            collateral1_liquidation_cf_bps = EXCLUDED.collateral1_liquidation_cf_bps,
-            collateral0_max_cf_bps = EXCLUDED.collateral0_max_cf_bps,
-            collateral1_max_cf_bps = EXCLUDED.collateral1_max_cf_bps,
+        INSERT INTO rate_model_accounts (
+            pubkey, exp_rate, target_util_start, target_util_end, half_life_ms,
+            min_rate, max_rate, initial_rate, slot, updated_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, now())
+        ON CONFLICT (pubkey) DO UPDATE SET
+            exp_rate = EXCLUDED.exp_rate,
+            target_util_start = EXCLUDED.target_util_start,
+            target_util_end = EXCLUDED.target_util_end,
+            half_life_ms = EXCLUDED.half_life_ms,
+            min_rate = EXCLUDED.min_rate,
+            max_rate = EXCLUDED.max_rate,
+            initial_rate = EXCLUDED.initial_rate,
             slot = EXCLUDED.slot,
-            event_timestamp = EXCLUDED.event_timestamp,
             updated_at = now()
+        WHERE rate_model_accounts.slot <= EXCLUDED.slot
         "#
     )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(event.position.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.collateral0))
-    .bind(bigdecimal::BigDecimal::from(event.collateral1))
-    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt0_shares)))
-    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.debt1_shares)))
-    .bind(event.collateral0_liquidation_cf_bps as i32)
-    .bind(event.collateral1_liquidation_cf_bps as i32)
-    .bind(event.collateral0_max_cf_bps as i32)
-    .bind(event.collateral1_max_cf_bps as i32)
-    .bind(bigdecimal::BigDecimal::from(slot))
-    .bind(event_timestamp)
-    .bind(chrono::Utc::now())
-    .execute(pool)
+    .bind(pubkey.to_string())
+    .bind(bigdecimal::BigDecimal::from(rate_model.exp_rate))
+    .bind(bigdecimal::BigDecimal::from(rate_model.target_util_start))
+    .bind(bigdecimal::BigDecimal::from(rate_model.target_util_end))
+    .bind(bigdecimal::BigDecimal::from(rate_model.half_life_ms))
+    .bind(bigdecimal::BigDecimal::from(rate_model.min_rate))
+    .bind(bigdecimal::BigDecimal::from(rate_model.max_rate))
+    .bind(bigdecimal::BigDecimal::from(rate_model.initial_rate))
+    .bind(slot)
+    .execute(&mut tx)
     .await;
     
-    if let Err(e) = upsert_latest_result {
-        log::error!("Failed to upsert into user_borrow_positions table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user borrow position: {}", e)));
+    let applied = match upsert_result {
+        Ok(result) => result.rows_affected() > 0,
+        Err(e) => {
+            log::error!("Failed to upsert into rate_model_accounts table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert rate model account: {}", e)));
+        }
+    };
+
+    // An older snapshot leaves the pools as they are
+    if applied {
+        interest::update_pool_rate_params(&mut tx, &pubkey.to_string(), &RateModelParams::from(rate_model)).await?;
     }
+
+    tx.commit().await.map_err(|e| {
+        carbon_core::error::Error::Custom(format!("Failed to commit rate model account: {}", e))
+    })?;
     
     Ok(())
 }
 
-/// Upsert a UserPositionLiquidatedEvent into the database
-pub async fn upsert_user_position_liquidated_event(
-    event: &UserPositionLiquidatedEvent,
-    tx_signature: &str,
+/// Upsert the latest state of a FutarchyAuthority account (older slots never overwrite newer state)
+pub async fn upsert_futarchy_authority_account(
+    pubkey: &Pubkey,
+    futarchy_authority: &FutarchyAuthority,
     slot: i64,
 ) -> CarbonResult<()> {
     let pool = get_db_pool()?;
     
     let upsert_result = sqlx::query(
         r#"
-        INSERT INTO user_position_liquidated_events (
-            pair, signer, position, liquidator, collateral0_liquidated, collateral1_liquidated,
-            debt0_liquidated, debt1_liquidated, collateral_price, shortfall, liquidation_bonus_applied,
-            k0, k1, transaction_signature, slot, event_timestamp
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
-        ON CONFLICT (transaction_signature) DO UPDATE SET
-            pair = EXCLUDED.pair,
-            signer = EXCLUDED.signer,
-            position = EXCLUDED.position,
-            liquidator = EXCLUDED.liquidator,
-            collateral0_liquidated = EXCLUDED.collateral0_liquidated,
-            collateral1_liquidated = EXCLUDED.collateral1_liquidated,
-            debt0_liquidated = EXCLUDED.debt0_liquidated,
-            debt1_liquidated = EXCLUDED.debt1_liquidated,
-            collateral_price = EXCLUDED.collateral_price,
-            shortfall = EXCLUDED.shortfall,
-            liquidation_bonus_applied = EXCLUDED.liquidation_bonus_applied,
-            k0 = EXCLUDED.k0,
-            k1 = EXCLUDED.k1,
+        INSERT INTO futarchy_authority_accounts (
+            pubkey, version, authority, futarchy_treasury, buybacks_vault, team_treasury,
+            swap_share_bps, interest_share_bps,
+            futarchy_treasury_bps, buybacks_vault_bps, team_treasury_bps,
+            global_reduce_only, slot, updated_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, now())
+        ON CONFLICT (pubkey) DO UPDATE SET
+            version = EXCLUDED.version,
+            authority = EXCLUDED.authority,
+            futarchy_treasury = EXCLUDED.futarchy_treasury,
+            buybacks_vault = EXCLUDED.buybacks_vault,
+            team_treasury = EXCLUDED.team_treasury,
+            swap_share_bps = EXCLUDED.swap_share_bps,
+            interest_share_bps = EXCLUDED.interest_share_bps,
+            futarchy_treasury_bps = EXCLUDED.futarchy_treasury_bps,
+            buybacks_vault_bps = EXCLUDED.buybacks_vault_bps,
+            team_treasury_bps = EXCLUDED.team_treasury_bps,
+            global_reduce_only = EXCLUDED.global_reduce_only,
             slot = EXCLUDED.slot,
-            event_timestamp = EXCLUDED.event_timestamp
+            updated_at = now()
+        WHERE futarchy_authority_accounts.slot <= EXCLUDED.slot
         "#
     )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(event.position.to_string())
-    .bind(event.liquidator.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.collateral0_liquidated))
-    .bind(bigdecimal::BigDecimal::from(event.collateral1_liquidated))
-    .bind(bigdecimal::BigDecimal::from(event.debt0_liquidated))
-    .bind(bigdecimal::BigDecimal::from(event.debt1_liquidated))
-    .bind(bigdecimal::BigDecimal::from(event.collateral_price))
-    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.shortfall)))
-    .bind(bigdecimal::BigDecimal::from(event.liquidation_bonus_applied))
-    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.k0)))
-    .bind(bigdecimal::BigDecimal::from(bigdecimal::num_bigint::BigInt::from(event.k1)))
-    .bind(tx_signature)
-    .bind(bigdecimal::BigDecimal::from(slot))
-    .bind(chrono::Utc::now())
+    .bind(pubkey.to_string())
+    .bind(futarchy_authority.version as i32)
+    .bind(futarchy_authority.authority.to_string())
+    .bind(futarchy_authority.recipients.futarchy_treasury.to_string())
+    .bind(futarchy_authority.recipients.buybacks_vault.to_string())
+    .bind(futarchy_authority.recipients.team_treasury.to_string())
+    .bind(futarchy_authority.revenue_share.swap_bps as i32)
+    .bind(futarchy_authority.revenue_share.interest_bps as i32)
+    .bind(futarchy_authority.revenue_distribution.futarchy_treasury_bps as i32)
+    .bind(futarchy_authority.revenue_distribution.buybacks_vault_bps as i32)
+    .bind(futarchy_authority.revenue_distribution.team_treasury_bps as i32)
+    .bind(futarchy_authority.global_reduce_only)
+    .bind(slot)
     .execute(pool)
     .await;
     
     if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into user_position_liquidated_events table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user position liquidated event: {}", e)));
+        log::error!("Failed to upsert into futarchy_authority_accounts table: {}", e);
+        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert futarchy authority account: {}", e)));
     }
     
     Ok(())
 }
 
-/// Upsert a PairCreatedEvent into the pools table
-pub async fn upsert_pair_created_event(
-    event: &PairCreatedEvent,
-    _tx_signature: &str,
-    _slot: i64,
-) -> CarbonResult<()> {
+/// Load the ingestion checkpoint of a datasource as (slot, signature)
+pub async fn get_ingestion_checkpoint(datasource: &str) -> CarbonResult<Option<(i64, String)>> {
     let pool = get_db_pool()?;
     
+    let checkpoint = sqlx::query_as::<_, (i64, String)>(
+        r#"
+        SELECT slot, signature FROM ingestion_checkpoints WHERE datasource = $1
+        "#
+    )
+    .bind(datasource)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to load ingestion checkpoint: {}", e)))?;
+    
+    Ok(checkpoint)
+}
+
+/// Advance the ingestion checkpoint of a datasource (older slots never move it back)
+pub async fn upsert_ingestion_checkpoint(
+    conn: &mut PgConnection,
+    checkpoint: &IngestionCheckpoint,
+) -> CarbonResult<()> {
     let upsert_result = sqlx::query(
         r#"
-        INSERT INTO pools (
-            pair_address, token0, token1, lp_mint, rate_model, swap_fee_bps, half_life, fixed_cf_bps, params_hash, version,
-            target_util_start_bps, target_util_end_bps, rate_half_life_ms, min_rate_bps, max_rate_bps
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
-        ON CONFLICT (pair_address) DO UPDATE SET
-            token0 = EXCLUDED.token0,
-            token1 = EXCLUDED.token1,
-            lp_mint = EXCLUDED.lp_mint,
-            rate_model = EXCLUDED.rate_model,
-            swap_fee_bps = EXCLUDED.swap_fee_bps,
-            half_life = EXCLUDED.half_life,
-            fixed_cf_bps = EXCLUDED.fixed_cf_bps,
-            params_hash = EXCLUDED.params_hash,
-            version = EXCLUDED.version,
-            target_util_start_bps = EXCLUDED.target_util_start_bps,
-            target_util_end_bps = EXCLUDED.target_util_end_bps,
-            rate_half_life_ms = EXCLUDED.rate_half_life_ms,
-            min_rate_bps = EXCLUDED.min_rate_bps,
-            max_rate_bps = EXCLUDED.max_rate_bps
+        INSERT INTO ingestion_checkpoints (datasource, slot, signature, updated_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (datasource) DO UPDATE SET
+            slot = EXCLUDED.slot,
+            signature = EXCLUDED.signature,
+            updated_at = now()
+        WHERE ingestion_checkpoints.slot <= EXCLUDED.slot
         "#
     )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.token0.to_string())
-    .bind(event.token1.to_string())
-    .bind(event.lp_mint.to_string())
-    .bind(event.rate_model.to_string())
-    .bind(event.swap_fee_bps as i32)
-    .bind(event.half_life as i64)
-    .bind(event.fixed_cf_bps.map(|bps| bps as i32))
-    .bind(event.params_hash)
-    .bind(event.version as i32)
-    .bind(event.target_util_start_bps as i64)
-    .bind(event.target_util_end_bps as i64)
-    .bind(event.rate_half_life_ms as i64)
-    .bind(event.min_rate_bps as i64)
-    .bind(event.max_rate_bps as i64)
-    .execute(pool)
+    .bind(checkpoint.datasource)
+    .bind(checkpoint.slot)
+    .bind(&checkpoint.signature)
+    .execute(&mut *conn)
     .await;
     
     if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into pools table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert pair created event: {}", e)));
+        log::error!("Failed to upsert into ingestion_checkpoints table: {}", e);
+        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert ingestion checkpoint: {}", e)));
     }
     
     Ok(())
 }
 
-/// Upsert a UserLiquidityPositionUpdatedEvent into the database
-pub async fn upsert_user_liquidity_position_updated_event(
-    event: &UserLiquidityPositionUpdatedEvent,
-    _tx_signature: &str,
-    _slot: i64,
+/// Record fully processed transactions (the first datasource to process one is kept). A
+/// transaction rolled back and landed again, in another slot, is recorded at its new slot
+/// and back to confirmed.
+pub async fn insert_processed_transactions(
+    conn: &mut PgConnection,
+    transactions: &[ProcessedTransaction],
 ) -> CarbonResult<()> {
-    let pool = get_db_pool()?;
+    for chunk in transactions.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO processed_transactions (signature, slot, datasource) "
+        );
+
+        query.push_values(chunk, |mut row, transaction| {
+            row.push_bind(transaction.signature.clone())
+                .push_bind(transaction.slot)
+                .push_bind(transaction.datasource);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (signature) DO UPDATE SET
+                slot = EXCLUDED.slot,
+                datasource = EXCLUDED.datasource,
+                commitment = 'confirmed'
+            WHERE processed_transactions.commitment = 'rolled_back'
+                OR processed_transactions.slot <> EXCLUDED.slot
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to insert into processed_transactions table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to record processed transactions: {}", e)));
+        }
+    }
     
-    // Compute event_timestamp once for reuse
-    let event_timestamp = chrono::Utc::now();
+    Ok(())
+}
+
+/// Return the subset of `signatures` that has not been processed yet, or only in a slot
+/// that was rolled back, in the given order
+pub async fn filter_unprocessed_signatures(signatures: &[String]) -> CarbonResult<Vec<String>> {
+    let pool = get_db_pool()?;
     
-    // Insert into user_lp_position_updated_events table (always inserts)
-    let insert_event_result = sqlx::query(
+    let processed: Vec<String> = sqlx::query_scalar(
         r#"
-        INSERT INTO user_lp_position_updated_events (
-            pair_address, lp_amount, amount0, amount1, signer, timestamp, slot
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        SELECT signature FROM processed_transactions
+        WHERE signature = ANY($1) AND commitment <> 'rolled_back'
         "#
     )
-    .bind(event.metadata.pair.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.lp_amount))
-    .bind(bigdecimal::BigDecimal::from(event.token0_amount))
-    .bind(bigdecimal::BigDecimal::from(event.token1_amount))
-    .bind(event.metadata.signer.to_string())
-    .bind(event_timestamp)
-    .bind(bigdecimal::BigDecimal::from(event.metadata.slot))
-    .execute(pool)
-    .await;
+    .bind(signatures)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to load processed transactions: {}", e)))?;
     
-    if let Err(e) = insert_event_result {
-        log::error!("Failed to insert into user_lp_position_updated_events table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to insert user liquidity position updated event: {}", e)));
-    }
+    let processed: std::collections::HashSet<String> = processed.into_iter().collect();
     
-    // Upsert into user_liquidity_positions table (latest position per pair per signer)
-    // Uses ON CONFLICT with unique constraint on (pair, signer)
-    let upsert_result = sqlx::query(
+    Ok(signatures.iter().filter(|signature| !processed.contains(*signature)).cloned().collect())
+}
+
+/// Store an update that failed processing in dead_letter_updates
+pub async fn insert_dead_letter(letter: &DeadLetter) -> CarbonResult<()> {
+    let pool = get_db_pool()?;
+
+    let payload = serde_json::to_value(&letter.update)
+        .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to serialize dead letter: {}", e)))?;
+
+    sqlx::query(
         r#"
-        INSERT INTO user_liquidity_positions (
-            pair, signer, token0_mint, token1_mint, amount0, amount1, lp_mint, lp_amount, slot
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-        ON CONFLICT (pair, signer) DO UPDATE SET
-            token0_mint = EXCLUDED.token0_mint,
-            token1_mint = EXCLUDED.token1_mint,
-            amount0 = EXCLUDED.amount0,
-            amount1 = EXCLUDED.amount1,
-            lp_mint = EXCLUDED.lp_mint,
-            lp_amount = EXCLUDED.lp_amount,
-            updated_at = now(),
-            slot = EXCLUDED.slot
+        INSERT INTO dead_letter_updates (
+            datasource, update_kind, slot, signature, pubkey, payload, last_error, attempts,
+            first_failed_at, last_failed_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9)
         "#
     )
-    .bind(event.metadata.pair.to_string())
-    .bind(event.metadata.signer.to_string())
-    .bind(event.token0_mint.to_string())
-    .bind(event.token1_mint.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.token0_amount))
-    .bind(bigdecimal::BigDecimal::from(event.token1_amount))
-    .bind(event.lp_mint.to_string())
-    .bind(bigdecimal::BigDecimal::from(event.lp_amount))
-    .bind(bigdecimal::BigDecimal::from(event.metadata.slot))
+    .bind(&letter.datasource)
+    .bind(letter.update.kind())
+    .bind(letter.update.slot() as i64)
+    .bind(letter.update.signature())
+    .bind(letter.update.pubkey())
+    .bind(payload)
+    .bind(&letter.error)
+    .bind(letter.attempts)
+    .bind(letter.failed_at)
     .execute(pool)
-    .await;
-    
-    if let Err(e) = upsert_result {
-        log::error!("Failed to upsert into user_liquidity_positions table: {}", e);
-        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert user liquidity position: {}", e)));
-    }
-    
+    .await
+    .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to store dead letter: {}", e)))?;
+
     Ok(())
 }
 
+/// Load the dead letters not replayed yet, oldest first
+pub async fn load_dead_letters(datasource: Option<&str>, limit: Option<usize>) -> CarbonResult<Vec<StoredDeadLetter>> {
+    let pool = get_db_pool()?;
+
+    let rows = sqlx::query_as::<_, (i64, String, serde_json::Value, String, i32, DateTime<Utc>)>(
+        r#"
+        SELECT id, datasource, payload, last_error, attempts, last_failed_at
+        FROM dead_letter_updates
+        WHERE replayed_at IS NULL AND ($1::VARCHAR IS NULL OR datasource = $1)
+        ORDER BY id
+        LIMIT $2
+        "#
+    )
+    .bind(datasource)
+    .bind(limit.map(|limit| limit as i64))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to load dead letters: {}", e)))?;
+
+    rows.into_iter()
+        .map(|(id, datasource, payload, error, attempts, failed_at)| {
+            let update = serde_json::from_value(payload).map_err(|e| {
+                carbon_core::error::Error::Custom(format!("Invalid payload of dead letter {}: {}", id, e))
+            })?;
+
+            Ok(StoredDeadLetter {
+                id,
+                letter: DeadLetter { datasource, update, error, attempts, failed_at },
+                replayed_at: None,
+            })
+        })
+        .collect()
+}
+
+/// Mark a dead letter as replayed, or record another failed attempt
+pub async fn record_dead_letter_replay(id: i64, outcome: Result<(), String>) -> CarbonResult<()> {
+    let pool = get_db_pool()?;
+
+    let result = match outcome {
+        Ok(()) => {
+            sqlx::query("UPDATE dead_letter_updates SET replayed_at = now() WHERE id = $1")
+                .bind(id)
+                .execute(pool)
+                .await
+        }
+        Err(error) => {
+            sqlx::query(
+                r#"
+                UPDATE dead_letter_updates
+                SET attempts = attempts + 1, last_error = $2, last_failed_at = now()
+                WHERE id = $1
+                "#
+            )
+            .bind(id)
+            .bind(error)
+            .execute(pool)
+            .await
+        }
+    };
+
+    result.map_err(|e| carbon_core::error::Error::Custom(format!("Failed to update dead letter {}: {}", id, e)))?;
+
+    Ok(())
+}
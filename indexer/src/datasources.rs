@@ -3,9 +3,11 @@ use async_trait::async_trait;
 use carbon_core::{
     error::CarbonResult,
     metrics::MetricsCollection,
-    datasource::{AccountUpdate, Datasource, DatasourceId, Update, UpdateType},
+    datasource::{AccountUpdate, Datasource, DatasourceId, TransactionUpdate, Update, UpdateType},
+    transformers::transaction_metadata_from_original_meta,
 };
 use carbon_helius_atlas_ws_datasource::{Filters, HeliusWebsocket};
+use carbon_rpc_program_subscribe_datasource::{Filters as ProgramSubscribeFilters, RpcProgramSubscribe};
 use carbon_rpc_transaction_crawler_datasource::{ConnectionConfig, Filters as TransactionFilters, RpcTransactionCrawler, RetryConfig};
 use helius::types::{
     Cluster, RpcTransactionsConfig, TransactionSubscribeFilter, 
@@ -14,8 +16,10 @@ use helius::types::{
 };
 use solana_client::{
     nonblocking::rpc_client::RpcClient,
-    rpc_config::{RpcProgramAccountsConfig, RpcBlockConfig},
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcBlockConfig, RpcTransactionConfig},
+    rpc_filter::RpcFilterType,
 };
+use solana_account_decoder::UiAccountEncoding;
 use solana_transaction_status::{UiTransactionEncoding, TransactionDetails};
 use solana_commitment_config::CommitmentConfig;
 use solana_pubkey::Pubkey;
@@ -23,17 +27,29 @@ use solana_signature::Signature;
 use tokio::sync::{mpsc::Sender, RwLock};
 use tokio_util::sync::CancellationToken;
 
-/// Creates a configured Helius Atlas WebSocket datasource for Omnipair transaction monitoring
-pub fn create_helius_datasource(api_key: &str, program_id: Pubkey) -> HeliusWebsocket {
+use crate::config::HeliusConfig;
+
+/// Creates a configured Helius Atlas WebSocket datasource streaming the transactions of
+/// every indexed program, narrowed by the configured account filters
+pub fn create_helius_datasource(
+    api_key: &str,
+    program_ids: &[Pubkey],
+    helius: &HeliusConfig,
+    cluster: Cluster,
+) -> HeliusWebsocket {
+    let to_strings = |pubkeys: &[Pubkey]| -> Option<Vec<String>> {
+        (!pubkeys.is_empty()).then(|| pubkeys.iter().map(|pubkey| pubkey.to_string()).collect())
+    };
+
     let filters = Filters {
         accounts: vec![],
         transactions: Some(RpcTransactionsConfig {
             filter: TransactionSubscribeFilter {
-                account_include: Some(vec![program_id.to_string()]),
-                account_exclude: None,
-                account_required: None,
+                account_include: to_strings(program_ids),
+                account_exclude: to_strings(&helius.account_exclude),
+                account_required: to_strings(&helius.account_required),
                 vote: Some(false),
-                failed: Some(false),
+                failed: Some(helius.include_failed),
                 signature: None,
             },
             options: TransactionSubscribeOptions {
@@ -46,12 +62,6 @@ pub fn create_helius_datasource(api_key: &str, program_id: Pubkey) -> HeliusWebs
         }),
     };
 
-    // Determine cluster based on environment variable
-    let cluster = match std::env::var("CLUSTER").unwrap_or_default().to_lowercase().as_str() {
-        "devnet" => Cluster::Devnet,
-        _ => Cluster::MainnetBeta,
-    };
-
     HeliusWebsocket::new(
         api_key.to_string(),
         filters,
@@ -91,7 +101,7 @@ async fn get_slot_for_block(rpc_url: &str, block_number: u64) -> Option<u64> {
 
 /// Helper function to find a signature at or before a given slot
 /// This is used to convert START_SLOT to a before_signature for the transaction crawler
-async fn find_signature_at_slot(rpc_url: &str, program_id: Pubkey, target_slot: u64) -> Option<Signature> {
+pub async fn find_signature_at_slot(rpc_url: &str, program_id: Pubkey, target_slot: u64) -> Option<Signature> {
     let rpc_client = RpcClient::new(rpc_url.to_string());
     
     log::info!("Searching for signature at or before slot {} for program {}", target_slot, program_id);
@@ -159,75 +169,98 @@ async fn find_signature_at_slot(rpc_url: &str, program_id: Pubkey, target_slot:
     None
 }
 
+/// Creates an RPC program subscribe datasource streaming Omnipair program account updates,
+/// limited to accounts of `data_size` bytes when given
+pub fn create_account_datasource(rpc_ws_url: String, program_id: Pubkey, data_size: Option<u64>) -> RpcProgramSubscribe {
+    let config = RpcProgramAccountsConfig {
+        filters: data_size.map(|size| vec![RpcFilterType::DataSize(size)]),
+        account_config: RpcAccountInfoConfig {
+            // Base58 is limited to 128 bytes, Pair accounts are larger
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            ..Default::default()
+        },
+        with_context: Some(true),
+        sort_results: None,
+    };
+
+    RpcProgramSubscribe::new(rpc_ws_url, ProgramSubscribeFilters::new(program_id, Some(config)))
+        .with_name(program_subscribe_name(&program_id, data_size))
+}
+
+/// Health datasource name of the program subscription of `program_id` and `data_size`,
+/// so that readiness requires every subscription to be connected
+pub fn program_subscribe_name(program_id: &Pubkey, data_size: Option<u64>) -> String {
+    match data_size {
+        Some(size) => format!("program_subscribe_{}_{}", program_id, size),
+        None => format!("program_subscribe_{}", program_id),
+    }
+}
+
+/// Converts START_BLOCK to the newest program signature at or before it, which bounds
+/// the first catch-up crawl when no checkpoint exists yet
+pub async fn find_start_block_signature(http_rpc_url: &str, program_id: Pubkey, block_number: u64) -> Option<Signature> {
+    if block_number == 0 {
+        log::info!("START_BLOCK is 0, no initial catch-up bound");
+        return None;
+    }
+
+    log::info!("Converting START_BLOCK {} to slot and signature for transaction crawler", block_number);
+
+    // First, get the slot for this block
+    match get_slot_for_block(http_rpc_url, block_number).await {
+        Some(slot) => {
+            log::info!("Block {} corresponds to slot {}", block_number, slot);
+
+            // Then find a signature at or before this slot
+            match find_signature_at_slot(http_rpc_url, program_id, slot).await {
+                Some(sig) => {
+                    log::info!("Found signature {} at slot {} (block {}) or earlier", sig, slot, block_number);
+                    Some(sig)
+                }
+                None => {
+                    log::warn!("No signature found at or before slot {} (block {})", slot, block_number);
+                    None
+                }
+            }
+        }
+        None => {
+            log::warn!("Failed to find slot for block {}", block_number);
+            None
+        }
+    }
+}
+
 /// Creates a configured RPC Transaction Crawler datasource for Omnipair transaction monitoring
-/// This is more efficient than the block crawler as it pre-filters transactions by program ID
-pub async fn create_transaction_crawler_datasource(htt_rpc_url: String, program_id: Pubkey, start_block: Option<u64>) -> CarbonResult<RpcTransactionCrawler> {
+/// This is more efficient than the block crawler as it pre-filters transactions by program ID.
+/// The crawler processes every transaction newer than `until_signature` up to the current tip, then stops.
+pub fn create_transaction_crawler_datasource(http_rpc_url: String, program_id: Pubkey, until_signature: Signature) -> RpcTransactionCrawler {
     let connection_config = ConnectionConfig::new(
         100, // batch_limit: fetch 100 signatures at a time
         Duration::from_secs(5), // polling_interval: check for new transactions every 5 seconds
         5, // max_concurrent_requests: fetch up to 5 transactions concurrently
-        RetryConfig::no_retry(), // retry_config: no retry for faster processing
+        RetryConfig::default(), // retry_config: a dropped transaction would leave a gap
         None, // max_signature_channel_size: use default
         None, // max_transaction_channel_size: use default
         true, // blocking_send: use blocking send for reliability
     );
 
-    // Convert START_BLOCK to until_signature if provided
-    // We use until_signature because the crawler processes newest first, and we want to start from START_BLOCK
-    let until_signature = if let Some(block_number) = start_block {
-        if block_number == 0 {
-            log::info!("START_BLOCK is 0, starting from most recent transactions");
-            None
-        } else {
-            log::info!("Converting START_BLOCK {} to slot and signature for transaction crawler", block_number);
-            
-            // First, get the slot for this block
-            match get_slot_for_block(&htt_rpc_url, block_number).await {
-                Some(slot) => {
-                    log::info!("Block {} corresponds to slot {}", block_number, slot);
-                    
-                    // Then find a signature at or before this slot
-                    match find_signature_at_slot(&htt_rpc_url, program_id, slot).await {
-                        Some(sig) => {
-                            log::info!("Found signature {} at slot {} (block {}) or earlier", sig, slot, block_number);
-                            Some(sig)
-                        }
-                        None => {
-                            log::warn!("No signature found at or before slot {} (block {}), starting from most recent", slot, block_number);
-                            None
-                        }
-                    }
-                }
-                None => {
-                    log::warn!("Failed to find slot for block {}, starting from most recent", block_number);
-                    None
-                }
-            }
-        }
-    } else {
-        log::info!("No START_BLOCK provided, starting from most recent transactions");
-        None
-    };
-
     let filters = TransactionFilters::new(
         None, // accounts: no additional filtering (program ID is handled by the account parameter)
         None, // before_signature: start from most recent (crawler processes newest first)
-        until_signature, // until_signature: stop at START_BLOCK signature (process from START_BLOCK to current)
+        Some(until_signature), // until_signature: stop at the checkpoint (process from checkpoint to current)
     );
-    
-    // Log the final filter configuration
-    match &until_signature {
-        Some(sig) => log::info!("Transaction crawler configured to process from signature: {} to current", sig),
-        None => log::info!("Transaction crawler configured to start from most recent transactions"),
-    }
 
-    Ok(RpcTransactionCrawler::new(
-        htt_rpc_url,
+    log::info!("Transaction crawler configured to process from signature: {} to current", until_signature);
+
+    RpcTransactionCrawler::new(
+        http_rpc_url,
         program_id, // account: the program ID to monitor (this does the pre-filtering)
         connection_config,
         filters,
         Some(CommitmentConfig::confirmed()),
-    ))
+    )
+    .with_stop_when_exhausted(true)
 }
 
 /// GPA Backfill Datasource for historical account data (kept for future use)
@@ -290,3 +323,79 @@ impl Datasource for GpaBackfillDatasource {
         vec![UpdateType::AccountUpdate]
     }
 }
+
+/// Datasource fetching an explicit list of transactions, used to fill slot gaps.
+/// Completes once every signature has been fetched.
+pub struct SignatureListDatasource {
+    pub rpc_url: String,
+    pub signatures: Vec<Signature>,
+}
+
+#[async_trait]
+impl Datasource for SignatureListDatasource {
+    async fn consume(
+        &self,
+        id: DatasourceId,
+        sender: Sender<(Update, DatasourceId)>,
+        cancellation_token: CancellationToken,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let rpc_client = RpcClient::new(self.rpc_url.clone());
+
+        for signature in &self.signatures {
+            if cancellation_token.is_cancelled() {
+                log::info!("Cancelling signature list datasource...");
+                break;
+            }
+
+            let transaction = rpc_client
+                .get_transaction_with_config(
+                    signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::Base64),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+                .await
+                .map_err(|e| carbon_core::error::Error::FailedToConsumeDatasource(
+                    format!("Failed to fetch transaction {}: {}", signature, e),
+                ))?;
+
+            let Some(meta) = transaction.transaction.meta.clone() else {
+                log::warn!("Meta is malformed for transaction: {}", signature);
+                continue;
+            };
+
+            if meta.status.is_err() {
+                continue;
+            }
+
+            let Some(decoded_transaction) = transaction.transaction.transaction.decode() else {
+                log::error!("Failed to decode transaction: {}", signature);
+                continue;
+            };
+
+            let update = Update::Transaction(Box::new(TransactionUpdate {
+                signature: *signature,
+                transaction: decoded_transaction,
+                meta: transaction_metadata_from_original_meta(meta)?,
+                is_vote: false,
+                slot: transaction.slot,
+                block_time: transaction.block_time,
+                block_hash: None,
+            }));
+
+            if let Err(e) = sender.send((update, id.clone())).await {
+                log::error!("Failed to send transaction update: {:?}", e);
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_types(&self) -> Vec<UpdateType> {
+        vec![UpdateType::Transaction]
+    }
+}
@@ -0,0 +1,423 @@
+//! Dead-letter queue.
+//!
+//! An update whose processing fails (a database outage, a schema bug) is not dropped: the
+//! pipelines hand it to a `DeadLetterWriter`, which stores the full update with its
+//! datasource, error and attempt count in `dead_letter_updates`. When the database itself
+//! is unreachable the update is appended to a JSON lines fallback file instead.
+//!
+//! The `replay-failed` command imports the fallback file, then runs every stored update
+//! through the regular processors again. Replayed updates are marked as such; updates that
+//! fail again stay queued with their attempt count incremented.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use carbon_core::{
+    datasource::{AccountDeletion, AccountUpdate, BlockDetails, DatasourceId, TransactionUpdate, Update},
+    dead_letter::{DeadLetterQueue, FailedUpdate},
+    error::{CarbonResult, Error},
+    pipeline::{Pipeline, PipelineBuilder},
+    transformers::transaction_metadata_from_original_meta,
+};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{encode_ui_account, UiAccount, UiAccountEncoding};
+use solana_transaction_status::{
+    EncodedTransactionWithStatusMeta, Rewards, UiTransactionEncoding, VersionedTransactionWithStatusMeta,
+};
+use tokio::io::AsyncWriteExt;
+
+use crate::{config::DeadLetterConfig, store::EventStore};
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct ReplayArgs {
+    /// Replay at most this many updates, oldest first
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Only replay updates from this datasource (e.g. helius_atlas, rpc_catch_up)
+    #[arg(long)]
+    pub datasource: Option<String>,
+}
+
+/// Serializable form of a pipeline `Update`. Transactions are kept in the base64 RPC
+/// encoding, accounts as base64 `UiAccount`s, so they decode exactly as the RPC
+/// datasources decode them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StoredUpdate {
+    Account {
+        pubkey: String,
+        slot: u64,
+        transaction_signature: Option<String>,
+        account: UiAccount,
+    },
+    Transaction {
+        signature: String,
+        slot: u64,
+        block_time: Option<i64>,
+        block_hash: Option<String>,
+        is_vote: bool,
+        transaction: Box<EncodedTransactionWithStatusMeta>,
+    },
+    AccountDeletion {
+        pubkey: String,
+        slot: u64,
+        transaction_signature: Option<String>,
+    },
+    BlockDetails {
+        slot: u64,
+        block_hash: Option<String>,
+        previous_block_hash: Option<String>,
+        rewards: Option<Rewards>,
+        num_reward_partitions: Option<u64>,
+        block_time: Option<i64>,
+        block_height: Option<u64>,
+    },
+}
+
+fn parse<T: std::str::FromStr>(field: &str, value: &str) -> CarbonResult<T> {
+    value
+        .parse()
+        .map_err(|_| Error::Custom(format!("Invalid {} '{}' in dead letter", field, value)))
+}
+
+fn parse_optional<T: std::str::FromStr>(field: &str, value: Option<&String>) -> CarbonResult<Option<T>> {
+    value.map(|value| parse(field, value)).transpose()
+}
+
+impl StoredUpdate {
+    pub fn encode(update: &Update) -> CarbonResult<Self> {
+        let stored = match update {
+            Update::Account(update) => StoredUpdate::Account {
+                pubkey: update.pubkey.to_string(),
+                slot: update.slot,
+                transaction_signature: update.transaction_signature.map(|signature| signature.to_string()),
+                account: encode_ui_account(&update.pubkey, &update.account, UiAccountEncoding::Base64, None, None),
+            },
+            Update::Transaction(update) => {
+                let transaction = VersionedTransactionWithStatusMeta {
+                    transaction: update.transaction.clone(),
+                    meta: update.meta.clone(),
+                }
+                .encode(UiTransactionEncoding::Base64, Some(0), true)
+                .map_err(|e| Error::Custom(format!("Failed to encode transaction {}: {}", update.signature, e)))?;
+
+                StoredUpdate::Transaction {
+                    signature: update.signature.to_string(),
+                    slot: update.slot,
+                    block_time: update.block_time,
+                    block_hash: update.block_hash.map(|hash| hash.to_string()),
+                    is_vote: update.is_vote,
+                    transaction: Box::new(transaction),
+                }
+            }
+            Update::AccountDeletion(deletion) => StoredUpdate::AccountDeletion {
+                pubkey: deletion.pubkey.to_string(),
+                slot: deletion.slot,
+                transaction_signature: deletion.transaction_signature.map(|signature| signature.to_string()),
+            },
+            Update::BlockDetails(details) => StoredUpdate::BlockDetails {
+                slot: details.slot,
+                block_hash: details.block_hash.map(|hash| hash.to_string()),
+                previous_block_hash: details.previous_block_hash.map(|hash| hash.to_string()),
+                rewards: details.rewards.clone(),
+                num_reward_partitions: details.num_reward_partitions,
+                block_time: details.block_time,
+                block_height: details.block_height,
+            },
+        };
+
+        Ok(stored)
+    }
+
+    pub fn decode(&self) -> CarbonResult<Update> {
+        let update = match self {
+            StoredUpdate::Account { pubkey, slot, transaction_signature, account } => Update::Account(AccountUpdate {
+                pubkey: parse("pubkey", pubkey)?,
+                account: account
+                    .decode()
+                    .ok_or_else(|| Error::Custom(format!("Failed to decode account {} in dead letter", pubkey)))?,
+                slot: *slot,
+                transaction_signature: parse_optional("signature", transaction_signature.as_ref())?,
+            }),
+            StoredUpdate::Transaction { signature, slot, block_time, block_hash, is_vote, transaction } => {
+                let meta = transaction
+                    .meta
+                    .clone()
+                    .ok_or_else(|| Error::Custom(format!("Transaction {} in dead letter has no meta", signature)))?;
+
+                Update::Transaction(Box::new(TransactionUpdate {
+                    signature: parse("signature", signature)?,
+                    transaction: transaction.transaction.decode().ok_or_else(|| {
+                        Error::Custom(format!("Failed to decode transaction {} in dead letter", signature))
+                    })?,
+                    meta: transaction_metadata_from_original_meta(meta)?,
+                    is_vote: *is_vote,
+                    slot: *slot,
+                    block_time: *block_time,
+                    block_hash: parse_optional("block hash", block_hash.as_ref())?,
+                }))
+            }
+            StoredUpdate::AccountDeletion { pubkey, slot, transaction_signature } => {
+                Update::AccountDeletion(AccountDeletion {
+                    pubkey: parse("pubkey", pubkey)?,
+                    slot: *slot,
+                    transaction_signature: parse_optional("signature", transaction_signature.as_ref())?,
+                })
+            }
+            StoredUpdate::BlockDetails {
+                slot,
+                block_hash,
+                previous_block_hash,
+                rewards,
+                num_reward_partitions,
+                block_time,
+                block_height,
+            } => Update::BlockDetails(BlockDetails {
+                slot: *slot,
+                block_hash: parse_optional("block hash", block_hash.as_ref())?,
+                previous_block_hash: parse_optional("block hash", previous_block_hash.as_ref())?,
+                rewards: rewards.clone(),
+                num_reward_partitions: *num_reward_partitions,
+                block_time: *block_time,
+                block_height: *block_height,
+            }),
+        };
+
+        Ok(update)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StoredUpdate::Account { .. } => "account",
+            StoredUpdate::Transaction { .. } => "transaction",
+            StoredUpdate::AccountDeletion { .. } => "account_deletion",
+            StoredUpdate::BlockDetails { .. } => "block_details",
+        }
+    }
+
+    pub fn slot(&self) -> u64 {
+        match self {
+            StoredUpdate::Account { slot, .. }
+            | StoredUpdate::Transaction { slot, .. }
+            | StoredUpdate::AccountDeletion { slot, .. }
+            | StoredUpdate::BlockDetails { slot, .. } => *slot,
+        }
+    }
+
+    /// Transaction signature, for transactions and account changes made by a transaction
+    pub fn signature(&self) -> Option<&str> {
+        match self {
+            StoredUpdate::Transaction { signature, .. } => Some(signature),
+            StoredUpdate::Account { transaction_signature, .. }
+            | StoredUpdate::AccountDeletion { transaction_signature, .. } => transaction_signature.as_deref(),
+            StoredUpdate::BlockDetails { .. } => None,
+        }
+    }
+
+    pub fn pubkey(&self) -> Option<&str> {
+        match self {
+            StoredUpdate::Account { pubkey, .. } | StoredUpdate::AccountDeletion { pubkey, .. } => Some(pubkey),
+            StoredUpdate::Transaction { .. } | StoredUpdate::BlockDetails { .. } => None,
+        }
+    }
+}
+
+/// An update that failed processing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub datasource: String,
+    pub update: StoredUpdate,
+    pub error: String,
+    pub attempts: i32,
+    pub failed_at: DateTime<Utc>,
+}
+
+impl DeadLetter {
+    pub fn new(failed_update: &FailedUpdate) -> CarbonResult<Self> {
+        Ok(Self {
+            datasource: failed_update.datasource_id.as_str().to_string(),
+            update: StoredUpdate::encode(&failed_update.update)?,
+            error: failed_update.error.clone(),
+            attempts: 1,
+            failed_at: Utc::now(),
+        })
+    }
+}
+
+/// A dead letter as kept by an `EventStore`
+#[derive(Debug, Clone)]
+pub struct StoredDeadLetter {
+    pub id: i64,
+    pub letter: DeadLetter,
+    pub replayed_at: Option<DateTime<Utc>>,
+}
+
+/// Keeps failed updates in the store, or in the fallback file when the store fails
+pub struct DeadLetterWriter {
+    store: Arc<dyn EventStore>,
+    fallback_path: Option<PathBuf>,
+}
+
+impl DeadLetterWriter {
+    pub fn new(store: Arc<dyn EventStore>, fallback_path: Option<PathBuf>) -> Self {
+        Self { store, fallback_path }
+    }
+}
+
+#[async_trait]
+impl DeadLetterQueue for DeadLetterWriter {
+    async fn push(&self, failed_update: FailedUpdate) -> CarbonResult<()> {
+        let letter = DeadLetter::new(&failed_update)?;
+
+        let error = match self.store.write_dead_letter(&letter).await {
+            Ok(()) => {
+                log::warn!(
+                    "Stored failed {} update at slot {} from {} in the dead-letter queue",
+                    letter.update.kind(),
+                    letter.update.slot(),
+                    letter.datasource
+                );
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        let Some(path) = &self.fallback_path else {
+            return Err(error);
+        };
+
+        log::error!("Failed to store dead letter, appending it to {}: {}", path.display(), error);
+        append_to_file(path, &letter).await
+    }
+}
+
+/// Registers a `DeadLetterWriter` on `store` on a pipeline, unless disabled
+pub fn register(builder: PipelineBuilder, config: &DeadLetterConfig, store: Arc<dyn EventStore>) -> PipelineBuilder {
+    if !config.enabled {
+        return builder;
+    }
+
+    builder.dead_letter_queue(Arc::new(DeadLetterWriter::new(store, config.fallback_path.clone())))
+}
+
+async fn append_to_file(path: &Path, letter: &DeadLetter) -> CarbonResult<()> {
+    let mut line = serde_json::to_string(letter)
+        .map_err(|e| Error::Custom(format!("Failed to serialize dead letter: {}", e)))?;
+    line.push('\n');
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to open {}: {}", path.display(), e)))?;
+
+    file.write_all(line.as_bytes())
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Moves the dead letters of the fallback file into the store. Letters that cannot be
+/// stored stay in the file. Returns the number of imported letters.
+pub async fn import_fallback_file(path: &Path, store: &dyn EventStore) -> CarbonResult<usize> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(Error::Custom(format!("Failed to read {}: {}", path.display(), e))),
+    };
+
+    let mut imported = 0;
+    let mut remaining = Vec::new();
+    let mut store_failed = false;
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        // Once the store fails, the rest of the file is kept as is
+        if store_failed {
+            remaining.push(line);
+            continue;
+        }
+
+        let letter: DeadLetter = match serde_json::from_str(line) {
+            Ok(letter) => letter,
+            Err(e) => {
+                log::error!("Keeping invalid dead letter in {}: {}", path.display(), e);
+                remaining.push(line);
+                continue;
+            }
+        };
+
+        match store.write_dead_letter(&letter).await {
+            Ok(()) => imported += 1,
+            Err(e) => {
+                log::error!("Failed to import dead letters from {}: {}", path.display(), e);
+                store_failed = true;
+                remaining.push(line);
+            }
+        }
+    }
+
+    let result = if remaining.is_empty() {
+        tokio::fs::remove_file(path).await
+    } else {
+        tokio::fs::write(path, remaining.join("\n") + "\n").await
+    };
+    result.map_err(|e| Error::Custom(format!("Failed to update {}: {}", path.display(), e)))?;
+
+    Ok(imported)
+}
+
+/// Outcome of a replay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub replayed: usize,
+    pub failed: usize,
+}
+
+/// Runs the queued dead letters through `pipeline` (built without datasources), oldest
+/// first, and records each outcome
+pub async fn replay_dead_letters(
+    pipeline: &mut Pipeline,
+    store: &dyn EventStore,
+    args: &ReplayArgs,
+) -> CarbonResult<ReplaySummary> {
+    let letters = store.load_dead_letters(args.datasource.as_deref(), args.limit).await?;
+    log::info!("Replaying {} failed updates", letters.len());
+
+    let mut summary = ReplaySummary::default();
+    for StoredDeadLetter { id, letter, .. } in letters {
+        let outcome = match letter.update.decode() {
+            Ok(update) => pipeline
+                .process(update, DatasourceId::new_named(&letter.datasource))
+                .await
+                .map_err(|e| format!("{:?}", e)),
+            Err(e) => Err(format!("{:?}", e)),
+        };
+
+        match &outcome {
+            Ok(()) => {
+                log::info!("Replayed {} update {} at slot {}", letter.update.kind(), id, letter.update.slot());
+                summary.replayed += 1;
+            }
+            Err(e) => {
+                log::error!(
+                    "Replay of {} update {} at slot {} failed again (attempt {}): {}",
+                    letter.update.kind(),
+                    id,
+                    letter.update.slot(),
+                    letter.attempts + 1,
+                    e
+                );
+                summary.failed += 1;
+            }
+        }
+
+        store.record_dead_letter_replay(id, outcome).await?;
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,82 @@
+use carbon_core::{
+    error::CarbonResult,
+    instruction::{InstructionMetadata, MAX_INSTRUCTION_STACK_DEPTH},
+};
+use chrono::{DateTime, Utc};
+
+/// Identity and timing of a single emitted event.
+///
+/// An event is uniquely identified by `(signature, instruction_index, inner_instruction_index)`,
+/// which is the idempotency key used by every event table. A transaction can emit the same
+/// event type several times (multi-hop swaps, batched liquidations), so the signature alone
+/// is not enough.
+#[derive(Debug, Clone)]
+pub struct EventContext {
+    pub signature: String,
+    pub slot: i64,
+    /// Index of the top-level instruction in the transaction message
+    pub instruction_index: i32,
+    /// 1-based position within the inner instructions of the top-level instruction,
+    /// or 0 for the top-level instruction itself
+    pub inner_instruction_index: i32,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl EventContext {
+    pub fn new(metadata: &InstructionMetadata, timestamp: DateTime<Utc>) -> CarbonResult<Self> {
+        let transaction_metadata = &metadata.transaction_metadata;
+
+        Ok(Self {
+            signature: transaction_metadata.signature.to_string(),
+            slot: transaction_metadata.slot as i64,
+            instruction_index: metadata.index as i32,
+            inner_instruction_index: inner_instruction_index(metadata)?,
+            timestamp,
+        })
+    }
+}
+
+/// Maps the instruction's `absolute_path` back to its position in the transaction's
+/// inner instruction list, replaying the same traversal used to build the path.
+pub fn inner_instruction_index(metadata: &InstructionMetadata) -> CarbonResult<i32> {
+    if metadata.absolute_path.len() <= 1 {
+        return Ok(0);
+    }
+
+    let group = metadata
+        .transaction_metadata
+        .meta
+        .inner_instructions
+        .as_ref()
+        .and_then(|groups| groups.iter().find(|group| group.index as u32 == metadata.index));
+
+    if let Some(group) = group {
+        let mut path_stack = [0u8; MAX_INSTRUCTION_STACK_DEPTH];
+        path_stack[0] = group.index;
+        let mut prev_height = 0;
+
+        for (position, inner_instruction) in group.instructions.iter().enumerate() {
+            let stack_height = inner_instruction.stack_height.unwrap_or(1) as usize;
+            if stack_height == 0 || stack_height > MAX_INSTRUCTION_STACK_DEPTH {
+                break;
+            }
+
+            if stack_height > prev_height {
+                path_stack[stack_height - 1] = 0;
+            } else {
+                path_stack[stack_height - 1] += 1;
+            }
+
+            if path_stack[..stack_height] == metadata.absolute_path[..] {
+                return Ok(position as i32 + 1);
+            }
+
+            prev_height = stack_height;
+        }
+    }
+
+    Err(carbon_core::error::Error::Custom(format!(
+        "Inner instruction {:?} not found in transaction {}",
+        metadata.absolute_path, metadata.transaction_metadata.signature
+    )))
+}
@@ -0,0 +1,320 @@
+//! Transactional event persistence.
+//!
+//! `OmnipairInstructionProcessor` does not write rows itself: it stages them in an
+//! `EventWriter` while the instructions of a Solana transaction are processed. Once the
+//! whole transaction went through, `CheckpointProcessor` commits it, and all of its rows
+//! are written in one database transaction, so a transaction is either fully indexed or
+//! not at all.
+//!
+//! In batched mode the writer holds committed transactions back and writes many of them
+//! at once with multi-row inserts, so backfills are not bound by per-row round trips.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use carbon_core::error::CarbonResult;
+use carbon_omnipair_decoder::instructions::{
+    adjust_collateral_event::AdjustCollateralEvent,
+    adjust_debt_event::AdjustDebtEvent,
+    burn_event::BurnEvent,
+    claim_protocol_fees_event::ClaimProtocolFeesEvent,
+    mint_event::MintEvent,
+    pair_created_event::PairCreatedEvent,
+    swap_event::SwapEvent,
+    update_pair_event::UpdatePairEvent,
+    user_liquidity_position_updated_event::UserLiquidityPositionUpdatedEvent,
+    user_position_created_event::UserPositionCreatedEvent,
+    user_position_liquidated_event::UserPositionLiquidatedEvent,
+    user_position_updated_event::UserPositionUpdatedEvent,
+};
+
+use crate::{
+    event_context::EventContext, flashloans::FlashloanRecord, notifier::OutboxEntry, pricing::SwapPricing,
+    store::EventStore, telemetry,
+};
+
+/// A transaction fully processed by a datasource
+#[derive(Debug, Clone)]
+pub struct ProcessedTransaction {
+    pub signature: String,
+    pub slot: i64,
+    pub datasource: &'static str,
+}
+
+/// Last fully processed transaction of a datasource
+#[derive(Debug, Clone)]
+pub struct IngestionCheckpoint {
+    pub datasource: &'static str,
+    pub slot: i64,
+    pub signature: String,
+}
+
+/// Rows of one or more Solana transactions, grouped per table
+#[derive(Debug, Default)]
+pub struct EventBatch {
+    pub swaps: Vec<(SwapEvent, EventContext)>,
+    /// Price columns of the swaps, written with them when the processor prices swaps
+    pub swap_pricing: Vec<(SwapPricing, EventContext)>,
+    pub mints: Vec<(MintEvent, EventContext)>,
+    pub burns: Vec<(BurnEvent, EventContext)>,
+    pub collateral_adjustments: Vec<(AdjustCollateralEvent, EventContext)>,
+    pub debt_adjustments: Vec<(AdjustDebtEvent, EventContext)>,
+    pub user_position_updates: Vec<(UserPositionUpdatedEvent, EventContext)>,
+    pub user_position_liquidations: Vec<(UserPositionLiquidatedEvent, EventContext)>,
+    pub user_positions_created: Vec<(UserPositionCreatedEvent, EventContext)>,
+    pub user_liquidity_position_updates: Vec<(UserLiquidityPositionUpdatedEvent, EventContext)>,
+    pub pairs_created: Vec<(PairCreatedEvent, EventContext)>,
+    pub pair_updates: Vec<(UpdatePairEvent, EventContext)>,
+    pub flashloans: Vec<(FlashloanRecord, EventContext)>,
+    pub protocol_fee_claims: Vec<(ClaimProtocolFeesEvent, EventContext)>,
+    pub notifications: Vec<OutboxEntry>,
+    pub processed_transactions: Vec<ProcessedTransaction>,
+    pub checkpoint: Option<IngestionCheckpoint>,
+}
+
+impl EventBatch {
+    /// Number of rows per event type
+    pub fn event_counts(&self) -> [(&'static str, usize); 13] {
+        [
+            ("swap", self.swaps.len()),
+            ("mint", self.mints.len()),
+            ("burn", self.burns.len()),
+            ("adjust_collateral", self.collateral_adjustments.len()),
+            ("adjust_debt", self.debt_adjustments.len()),
+            ("user_position_updated", self.user_position_updates.len()),
+            ("user_position_liquidated", self.user_position_liquidations.len()),
+            ("user_position_created", self.user_positions_created.len()),
+            ("user_liquidity_position_updated", self.user_liquidity_position_updates.len()),
+            ("pair_created", self.pairs_created.len()),
+            ("update_pair", self.pair_updates.len()),
+            ("flashloan", self.flashloans.len()),
+            ("claim_protocol_fees", self.protocol_fee_claims.len()),
+        ]
+    }
+
+    /// Moves every row of `other` after the rows of this batch
+    pub fn append(&mut self, mut other: EventBatch) {
+        self.swaps.append(&mut other.swaps);
+        self.swap_pricing.append(&mut other.swap_pricing);
+        self.mints.append(&mut other.mints);
+        self.burns.append(&mut other.burns);
+        self.collateral_adjustments.append(&mut other.collateral_adjustments);
+        self.debt_adjustments.append(&mut other.debt_adjustments);
+        self.user_position_updates.append(&mut other.user_position_updates);
+        self.user_position_liquidations.append(&mut other.user_position_liquidations);
+        self.user_positions_created.append(&mut other.user_positions_created);
+        self.user_liquidity_position_updates.append(&mut other.user_liquidity_position_updates);
+        self.pairs_created.append(&mut other.pairs_created);
+        self.pair_updates.append(&mut other.pair_updates);
+        self.flashloans.append(&mut other.flashloans);
+        self.protocol_fee_claims.append(&mut other.protocol_fee_claims);
+        self.notifications.append(&mut other.notifications);
+        self.processed_transactions.append(&mut other.processed_transactions);
+
+        if let Some(checkpoint) = other.checkpoint {
+            let newer = self.checkpoint.as_ref().is_none_or(|current| current.slot <= checkpoint.slot);
+            if newer {
+                self.checkpoint = Some(checkpoint);
+            }
+        }
+    }
+}
+
+/// When committed transactions reach the database
+#[derive(Debug, Clone, Copy)]
+pub enum WriteMode {
+    /// Every transaction is written as soon as it is committed
+    PerTransaction,
+    /// Committed transactions are written together once `max_transactions` are pending or
+    /// the oldest has been pending for `max_delay`, and on `EventWriter::flush`
+    Batched {
+        max_transactions: usize,
+        max_delay: Duration,
+    },
+}
+
+#[derive(Default)]
+struct WriterState {
+    /// Signature of the transaction whose rows are being staged
+    staged_signature: Option<String>,
+    /// (instruction_index, inner_instruction_index) of the last staged event. Events are
+    /// staged once each, in instruction order, so staging it or an earlier one again means
+    /// the transaction is processed again from the start.
+    staged_event: Option<(i32, i32)>,
+    staged: EventBatch,
+    /// Committed transactions not written yet (batched mode)
+    pending: EventBatch,
+    pending_signatures: HashSet<String>,
+    pending_since: Option<Instant>,
+}
+
+impl WriterState {
+    /// Whether the event of `ctx` belongs to the attempt at its transaction being staged
+    fn is_staging(&self, ctx: &EventContext) -> bool {
+        self.staged_signature.as_deref() == Some(ctx.signature.as_str())
+            && self
+                .staged_event
+                .is_some_and(|staged_event| staged_event < (ctx.instruction_index, ctx.inner_instruction_index))
+    }
+}
+
+/// Stages the rows of the transaction being processed and writes committed transactions
+/// to its `EventStore`.
+///
+/// A pipeline processes its transactions one at a time, so each pipeline needs its own
+/// writer.
+pub struct EventWriter {
+    store: Arc<dyn EventStore>,
+    mode: WriteMode,
+    state: Mutex<WriterState>,
+}
+
+impl EventWriter {
+    pub fn new(store: Arc<dyn EventStore>, mode: WriteMode) -> Self {
+        Self {
+            store,
+            mode,
+            state: Mutex::new(WriterState::default()),
+        }
+    }
+
+    /// Adds rows of the transaction identified by `ctx`. Rows left over from a transaction
+    /// that was never committed (its processing failed) are discarded, including those of
+    /// an earlier attempt at the same transaction.
+    pub fn stage<R>(&self, ctx: &EventContext, stage: impl FnOnce(&mut EventBatch) -> R) -> R {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if !state.is_staging(ctx) {
+            state.staged = EventBatch::default();
+            state.staged_signature = Some(ctx.signature.clone());
+        }
+        state.staged_event = Some((ctx.instruction_index, ctx.inner_instruction_index));
+
+        stage(&mut state.staged)
+    }
+
+    /// Reads the rows staged so far for the transaction identified by `ctx`, without
+    /// staging the event of `ctx`
+    pub fn staged<R>(&self, ctx: &EventContext, read: impl FnOnce(&EventBatch) -> R) -> R {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if state.is_staging(ctx) { read(&state.staged) } else { read(&EventBatch::default()) }
+    }
+
+    /// Discards the rows staged for `signature`, whose processing failed
+    pub fn discard(&self, signature: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if state.staged_signature.as_deref() == Some(signature) {
+            state.staged = EventBatch::default();
+            state.staged_signature = None;
+            state.staged_event = None;
+        }
+    }
+
+    /// Commits the staged rows of a fully processed transaction, recording it in
+    /// `processed_transactions` and, with `advance_checkpoint`, as the checkpoint of
+    /// `datasource`
+    pub async fn commit(
+        &self,
+        signature: &str,
+        slot: i64,
+        datasource: &'static str,
+        advance_checkpoint: bool,
+    ) -> CarbonResult<()> {
+        let mut batch = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+            if state.pending_signatures.contains(signature) {
+                return Ok(());
+            }
+
+            if state.staged_signature.as_deref() == Some(signature) {
+                state.staged_signature = None;
+                state.staged_event = None;
+                std::mem::take(&mut state.staged)
+            } else {
+                EventBatch::default()
+            }
+        };
+
+        batch.processed_transactions.push(ProcessedTransaction {
+            signature: signature.to_string(),
+            slot,
+            datasource,
+        });
+
+        if advance_checkpoint {
+            batch.checkpoint = Some(IngestionCheckpoint {
+                datasource,
+                slot,
+                signature: signature.to_string(),
+            });
+        }
+
+        match self.mode {
+            WriteMode::PerTransaction => self.write(&batch).await,
+            WriteMode::Batched { max_transactions, max_delay } => {
+                let due = {
+                    let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+                    state.pending.append(batch);
+                    state.pending_signatures.insert(signature.to_string());
+                    let pending_since = *state.pending_since.get_or_insert_with(Instant::now);
+
+                    state.pending_signatures.len() >= max_transactions || pending_since.elapsed() >= max_delay
+                };
+
+                if due { self.flush().await } else { Ok(()) }
+            }
+        }
+    }
+
+    /// Writes `batch` to the store, recording its latency and persisted events
+    async fn write(&self, batch: &EventBatch) -> CarbonResult<()> {
+        let started_at = Instant::now();
+        let result = self.store.write_event_batch(batch).await;
+
+        telemetry::record_db_write(started_at.elapsed(), result.is_ok());
+        if result.is_ok() {
+            telemetry::record_events_persisted(batch);
+        }
+
+        result
+    }
+
+    /// Writes every committed transaction that is still pending. On failure they stay
+    /// pending and are written with the next flush.
+    pub async fn flush(&self) -> CarbonResult<()> {
+        let (batch, signatures) = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.pending_since = None;
+            (std::mem::take(&mut state.pending), std::mem::take(&mut state.pending_signatures))
+        };
+
+        if signatures.is_empty() {
+            return Ok(());
+        }
+
+        match self.write(&batch).await {
+            Ok(()) => {
+                log::debug!("Wrote {} transactions", signatures.len());
+                Ok(())
+            }
+            Err(e) => {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+                let mut retry = batch;
+                retry.append(std::mem::take(&mut state.pending));
+                state.pending = retry;
+                state.pending_signatures.extend(signatures);
+                state.pending_since.get_or_insert_with(Instant::now);
+
+                Err(e)
+            }
+        }
+    }
+}
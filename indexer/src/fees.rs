@@ -0,0 +1,171 @@
+//! Swap fee accounting.
+//!
+//! A swap pays its fee in the input token: `amount_in - amount_in_after_fee`, stored
+//! exactly in `fee_paid0` or `fee_paid1` while the other column is 0. What that fee is
+//! worth in the output token at the post-swap reserve ratio is derived separately into
+//! `fee_value_out`, with integer arithmetic.
+//!
+//! Newly written swaps are checked against the `swap_fee_bps` of their pair. Swaps whose
+//! fee is not `amount_in * swap_fee_bps / 10_000`, rounded either way, are logged, counted
+//! and recorded in `swap_fee_mismatches`.
+
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use carbon_core::error::{CarbonResult, Error};
+use carbon_omnipair_decoder::instructions::swap_event::SwapEvent;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+
+use crate::{database::INSERT_CHUNK_ROWS, event_context::EventContext, telemetry};
+
+/// Basis points in a whole
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Fee paid by a swap, in its input token
+pub fn input_fee(swap: &SwapEvent) -> u64 {
+    swap.amount_in.saturating_sub(swap.amount_in_after_fee)
+}
+
+/// Fees paid by a swap as (token0, token1): the input token carries the whole fee
+pub fn fees_paid(swap: &SwapEvent) -> (u64, u64) {
+    let fee = input_fee(swap);
+    if swap.is_token0_in {
+        (fee, 0)
+    } else {
+        (0, fee)
+    }
+}
+
+/// Input token fee valued in the output token at the post-swap reserve ratio, rounded
+/// down; `None` when the input reserve is empty
+pub fn fee_value_out(swap: &SwapEvent) -> Option<u128> {
+    let (reserve_in, reserve_out) = if swap.is_token0_in {
+        (swap.reserve0, swap.reserve1)
+    } else {
+        (swap.reserve1, swap.reserve0)
+    };
+
+    if reserve_in == 0 {
+        return None;
+    }
+
+    Some(input_fee(swap) as u128 * reserve_out as u128 / reserve_in as u128)
+}
+
+/// Fees a swap of `amount_in` may pay at `swap_fee_bps`: the exact fee rounded down or up
+pub fn expected_fee(amount_in: u64, swap_fee_bps: u16) -> RangeInclusive<u64> {
+    let exact = amount_in as u128 * swap_fee_bps as u128;
+    (exact / BPS_DENOMINATOR) as u64..=exact.div_ceil(BPS_DENOMINATOR) as u64
+}
+
+/// A swap whose fee disagrees with the fee rate of its pair
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeMismatch {
+    pub fee: u64,
+    pub expected: RangeInclusive<u64>,
+    pub swap_fee_bps: u16,
+}
+
+/// Checks the fee of `swap` against `swap_fee_bps`, `None` when they agree
+pub fn check_fee(swap: &SwapEvent, swap_fee_bps: u16) -> Option<FeeMismatch> {
+    let fee = input_fee(swap);
+    let expected = expected_fee(swap.amount_in, swap_fee_bps);
+
+    (!expected.contains(&fee)).then_some(FeeMismatch { fee, expected, swap_fee_bps })
+}
+
+/// Checks newly written swaps against the fee rate of their pair in `pools` and records
+/// the mismatches. Swaps of pairs missing from `pools` are not checked.
+pub async fn validate_swap_fees(conn: &mut PgConnection, swaps: &[&(SwapEvent, EventContext)]) -> CarbonResult<()> {
+    if swaps.is_empty() {
+        return Ok(());
+    }
+
+    let mut pairs: Vec<String> = swaps.iter().map(|(swap, _)| swap.metadata.pair.to_string()).collect();
+    pairs.sort_unstable();
+    pairs.dedup();
+
+    let fee_rates: HashMap<String, i32> = sqlx::query_as::<_, (String, i32)>(
+        "SELECT pair_address, swap_fee_bps::INTEGER FROM pools WHERE pair_address = ANY($1) AND swap_fee_bps IS NOT NULL",
+    )
+    .bind(&pairs)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| Error::Custom(format!("Failed to load pair fee rates: {}", e)))?
+    .into_iter()
+    .collect();
+
+    let mut mismatches = Vec::new();
+    for (swap, ctx) in swaps.iter().copied() {
+        let pair = swap.metadata.pair.to_string();
+        let Some(swap_fee_bps) = fee_rates.get(&pair).and_then(|bps| u16::try_from(*bps).ok()) else {
+            continue;
+        };
+
+        if let Some(mismatch) = check_fee(swap, swap_fee_bps) {
+            log::warn!(
+                "Swap {} ({}:{}) on pair {} paid a fee of {} for {} in, expected {}..={} at {} bps",
+                ctx.signature,
+                ctx.instruction_index,
+                ctx.inner_instruction_index,
+                pair,
+                mismatch.fee,
+                swap.amount_in,
+                mismatch.expected.start(),
+                mismatch.expected.end(),
+                swap_fee_bps
+            );
+            mismatches.push((swap, ctx, mismatch));
+        }
+    }
+
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    telemetry::record_swap_fee_mismatches(mismatches.len());
+
+    for chunk in mismatches.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO swap_fee_mismatches (
+                tx_sig, instruction_index, inner_instruction_index, pair, slot, timestamp,
+                amount_in, fee_paid, swap_fee_bps, expected_fee_min, expected_fee_max
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (swap, ctx, mismatch)| {
+            row.push_bind(ctx.signature.clone())
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index)
+                .push_bind(swap.metadata.pair.to_string())
+                .push_bind(ctx.slot)
+                .push_bind(ctx.timestamp)
+                .push_bind(BigDecimal::from(BigInt::from(swap.amount_in)))
+                .push_bind(BigDecimal::from(BigInt::from(mismatch.fee)))
+                .push_bind(mismatch.swap_fee_bps as i32)
+                .push_bind(BigDecimal::from(BigInt::from(*mismatch.expected.start())))
+                .push_bind(BigDecimal::from(BigInt::from(*mismatch.expected.end())));
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (tx_sig, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                slot = EXCLUDED.slot,
+                timestamp = EXCLUDED.timestamp,
+                amount_in = EXCLUDED.amount_in,
+                fee_paid = EXCLUDED.fee_paid,
+                swap_fee_bps = EXCLUDED.swap_fee_bps,
+                expected_fee_min = EXCLUDED.expected_fee_min,
+                expected_fee_max = EXCLUDED.expected_fee_max
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into swap_fee_mismatches table: {}", e);
+            return Err(Error::Custom(format!("Failed to record swap fee mismatches: {}", e)));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,505 @@
+//! Commitment finalizer.
+//!
+//! Rows are written at `confirmed` commitment. The finalizer follows the finalized slot
+//! of the cluster and, for every slot range that has pending rows, asks the RPC node
+//! which slots were rooted: rows in rooted slots become `finalized`, rows in skipped
+//! slots become `rolled_back`. The `finalized_*` views only expose finalized rows.
+//!
+//! Rolling back a slot also undoes what it contributed to derived state, in the same
+//! transaction:
+//! - latest-state rows (`pair_states`, `user_borrow_positions`,
+//!   `user_liquidity_positions`) written at a rolled back slot are re-derived from the
+//!   latest event of their key that was not rolled back, or removed when there is none;
+//!   a re-derived row is back at `confirmed` and settled again by a later pass
+//! - `pools` rows only come from the pair's `PairCreatedEvent`, so a rolled back one is
+//!   removed until the creation lands again
+//! - the candles of every UTC day with a rolled back swap are rebuilt from `swaps`
+//!
+//! Account rows observed at a rolled back slot have no history to fall back on; they are
+//! fetched again at finalized commitment once the range is settled.
+//!
+//! Slots older than the first block the RPC node keeps cannot be checked: their rows stay
+//! `confirmed` and a warning is logged. Pointing the HTTP RPC at an archival node
+//! settles them.
+
+use std::{collections::BTreeSet, str::FromStr, sync::Arc, time::Duration};
+
+use carbon_core::{
+    account::AccountDecoder,
+    error::{CarbonResult, Error},
+};
+use carbon_omnipair_decoder::{accounts::OmnipairAccount, OmnipairDecoder};
+use chrono::{DateTime, Utc};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_commitment_config::CommitmentConfig;
+use solana_pubkey::Pubkey;
+use sqlx::PgConnection;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    candles::{self, Resolution},
+    database::get_db_pool,
+    store::EventStore,
+};
+
+/// Tables carrying a `commitment` column, all keyed by `slot`
+const COMMITMENT_TABLES: [&str; 24] = [
+    "pools",
+    "swaps",
+    "adjust_liquidity",
+    "user_liquidity_positions",
+    "user_lp_position_updated_events",
+    "user_borrow_positions",
+    "user_position_updated_events",
+    "user_position_liquidated_events",
+    "adjust_collateral_events",
+    "adjust_debt_events",
+    "leverage_position_created_events",
+    "leverage_position_updated_events",
+    "pair_state_updates",
+    "pair_states",
+    "flashloans",
+    "protocol_fee_claims",
+    "protocol_revenue_ledger",
+    "swap_fee_accruals",
+    "user_position_created_events",
+    "pair_accounts",
+    "user_position_accounts",
+    "rate_model_accounts",
+    "futarchy_authority_accounts",
+    "processed_transactions",
+];
+
+/// Account tables, refreshed from RPC when their row is rolled back
+const ACCOUNT_TABLES: [&str; 4] = [
+    "pair_accounts",
+    "user_position_accounts",
+    "rate_model_accounts",
+    "futarchy_authority_accounts",
+];
+
+/// Re-derives `pair_states` rows written at a rolled back slot from `pair_state_updates`
+const REDERIVE_PAIR_STATES: &str = r#"
+    UPDATE pair_states SET
+        price0_ema = e.price0_ema,
+        price1_ema = e.price1_ema,
+        rate0 = e.rate0,
+        rate1 = e.rate1,
+        accrued_interest0 = e.accrued_interest0,
+        accrued_interest1 = e.accrued_interest1,
+        cash_reserve0 = e.cash_reserve0,
+        cash_reserve1 = e.cash_reserve1,
+        reserve0_after_interest = e.reserve0_after_interest,
+        reserve1_after_interest = e.reserve1_after_interest,
+        utilization0 = e.utilization0,
+        utilization1 = e.utilization1,
+        borrow_apr0 = e.borrow_apr0,
+        borrow_apr1 = e.borrow_apr1,
+        supply_apr0 = e.supply_apr0,
+        supply_apr1 = e.supply_apr1,
+        tx_sig = e.tx_sig,
+        slot = e.slot,
+        event_timestamp = e."timestamp",
+        updated_at = now()
+    FROM (
+        SELECT DISTINCT ON (pair) *
+        FROM pair_state_updates
+        WHERE commitment <> 'rolled_back'
+            AND pair IN (
+                SELECT pair FROM pair_states WHERE commitment = 'rolled_back' AND slot BETWEEN $1 AND $2
+            )
+        ORDER BY pair, slot DESC, id DESC
+    ) e
+    WHERE pair_states.pair = e.pair
+        AND pair_states.commitment = 'rolled_back'
+        AND pair_states.slot BETWEEN $1 AND $2
+"#;
+
+/// Re-derives `user_borrow_positions` rows written at a rolled back slot from
+/// `user_position_updated_events`
+const REDERIVE_USER_BORROW_POSITIONS: &str = r#"
+    UPDATE user_borrow_positions SET
+        position = e.position,
+        collateral0 = e.collateral0,
+        collateral1 = e.collateral1,
+        debt0_shares = e.debt0_shares,
+        debt1_shares = e.debt1_shares,
+        collateral0_liquidation_cf_bps = e.collateral0_liquidation_cf_bps,
+        collateral1_liquidation_cf_bps = e.collateral1_liquidation_cf_bps,
+        collateral0_max_cf_bps = e.collateral0_max_cf_bps,
+        collateral1_max_cf_bps = e.collateral1_max_cf_bps,
+        slot = e.slot,
+        event_timestamp = e.event_timestamp,
+        updated_at = now()
+    FROM (
+        SELECT DISTINCT ON (pair, signer) *
+        FROM user_position_updated_events
+        WHERE commitment <> 'rolled_back'
+            AND (pair, signer) IN (
+                SELECT pair, signer FROM user_borrow_positions
+                WHERE commitment = 'rolled_back' AND slot BETWEEN $1 AND $2
+            )
+        ORDER BY pair, signer, slot DESC, id DESC
+    ) e
+    WHERE user_borrow_positions.pair = e.pair
+        AND user_borrow_positions.signer = e.signer
+        AND user_borrow_positions.commitment = 'rolled_back'
+        AND user_borrow_positions.slot BETWEEN $1 AND $2
+"#;
+
+/// Re-derives `user_liquidity_positions` rows written at a rolled back slot from
+/// `user_lp_position_updated_events`; the mints of a pair never change
+const REDERIVE_USER_LIQUIDITY_POSITIONS: &str = r#"
+    UPDATE user_liquidity_positions SET
+        amount0 = e.amount0,
+        amount1 = e.amount1,
+        lp_amount = e.lp_amount,
+        slot = e.slot,
+        updated_at = now()
+    FROM (
+        SELECT DISTINCT ON (pair_address, signer) pair_address, signer, amount0, amount1, lp_amount, slot
+        FROM user_lp_position_updated_events
+        WHERE commitment <> 'rolled_back'
+            AND (pair_address, signer) IN (
+                SELECT pair, signer FROM user_liquidity_positions
+                WHERE commitment = 'rolled_back' AND slot BETWEEN $1 AND $2
+            )
+        ORDER BY pair_address, signer, slot DESC, id DESC
+    ) e
+    WHERE user_liquidity_positions.pair = e.pair_address
+        AND user_liquidity_positions.signer = e.signer
+        AND user_liquidity_positions.commitment = 'rolled_back'
+        AND user_liquidity_positions.slot BETWEEN $1 AND $2
+"#;
+
+/// Latest-state tables and the statement re-deriving their rolled back rows
+const LATEST_STATE_TABLES: [(&str, Option<&str>); 4] = [
+    ("pair_states", Some(REDERIVE_PAIR_STATES)),
+    ("user_borrow_positions", Some(REDERIVE_USER_BORROW_POSITIONS)),
+    ("user_liquidity_positions", Some(REDERIVE_USER_LIQUIDITY_POSITIONS)),
+    ("pools", None),
+];
+
+/// Maximum number of slots settled per pass
+const MAX_SLOT_RANGE: u64 = 50_000;
+
+/// Maximum number of accounts per `getMultipleAccounts` request
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Delay between passes once every pending row up to the finalized slot is settled
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Commitment of a stored row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Commitment {
+    Confirmed,
+    Finalized,
+    RolledBack,
+}
+
+/// Outcome of settling a slot range
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Settlement {
+    pub finalized: u64,
+    pub rolled_back: u64,
+    /// Latest-state rows re-derived from an earlier event
+    pub rederived: u64,
+    /// Latest-state rows removed because no earlier event survives
+    pub removed: u64,
+    /// Accounts whose stored state was observed at a rolled back slot
+    pub rolled_back_accounts: Vec<Pubkey>,
+}
+
+/// Settles pending rows until cancelled
+pub async fn run_finalizer(http_rpc_url: String, store: Arc<dyn EventStore>, cancellation_token: CancellationToken) {
+    let rpc_client = RpcClient::new(http_rpc_url);
+
+    log::info!("Commitment finalizer started");
+
+    let mut unverifiable_slot = None;
+
+    loop {
+        let settled = match finalize_next_range(&rpc_client, store.as_ref(), &mut unverifiable_slot).await {
+            Ok(settled) => settled,
+            Err(e) => {
+                log::error!("Commitment finalizer pass failed: {:?}", e);
+                false
+            }
+        };
+
+        // Keep going without waiting while there is a backlog to settle
+        if settled {
+            continue;
+        }
+
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                log::info!("Commitment finalizer stopped");
+                return;
+            }
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+/// Settles the oldest range of pending slots the RPC node has blocks for, up to the
+/// finalized slot. `unverifiable_slot` is the oldest pending slot already reported as out
+/// of the node's history. Returns whether a range was settled.
+async fn finalize_next_range(
+    rpc_client: &RpcClient,
+    store: &dyn EventStore,
+    unverifiable_slot: &mut Option<i64>,
+) -> CarbonResult<bool> {
+    let first_available_block = rpc_client
+        .get_first_available_block()
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to fetch first available block: {}", e)))?;
+
+    let oldest_pending = store.oldest_pending_slot(0).await?;
+    if let Some(slot) = oldest_pending.filter(|slot| (*slot as u64) < first_available_block)
+        && *unverifiable_slot != Some(slot)
+    {
+        log::warn!(
+            "Rows from slot {} on are older than the first block of the RPC node ({}) and stay confirmed; \
+             use an archival node to settle them",
+            slot,
+            first_available_block
+        );
+        *unverifiable_slot = Some(slot);
+    }
+
+    let Some(start_slot) = store.oldest_pending_slot(first_available_block as i64).await? else {
+        return Ok(false);
+    };
+    let start_slot = start_slot as u64;
+
+    let finalized_slot = rpc_client
+        .get_slot_with_commitment(CommitmentConfig::finalized())
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to fetch finalized slot: {}", e)))?;
+
+    if start_slot > finalized_slot {
+        return Ok(false);
+    }
+
+    let end_slot = finalized_slot.min(start_slot + MAX_SLOT_RANGE - 1);
+
+    let rooted_slots: Vec<i64> = rpc_client
+        .get_blocks_with_commitment(start_slot, Some(end_slot), CommitmentConfig::finalized())
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to fetch rooted blocks: {}", e)))?
+        .into_iter()
+        .map(|slot| slot as i64)
+        .collect();
+
+    let settlement = store.settle_slots(start_slot as i64, end_slot as i64, Some(&rooted_slots)).await?;
+
+    if settlement.rolled_back > 0 {
+        log::warn!(
+            "Slots {}..={}: {} rows finalized, {} rows rolled back (skipped slots), {} latest-state rows re-derived, {} removed",
+            start_slot,
+            end_slot,
+            settlement.finalized,
+            settlement.rolled_back,
+            settlement.rederived,
+            settlement.removed
+        );
+    } else {
+        log::debug!("Slots {}..={}: {} rows finalized", start_slot, end_slot, settlement.finalized);
+    }
+
+    refresh_accounts(rpc_client, store, &settlement.rolled_back_accounts).await;
+
+    Ok(true)
+}
+
+/// Fetches `pubkeys` at finalized commitment and stores their state in place of the rolled
+/// back one. Accounts that cannot be refreshed keep their rolled back row until their next
+/// update.
+pub async fn refresh_accounts(rpc_client: &RpcClient, store: &dyn EventStore, pubkeys: &[Pubkey]) {
+    for chunk in pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let response = match rpc_client
+            .get_multiple_accounts_with_commitment(chunk, CommitmentConfig::finalized())
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                log::error!("Failed to refresh {} rolled back accounts: {}", chunk.len(), e);
+                continue;
+            }
+        };
+        let slot = response.context.slot as i64;
+
+        for (pubkey, account) in chunk.iter().zip(response.value) {
+            let Some(account) = account else {
+                log::warn!("Rolled back account {} no longer exists", pubkey);
+                continue;
+            };
+
+            let result = match OmnipairDecoder.decode_account(&account).map(|decoded| decoded.data) {
+                Some(OmnipairAccount::Pair(pair)) => store.upsert_pair_account(pubkey, &pair, slot).await,
+                Some(OmnipairAccount::UserPosition(position)) => {
+                    store.upsert_user_position_account(pubkey, &position, slot).await
+                }
+                Some(OmnipairAccount::RateModel(rate_model)) => {
+                    store.upsert_rate_model_account(pubkey, &rate_model, slot).await
+                }
+                Some(OmnipairAccount::FutarchyAuthority(futarchy_authority)) => {
+                    store.upsert_futarchy_authority_account(pubkey, &futarchy_authority, slot).await
+                }
+                None => {
+                    log::warn!("Rolled back account {} is no longer an Omnipair account", pubkey);
+                    continue;
+                }
+            };
+
+            if let Err(e) = result {
+                log::error!("Failed to refresh rolled back account {}: {:?}", pubkey, e);
+            }
+        }
+    }
+}
+
+/// Lowest slot from `from_slot` on with rows still at `confirmed` commitment
+pub async fn oldest_pending_slot(from_slot: i64) -> CarbonResult<Option<i64>> {
+    let pool = get_db_pool()?;
+
+    let query = COMMITMENT_TABLES
+        .iter()
+        .map(|table| {
+            format!("SELECT MIN(slot)::BIGINT AS slot FROM {} WHERE commitment = 'confirmed' AND slot >= $1", table)
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+
+    sqlx::query_scalar(&format!("SELECT MIN(slot) FROM ({}) pending", query))
+        .bind(from_slot)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to load pending slots: {}", e)))
+}
+
+/// Marks pending rows in `start_slot..=end_slot` as finalized when their slot is rooted
+/// (every slot when `rooted_slots` is `None`) and as rolled back otherwise, and undoes
+/// what the rolled back rows contributed to derived state, atomically across all tables
+pub async fn settle_range(start_slot: i64, end_slot: i64, rooted_slots: Option<&[i64]>) -> CarbonResult<Settlement> {
+    let pool = get_db_pool()?;
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        Error::Custom(format!("Failed to begin finalizer transaction: {}", e))
+    })?;
+
+    let mut settlement = Settlement::default();
+
+    for table in COMMITMENT_TABLES {
+        let counts: (i64, i64) = sqlx::query_as(&format!(
+            r#"
+            WITH settled AS (
+                UPDATE {table}
+                SET commitment = CASE
+                    WHEN $3::BIGINT[] IS NULL OR slot::BIGINT = ANY($3) THEN 'finalized'::commitment_status
+                    ELSE 'rolled_back'::commitment_status
+                END
+                WHERE commitment = 'confirmed' AND slot BETWEEN $1 AND $2
+                RETURNING commitment
+            )
+            SELECT
+                COUNT(*) FILTER (WHERE commitment = 'finalized'),
+                COUNT(*) FILTER (WHERE commitment = 'rolled_back')
+            FROM settled
+            "#
+        ))
+        .bind(start_slot)
+        .bind(end_slot)
+        .bind(rooted_slots)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to settle commitment of {}: {}", table, e)))?;
+
+        settlement.finalized += counts.0 as u64;
+        settlement.rolled_back += counts.1 as u64;
+    }
+
+    if settlement.rolled_back > 0 {
+        undo_rolled_back(&mut tx, start_slot, end_slot, &mut settlement).await?;
+    }
+
+    tx.commit().await.map_err(|e| {
+        Error::Custom(format!("Failed to commit finalizer transaction: {}", e))
+    })?;
+
+    Ok(settlement)
+}
+
+/// Re-derives the latest state, rebuilds the candles and lists the accounts affected by
+/// the rows of `start_slot..=end_slot` that were rolled back
+async fn undo_rolled_back(
+    conn: &mut PgConnection,
+    start_slot: i64,
+    end_slot: i64,
+    settlement: &mut Settlement,
+) -> CarbonResult<()> {
+    for (table, rederive) in LATEST_STATE_TABLES {
+        if let Some(rederive) = rederive {
+            let result = sqlx::query(rederive)
+                .bind(start_slot)
+                .bind(end_slot)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| Error::Custom(format!("Failed to re-derive {}: {}", table, e)))?;
+            settlement.rederived += result.rows_affected();
+        }
+
+        // Rows left rolled back have no surviving event
+        let result = sqlx::query(&format!(
+            "DELETE FROM {} WHERE commitment = 'rolled_back' AND slot BETWEEN $1 AND $2",
+            table
+        ))
+        .bind(start_slot)
+        .bind(end_slot)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to remove rolled back rows of {}: {}", table, e)))?;
+        settlement.removed += result.rows_affected();
+    }
+
+    let swaps = sqlx::query_as::<_, (String, DateTime<Utc>)>(
+        r#"
+        SELECT DISTINCT pair, "timestamp" FROM swaps
+        WHERE commitment = 'rolled_back' AND slot BETWEEN $1 AND $2
+        "#,
+    )
+    .bind(start_slot)
+    .bind(end_slot)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| Error::Custom(format!("Failed to load rolled back swaps: {}", e)))?;
+
+    let days: BTreeSet<(String, DateTime<Utc>)> = swaps
+        .into_iter()
+        .map(|(pair, timestamp)| (pair, Resolution::OneDay.bucket_start(timestamp)))
+        .collect();
+    for (pair, day) in &days {
+        candles::replace_day_candles(conn, pair, *day).await?;
+    }
+
+    let query = ACCOUNT_TABLES
+        .iter()
+        .map(|table| format!("SELECT pubkey::TEXT FROM {} WHERE commitment = 'rolled_back' AND slot BETWEEN $1 AND $2", table))
+        .collect::<Vec<_>>()
+        .join(" UNION ");
+
+    let pubkeys: Vec<String> = sqlx::query_scalar(&query)
+        .bind(start_slot)
+        .bind(end_slot)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to load rolled back accounts: {}", e)))?;
+
+    settlement.rolled_back_accounts = pubkeys
+        .iter()
+        .filter_map(|pubkey| Pubkey::from_str(pubkey).ok())
+        .collect();
+
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use carbon_core::{
+    deserialize::ArrangeAccounts,
+    error::CarbonResult,
+    instruction::{InstructionDecoder, NestedInstruction, NestedInstructions},
+};
+use carbon_omnipair_decoder::{
+    instructions::{
+        flashloan::Flashloan,
+        flashloan_event::FlashloanEvent,
+        OmnipairInstruction,
+    },
+    OmnipairDecoder,
+};
+use serde::Serialize;
+use solana_pubkey::Pubkey;
+
+use crate::event_context::inner_instruction_index;
+
+/// A flashloan assembled from the `Flashloan` instruction, its `FlashloanEvent`
+/// and the receiver program's inner instructions.
+#[derive(Debug, Clone)]
+pub struct FlashloanRecord {
+    pub pair: Pubkey,
+    pub signer: Pubkey,
+    pub receiver: Pubkey,
+    pub receiver_program: Pubkey,
+    pub amount0: u64,
+    pub amount1: u64,
+    /// `None` when the transaction did not carry a decodable `FlashloanEvent`
+    pub fee0: Option<u64>,
+    pub fee1: Option<u64>,
+    pub receiver_instructions: Vec<ReceiverInstruction>,
+}
+
+/// An instruction executed by the receiver program while it held the loan
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceiverInstruction {
+    pub inner_instruction_index: i32,
+    pub stack_height: u32,
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    /// Base58 encoded instruction data
+    pub data: String,
+}
+
+impl FlashloanRecord {
+    /// `program_id` is the program that executed the flashloan, whose inner instructions
+    /// carry the `FlashloanEvent`
+    pub fn from_instruction(
+        flashloan: &Flashloan,
+        accounts: &[solana_instruction::AccountMeta],
+        program_id: &Pubkey,
+        nested_instructions: &NestedInstructions,
+    ) -> CarbonResult<Self> {
+        let accounts = Flashloan::arrange_accounts(accounts).ok_or_else(|| {
+            carbon_core::error::Error::Custom("Failed to arrange Flashloan accounts".to_string())
+        })?;
+
+        let event = find_flashloan_event(nested_instructions, program_id, &accounts.pair);
+        if event.is_none() {
+            log::warn!(
+                "No FlashloanEvent found for flashloan on pair {}, recording amounts from instruction args",
+                accounts.pair
+            );
+        }
+
+        let mut receiver_instructions = Vec::new();
+        collect_receiver_instructions(
+            nested_instructions,
+            &accounts.receiver_program,
+            false,
+            &mut receiver_instructions,
+        )?;
+
+        Ok(match event {
+            Some(event) => Self {
+                pair: event.metadata.pair,
+                signer: event.metadata.signer,
+                receiver: event.receiver,
+                receiver_program: accounts.receiver_program,
+                amount0: event.amount0,
+                amount1: event.amount1,
+                fee0: Some(event.fee0),
+                fee1: Some(event.fee1),
+                receiver_instructions,
+            },
+            None => Self {
+                pair: accounts.pair,
+                signer: accounts.user,
+                receiver: accounts.receiver_program,
+                receiver_program: accounts.receiver_program,
+                amount0: flashloan.args.amount0,
+                amount1: flashloan.args.amount1,
+                fee0: None,
+                fee1: None,
+                receiver_instructions,
+            },
+        })
+    }
+}
+
+/// Finds the `FlashloanEvent` emitted by the flashloan instruction for the given pair
+fn find_flashloan_event(
+    nested_instructions: &NestedInstructions,
+    program_id: &Pubkey,
+    pair: &Pubkey,
+) -> Option<FlashloanEvent> {
+    nested_instructions.iter().find_map(|nested| {
+        if nested.instruction.program_id == *program_id
+            && let Some(decoded) = OmnipairDecoder.decode_instruction(&nested.instruction)
+            && let OmnipairInstruction::FlashloanEvent(event) = decoded.data
+            && event.metadata.pair == *pair
+        {
+            return Some(event);
+        }
+
+        find_flashloan_event(&nested.inner_instructions, program_id, pair)
+    })
+}
+
+/// Collects every instruction invoked by the receiver program, including the CPI into
+/// the receiver itself and everything it called in turn, in execution order.
+fn collect_receiver_instructions(
+    nested_instructions: &NestedInstructions,
+    receiver_program: &Pubkey,
+    within_receiver: bool,
+    out: &mut Vec<ReceiverInstruction>,
+) -> CarbonResult<()> {
+    for nested in nested_instructions.iter() {
+        let is_receiver = within_receiver || nested.instruction.program_id == *receiver_program;
+
+        if is_receiver {
+            out.push(receiver_instruction(nested)?);
+        }
+
+        collect_receiver_instructions(&nested.inner_instructions, receiver_program, is_receiver, out)?;
+    }
+
+    Ok(())
+}
+
+fn receiver_instruction(nested: &NestedInstruction) -> CarbonResult<ReceiverInstruction> {
+    Ok(ReceiverInstruction {
+        inner_instruction_index: inner_instruction_index(&nested.metadata)?,
+        stack_height: nested.metadata.stack_height,
+        program_id: nested.instruction.program_id.to_string(),
+        accounts: nested
+            .instruction
+            .accounts
+            .iter()
+            .map(|account| account.pubkey.to_string())
+            .collect(),
+        data: bs58::encode(&nested.instruction.data).into_string(),
+    })
+}
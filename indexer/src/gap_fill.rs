@@ -0,0 +1,225 @@
+//! Slot gap fills.
+//!
+//! The Helius stream reports slot ranges in which it may have missed transactions (Clock
+//! sysvar jumps and reconnections). Each gap is turned into `getSignaturesForAddress`
+//! crawls over exactly that slot range, in rounds of bounded length until the start of the
+//! gap is reached; signatures already in `processed_transactions` are skipped and the rest
+//! are indexed through the regular `OmnipairInstructionProcessor`.
+
+use std::{collections::HashSet, str::FromStr, sync::Arc, time::Duration};
+
+use carbon_core::{datasource::DatasourceId, error::CarbonResult, pipeline::Pipeline};
+use carbon_helius_atlas_ws_datasource::SlotGap;
+use carbon_omnipair_decoder::OmnipairDecoder;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config};
+use solana_commitment_config::CommitmentConfig;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{
+    block_time::BlockTimeResolver,
+    checkpoints::{CheckpointProcessor, GAP_FILL_DATASOURCE},
+    config::Config,
+    dead_letter,
+    datasources::SignatureListDatasource,
+    event_writer::{EventWriter, WriteMode},
+    notifier::Notifier,
+    pricing,
+    processors::OmnipairInstructionProcessor,
+    store::EventStore,
+    telemetry,
+};
+
+/// Signatures fetched per `getSignaturesForAddress` page
+const SIGNATURE_PAGE_LIMIT: usize = 1000;
+
+/// Upper bound on pages scanned per crawl of a gap. A gap far behind the tip is crawled
+/// in several rounds, each continuing from the oldest signature the previous one reached.
+const MAX_GAP_PAGES: usize = 50;
+
+/// Delay before crawling a gap, so its slots are confirmed on the RPC node
+const GAP_FILL_DELAY: Duration = Duration::from_secs(10);
+
+/// Fills every reported slot gap for the configured programs until the gap channel closes
+pub async fn run_gap_filler(
+    config: Config,
+    mut gaps: UnboundedReceiver<SlotGap>,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+) {
+    while let Some(gap) = gaps.recv().await {
+        tokio::time::sleep(GAP_FILL_DELAY).await;
+
+        if let Err(e) = fill_gap(&config, gap, store.clone(), notifier.clone()).await {
+            log::error!("Failed to fill slot gap {}..={}: {:?}", gap.from_slot, gap.to_slot, e);
+        }
+    }
+}
+
+/// Indexes the transactions of the configured programs in `gap` that have not been
+/// processed yet
+pub async fn fill_gap(
+    config: &Config,
+    gap: SlotGap,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+) -> CarbonResult<()> {
+    let program_ids = config.program_ids();
+    let rpc_client = RpcClient::new(config.http_rpc_url.clone());
+
+    // Crawl cursor of every program whose part of the gap is not reached yet
+    let mut cursors: Vec<(Pubkey, Option<Signature>)> = program_ids.iter().map(|program_id| (*program_id, None)).collect();
+    while !cursors.is_empty() {
+        let mut signatures = Vec::new();
+        let mut remaining = Vec::new();
+        for (program_id, before) in cursors {
+            let (program_signatures, resume_before) = collect_gap_signatures(&rpc_client, &program_id, gap, before).await?;
+            signatures.extend(program_signatures);
+            if let Some(resume_before) = resume_before {
+                remaining.push((program_id, Some(resume_before)));
+            }
+        }
+
+        index_gap_signatures(config, gap, signatures, store.clone(), notifier.clone()).await?;
+        cursors = remaining;
+    }
+
+    log::info!("Filled slot gap {}..={}", gap.from_slot, gap.to_slot);
+
+    Ok(())
+}
+
+/// Indexes the transactions of `signatures` (with their slot) that have not been
+/// processed yet
+async fn index_gap_signatures(
+    config: &Config,
+    gap: SlotGap,
+    mut signatures: Vec<(u64, Signature)>,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+) -> CarbonResult<()> {
+    let http_rpc_url = config.http_rpc_url.as_str();
+    let program_ids = config.program_ids();
+
+    // A transaction invoking several programs is listed once per program
+    signatures.sort_by_key(|(slot, _)| *slot);
+    let mut seen = HashSet::new();
+    let signatures: Vec<Signature> = signatures
+        .into_iter()
+        .map(|(_, signature)| signature)
+        .filter(|signature| seen.insert(*signature))
+        .collect();
+
+    let signature_strings: Vec<String> = signatures.iter().map(|s| s.to_string()).collect();
+    let unprocessed = store.filter_unprocessed_signatures(&signature_strings).await?;
+
+    log::info!(
+        "Slot gap {}..={}: {} program transactions, {} not yet processed",
+        gap.from_slot,
+        gap.to_slot,
+        signatures.len(),
+        unprocessed.len()
+    );
+
+    if unprocessed.is_empty() {
+        return Ok(());
+    }
+
+    let signatures = unprocessed
+        .iter()
+        .map(|signature| {
+            Signature::from_str(signature).map_err(|e| {
+                carbon_core::error::Error::Custom(format!("Invalid signature {}: {}", signature, e))
+            })
+        })
+        .collect::<CarbonResult<Vec<_>>>()?;
+
+    let block_times = Arc::new(BlockTimeResolver::new(http_rpc_url.to_string()));
+    let writer = Arc::new(EventWriter::new(store.clone(), WriteMode::PerTransaction));
+
+    let builder = dead_letter::register(Pipeline::builder(), &config.dead_letter, store).datasource_with_id(
+        SignatureListDatasource {
+            rpc_url: http_rpc_url.to_string(),
+            signatures,
+        },
+        DatasourceId::new_named(GAP_FILL_DATASOURCE),
+    );
+
+    let mut pipeline = telemetry::register(builder, &config.metrics)
+        .instruction(
+            OmnipairDecoder,
+            OmnipairInstructionProcessor::new(block_times, notifier, writer.clone())
+                .with_programs(program_ids.iter().copied())
+                .with_pricing(pricing::create_swap_pricer(config)),
+        )
+        .instruction(OmnipairDecoder, CheckpointProcessor::without_checkpoint(writer, GAP_FILL_DATASOURCE))
+        .shutdown_strategy(carbon_core::pipeline::ShutdownStrategy::ProcessPending)
+        .build()?;
+
+    pipeline.run().await
+}
+
+/// Walks the program signatures from the tip (or from `before`) down to `gap.from_slot`
+/// and returns the successful ones inside the gap with their slot, oldest first. When
+/// `MAX_GAP_PAGES` pages do not reach the start of the gap, the oldest signature reached
+/// is returned too, to continue the crawl from.
+async fn collect_gap_signatures(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    gap: SlotGap,
+    mut before: Option<Signature>,
+) -> CarbonResult<(Vec<(u64, Signature)>, Option<Signature>)> {
+    let mut signatures = Vec::new();
+
+    for _ in 0..MAX_GAP_PAGES {
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                program_id,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_LIMIT),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await
+            .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to fetch signatures: {}", e)))?;
+
+        let Some(last) = page.last() else {
+            signatures.reverse();
+            return Ok((signatures, None));
+        };
+
+        for sig_info in page.iter().filter(|s| s.slot >= gap.from_slot && s.slot <= gap.to_slot) {
+            if sig_info.err.is_some() {
+                continue;
+            }
+            match Signature::from_str(&sig_info.signature) {
+                Ok(signature) => signatures.push((sig_info.slot, signature)),
+                Err(e) => log::error!("Invalid signature {}: {:?}", sig_info.signature, e),
+            }
+        }
+
+        if last.slot < gap.from_slot {
+            signatures.reverse();
+            return Ok((signatures, None));
+        }
+
+        before = Some(Signature::from_str(&last.signature).map_err(|e| {
+            carbon_core::error::Error::Custom(format!("Invalid signature {}: {}", last.signature, e))
+        })?);
+    }
+
+    log::info!(
+        "Slot gap {}..={} of program {} is more than {} signatures deep, continuing from {} once the part reached is filled",
+        gap.from_slot,
+        gap.to_slot,
+        program_id,
+        MAX_GAP_PAGES * SIGNATURE_PAGE_LIMIT,
+        before.map(|signature| signature.to_string()).unwrap_or_default()
+    );
+
+    signatures.reverse();
+    Ok((signatures, before))
+}
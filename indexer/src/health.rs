@@ -1,10 +1,291 @@
-use axum::{routing::get, Router, Json};
+//! Health server.
+//!
+//! `/health` keeps its original plain status body. `/health/live` answers as long as the
+//! process serves requests, with its uptime. `/health/ready` checks that the indexer is
+//! actually making progress and answers 503 otherwise: the database is reachable, every
+//! datasource is connected, the live stream is within `max_slot_lag` slots of the chain
+//! tip and an update was processed within `max_update_age`.
+//!
+//! Datasource states, the queue depth and processed updates are collected by
+//! registering `HealthState` as pipeline metrics; processed transactions are recorded by
+//! the live `CheckpointProcessor`.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use carbon_core::{error::CarbonResult, metrics::Metrics};
+use chrono::{DateTime, Utc};
 use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_commitment_config::CommitmentConfig;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::HealthConfig;
+
+/// How often the chain tip slot is polled
+const CHAIN_TIP_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Longest time the readiness database check may take
+const DATABASE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Gauges reported by the datasources, named `<datasource>_connected` and `<datasource>_slot`
+const CONNECTED_GAUGE_SUFFIX: &str = "_connected";
+const SLOT_GAUGE_SUFFIX: &str = "_slot";
+
+/// Connection state of a streaming datasource
+#[derive(Debug, Clone, Default)]
+struct DatasourceHealth {
+    connected: bool,
+    /// Last slot the datasource reported, if it tracks one
+    slot: Option<u64>,
+}
+
+/// Last transaction fully processed by the live pipeline
+#[derive(Debug, Clone)]
+struct ProcessedTransaction {
+    slot: u64,
+    signature: String,
+    at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+struct HealthInner {
+    datasources: BTreeMap<String, DatasourceHealth>,
+    queue_depth: u64,
+    last_transaction: Option<ProcessedTransaction>,
+    last_update_at: Option<Instant>,
+    chain_tip_slot: Option<u64>,
+}
+
+/// Progress of the indexer, shared by the pipelines and the health server
+pub struct HealthState {
+    config: HealthConfig,
+    started_at: Instant,
+    inner: Mutex<HealthInner>,
+}
+
+impl HealthState {
+    /// `datasources` are the streaming datasources that must be connected to be ready
+    pub fn new(config: HealthConfig, datasources: &[impl AsRef<str>]) -> Self {
+        let inner = HealthInner {
+            datasources: datasources
+                .iter()
+                .map(|name| (name.as_ref().to_string(), DatasourceHealth::default()))
+                .collect(),
+            ..HealthInner::default()
+        };
+
+        Self {
+            config,
+            started_at: Instant::now(),
+            inner: Mutex::new(inner),
+        }
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, HealthInner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Marks every datasource as disconnected, when the pipeline is restarted
+    pub fn reset_datasources(&self) {
+        let mut inner = self.inner();
+        for datasource in inner.datasources.values_mut() {
+            datasource.connected = false;
+        }
+        inner.queue_depth = 0;
+    }
+
+    /// Records a transaction fully processed by the live pipeline
+    pub fn record_transaction(&self, slot: u64, signature: &str) {
+        self.inner().last_transaction = Some(ProcessedTransaction {
+            slot,
+            signature: signature.to_string(),
+            at: Utc::now(),
+        });
+    }
+
+    pub fn set_chain_tip_slot(&self, slot: u64) {
+        self.inner().chain_tip_slot = Some(slot);
+    }
+
+    /// Slots the slowest streaming datasource trails the chain tip, once both are known
+    pub fn slot_lag(&self) -> Option<u64> {
+        Self::compute_slot_lag(&self.inner())
+    }
+
+    /// Slots between the chain tip and the slowest datasource that reports its slot
+    fn compute_slot_lag(inner: &HealthInner) -> Option<u64> {
+        let stream_slot = inner.datasources.values().filter_map(|datasource| datasource.slot).min()?;
+        Some(inner.chain_tip_slot?.saturating_sub(stream_slot))
+    }
+
+    /// Time since the last processed update, or since startup when there was none yet
+    fn update_age(&self, inner: &HealthInner) -> Duration {
+        inner.last_update_at.unwrap_or(self.started_at).elapsed()
+    }
+
+    /// Body of `/health` and `/`, unchanged since before `/health/live` and `/health/ready`
+    pub fn status(&self) -> Value {
+        json!({
+            "status": "healthy",
+            "service": "omnipair-indexer",
+            "timestamp": Utc::now().to_rfc3339(),
+            "uptime": format!("{}s", self.uptime().as_secs()),
+        })
+    }
+
+    pub fn liveness(&self) -> Value {
+        json!({
+            "status": "alive",
+            "service": "omnipair-indexer",
+            "timestamp": Utc::now().to_rfc3339(),
+            "uptime_seconds": self.uptime().as_secs(),
+        })
+    }
+
+    /// Readiness report and the checks that failed, given the database check result
+    pub fn readiness(&self, database: Result<(), String>) -> (Vec<String>, Value) {
+        let inner = self.inner();
+        let mut failures = Vec::new();
+
+        if let Err(e) = &database {
+            failures.push(format!("database: {}", e));
+        }
 
-pub async fn run_health_server(port: u16) {
+        for (name, datasource) in &inner.datasources {
+            if !datasource.connected {
+                failures.push(format!("datasource {}: disconnected", name));
+            }
+        }
+
+        let slot_lag = Self::compute_slot_lag(&inner);
+        if let Some(lag) = slot_lag
+            && self.config.max_slot_lag > 0
+            && lag > self.config.max_slot_lag
+        {
+            failures.push(format!("slot lag {} exceeds {}", lag, self.config.max_slot_lag));
+        }
+
+        let update_age = self.update_age(&inner);
+        if !self.config.max_update_age.is_zero() && update_age > self.config.max_update_age {
+            failures.push(format!(
+                "no update processed for {}s (limit {}s)",
+                update_age.as_secs(),
+                self.config.max_update_age.as_secs()
+            ));
+        }
+
+        let datasources: serde_json::Map<String, Value> = inner
+            .datasources
+            .iter()
+            .map(|(name, datasource)| {
+                (name.clone(), json!({ "connected": datasource.connected, "slot": datasource.slot }))
+            })
+            .collect();
+
+        let report = json!({
+            "status": if failures.is_empty() { "ready" } else { "not_ready" },
+            "service": "omnipair-indexer",
+            "timestamp": Utc::now().to_rfc3339(),
+            "uptime_seconds": self.uptime().as_secs(),
+            "database": { "connected": database.is_ok(), "error": database.err() },
+            "datasources": datasources,
+            "last_processed": inner.last_transaction.as_ref().map(|transaction| json!({
+                "slot": transaction.slot,
+                "signature": transaction.signature,
+                "at": transaction.at.to_rfc3339(),
+            })),
+            "chain_tip_slot": inner.chain_tip_slot,
+            "slot_lag": slot_lag,
+            "queue_depth": inner.queue_depth,
+            "seconds_since_last_update": update_age.as_secs(),
+            "thresholds": {
+                "max_slot_lag": self.config.max_slot_lag,
+                "max_update_age_seconds": self.config.max_update_age.as_secs(),
+            },
+            "failures": failures,
+        });
+
+        (failures, report)
+    }
+}
+
+#[async_trait]
+impl Metrics for HealthState {
+    async fn initialize(&self) -> CarbonResult<()> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> CarbonResult<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> CarbonResult<()> {
+        Ok(())
+    }
+
+    async fn update_gauge(&self, name: &str, value: f64) -> CarbonResult<()> {
+        let mut inner = self.inner();
+
+        if name == "updates_queued" {
+            inner.queue_depth = value as u64;
+        } else if let Some(datasource) = name.strip_suffix(CONNECTED_GAUGE_SUFFIX) {
+            inner.datasources.entry(datasource.to_string()).or_default().connected = value > 0.0;
+        } else if let Some(datasource) = name.strip_suffix(SLOT_GAUGE_SUFFIX) {
+            inner.datasources.entry(datasource.to_string()).or_default().slot = Some(value as u64);
+        }
+
+        Ok(())
+    }
+
+    async fn increment_counter(&self, name: &str, _value: u64) -> CarbonResult<()> {
+        if name == "updates_successful" {
+            self.inner().last_update_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    async fn record_histogram(&self, _name: &str, _value: f64) -> CarbonResult<()> {
+        Ok(())
+    }
+}
+
+/// Polls the confirmed chain tip slot until `shutdown` is cancelled, exporting the slot lag
+pub async fn run_chain_tip_poller(http_rpc_url: String, health: Arc<HealthState>, shutdown: CancellationToken) {
+    let rpc_client = RpcClient::new_with_commitment(http_rpc_url, CommitmentConfig::confirmed());
+
+    loop {
+        match rpc_client.get_slot().await {
+            Ok(slot) => {
+                health.set_chain_tip_slot(slot);
+                crate::telemetry::set_chain_tip(slot, health.slot_lag());
+            }
+            Err(e) => log::warn!("Failed to fetch chain tip slot: {}", e),
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = tokio::time::sleep(CHAIN_TIP_POLL_INTERVAL) => {}
+        }
+    }
+}
+
+pub async fn run_health_server(port: u16, health: Arc<HealthState>) {
     let app = Router::new()
-        .route("/health", get(health_check))
-        .route("/", get(health_check)); // Also respond to root path
+        .route("/health/live", get(liveness))
+        .route("/health/ready", get(readiness))
+        .route("/health", get(status))
+        .route("/", get(status)) // Also respond to root path
+        .with_state(health);
 
     let listener = match tokio::net::TcpListener::bind(("0.0.0.0", port)).await {
         Ok(listener) => listener,
@@ -15,21 +296,33 @@ pub async fn run_health_server(port: u16) {
     };
 
     log::info!("Health server listening on http://0.0.0.0:{}", port);
-    log::info!("Health endpoint: http://0.0.0.0:{}/health", port);
+    log::info!(
+        "Health endpoints: http://0.0.0.0:{}/health, http://0.0.0.0:{}/health/live, http://0.0.0.0:{}/health/ready",
+        port, port, port
+    );
 
     if let Err(e) = axum::serve(listener, app).await {
         log::warn!("Health server stopped: {:?}", e);
     }
 }
 
-async fn health_check() -> Json<Value> {
-    Json(json!({
-        "status": "healthy",
-        "service": "omnipair-indexer",
-        "timestamp": chrono::Utc::now().to_rfc3339(),
-        "uptime": format!("{}s", std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()),
-    }))
+async fn status(State(health): State<Arc<HealthState>>) -> Json<Value> {
+    Json(health.status())
+}
+
+async fn liveness(State(health): State<Arc<HealthState>>) -> Json<Value> {
+    Json(health.liveness())
+}
+
+async fn readiness(State(health): State<Arc<HealthState>>) -> (StatusCode, Json<Value>) {
+    let database = match tokio::time::timeout(DATABASE_CHECK_TIMEOUT, crate::database::check_connection()).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("no response within {}s", DATABASE_CHECK_TIMEOUT.as_secs())),
+    };
+
+    let (failures, report) = health.readiness(database);
+    let status = if failures.is_empty() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (status, Json(report))
 }
@@ -0,0 +1,135 @@
+//! Interest analytics.
+//!
+//! Every `UpdatePairEvent` is stored in `pair_state_updates` (and the latest one per pair
+//! in `pair_states`) together with the lending state of both tokens at that point:
+//! - utilization: total debt over reserve, the debt being the part of the reserve that is
+//!   lent out (`reserve_after_interest - cash_reserve`)
+//! - borrow APR: the on-chain rate, NAD-scaled and annual. `rate0`/`rate1` are in the unit
+//!   of the program's `getRates` view, which the API reports as an annual percentage,
+//!   `rate / 1e7` (`api/src/services/PairStateService.ts`): 1e9 is 100% a year
+//! - supply APR: what LPs earn, the borrow APR on the utilized share of the reserve net of
+//!   the protocol's interest share; `None` while that share is unknown
+//!
+//! The rate-model columns of `pools` follow the pair's `RateModel` account, converted from
+//! NAD to basis points.
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use carbon_core::error::{CarbonResult, Error};
+use carbon_omnipair_decoder::{accounts::rate_model::RateModel, instructions::update_pair_event::UpdatePairEvent};
+use sqlx::PgConnection;
+
+use crate::{fees::BPS_DENOMINATOR, pricing::NAD_DECIMALS};
+
+/// Decimal places of stored utilizations and APRs
+pub const RATE_SCALE: i64 = 18;
+
+/// NAD-scaled fractions per basis point
+const NAD_PER_BPS: u64 = 10u64.pow(NAD_DECIMALS) / BPS_DENOMINATOR as u64;
+
+/// Lending state of one token of a pair
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenRates {
+    /// Fraction of the reserve lent out, 0 when the reserve is empty
+    pub utilization: BigDecimal,
+    pub borrow_apr: BigDecimal,
+    pub supply_apr: Option<BigDecimal>,
+}
+
+/// Lending state of a token with `reserve`, of which `cash_reserve` is not lent out,
+/// borrowed at the NAD-scaled annual `rate`
+pub fn token_rates(reserve: u64, cash_reserve: u64, rate: u64, interest_share_bps: Option<u16>) -> TokenRates {
+    let total_debt = reserve.saturating_sub(cash_reserve);
+    let utilization = if reserve == 0 {
+        BigDecimal::from(0)
+    } else {
+        let scaled = BigInt::from(total_debt) * BigInt::from(10u8).pow(RATE_SCALE as u32) / BigInt::from(reserve);
+        BigDecimal::new(scaled, RATE_SCALE)
+    };
+    let borrow_apr = BigDecimal::new(BigInt::from(rate), NAD_DECIMALS as i64);
+
+    let supply_apr = interest_share_bps.map(|share_bps| {
+        let lp_share = BigDecimal::new(BigInt::from((BPS_DENOMINATOR as u64).saturating_sub(share_bps as u64)), 4);
+        (&borrow_apr * &utilization * lp_share).with_scale(RATE_SCALE)
+    });
+
+    TokenRates { utilization, borrow_apr, supply_apr }
+}
+
+/// Lending state of token0 and token1 after a pair update
+pub fn pair_rates(event: &UpdatePairEvent, interest_share_bps: Option<u16>) -> (TokenRates, TokenRates) {
+    (
+        token_rates(event.reserve0_after_interest, event.cash_reserve0, event.rate0, interest_share_bps),
+        token_rates(event.reserve1_after_interest, event.cash_reserve1, event.rate1, interest_share_bps),
+    )
+}
+
+/// Rate-model columns of `pools`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateModelParams {
+    pub target_util_start_bps: i64,
+    pub target_util_end_bps: i64,
+    pub rate_half_life_ms: i64,
+    pub min_rate_bps: i64,
+    pub max_rate_bps: i64,
+}
+
+impl From<&RateModel> for RateModelParams {
+    fn from(rate_model: &RateModel) -> Self {
+        let bps = |nad: u64| (nad / NAD_PER_BPS) as i64;
+
+        Self {
+            target_util_start_bps: bps(rate_model.target_util_start),
+            target_util_end_bps: bps(rate_model.target_util_end),
+            rate_half_life_ms: rate_model.half_life_ms as i64,
+            min_rate_bps: bps(rate_model.min_rate),
+            max_rate_bps: bps(rate_model.max_rate),
+        }
+    }
+}
+
+/// Share of borrow interest kept by the protocol, from the latest futarchy authority
+/// account; `None` until one has been indexed
+pub async fn protocol_interest_share(conn: &mut PgConnection) -> CarbonResult<Option<u16>> {
+    let share = sqlx::query_scalar::<_, i32>(
+        "SELECT interest_share_bps FROM futarchy_authority_accounts ORDER BY slot DESC LIMIT 1",
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| Error::Custom(format!("Failed to load protocol interest share: {}", e)))?;
+
+    Ok(share.and_then(|share| u16::try_from(share).ok()))
+}
+
+/// Copies the parameters of the `RateModel` account `rate_model` onto the pools using it
+pub async fn update_pool_rate_params(
+    conn: &mut PgConnection,
+    rate_model: &str,
+    params: &RateModelParams,
+) -> CarbonResult<()> {
+    let update_result = sqlx::query(
+        r#"
+        UPDATE pools SET
+            target_util_start_bps = $2,
+            target_util_end_bps = $3,
+            rate_half_life_ms = $4,
+            min_rate_bps = $5,
+            max_rate_bps = $6
+        WHERE rate_model = $1
+        "#,
+    )
+    .bind(rate_model)
+    .bind(params.target_util_start_bps)
+    .bind(params.target_util_end_bps)
+    .bind(params.rate_half_life_ms)
+    .bind(params.min_rate_bps)
+    .bind(params.max_rate_bps)
+    .execute(&mut *conn)
+    .await;
+
+    if let Err(e) = update_result {
+        log::error!("Failed to update rate model parameters of pools: {}", e);
+        return Err(Error::Custom(format!("Failed to update pool rate parameters: {}", e)));
+    }
+
+    Ok(())
+}
@@ -3,19 +3,42 @@
 //! This crate provides a clean, modular architecture for indexing Omnipair protocol
 //! events from Solana blockchain transactions.
 
+pub mod backfill;
+pub mod block_time;
+pub mod candles;
+pub mod checkpoints;
 pub mod config;
 pub mod database;
 pub mod datasources;
+pub mod dead_letter;
+pub mod event_context;
+pub mod event_writer;
+pub mod fees;
+pub mod finalizer;
+pub mod flashloans;
+pub mod gap_fill;
 pub mod health;
+pub mod interest;
+pub mod notifier;
 pub mod pipeline;
+pub mod pricing;
 pub mod processors;
+pub mod reindex;
+pub mod revenue;
+pub mod row_diff;
+pub mod schema;
 pub mod signals;
+pub mod store;
+pub mod telemetry;
 
 // Re-export commonly used types for convenience
+pub use backfill::{run_backfill, BackfillArgs};
+pub use block_time::BlockTimeResolver;
 pub use config::{Args, Config};
-pub use database::{init_db_pool, upsert_swap_event};
-pub use processors::OmnipairInstructionProcessor;
-pub use datasources::{create_helius_datasource, GpaBackfillDatasource};
-pub use pipeline::{create_pipeline, run_pipeline};
+pub use database::{init_db_pool, write_event_batch};
+pub use processors::{OmnipairAccountProcessor, OmnipairInstructionProcessor};
+pub use datasources::{create_account_datasource, create_helius_datasource, GpaBackfillDatasource};
+pub use pipeline::{create_catch_up_pipeline, create_pipeline, run_catch_up, run_pipeline};
 pub use health::run_health_server;
 pub use signals::shutdown_signal;
+pub use store::{EventStore, InMemoryEventStore, PostgresEventStore};
@@ -1,19 +1,45 @@
 use carbon_core::error::CarbonResult;
-use carbon_omnipair_decoder::PROGRAM_ID as OMNIPAIR_PROGRAM_ID;
 use clap::Parser;
-use std::time::Duration;
+use std::{
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
 
+mod backfill;
+mod block_time;
+mod candles;
+mod checkpoints;
 mod config;
 mod database;
 mod datasources;
+mod dead_letter;
+mod event_context;
+mod event_writer;
+mod fees;
+mod finalizer;
+mod flashloans;
+mod gap_fill;
 mod health;
+mod interest;
+mod notifier;
 mod pipeline;
+mod pricing;
 mod processors;
+mod reindex;
+mod revenue;
+mod row_diff;
+mod schema;
 mod signals;
+mod store;
+mod telemetry;
 
-use config::{Args, Config};
-use health::run_health_server;
-use pipeline::{create_pipeline, run_pipeline};
+use config::{Args, Command, Config};
+use datasources::program_subscribe_name;
+use health::{run_chain_tip_poller, run_health_server, HealthState};
+use notifier::Notifier;
+use pipeline::{create_catch_up_pipeline, create_pipeline, run_catch_up, run_pipeline};
+use store::{EventStore, PostgresEventStore};
 
 #[tokio::main]
 pub async fn main() -> CarbonResult<()> {
@@ -21,11 +47,26 @@ pub async fn main() -> CarbonResult<()> {
     dotenv::dotenv().ok();
     env_logger::init();
 
-    let args = Args::parse();
-    let config = Config::from_args(args);
+    let mut args = Args::parse();
+    let command = args.command.take();
+    let config = match Config::load(args) {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("{}", e);
+            return Err(carbon_core::error::Error::Custom(e));
+        }
+    };
+
+    match command {
+        Some(Command::Backfill(backfill_args)) => return run_backfill_command(&config, &backfill_args).await,
+        Some(Command::Migrate(migrate_args)) => return run_migrate_command(&config, &migrate_args).await,
+        Some(Command::ReplayFailed(replay_args)) => return run_replay_command(&config, &replay_args).await,
+        Some(Command::Reindex(reindex_args)) => return run_reindex_command(&config, &reindex_args).await,
+        Some(Command::RebuildCandles(candles_args)) => return run_rebuild_candles_command(&config, &candles_args).await,
+        None => {}
+    }
 
     log::info!("Starting Omnipair Indexer Daemon");
-    log::info!("Program ID: {:?}", *OMNIPAIR_PROGRAM_ID);
 
     // Validate configuration
     if let Err(e) = config.validate() {
@@ -36,35 +77,88 @@ pub async fn main() -> CarbonResult<()> {
     // Log configuration
     config.log_configuration();
 
+    // Streaming datasources that must be connected for the indexer to be ready
+    let mut streams = Vec::new();
+    if config.processors.events {
+        streams.push("helius_atlas_ws".to_string());
+    }
+    if config.processors.accounts {
+        streams.extend(
+            config
+                .program_subscriptions()
+                .iter()
+                .map(|(program_id, data_size)| program_subscribe_name(program_id, *data_size)),
+        );
+    }
+    let health = Arc::new(HealthState::new(config.health.clone(), &streams));
+
     // Start health check server FIRST before any other initialization
     // This ensures Railway health checks pass while services are starting
-    if config.health_port != 0 {
+    if config.health.port != 0 {
         log::info!(
             "Starting health check server on port {}",
-            config.health_port
+            config.health.port
         );
-        tokio::spawn(run_health_server(config.health_port));
+        tokio::spawn(run_health_server(config.health.port, health.clone()));
+    }
+
+    // Metrics recorded outside of the pipelines need the exporter before they start
+    if let Err(e) = telemetry::install_exporter(&config.metrics).await {
+        log::error!("Failed to start Prometheus exporter: {}", e);
+        return Err(e);
     }
 
     // Initialize database connection pool
     log::info!("Initializing database connection pool...");
-    if let Err(e) = database::init_db_pool().await {
+    if let Err(e) = database::init_db_pool(&config.database).await {
         log::error!("Failed to initialize database pool: {}", e);
         return Err(e);
     }
 
+    if let Err(e) = schema::ensure_schema(config.database.auto_migrate).await {
+        log::error!("{}", e);
+        return Err(e);
+    }
+
+    // Webhook deliveries run in the background from the outbox table
+    let notifier_enabled = config.notifier.is_enabled();
+    let notifier = Arc::new(Notifier::new(config.notifier.clone()));
+    let notifier_shutdown = CancellationToken::new();
+    if notifier_enabled {
+        tokio::spawn(notifier.clone().run_delivery_worker(notifier_shutdown.clone()));
+    }
+
+    let store: Arc<dyn EventStore> = Arc::new(PostgresEventStore);
+
+    // Rows are written at confirmed commitment and settled in the background
+    let finalizer_shutdown = CancellationToken::new();
+    tokio::spawn(finalizer::run_finalizer(config.http_rpc_url.clone(), store.clone(), finalizer_shutdown.clone()));
+
+    // The chain tip is the reference for the readiness slot lag
+    tokio::spawn(run_chain_tip_poller(config.http_rpc_url.clone(), health.clone(), finalizer_shutdown.clone()));
+
     // Main daemon loop with exponential backoff for reconnection
-    run_daemon_loop(&config).await
+    let result = run_daemon_loop(&config, store, notifier, health).await;
+    notifier_shutdown.cancel();
+    finalizer_shutdown.cancel();
+    result
 }
 
-async fn run_daemon_loop(config: &Config) -> CarbonResult<()> {
+async fn run_daemon_loop(
+    config: &Config,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+    health: Arc<HealthState>,
+) -> CarbonResult<()> {
     let mut retry_delay = Duration::from_secs(1);
     let max_retry_delay = Duration::from_secs(30);
 
     loop {
         log::info!("Starting indexer pipeline...");
 
-        match run_indexer_instance(config).await {
+        health.reset_datasources();
+
+        match run_indexer_instance(config, store.clone(), notifier.clone(), health.clone()).await {
             Ok(_) => {
                 log::warn!("Pipeline finished unexpectedly, restarting...");
             }
@@ -87,7 +181,163 @@ async fn run_daemon_loop(config: &Config) -> CarbonResult<()> {
     }
 }
 
-async fn run_indexer_instance(config: &Config) -> CarbonResult<()> {
-    let pipeline = create_pipeline(config).await?;
-    run_pipeline(pipeline).await
+async fn run_indexer_instance(
+    config: &Config,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+    health: Arc<HealthState>,
+) -> CarbonResult<()> {
+    // The live stream starts right away; the catch-up crawl fills the gap since the last checkpoint
+    let caught_up = Arc::new(AtomicBool::new(false));
+    let (gap_sender, gap_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let pipeline = create_pipeline(config, store.clone(), notifier.clone(), caught_up.clone(), gap_sender, health).await?;
+
+    // Slot gaps reported by the live stream are filled from RPC in the background
+    let gap_filler = tokio::spawn(gap_fill::run_gap_filler(
+        config.clone(),
+        gap_receiver,
+        store.clone(),
+        notifier.clone(),
+    ));
+
+    // Without the events processor there is no transaction history to catch up on
+    let catch_up_pipeline = if config.processors.events {
+        create_catch_up_pipeline(config, store, notifier).await?
+    } else {
+        None
+    };
+    let catch_up = match catch_up_pipeline {
+        Some(catch_up_pipeline) => Some(tokio::spawn(run_catch_up(catch_up_pipeline, caught_up))),
+        None => {
+            caught_up.store(true, Ordering::Release);
+            None
+        }
+    };
+
+    let result = run_pipeline(pipeline).await;
+
+    gap_filler.abort();
+
+    if let Some(catch_up) = catch_up {
+        catch_up.abort();
+    }
+
+    result
+}
+
+async fn run_backfill_command(config: &Config, args: &backfill::BackfillArgs) -> CarbonResult<()> {
+    log::info!("Starting Omnipair backfill");
+    log::info!("  Cluster: {}", config.cluster);
+    log::info!("  RPC: {}", config.http_rpc_url);
+
+    log::info!("Initializing database connection pool...");
+    if let Err(e) = database::init_db_pool(&config.database).await {
+        log::error!("Failed to initialize database pool: {}", e);
+        return Err(e);
+    }
+
+    if let Err(e) = schema::ensure_schema(config.database.auto_migrate).await {
+        log::error!("{}", e);
+        return Err(e);
+    }
+
+    // Programs are backfilled one after the other over the same range
+    let store: Arc<dyn EventStore> = Arc::new(PostgresEventStore);
+    let pricer = pricing::create_swap_pricer(config);
+    for program in &config.programs {
+        log::info!("Backfilling program {} ({})", program.name, program.program_id);
+
+        let result =
+            backfill::run_backfill(&config.http_rpc_url, program.program_id, args, store.clone(), pricer.clone()).await;
+        if let Err(e) = result {
+            log::error!("Backfill of program {} failed: {:?}", program.name, e);
+            return Err(e);
+        }
+    }
+
+    log::info!("Backfill completed successfully");
+    Ok(())
+}
+
+async fn run_replay_command(config: &Config, args: &dead_letter::ReplayArgs) -> CarbonResult<()> {
+    log::info!("Initializing database connection pool...");
+    if let Err(e) = database::init_db_pool(&config.database).await {
+        log::error!("Failed to initialize database pool: {}", e);
+        return Err(e);
+    }
+
+    if let Err(e) = schema::ensure_schema(config.database.auto_migrate).await {
+        log::error!("{}", e);
+        return Err(e);
+    }
+
+    let store: Arc<dyn EventStore> = Arc::new(PostgresEventStore);
+
+    // Updates that failed while the database was unreachable went to the fallback file
+    if let Some(path) = &config.dead_letter.fallback_path {
+        let imported = dead_letter::import_fallback_file(path, store.as_ref()).await?;
+        if imported > 0 {
+            log::info!("Imported {} failed updates from {}", imported, path.display());
+        }
+    }
+
+    // Webhooks are enqueued in the outbox and delivered by the running daemon
+    let notifier = Arc::new(Notifier::new(config.notifier.clone()));
+    let mut pipeline = pipeline::create_replay_pipeline(config, store.clone(), notifier)?;
+    let summary = dead_letter::replay_dead_letters(&mut pipeline, store.as_ref(), args).await?;
+
+    log::info!("Replay completed: {} replayed, {} failed again", summary.replayed, summary.failed);
+    if summary.failed > 0 {
+        return Err(carbon_core::error::Error::Custom(format!(
+            "{} updates failed again and stay in the dead-letter queue",
+            summary.failed
+        )));
+    }
+
+    Ok(())
+}
+
+async fn run_reindex_command(config: &Config, args: &reindex::ReindexArgs) -> CarbonResult<()> {
+    log::info!("Starting Omnipair reindex");
+    log::info!("  Cluster: {}", config.cluster);
+    log::info!("  RPC: {}", config.http_rpc_url);
+
+    // Dry runs only read the stored rows, so they never migrate
+    log::info!("Initializing database connection pool...");
+    if let Err(e) = database::init_db_pool(&config.database).await {
+        log::error!("Failed to initialize database pool: {}", e);
+        return Err(e);
+    }
+
+    if let Err(e) = schema::ensure_schema(config.database.auto_migrate && !args.dry_run).await {
+        log::error!("{}", e);
+        return Err(e);
+    }
+
+    reindex::run_reindex(config, args).await
+}
+
+async fn run_rebuild_candles_command(config: &Config, args: &candles::RebuildCandlesArgs) -> CarbonResult<()> {
+    log::info!("Initializing database connection pool...");
+    if let Err(e) = database::init_db_pool(&config.database).await {
+        log::error!("Failed to initialize database pool: {}", e);
+        return Err(e);
+    }
+
+    if let Err(e) = schema::ensure_schema(config.database.auto_migrate).await {
+        log::error!("{}", e);
+        return Err(e);
+    }
+
+    candles::rebuild_candles(args).await
+}
+
+async fn run_migrate_command(config: &Config, args: &schema::MigrateArgs) -> CarbonResult<()> {
+    log::info!("Initializing database connection pool...");
+    if let Err(e) = database::init_db_pool(&config.database).await {
+        log::error!("Failed to initialize database pool: {}", e);
+        return Err(e);
+    }
+
+    schema::run_migrate(args).await
 }
@@ -0,0 +1,424 @@
+//! Webhook notifications delivered through a durable outbox.
+//!
+//! Processors only enqueue rows into `webhook_outbox` (one per configured endpoint), so a
+//! slow or failing receiver never stalls indexing. A background worker claims due rows,
+//! POSTs them with an HMAC-SHA256 signature and records the delivery status, retrying
+//! with exponential backoff until `max_attempts` is reached.
+//!
+//! A claim is a lease: rows stay `delivering` for at most `CLAIM_LEASE`, after which
+//! they are claimed again, so a worker that died mid-batch does not strand them while
+//! another worker still sending its own batch keeps its rows. A claim is identified by
+//! the `updated_at` it set: a worker whose lease expired and was claimed again does not
+//! record its outcome over the new claim.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use carbon_core::error::CarbonResult;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    database::{get_db_pool, INSERT_CHUNK_ROWS},
+    event_context::EventContext,
+    telemetry,
+};
+
+/// Event types that can be routed to webhook endpoints
+pub const PAIR_CREATED: &str = "pair_created";
+pub const SWAP: &str = "swap";
+pub const USER_POSITION_LIQUIDATED: &str = "user_position_liquidated";
+
+pub const EVENT_TYPES: [&str; 3] = [PAIR_CREATED, SWAP, USER_POSITION_LIQUIDATED];
+
+/// Header carrying `sha256=<hex hmac>` of `"{timestamp}.{body}"`
+pub const SIGNATURE_HEADER: &str = "X-Omnipair-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Omnipair-Timestamp";
+pub const EVENT_HEADER: &str = "X-Omnipair-Event";
+pub const DELIVERY_HEADER: &str = "X-Omnipair-Delivery";
+
+const CLAIM_BATCH_SIZE: i64 = 50;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// How long claimed rows belong to the worker that claimed them; longer than a batch of
+/// deliveries that all time out
+const CLAIM_LEASE: Duration = Duration::from_secs(CLAIM_BATCH_SIZE as u64 * REQUEST_TIMEOUT.as_secs() * 2);
+
+#[derive(Debug, Clone)]
+pub struct NotifierConfig {
+    /// Webhook endpoints per event type
+    pub endpoints: HashMap<String, Vec<String>>,
+    /// Shared secret used to sign payloads; deliveries are unsigned when absent
+    pub hmac_secret: Option<String>,
+    pub max_attempts: i32,
+    pub initial_backoff: Duration,
+    pub poll_interval: Duration,
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: HashMap::new(),
+            hmac_secret: None,
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(5),
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+impl NotifierConfig {
+    /// Parses `event_type=url` endpoint specs, e.g. `pair_created=https://example.com/webhook`
+    pub fn parse_endpoints(specs: &[String]) -> Result<HashMap<String, Vec<String>>, String> {
+        let mut endpoints: HashMap<String, Vec<String>> = HashMap::new();
+
+        for spec in specs.iter().map(|spec| spec.trim()).filter(|spec| !spec.is_empty()) {
+            let (event_type, url) = spec
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid webhook endpoint '{}', expected <event_type>=<url>", spec))?;
+
+            endpoints
+                .entry(event_type.trim().to_string())
+                .or_default()
+                .push(url.trim().to_string());
+        }
+
+        Ok(endpoints)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (event_type, urls) in &self.endpoints {
+            if !EVENT_TYPES.contains(&event_type.as_str()) {
+                return Err(format!(
+                    "Unknown webhook event type '{}', expected one of: {}",
+                    event_type,
+                    EVENT_TYPES.join(", ")
+                ));
+            }
+
+            for url in urls {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    return Err(format!("Webhook endpoint for '{}' must be an http(s) URL, got '{}'", event_type, url));
+                }
+            }
+        }
+
+        if self.max_attempts < 1 {
+            return Err("Webhook max attempts must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.endpoints.values().any(|urls| !urls.is_empty())
+    }
+}
+
+pub struct Notifier {
+    config: NotifierConfig,
+    client: reqwest::Client,
+}
+
+/// A notification waiting to be enqueued in `webhook_outbox`
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub event_type: &'static str,
+    pub event_key: String,
+    pub endpoint: String,
+    pub payload: serde_json::Value,
+}
+
+struct OutboxRow {
+    id: i64,
+    event_type: String,
+    endpoint: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    /// `updated_at` set by the claim, which is only still current while the lease is held
+    claimed_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Notifier {
+    pub fn new(config: NotifierConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+
+        Self { config, client }
+    }
+
+    /// Builds one outbox entry for every endpoint configured for `event_type`; they are
+    /// enqueued together with the rows of the transaction that emitted the event.
+    ///
+    /// The envelope fields (`event_type`, `signature`, `slot`, `timestamp`) are merged into
+    /// `payload`. Re-processing the same event does not enqueue it twice.
+    pub fn outbox_entries(
+        &self,
+        event_type: &'static str,
+        ctx: &EventContext,
+        payload: serde_json::Value,
+    ) -> Vec<OutboxEntry> {
+        let Some(endpoints) = self.config.endpoints.get(event_type) else {
+            return Vec::new();
+        };
+
+        let mut payload = payload;
+        if let Some(object) = payload.as_object_mut() {
+            object.insert("event_type".to_string(), event_type.into());
+            object.insert("signature".to_string(), ctx.signature.clone().into());
+            object.insert("slot".to_string(), ctx.slot.into());
+            object.insert("timestamp".to_string(), ctx.timestamp.to_rfc3339().into());
+        }
+
+        let event_key = format!("{}:{}:{}", ctx.signature, ctx.instruction_index, ctx.inner_instruction_index);
+
+        endpoints
+            .iter()
+            .map(|endpoint| OutboxEntry {
+                event_type,
+                event_key: event_key.clone(),
+                endpoint: endpoint.clone(),
+                payload: payload.clone(),
+            })
+            .collect()
+    }
+
+    /// Delivers due outbox rows until cancelled
+    pub async fn run_delivery_worker(self: Arc<Self>, cancellation_token: CancellationToken) {
+        log::info!("Webhook delivery worker started");
+
+        // Rows whose claim expired in 'delivering' were never acknowledged
+        if let Err(e) = self.requeue_abandoned().await {
+            log::error!("Failed to requeue abandoned webhook deliveries: {}", e);
+        }
+
+        loop {
+            let delivered = match self.deliver_due().await {
+                Ok(count) => count,
+                Err(e) => {
+                    log::error!("Webhook delivery pass failed: {}", e);
+                    0
+                }
+            };
+
+            // Keep draining while there is work, otherwise wait for the next poll
+            if delivered > 0 && !cancellation_token.is_cancelled() {
+                continue;
+            }
+
+            tokio::select! {
+                _ = cancellation_token.cancelled() => {
+                    log::info!("Webhook delivery worker stopped");
+                    return;
+                }
+                _ = tokio::time::sleep(self.config.poll_interval) => {}
+            }
+        }
+    }
+
+    /// Returns rows whose claim expired to `pending`; rows claimed by a worker that is
+    /// still delivering them are left alone
+    async fn requeue_abandoned(&self) -> CarbonResult<()> {
+        let pool = get_db_pool()?;
+
+        sqlx::query(
+            r#"
+            UPDATE webhook_outbox SET status = 'pending', updated_at = now()
+            WHERE status = 'delivering' AND updated_at < now() - make_interval(secs => $1)
+            "#
+        )
+        .bind(CLAIM_LEASE.as_secs_f64())
+        .execute(pool)
+        .await
+        .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to requeue webhooks: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Claims a batch of due rows, including rows whose claim expired, and attempts each
+    /// delivery, returning the batch size
+    async fn deliver_due(&self) -> CarbonResult<usize> {
+        let pool = get_db_pool()?;
+
+        let rows = sqlx::query_as::<_, (i64, String, String, serde_json::Value, i32, chrono::DateTime<chrono::Utc>)>(
+            r#"
+            UPDATE webhook_outbox
+            SET status = 'delivering', updated_at = now()
+            WHERE id IN (
+                SELECT id FROM webhook_outbox
+                WHERE (status = 'pending' AND next_attempt_at <= now())
+                    OR (status = 'delivering' AND updated_at < now() - make_interval(secs => $2))
+                ORDER BY next_attempt_at, id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, event_type, endpoint, payload, attempts, updated_at
+            "#
+        )
+        .bind(CLAIM_BATCH_SIZE)
+        .bind(CLAIM_LEASE.as_secs_f64())
+        .fetch_all(pool)
+        .await
+        .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to claim webhook deliveries: {}", e)))?;
+
+        let count = rows.len();
+
+        for (id, event_type, endpoint, payload, attempts, claimed_at) in rows {
+            let row = OutboxRow { id, event_type, endpoint, payload, attempts, claimed_at };
+            let outcome = self.deliver(&row).await;
+            self.record_outcome(&row, outcome).await?;
+        }
+
+        Ok(count)
+    }
+
+    /// POSTs a single row, returning the HTTP status on success or an error description
+    async fn deliver(&self, row: &OutboxRow) -> Result<u16, (Option<u16>, String)> {
+        let body = serde_json::to_vec(&row.payload).map_err(|e| (None, e.to_string()))?;
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+
+        let mut request = self
+            .client
+            .post(&row.endpoint)
+            .header("Content-Type", "application/json")
+            .header(EVENT_HEADER, &row.event_type)
+            .header(DELIVERY_HEADER, row.id.to_string())
+            .header(TIMESTAMP_HEADER, &timestamp);
+
+        if let Some(secret) = &self.config.hmac_secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &timestamp, &body));
+        }
+
+        match request.body(body).send().await {
+            Ok(response) if response.status().is_success() => Ok(response.status().as_u16()),
+            Ok(response) => Err((
+                Some(response.status().as_u16()),
+                format!("Receiver responded with status {}", response.status()),
+            )),
+            Err(e) => Err((None, e.to_string())),
+        }
+    }
+
+    /// Records the outcome of a delivery, unless the lease of the claim was lost: the row
+    /// was requeued or claimed again, and the outcome of the newer claim wins
+    async fn record_outcome(&self, row: &OutboxRow, outcome: Result<u16, (Option<u16>, String)>) -> CarbonResult<()> {
+        let pool = get_db_pool()?;
+        let attempts = row.attempts + 1;
+
+        let (result, delivery_outcome) = match outcome {
+            Ok(status_code) => {
+                log::info!("Delivered {} webhook {} to {}", row.event_type, row.id, row.endpoint);
+
+                let result = sqlx::query(
+                    r#"
+                    UPDATE webhook_outbox
+                    SET status = 'delivered', attempts = $2, last_status_code = $3, last_error = NULL,
+                        delivered_at = now(), updated_at = now()
+                    WHERE id = $1 AND status = 'delivering' AND updated_at = $4
+                    "#
+                )
+                .bind(row.id)
+                .bind(attempts)
+                .bind(status_code as i32)
+                .bind(row.claimed_at)
+                .execute(pool)
+                .await;
+                (result, "delivered")
+            }
+            Err((status_code, error)) => {
+                let exhausted = attempts >= self.config.max_attempts;
+                let backoff = backoff(self.config.initial_backoff, attempts);
+
+                if exhausted {
+                    log::error!(
+                        "Giving up on {} webhook {} to {} after {} attempts: {}",
+                        row.event_type, row.id, row.endpoint, attempts, error
+                    );
+                } else {
+                    log::warn!(
+                        "Failed to deliver {} webhook {} to {} (attempt {}), retrying in {}s: {}",
+                        row.event_type, row.id, row.endpoint, attempts, backoff.as_secs(), error
+                    );
+                }
+
+                let result = sqlx::query(
+                    r#"
+                    UPDATE webhook_outbox
+                    SET status = $2::webhook_delivery_status, attempts = $3, last_status_code = $4, last_error = $5,
+                        next_attempt_at = now() + make_interval(secs => $6), updated_at = now()
+                    WHERE id = $1 AND status = 'delivering' AND updated_at = $7
+                    "#
+                )
+                .bind(row.id)
+                .bind(if exhausted { "failed" } else { "pending" })
+                .bind(attempts)
+                .bind(status_code.map(|code| code as i32))
+                .bind(error)
+                .bind(backoff.as_secs_f64())
+                .bind(row.claimed_at)
+                .execute(pool)
+                .await;
+                (result, if exhausted { "failed" } else { "retry" })
+            }
+        };
+
+        let result = result
+            .map_err(|e| carbon_core::error::Error::Custom(format!("Failed to record webhook delivery status: {}", e)))?;
+
+        if result.rows_affected() == 0 {
+            log::warn!(
+                "Lost the claim on {} webhook {} to {} before recording its delivery; it was claimed again",
+                row.event_type, row.id, row.endpoint
+            );
+            telemetry::record_webhook_delivery(&row.event_type, "lease_lost");
+        } else {
+            telemetry::record_webhook_delivery(&row.event_type, delivery_outcome);
+        }
+
+        Ok(())
+    }
+}
+
+/// Exponential backoff: `initial * 2^(attempts - 1)`, capped at one hour
+pub fn backoff(initial: Duration, attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    initial.saturating_mul(2u32.saturating_pow(exponent)).min(MAX_BACKOFF)
+}
+
+/// `sha256=<hex>` HMAC of `"{timestamp}.{body}"`, so receivers can reject replays
+pub fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Enqueue outbox entries; entries already enqueued are left untouched
+pub async fn enqueue_outbox_entries(conn: &mut PgConnection, entries: &[OutboxEntry]) -> CarbonResult<()> {
+    for chunk in entries.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO webhook_outbox (event_type, event_key, endpoint, payload) "
+        );
+
+        query.push_values(chunk, |mut row, entry| {
+            row.push_bind(entry.event_type)
+                .push_bind(entry.event_key.clone())
+                .push_bind(entry.endpoint.clone())
+                .push_bind(entry.payload.clone());
+        });
+
+        query.push(" ON CONFLICT (event_key, event_type, endpoint) DO NOTHING");
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to enqueue webhooks: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to enqueue webhooks: {}", e)));
+        }
+    }
+
+    Ok(())
+}
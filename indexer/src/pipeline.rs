@@ -1,50 +1,233 @@
-use std::sync::Arc;
-use carbon_core::{error::CarbonResult, pipeline::Pipeline};
-use carbon_omnipair_decoder::{OmnipairDecoder, PROGRAM_ID as OMNIPAIR_PROGRAM_ID};
-use carbon_log_metrics::LogMetrics;
+use std::{
+    str::FromStr,
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    time::Duration,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use carbon_core::{datasource::DatasourceId, error::CarbonResult, filter::DatasourceFilter, pipeline::Pipeline};
+use carbon_helius_atlas_ws_datasource::SlotGap;
+use carbon_omnipair_decoder::OmnipairDecoder;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+};
 
 use crate::{
+    block_time::BlockTimeResolver,
+    checkpoints::{CheckpointProcessor, CATCH_UP_DATASOURCE, LIVE_DATASOURCE, REPLAY_DATASOURCE},
     config::Config,
-    datasources::{create_helius_datasource, create_transaction_crawler_datasource},
-    processors::OmnipairInstructionProcessor,
+    dead_letter,
+    event_writer::{EventWriter, WriteMode},
+    health::HealthState,
+    notifier::Notifier,
+    pricing,
+    datasources::{
+        create_account_datasource, create_helius_datasource, create_transaction_crawler_datasource,
+        find_signature_at_slot, find_start_block_signature, GpaBackfillDatasource,
+    },
+    processors::{OmnipairAccountProcessor, OmnipairInstructionProcessor},
+    store::EventStore,
+    telemetry,
 };
 
-/// Creates and configures the indexer pipeline based on the provided configuration
-pub async fn create_pipeline(config: &Config) -> CarbonResult<Pipeline> {
-    // Require Helius API key for transaction monitoring
-    let api_key = config.helius_api_key.as_ref()
-        .ok_or_else(|| carbon_core::error::Error::Custom(
-            "HELIUS_API_KEY is required for Atlas WS".to_string()
+/// Delay before the catch-up crawl starts, giving the live subscription time to connect
+/// so that no transaction falls between the crawled tip and the first streamed one
+const CATCH_UP_START_DELAY: Duration = Duration::from_secs(5);
+
+/// Creates and configures the indexer pipeline based on the provided configuration.
+/// The live checkpoint only advances once `caught_up` is set; slot gaps detected on the
+/// live stream are reported on `gap_sender`, its progress on `health`.
+pub async fn create_pipeline(
+    config: &Config,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+    caught_up: Arc<AtomicBool>,
+    gap_sender: UnboundedSender<SlotGap>,
+    health: Arc<HealthState>,
+) -> CarbonResult<Pipeline> {
+    let program_ids = config.program_ids();
+    let mut builder = telemetry::register(Pipeline::builder(), &config.metrics);
+    builder = dead_letter::register(builder, &config.dead_letter, store.clone())
+        .metrics(health.clone())
+        .metrics_flush_interval(3)
+        .shutdown_strategy(carbon_core::pipeline::ShutdownStrategy::ProcessPending);
+
+    // Shared by both processors, so Pair account updates fill the pricer's token cache
+    let pricer = pricing::create_swap_pricer(config);
+
+    if config.processors.events {
+        // Require Helius API key for transaction monitoring
+        let api_key = config.helius.api_key.as_ref()
+            .ok_or_else(|| carbon_core::error::Error::Custom(
+                "HELIUS_API_KEY is required for Atlas WS".to_string()
+            ))?;
+        let cluster = config.cluster.helius_cluster().ok_or_else(|| carbon_core::error::Error::Custom(
+            format!("Helius Atlas WS does not serve {}", config.cluster)
         ))?;
 
-    log::info!("Using Helius Atlas WebSocket for realtime transaction monitoring");
+        log::info!("Using Helius Atlas WebSocket for realtime transaction monitoring");
+
+        // Create Atlas WebSocket datasource
+        let atlas_datasource = create_helius_datasource(api_key, &program_ids, &config.helius, cluster)
+            .with_gap_sender(gap_sender);
+
+        // Resolve event timestamps from on-chain block time (Atlas WS does not carry it)
+        let block_times = Arc::new(BlockTimeResolver::new(config.http_rpc_url.clone()));
+
+        // Create instruction processor; each transaction is written atomically once processed
+        let writer = Arc::new(EventWriter::new(store.clone(), WriteMode::PerTransaction));
+        let instruction_processor = OmnipairInstructionProcessor::new(block_times, notifier, writer.clone())
+            .with_programs(program_ids.iter().copied())
+            .with_pricing(pricer.clone());
+
+        let live_datasource_id = DatasourceId::new_named(LIVE_DATASOURCE);
+        builder = builder
+            .datasource_with_id(atlas_datasource, live_datasource_id.clone())
+            .instruction(OmnipairDecoder, instruction_processor)
+            .instruction_with_filters(
+                OmnipairDecoder,
+                CheckpointProcessor::new(writer, LIVE_DATASOURCE, caught_up).with_health(health),
+                vec![Box::new(DatasourceFilter::new(live_datasource_id))],
+            );
+    }
+
+    // Account monitoring: program account updates feed the latest account state tables.
+    // Program subscribe only reports changes, so existing accounts are seeded with a GPA snapshot.
+    if config.processors.accounts {
+        log::info!("Using RPC program subscribe for account monitoring");
+        let subscribe = &config.program_subscribe;
+        for (program_id, data_size) in config.program_subscriptions() {
+            builder = builder.datasource(create_account_datasource(subscribe.ws_url.clone(), program_id, data_size));
+
+            if subscribe.seed_snapshot {
+                builder = builder.datasource(GpaBackfillDatasource {
+                    rpc_url: config.http_rpc_url.clone(),
+                    program_id,
+                    config: data_size.map(|size| RpcProgramAccountsConfig {
+                        filters: Some(vec![RpcFilterType::DataSize(size)]),
+                        account_config: RpcAccountInfoConfig {
+                            encoding: Some(UiAccountEncoding::Base64),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+                });
+            }
+        }
 
-    // Create Atlas WebSocket datasource
-    let atlas_datasource = create_helius_datasource(api_key, *OMNIPAIR_PROGRAM_ID);
+        builder = builder.account(OmnipairDecoder, OmnipairAccountProcessor::new(store).with_pricing(pricer));
+    }
 
-    // Create transaction crawler datasource (more efficient than block crawler)
-    let _transaction_crawler_datasource = create_transaction_crawler_datasource(
-        config.http_rpc_url.clone(),
-        *OMNIPAIR_PROGRAM_ID,
-        Some(config.start_block)
-    ).await?;
+    let pipeline = builder.build()?;
 
-    // Create instruction processor
-    let instruction_processor = OmnipairInstructionProcessor::new();
+    if config.processors.events {
+        log::info!("Pipeline configured: realtime transactions via Helius Atlas WebSocket (TransactionUpdate)");
+    }
+
+    Ok(pipeline)
+}
+
+/// Creates the catch-up pipeline crawling every program from the live checkpoint (or its
+/// start block when no checkpoint exists yet) up to the chain tip. Returns `None` when
+/// there is no bound to catch up from.
+pub async fn create_catch_up_pipeline(
+    config: &Config,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+) -> CarbonResult<Option<Pipeline>> {
+    let checkpoint = store.get_ingestion_checkpoint(LIVE_DATASOURCE).await?;
+    if let Some((slot, signature)) = &checkpoint {
+        log::info!("Catching up from checkpoint at slot {} ({})", slot, signature);
+    }
 
-    // Build the pipeline
-    let pipeline = Pipeline::builder()
-        //.datasource(_transaction_crawler_datasource)
-        .datasource(atlas_datasource)
-        .metrics(Arc::new(LogMetrics::new()))
+    let mut crawlers = Vec::new();
+    for program in &config.programs {
+        let until_signature = match &checkpoint {
+            // The checkpoint transaction belongs to one of the programs; with several, each
+            // crawl is bounded by its own newest signature at or before the checkpoint slot
+            Some((_, signature)) if config.programs.len() == 1 => {
+                let signature = solana_signature::Signature::from_str(signature).map_err(|e| {
+                    carbon_core::error::Error::Custom(format!("Invalid checkpoint signature {}: {}", signature, e))
+                })?;
+                Some(signature)
+            }
+            Some((slot, _)) => find_signature_at_slot(&config.http_rpc_url, program.program_id, *slot as u64).await,
+            None => find_start_block_signature(&config.http_rpc_url, program.program_id, program.start_block).await,
+        };
+
+        match until_signature {
+            Some(until_signature) => crawlers.push(create_transaction_crawler_datasource(
+                config.http_rpc_url.clone(),
+                program.program_id,
+                until_signature,
+            )),
+            None => log::info!("No catch-up bound for program {}, skipping it", program.name),
+        }
+    }
+
+    if crawlers.is_empty() {
+        log::info!("No ingestion checkpoint, skipping catch-up (use the backfill command to seed history)");
+        return Ok(None);
+    }
+
+    let block_times = Arc::new(BlockTimeResolver::new(config.http_rpc_url.clone()));
+    let writer = Arc::new(EventWriter::new(store.clone(), WriteMode::PerTransaction));
+    let instruction_processor = OmnipairInstructionProcessor::new(block_times, notifier, writer.clone())
+        .with_programs(config.program_ids())
+        .with_pricing(pricing::create_swap_pricer(config));
+
+    let mut builder = dead_letter::register(Pipeline::builder(), &config.dead_letter, store);
+    for crawler in crawlers {
+        builder = builder.datasource_with_id(crawler, DatasourceId::new_named(CATCH_UP_DATASOURCE));
+    }
+
+    let pipeline = telemetry::register(builder, &config.metrics)
         .metrics_flush_interval(3)
         .instruction(OmnipairDecoder, instruction_processor)
+        .instruction(OmnipairDecoder, CheckpointProcessor::without_checkpoint(writer, CATCH_UP_DATASOURCE))
         .shutdown_strategy(carbon_core::pipeline::ShutdownStrategy::ProcessPending)
         .build()?;
-    
-    log::info!("Pipeline configured: historical transactions via RPC Transaction Crawler (TransactionUpdate)");
 
-    Ok(pipeline)
+    Ok(Some(pipeline))
+}
+
+/// Creates the pipeline of the `replay-failed` command. It has no datasource: dead letters
+/// are passed to `Pipeline::process` one by one, and the same processors as the live
+/// pipeline handle both transactions and account updates.
+pub fn create_replay_pipeline(
+    config: &Config,
+    store: Arc<dyn EventStore>,
+    notifier: Arc<Notifier>,
+) -> CarbonResult<Pipeline> {
+    let block_times = Arc::new(BlockTimeResolver::new(config.http_rpc_url.clone()));
+    let writer = Arc::new(EventWriter::new(store.clone(), WriteMode::PerTransaction));
+    let pricer = pricing::create_swap_pricer(config);
+    let instruction_processor = OmnipairInstructionProcessor::new(block_times, notifier, writer.clone())
+        .with_programs(config.program_ids())
+        .with_pricing(pricer.clone());
+
+    telemetry::register(Pipeline::builder(), &config.metrics)
+        .instruction(OmnipairDecoder, instruction_processor)
+        .instruction(OmnipairDecoder, CheckpointProcessor::without_checkpoint(writer, REPLAY_DATASOURCE))
+        .account(OmnipairDecoder, OmnipairAccountProcessor::new(store).with_pricing(pricer))
+        .build()
+}
+
+/// Runs the catch-up pipeline to completion and marks the live stream as caught up
+pub async fn run_catch_up(mut pipeline: Pipeline, caught_up: Arc<AtomicBool>) {
+    tokio::time::sleep(CATCH_UP_START_DELAY).await;
+    log::info!("Starting catch-up crawl...");
+
+    match pipeline.run().await {
+        Ok(_) => {
+            log::info!("Catch-up completed, live checkpoint enabled");
+            caught_up.store(true, Ordering::Release);
+        }
+        Err(e) => {
+            log::error!("Catch-up failed, checkpoint stays at its previous position: {:?}", e);
+        }
+    }
 }
 
 /// Runs the indexer pipeline with graceful shutdown handling
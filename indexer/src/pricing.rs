@@ -0,0 +1,209 @@
+//! Decimal-aware swap prices and USD volume.
+//!
+//! Swaps are priced while they are processed, so each swap row is inserted with its
+//! `price`, `ema_price` and `volume_usd` already filled in. Prices are expressed in whole
+//! tokens (token0 in token1), scaled by the decimals of the pair's mints; the decimals are
+//! cached from the `Pair` account updates the account pipeline receives, and otherwise read
+//! once per pair from its confirmed `Pair` account.
+//!
+//! USD reference prices come from a `PriceSource`. `StaticPriceSource` serves fixed prices
+//! from the `[pricing]` configuration, for offline use and stablecoin pairs; swaps whose
+//! tokens have no USD price are written with a NULL `volume_usd`.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use carbon_core::{
+    account::AccountDecoder,
+    error::{CarbonResult, Error},
+};
+use carbon_omnipair_decoder::{
+    accounts::{pair::Pair, OmnipairAccount},
+    instructions::swap_event::SwapEvent,
+    OmnipairDecoder,
+};
+use chrono::{DateTime, Utc};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_commitment_config::CommitmentConfig;
+use solana_pubkey::Pubkey;
+
+use crate::{candles::PRICE_SCALE, config::Config};
+
+/// Decimal places of on-chain EMA prices (NAD)
+pub const NAD_DECIMALS: u32 = 9;
+
+/// Decimal places of stored USD volumes
+pub const USD_SCALE: i64 = 6;
+
+/// Mints and decimals of a pair, which never change once the pair is created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairTokens {
+    pub token0: Pubkey,
+    pub token1: Pubkey,
+    pub token0_decimals: u8,
+    pub token1_decimals: u8,
+}
+
+impl From<&Pair> for PairTokens {
+    fn from(pair: &Pair) -> Self {
+        Self {
+            token0: pair.token0,
+            token1: pair.token1,
+            token0_decimals: pair.token0_decimals,
+            token1_decimals: pair.token1_decimals,
+        }
+    }
+}
+
+/// USD reference prices of tokens
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Price in USD of one whole token of `mint` at `timestamp`, or `None` when unknown
+    async fn usd_price(&self, mint: &Pubkey, timestamp: DateTime<Utc>) -> CarbonResult<Option<BigDecimal>>;
+}
+
+/// Fixed USD prices per mint, whatever the time
+#[derive(Debug, Clone, Default)]
+pub struct StaticPriceSource {
+    prices: HashMap<Pubkey, BigDecimal>,
+}
+
+impl StaticPriceSource {
+    pub fn new(prices: HashMap<Pubkey, BigDecimal>) -> Self {
+        Self { prices }
+    }
+}
+
+#[async_trait]
+impl PriceSource for StaticPriceSource {
+    async fn usd_price(&self, mint: &Pubkey, _timestamp: DateTime<Utc>) -> CarbonResult<Option<BigDecimal>> {
+        Ok(self.prices.get(mint).cloned())
+    }
+}
+
+/// Price columns of a swap row
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SwapPricing {
+    /// Post-swap price of one whole token0 in token1; `None` when reserve0 is empty
+    pub price: Option<BigDecimal>,
+    /// EMA price of one whole token0 in token1, from the pair update of the same
+    /// transaction; `None` when the transaction carried none
+    pub ema_price: Option<BigDecimal>,
+    /// USD value of the input token, or of the output token when the input token has
+    /// no USD price
+    pub volume_usd: Option<BigDecimal>,
+}
+
+/// Price of one whole token0 in whole token1 from the raw ratio `amount1 / amount0`,
+/// truncated to `PRICE_SCALE` decimals
+pub fn scaled_price(amount1: u128, amount0: u128, tokens: &PairTokens) -> Option<BigDecimal> {
+    if amount0 == 0 {
+        return None;
+    }
+
+    let ten = BigInt::from(10u8);
+    let numerator = BigInt::from(amount1) * ten.pow(tokens.token0_decimals as u32 + PRICE_SCALE as u32);
+    let denominator = BigInt::from(amount0) * ten.pow(tokens.token1_decimals as u32);
+
+    Some(BigDecimal::new(numerator / denominator, PRICE_SCALE))
+}
+
+/// Post-swap spot price `reserve1 / reserve0` in whole tokens
+pub fn spot_price(reserve0: u64, reserve1: u64, tokens: &PairTokens) -> Option<BigDecimal> {
+    scaled_price(reserve1 as u128, reserve0 as u128, tokens)
+}
+
+/// On-chain EMA price of token0 (NAD-scaled raw ratio) in whole tokens
+pub fn ema_price(price0_ema: u64, tokens: &PairTokens) -> Option<BigDecimal> {
+    scaled_price(price0_ema as u128, 10u128.pow(NAD_DECIMALS), tokens)
+}
+
+/// USD value of `amount` raw units of a token, truncated to `USD_SCALE` decimals
+pub fn usd_value(amount: u64, decimals: u8, usd_price: &BigDecimal) -> BigDecimal {
+    (BigDecimal::new(BigInt::from(amount), decimals as i64) * usd_price).with_scale(USD_SCALE)
+}
+
+/// Prices swaps, resolving the decimals of each pair from its `Pair` account once
+pub struct SwapPricer {
+    rpc_client: RpcClient,
+    prices: Arc<dyn PriceSource>,
+    pairs: Mutex<HashMap<Pubkey, PairTokens>>,
+}
+
+impl SwapPricer {
+    pub fn new(rpc_url: String, prices: Arc<dyn PriceSource>) -> Self {
+        Self {
+            rpc_client: RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()),
+            prices,
+            pairs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records the tokens of a pair, so they are not fetched from RPC
+    pub fn remember_pair(&self, pair: Pubkey, tokens: PairTokens) {
+        self.pairs.lock().unwrap_or_else(|e| e.into_inner()).insert(pair, tokens);
+    }
+
+    /// Tokens of `pair`, from the cache or its `Pair` account
+    pub async fn pair_tokens(&self, pair: &Pubkey) -> CarbonResult<PairTokens> {
+        if let Some(tokens) = self.pairs.lock().unwrap_or_else(|e| e.into_inner()).get(pair) {
+            return Ok(tokens.clone());
+        }
+
+        let account = self
+            .rpc_client
+            .get_account(pair)
+            .await
+            .map_err(|e| Error::Custom(format!("Failed to fetch pair account {}: {}", pair, e)))?;
+
+        let tokens = match OmnipairDecoder.decode_account(&account).map(|decoded| decoded.data) {
+            Some(OmnipairAccount::Pair(pair_account)) => PairTokens::from(&pair_account),
+            _ => return Err(Error::Custom(format!("Account {} is not an Omnipair pair", pair))),
+        };
+
+        self.remember_pair(*pair, tokens.clone());
+        Ok(tokens)
+    }
+
+    /// Prices a swap at `timestamp`; `price0_ema` is the NAD-scaled EMA of the pair update
+    /// emitted before the swap in the same transaction
+    pub async fn price_swap(
+        &self,
+        swap: &SwapEvent,
+        timestamp: DateTime<Utc>,
+        price0_ema: Option<u64>,
+    ) -> CarbonResult<SwapPricing> {
+        let tokens = self.pair_tokens(&swap.metadata.pair).await?;
+
+        let (mint_in, decimals_in, mint_out, decimals_out) = if swap.is_token0_in {
+            (tokens.token0, tokens.token0_decimals, tokens.token1, tokens.token1_decimals)
+        } else {
+            (tokens.token1, tokens.token1_decimals, tokens.token0, tokens.token0_decimals)
+        };
+
+        let volume_usd = match self.prices.usd_price(&mint_in, timestamp).await? {
+            Some(usd_price) => Some(usd_value(swap.amount_in, decimals_in, &usd_price)),
+            None => self
+                .prices
+                .usd_price(&mint_out, timestamp)
+                .await?
+                .map(|usd_price| usd_value(swap.amount_out, decimals_out, &usd_price)),
+        };
+
+        Ok(SwapPricing {
+            price: spot_price(swap.reserve0, swap.reserve1, &tokens),
+            ema_price: price0_ema.and_then(|price0_ema| ema_price(price0_ema, &tokens)),
+            volume_usd,
+        })
+    }
+}
+
+/// Swap pricer with the USD prices of the configuration
+pub fn create_swap_pricer(config: &Config) -> Arc<SwapPricer> {
+    let prices = StaticPriceSource::new(config.pricing.usd_prices.clone());
+    Arc::new(SwapPricer::new(config.http_rpc_url.clone(), Arc::new(prices)))
+}
@@ -1,19 +1,53 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 use async_trait::async_trait;
 use carbon_core::{
+    account::{AccountMetadata, DecodedAccount},
     error::CarbonResult,
     metrics::MetricsCollection,
     processor::Processor,
     instruction::{DecodedInstruction, InstructionMetadata, NestedInstructions},
 };
+use carbon_omnipair_decoder::{accounts::OmnipairAccount, instructions::flashloan::Flashloan};
 use carbon_omnipair_decoder::instructions::OmnipairInstruction;
-use crate::database;
+use solana_pubkey::Pubkey;
+use crate::{
+    block_time::BlockTimeResolver,
+    event_context::EventContext,
+    event_writer::EventWriter,
+    flashloans::FlashloanRecord,
+    notifier::{self, Notifier},
+    pricing::{PairTokens, SwapPricer},
+    store::EventStore,
+};
 
-pub struct OmnipairInstructionProcessor;
+/// Decodes Omnipair events into rows staged on `writer`; they are written once the whole
+/// transaction is committed by `CheckpointProcessor`
+pub struct OmnipairInstructionProcessor {
+    block_times: Arc<BlockTimeResolver>,
+    notifier: Arc<Notifier>,
+    writer: Arc<EventWriter>,
+    /// Programs whose instructions are indexed; empty accepts every program
+    programs: HashSet<Pubkey>,
+    /// Fills the price columns of swaps; without it they are left NULL
+    pricer: Option<Arc<SwapPricer>>,
+}
 
 impl OmnipairInstructionProcessor {
-    pub fn new() -> Self {
-        Self
+    pub fn new(block_times: Arc<BlockTimeResolver>, notifier: Arc<Notifier>, writer: Arc<EventWriter>) -> Self {
+        Self { block_times, notifier, writer, programs: HashSet::new(), pricer: None }
+    }
+
+    /// Only indexes instructions of `programs`. The decoder does not check the program
+    /// ID, so instructions of other programs would otherwise be decoded as Omnipair ones.
+    pub fn with_programs(mut self, programs: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.programs = programs.into_iter().collect();
+        self
+    }
+
+    /// Prices swaps with `pricer`, so swap rows are inserted with their price and USD volume
+    pub fn with_pricing(mut self, pricer: Arc<SwapPricer>) -> Self {
+        self.pricer = Some(pricer);
+        self
     }
 }
 
@@ -28,58 +62,86 @@ impl Processor for OmnipairInstructionProcessor {
 
     async fn process(
         &mut self,
-        (metadata, instruction, _nested_instructions, _raw_instruction): Self::InputType,
+        (metadata, instruction, nested_instructions, raw_instruction): Self::InputType,
         _metrics: Arc<MetricsCollection>,
     ) -> CarbonResult<()> {
+        if !self.programs.is_empty() && !self.programs.contains(&raw_instruction.program_id) {
+            return Ok(());
+        }
+
         log::info!("Processing instruction: {:?}", instruction.data);
         
-        match instruction.data {
+        let result = match instruction.data {
             OmnipairInstruction::SwapEvent(swap_event) => {
-                self.process_swap_event(swap_event, &metadata).await?;
+                self.process_swap_event(swap_event, &metadata).await
             }
             OmnipairInstruction::AdjustCollateralEvent(event) => {
-                self.process_adjust_collateral_event(event, &metadata).await?;
+                self.process_adjust_collateral_event(event, &metadata).await
             }
             OmnipairInstruction::AdjustDebtEvent(event) => {
-                self.process_adjust_debt_event(event, &metadata).await?;
+                self.process_adjust_debt_event(event, &metadata).await
             }
             OmnipairInstruction::AdjustLiquidityEvent(event) => {
-                self.process_adjust_liquidity_event(event, &metadata).await?;
+                self.process_adjust_liquidity_event(event, &metadata).await
             }
             OmnipairInstruction::BurnEvent(event) => {
-                self.process_burn_event(event, &metadata).await?;
+                self.process_burn_event(event, &metadata).await
             }
             OmnipairInstruction::MintEvent(event) => {
-                self.process_mint_event(event, &metadata).await?;
+                self.process_mint_event(event, &metadata).await
             }
             OmnipairInstruction::PairCreatedEvent(event) => {
-                self.process_pair_created_event(event, &metadata).await?;
+                self.process_pair_created_event(event, &metadata).await
             }
             OmnipairInstruction::UpdatePairEvent(event) => {
-                self.process_update_pair_event(event, &metadata).await?;
+                self.process_update_pair_event(event, &metadata).await
             }
             OmnipairInstruction::UserPositionCreatedEvent(event) => {
-                self.process_user_position_created_event(event, &metadata).await?;
+                self.process_user_position_created_event(event, &metadata).await
             }
             OmnipairInstruction::UserPositionLiquidatedEvent(event) => {
-                self.process_user_position_liquidated_event(event, &metadata).await?;
+                self.process_user_position_liquidated_event(event, &metadata).await
             }
             OmnipairInstruction::UserPositionUpdatedEvent(event) => {
-                self.process_user_position_updated_event(event, &metadata).await?;
+                self.process_user_position_updated_event(event, &metadata).await
             }
             OmnipairInstruction::UserLiquidityPositionUpdatedEvent(event) => {
-                self.process_user_liquidity_position_updated_event(event, &metadata).await?;
+                self.process_user_liquidity_position_updated_event(event, &metadata).await
+            }
+            OmnipairInstruction::ClaimProtocolFeesEvent(event) => {
+                self.process_claim_protocol_fees_event(event, &metadata).await
+            }
+            OmnipairInstruction::Flashloan(flashloan) => {
+                self.process_flashloan(flashloan, &instruction.accounts, &raw_instruction.program_id, &metadata, &nested_instructions).await
+            }
+            OmnipairInstruction::FlashloanEvent(event) => {
+                // Persisted together with its Flashloan instruction, which carries the receiver program
+                log::debug!("FlashloanEvent recorded with Flashloan instruction - Pair: {}", event.metadata.pair);
+                Ok(())
             }
             _ => {
                 log::debug!("Unhandled instruction type: {:?}", instruction.data);
+                Ok(())
             }
+        };
+
+        // The transaction is not committed, so its rows staged so far must not be written
+        // with a later attempt
+        if result.is_err() {
+            self.writer.discard(&metadata.transaction_metadata.signature.to_string());
         }
 
-        Ok(())
+        result
     }
 }
 
 impl OmnipairInstructionProcessor {
+    /// Builds the identity and block timestamp shared by all rows written for this instruction
+    async fn event_context(&self, metadata: &InstructionMetadata) -> CarbonResult<EventContext> {
+        let timestamp = self.block_times.event_timestamp(&metadata.transaction_metadata).await?;
+        EventContext::new(metadata, timestamp)
+    }
+
     async fn process_swap_event(
         &self, 
         swap_event: carbon_omnipair_decoder::instructions::swap_event::SwapEvent,
@@ -90,19 +152,61 @@ impl OmnipairInstructionProcessor {
             swap_event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        if let Err(e) = database::upsert_swap_event(&swap_event, &tx_signature, slot).await {
-            log::error!("Failed to insert swap event: {}", e);
-            return Err(e);
-        }
+        let payload = serde_json::json!({
+            "pair": swap_event.metadata.pair.to_string(),
+            "user": swap_event.metadata.signer.to_string(),
+            "is_token0_in": swap_event.is_token0_in,
+            "amount_in": swap_event.amount_in.to_string(),
+            "amount_out": swap_event.amount_out.to_string(),
+            "reserve0": swap_event.reserve0.to_string(),
+            "reserve1": swap_event.reserve1.to_string()
+        });
+
+        let pricing = match &self.pricer {
+            Some(pricer) => {
+                // The swap instruction updates the pair before swapping, so the EMA in effect
+                // is the one of the latest update of the pair staged for this transaction
+                let price0_ema = self.writer.staged(&ctx, |batch| {
+                    batch
+                        .pair_updates
+                        .iter()
+                        .rev()
+                        .find(|(update, _)| update.metadata.pair == swap_event.metadata.pair)
+                        .map(|(update, _)| update.price0_ema)
+                });
+                // A swap that cannot be priced is still indexed, with NULL price columns
+                match pricer.price_swap(&swap_event, ctx.timestamp, price0_ema).await {
+                    Ok(pricing) => Some(pricing),
+                    Err(e) => {
+                        log::warn!(
+                            "Failed to price swap {} of pair {}, storing it without price: {}",
+                            ctx.signature,
+                            swap_event.metadata.pair,
+                            e
+                        );
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let notifications = self.notifier.outbox_entries(notifier::SWAP, &ctx, payload);
+        self.writer.stage(&ctx, |batch| {
+            batch.swaps.push((swap_event.clone(), ctx.clone()));
+            if let Some(pricing) = pricing {
+                batch.swap_pricing.push((pricing, ctx.clone()));
+            }
+            batch.notifications.extend(notifications);
+        });
 
         log::info!(
             "Successfully processed SwapEvent - Pair: {}, User: {}, TxSig: {}", 
             swap_event.metadata.pair, 
             swap_event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -118,13 +222,9 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        if let Err(e) = database::upsert_adjust_collateral_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to insert adjust collateral event: {}", e);
-            return Err(e);
-        }
+        self.writer.stage(&ctx, |batch| batch.collateral_adjustments.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed AdjustCollateralEvent - Amount0: {}, Amount1: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -132,7 +232,7 @@ impl OmnipairInstructionProcessor {
             event.amount1,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -148,13 +248,9 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        if let Err(e) = database::upsert_adjust_debt_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to insert adjust debt event: {}", e);
-            return Err(e);
-        }
+        self.writer.stage(&ctx, |batch| batch.debt_adjustments.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed AdjustDebtEvent - Amount0: {}, Amount1: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -162,7 +258,7 @@ impl OmnipairInstructionProcessor {
             event.amount1,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -203,14 +299,10 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        // Save to adjust_liquidity table with event_type = "remove"
-        if let Err(e) = database::upsert_burn_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to save burn event to database: {}", e);
-            return Err(e);
-        }
+        // Saved to adjust_liquidity table with event_type = "remove"
+        self.writer.stage(&ctx, |batch| batch.burns.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed BurnEvent - Amount0: {}, Amount1: {}, Liquidity: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -219,7 +311,7 @@ impl OmnipairInstructionProcessor {
             event.liquidity,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -235,14 +327,10 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        // Save to adjust_liquidity table with event_type = "add"
-        if let Err(e) = database::upsert_mint_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to save mint event to database: {}", e);
-            return Err(e);
-        }
+        // Saved to adjust_liquidity table with event_type = "add"
+        self.writer.stage(&ctx, |batch| batch.mints.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed MintEvent - Amount0: {}, Amount1: {}, Liquidity: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -251,7 +339,7 @@ impl OmnipairInstructionProcessor {
             event.liquidity,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -267,40 +355,20 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        if let Err(e) = database::upsert_pair_created_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to insert pair created event: {}", e);
-            return Err(e);
-        }
-
-        // Send callback
-        let webhook_url = "https://post-pools-bot-production.up.railway.app/webhook";
-        let webhook_payload = serde_json::json!({
+        // Queue notifications; delivery happens off the processing path
+        let payload = serde_json::json!({
             "pair": event.metadata.pair.to_string(),
             "token0": event.token0.to_string(),
             "token1": event.token1.to_string()
         });
         
-        match reqwest::Client::new()
-            .post(webhook_url)
-            .header("Content-Type", "application/json")
-            .json(&webhook_payload)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                if response.status().is_success() {
-                    log::info!("Successfully sent webhook notification for pair: {}", event.metadata.pair);
-                } else {
-                    log::warn!("Webhook request failed with status: {} for pair: {}", response.status(), event.metadata.pair);
-                }
-            }
-            Err(e) => {
-                log::error!("Failed to send webhook notification for pair {}: {}", event.metadata.pair, e);
-            }
-        }
+        let notifications = self.notifier.outbox_entries(notifier::PAIR_CREATED, &ctx, payload);
+        self.writer.stage(&ctx, |batch| {
+            batch.pairs_created.push((event.clone(), ctx.clone()));
+            batch.notifications.extend(notifications);
+        });
         
         log::info!(
             "Successfully processed PairCreatedEvent - Token0: {}, Token1: {}, Pair: {}, User: {}, Lp Mint: {}, Rate Model: {}, Swap Fee Bps: {}, Half Life: {}, Fixed Cf Bps: {:?}, Params Hash: {:?}, Version: {}, TxSig: {}", 
@@ -315,7 +383,7 @@ impl OmnipairInstructionProcessor {
             event.fixed_cf_bps,
             event.params_hash,
             event.version,
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -331,7 +399,9 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
+        let ctx = self.event_context(metadata).await?;
+        
+        self.writer.stage(&ctx, |batch| batch.pair_updates.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed UpdatePairEvent - Price0 EMA: {}, Price1 EMA: {}, Rate0: {}, Rate1: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -341,7 +411,7 @@ impl OmnipairInstructionProcessor {
             event.rate1,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -357,14 +427,16 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
+        let ctx = self.event_context(metadata).await?;
+        
+        self.writer.stage(&ctx, |batch| batch.user_positions_created.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed UserPositionCreatedEvent - Position: {}, Pair: {}, User: {}, TxSig: {}", 
             event.position,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -380,13 +452,24 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        if let Err(e) = database::upsert_user_position_liquidated_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to insert user position liquidated event: {}", e);
-            return Err(e);
-        }
+        let payload = serde_json::json!({
+            "pair": event.metadata.pair.to_string(),
+            "user": event.metadata.signer.to_string(),
+            "position": event.position.to_string(),
+            "liquidator": event.liquidator.to_string(),
+            "collateral0_liquidated": event.collateral0_liquidated.to_string(),
+            "collateral1_liquidated": event.collateral1_liquidated.to_string(),
+            "debt0_liquidated": event.debt0_liquidated.to_string(),
+            "debt1_liquidated": event.debt1_liquidated.to_string()
+        });
+        
+        let notifications = self.notifier.outbox_entries(notifier::USER_POSITION_LIQUIDATED, &ctx, payload);
+        self.writer.stage(&ctx, |batch| {
+            batch.user_position_liquidations.push((event.clone(), ctx.clone()));
+            batch.notifications.extend(notifications);
+        });
         
         log::info!(
             "Successfully processed UserPositionLiquidatedEvent - Position: {}, Liquidator: {}, Collateral0 Liquidated: {}, Collateral1 Liquidated: {}, Debt0 Liquidated: {}, Debt1 Liquidated: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -398,7 +481,7 @@ impl OmnipairInstructionProcessor {
             event.debt1_liquidated,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -414,13 +497,9 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        if let Err(e) = database::upsert_user_position_updated_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to insert user position updated event: {}", e);
-            return Err(e);
-        }
+        self.writer.stage(&ctx, |batch| batch.user_position_updates.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed UserPositionUpdatedEvent - Position: {}, Collateral0: {}, Collateral1: {}, Debt0 Shares: {}, Debt1 Shares: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -431,7 +510,7 @@ impl OmnipairInstructionProcessor {
             event.debt1_shares,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
         );
         
         Ok(())
@@ -447,13 +526,9 @@ impl OmnipairInstructionProcessor {
             event,
         );
         
-        let tx_signature = metadata.transaction_metadata.signature.to_string();
-        let slot = metadata.transaction_metadata.slot as i64;
+        let ctx = self.event_context(metadata).await?;
         
-        if let Err(e) = database::upsert_user_liquidity_position_updated_event(&event, &tx_signature, slot).await {
-            log::error!("Failed to insert user liquidity position updated event: {}", e);
-            return Err(e);
-        }
+        self.writer.stage(&ctx, |batch| batch.user_liquidity_position_updates.push((event.clone(), ctx.clone())));
         
         log::info!(
             "Successfully processed UserLiquidityPositionUpdatedEvent - Token0 Amount: {}, Token1 Amount: {}, LP Amount: {}, Token0 Mint: {}, Token1 Mint: {}, LP Mint: {}, Pair: {}, User: {}, TxSig: {}", 
@@ -465,10 +540,144 @@ impl OmnipairInstructionProcessor {
             event.lp_mint,
             event.metadata.pair, 
             event.metadata.signer, 
-            tx_signature
+            ctx.signature
+        );
+        
+        Ok(())
+    }
+
+    async fn process_flashloan(
+        &self,
+        flashloan: Flashloan,
+        accounts: &[solana_instruction::AccountMeta],
+        program_id: &Pubkey,
+        metadata: &InstructionMetadata,
+        nested_instructions: &NestedInstructions,
+    ) -> CarbonResult<()> {
+        log::info!(
+            "Flashloan processed - Details: {:#?}",
+            flashloan,
+        );
+        
+        let ctx = self.event_context(metadata).await?;
+        let record = FlashloanRecord::from_instruction(&flashloan, accounts, program_id, nested_instructions)?;
+        
+        self.writer.stage(&ctx, |batch| batch.flashloans.push((record.clone(), ctx.clone())));
+        
+        log::info!(
+            "Successfully processed Flashloan - Amount0: {}, Amount1: {}, Fee0: {:?}, Fee1: {:?}, Receiver: {}, Receiver Program: {}, Receiver Instructions: {}, Pair: {}, User: {}, TxSig: {}", 
+            record.amount0,
+            record.amount1,
+            record.fee0,
+            record.fee1,
+            record.receiver,
+            record.receiver_program,
+            record.receiver_instructions.len(),
+            record.pair, 
+            record.signer, 
+            ctx.signature
+        );
+        
+        Ok(())
+    }
+
+    async fn process_claim_protocol_fees_event(
+        &self,
+        event: carbon_omnipair_decoder::instructions::claim_protocol_fees_event::ClaimProtocolFeesEvent,
+        metadata: &InstructionMetadata,
+    ) -> CarbonResult<()> {
+        log::info!(
+            "ClaimProtocolFeesEvent processed - Details: {:#?}",
+            event,
+        );
+        
+        let ctx = self.event_context(metadata).await?;
+        
+        self.writer.stage(&ctx, |batch| batch.protocol_fee_claims.push((event.clone(), ctx.clone())));
+        
+        log::info!(
+            "Successfully processed ClaimProtocolFeesEvent - Futarchy Treasury: {}/{}, Buybacks Vault: {}/{}, Team Treasury: {}/{}, Pair: {}, User: {}, TxSig: {}", 
+            event.futarchy_treasury_amount0,
+            event.futarchy_treasury_amount1,
+            event.buybacks_vault_amount0,
+            event.buybacks_vault_amount1,
+            event.team_treasury_amount0,
+            event.team_treasury_amount1,
+            event.metadata.pair, 
+            event.metadata.signer, 
+            ctx.signature
         );
         
         Ok(())
     }
 
 }
+
+/// Persists the latest decoded state of Omnipair program accounts
+pub struct OmnipairAccountProcessor {
+    store: Arc<dyn EventStore>,
+    /// Pricer whose token cache is filled from `Pair` accounts
+    pricer: Option<Arc<SwapPricer>>,
+}
+
+impl OmnipairAccountProcessor {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self { store, pricer: None }
+    }
+
+    /// Remembers the tokens of every `Pair` account on `pricer`, so swaps of known pairs
+    /// are priced without fetching the pair from RPC
+    pub fn with_pricing(mut self, pricer: Arc<SwapPricer>) -> Self {
+        self.pricer = Some(pricer);
+        self
+    }
+}
+
+#[async_trait]
+impl Processor for OmnipairAccountProcessor {
+    type InputType = (
+        AccountMetadata,
+        DecodedAccount<OmnipairAccount>,
+        solana_account::Account,
+    );
+
+    async fn process(
+        &mut self,
+        (metadata, account, _raw_account): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        let slot = metadata.slot as i64;
+
+        let (account_type, result) = match &account.data {
+            OmnipairAccount::Pair(pair) => {
+                if let Some(pricer) = &self.pricer {
+                    pricer.remember_pair(metadata.pubkey, PairTokens::from(pair));
+                }
+                ("Pair", self.store.upsert_pair_account(&metadata.pubkey, pair, slot).await)
+            }
+            OmnipairAccount::UserPosition(position) => {
+                ("UserPosition", self.store.upsert_user_position_account(&metadata.pubkey, position, slot).await)
+            }
+            OmnipairAccount::RateModel(rate_model) => {
+                ("RateModel", self.store.upsert_rate_model_account(&metadata.pubkey, rate_model, slot).await)
+            }
+            OmnipairAccount::FutarchyAuthority(futarchy_authority) => {
+                ("FutarchyAuthority", self.store.upsert_futarchy_authority_account(&metadata.pubkey, futarchy_authority, slot).await)
+            }
+        };
+
+        if let Err(e) = result {
+            log::error!("Failed to upsert {} account {}: {}", account_type, metadata.pubkey, e);
+            return Err(e);
+        }
+
+        log::debug!(
+            "Successfully processed {} account - Pubkey: {}, Slot: {}",
+            account_type,
+            metadata.pubkey,
+            slot
+        );
+
+        Ok(())
+    }
+}
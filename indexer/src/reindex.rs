@@ -0,0 +1,348 @@
+//! On-demand reindexing.
+//!
+//! The `reindex` command re-fetches a list of transactions from RPC (given on the command
+//! line, in a file, or every transaction of one pair within a slot range) and runs them
+//! through the regular `OmnipairInstructionProcessor`. Every event table is upserted on
+//! its idempotency key, so rows written by a buggy processor are overwritten in place.
+//!
+//! With `--dry-run` the same pipeline writes to an `InMemoryEventStore` instead of the
+//! database: the decoded instructions are printed, the staged rows are compared with the
+//! stored rows of the same transactions in a read-only transaction, and only new rows and
+//! the changed columns of existing ones are printed. Nothing is written.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use carbon_core::{
+    datasource::DatasourceId,
+    error::{CarbonResult, Error},
+    instruction::{DecodedInstruction, InstructionMetadata, NestedInstructions},
+    metrics::MetricsCollection,
+    pipeline::Pipeline,
+    processor::Processor,
+};
+use carbon_omnipair_decoder::{instructions::OmnipairInstruction, OmnipairDecoder};
+use clap::{ArgGroup, Args as ClapArgs};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config};
+use solana_commitment_config::CommitmentConfig;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+
+use crate::{
+    block_time::BlockTimeResolver,
+    checkpoints::{CheckpointProcessor, REINDEX_DATASOURCE},
+    config::Config,
+    database,
+    datasources::SignatureListDatasource,
+    dead_letter,
+    event_writer::{EventWriter, WriteMode},
+    interest,
+    notifier::{Notifier, NotifierConfig},
+    pricing,
+    processors::OmnipairInstructionProcessor,
+    row_diff::{self, RowChange, Values},
+    store::{EventStore, InMemoryEventStore, InMemoryTables},
+    telemetry,
+};
+
+/// Signatures fetched per `getSignaturesForAddress` page
+const SIGNATURE_PAGE_LIMIT: usize = 1000;
+
+/// Transactions selected by the `reindex` subcommand. Signatures given directly, in a file
+/// and from a pair's slot range are combined.
+#[derive(ClapArgs, Debug, Clone, Default)]
+#[command(group(
+    ArgGroup::new("transactions")
+        .required(true)
+        .multiple(true)
+        .args(["signatures", "signatures_file", "pair"])
+))]
+pub struct ReindexArgs {
+    /// Transaction signature to reindex, repeatable
+    #[arg(long = "signature")]
+    pub signatures: Vec<String>,
+
+    /// File with one transaction signature per line (blank lines and # comments are skipped)
+    #[arg(long)]
+    pub signatures_file: Option<PathBuf>,
+
+    /// Reindex every transaction referencing this pair between --start-slot and --end-slot
+    #[arg(long, requires_all = ["start_slot", "end_slot"])]
+    pub pair: Option<String>,
+
+    /// First slot of the pair range (inclusive)
+    #[arg(long, requires = "pair")]
+    pub start_slot: Option<u64>,
+
+    /// Last slot of the pair range (inclusive)
+    #[arg(long, requires = "pair")]
+    pub end_slot: Option<u64>,
+
+    /// Print the decoded instructions and the rows that would change, without writing
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+fn parse_signature(signature: &str) -> CarbonResult<Signature> {
+    Signature::from_str(signature).map_err(|e| Error::Custom(format!("Invalid signature '{}': {}", signature, e)))
+}
+
+/// Signatures of a signatures file, in file order
+pub fn read_signatures_file(path: &Path) -> CarbonResult<Vec<Signature>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::Custom(format!("Failed to read {}: {}", path.display(), e)))?;
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_signature)
+        .collect()
+}
+
+/// Resolves the selected transactions to a list of signatures, each listed once: given
+/// signatures first, then the file, then the pair range oldest first
+pub async fn collect_signatures(http_rpc_url: &str, args: &ReindexArgs) -> CarbonResult<Vec<Signature>> {
+    let mut signatures = args
+        .signatures
+        .iter()
+        .map(|signature| parse_signature(signature))
+        .collect::<CarbonResult<Vec<_>>>()?;
+
+    if let Some(path) = &args.signatures_file {
+        signatures.extend(read_signatures_file(path)?);
+    }
+
+    if let (Some(pair), Some(start_slot), Some(end_slot)) = (&args.pair, args.start_slot, args.end_slot) {
+        if start_slot > end_slot {
+            return Err(Error::Custom(format!("--start-slot {} is after --end-slot {}", start_slot, end_slot)));
+        }
+        let pair = Pubkey::from_str(pair).map_err(|e| Error::Custom(format!("Invalid pair '{}': {}", pair, e)))?;
+
+        let rpc_client = RpcClient::new(http_rpc_url.to_string());
+        let pair_signatures = collect_pair_signatures(&rpc_client, &pair, start_slot, end_slot).await?;
+        log::info!(
+            "Pair {} has {} successful transactions in slots {}..={}",
+            pair,
+            pair_signatures.len(),
+            start_slot,
+            end_slot
+        );
+        signatures.extend(pair_signatures);
+    }
+
+    let mut seen = HashSet::new();
+    signatures.retain(|signature| seen.insert(*signature));
+
+    Ok(signatures)
+}
+
+/// Walks the pair's signatures from the tip down to `start_slot` and returns the
+/// successful ones within the range, oldest first
+async fn collect_pair_signatures(
+    rpc_client: &RpcClient,
+    pair: &Pubkey,
+    start_slot: u64,
+    end_slot: u64,
+) -> CarbonResult<Vec<Signature>> {
+    let mut signatures = Vec::new();
+    let mut before: Option<Signature> = None;
+
+    loop {
+        let page = rpc_client
+            .get_signatures_for_address_with_config(
+                pair,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURE_PAGE_LIMIT),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await
+            .map_err(|e| Error::Custom(format!("Failed to fetch signatures: {}", e)))?;
+
+        let Some(last) = page.last() else {
+            break;
+        };
+
+        for sig_info in page.iter().filter(|s| s.slot >= start_slot && s.slot <= end_slot) {
+            if sig_info.err.is_none() {
+                signatures.push(parse_signature(&sig_info.signature)?);
+            }
+        }
+
+        if last.slot < start_slot {
+            break;
+        }
+
+        before = Some(parse_signature(&last.signature)?);
+    }
+
+    signatures.reverse();
+    Ok(signatures)
+}
+
+/// Prints every decoded Omnipair instruction of the indexed programs, for dry runs
+pub struct InstructionPrinter {
+    programs: HashSet<Pubkey>,
+}
+
+impl InstructionPrinter {
+    pub fn new(programs: impl IntoIterator<Item = Pubkey>) -> Self {
+        Self { programs: programs.into_iter().collect() }
+    }
+}
+
+#[async_trait]
+impl Processor for InstructionPrinter {
+    type InputType = (
+        InstructionMetadata,
+        DecodedInstruction<OmnipairInstruction>,
+        NestedInstructions,
+        solana_instruction::Instruction,
+    );
+
+    async fn process(
+        &mut self,
+        (metadata, instruction, _nested_instructions, raw_instruction): Self::InputType,
+        _metrics: Arc<MetricsCollection>,
+    ) -> CarbonResult<()> {
+        if !self.programs.is_empty() && !self.programs.contains(&raw_instruction.program_id) {
+            return Ok(());
+        }
+
+        println!(
+            "{} slot {} instruction {:?}:\n{:#?}",
+            metadata.transaction_metadata.signature,
+            metadata.transaction_metadata.slot,
+            metadata.absolute_path,
+            instruction.data
+        );
+
+        Ok(())
+    }
+}
+
+fn print_values(values: &Values, columns: &[(&str, &str)]) {
+    for ((column, _), value) in columns.iter().zip(values) {
+        println!("  {} = {}", column, value.as_deref().unwrap_or("NULL"));
+    }
+}
+
+/// Prints the staged rows of a dry run that are new or differ from the stored ones, per
+/// table, and returns how many rows would be inserted and updated. The stored rows are
+/// read in a read-only transaction.
+pub async fn print_changes(tables: &InMemoryTables) -> CarbonResult<(usize, usize)> {
+    let pool = database::get_db_pool()?;
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to begin read-only transaction: {}", e)))?;
+    sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut tx)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to begin read-only transaction: {}", e)))?;
+
+    let interest_share_bps = interest::protocol_interest_share(&mut tx).await?;
+    let (mut inserts, mut updates) = (0, 0);
+
+    for table in row_diff::staged_tables(tables, interest_share_bps) {
+        let stored = row_diff::load_stored_rows(&mut tx, &table).await?;
+
+        for change in row_diff::changed_rows(&table, &stored) {
+            match change {
+                RowChange::Insert(row) => {
+                    inserts += 1;
+                    println!("insert {} {}:", table.name, row.key);
+                    print_values(&row.values, table.columns);
+                }
+                RowChange::Update(row, columns) => {
+                    updates += 1;
+                    println!("update {} {}:", table.name, row.key);
+                    for column in columns {
+                        println!(
+                            "  {}: {} -> {}",
+                            column.column,
+                            column.stored.as_deref().unwrap_or("NULL"),
+                            column.staged.as_deref().unwrap_or("NULL")
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    tx.rollback().await.map_err(|e| Error::Custom(format!("Failed to end read-only transaction: {}", e)))?;
+
+    Ok((inserts, updates))
+}
+
+/// Reindexes the selected transactions of the configured programs, or prints what would
+/// change with `--dry-run`. The database pool must be initialized; dry runs only read it.
+pub async fn run_reindex(config: &Config, args: &ReindexArgs) -> CarbonResult<()> {
+    let signatures = collect_signatures(&config.http_rpc_url, args).await?;
+    if signatures.is_empty() {
+        log::info!("No transactions to reindex");
+        return Ok(());
+    }
+    log::info!("Reindexing {} transactions", signatures.len());
+
+    let memory_store = Arc::new(InMemoryEventStore::new());
+    let store: Arc<dyn EventStore> = if args.dry_run {
+        memory_store.clone()
+    } else {
+        Arc::new(crate::store::PostgresEventStore)
+    };
+
+    // Reindexed events already triggered their webhooks when first indexed
+    let block_times = Arc::new(BlockTimeResolver::new(config.http_rpc_url.clone()));
+    let notifier = Arc::new(Notifier::new(NotifierConfig::default()));
+    let writer = Arc::new(EventWriter::new(store.clone(), WriteMode::PerTransaction));
+
+    let mut builder = Pipeline::builder().datasource_with_id(
+        SignatureListDatasource {
+            rpc_url: config.http_rpc_url.clone(),
+            signatures,
+        },
+        DatasourceId::new_named(REINDEX_DATASOURCE),
+    );
+    if args.dry_run {
+        builder = builder.instruction(OmnipairDecoder, InstructionPrinter::new(config.program_ids()));
+    } else {
+        builder = telemetry::register(dead_letter::register(builder, &config.dead_letter, store), &config.metrics);
+    }
+
+    let mut pipeline = builder
+        .instruction(
+            OmnipairDecoder,
+            OmnipairInstructionProcessor::new(block_times, notifier, writer.clone())
+                .with_programs(config.program_ids())
+                .with_pricing(pricing::create_swap_pricer(config)),
+        )
+        .instruction(OmnipairDecoder, CheckpointProcessor::without_checkpoint(writer, REINDEX_DATASOURCE))
+        .shutdown_strategy(carbon_core::pipeline::ShutdownStrategy::ProcessPending)
+        .build()?;
+
+    pipeline.run().await?;
+
+    if args.dry_run {
+        let tables = memory_store.snapshot();
+        let (inserts, updates) = print_changes(&tables).await?;
+        log::info!(
+            "Dry run: {} transactions with program instructions, {} rows to insert, {} to update, nothing was written",
+            tables.processed_transactions.len(),
+            inserts,
+            updates
+        );
+    } else {
+        log::info!("Reindex completed");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! Protocol revenue ledger.
+//!
+//! Every `ClaimProtocolFeesEvent` is stored as a claim plus one ledger entry per
+//! recipient, and every swap accrues its fee (`amount_in - amount_in_after_fee`) for the
+//! pair. The `pair_protocol_revenue` and `protocol_revenue_by_recipient` views report
+//! claimed vs. unclaimed revenue on top of these tables.
+
+use carbon_core::error::CarbonResult;
+use carbon_omnipair_decoder::instructions::{
+    claim_protocol_fees_event::ClaimProtocolFeesEvent,
+    swap_event::SwapEvent,
+};
+
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+
+use crate::{database::INSERT_CHUNK_ROWS, event_context::EventContext, fees};
+
+/// Protocol revenue recipients, matching the `revenue_recipient` enum
+const RECIPIENTS: [&str; 3] = ["futarchy_treasury", "buybacks_vault", "team_treasury"];
+
+/// Record protocol fee claims and their per-recipient ledger entries
+pub async fn record_protocol_fee_claims(
+    conn: &mut PgConnection,
+    claims: &[(ClaimProtocolFeesEvent, EventContext)],
+) -> CarbonResult<()> {
+    for (event, ctx) in claims {
+        record_protocol_fee_claim(conn, event, ctx).await?;
+    }
+
+    Ok(())
+}
+
+/// Record a protocol fee claim and its per-recipient ledger entries
+async fn record_protocol_fee_claim(
+    conn: &mut PgConnection,
+    event: &ClaimProtocolFeesEvent,
+    ctx: &EventContext,
+) -> CarbonResult<()> {
+    let upsert_result = sqlx::query(
+        r#"
+        INSERT INTO protocol_fee_claims (
+            pair, signer, token0, token1,
+            futarchy_treasury_amount0, futarchy_treasury_amount1,
+            buybacks_vault_amount0, buybacks_vault_amount1,
+            team_treasury_amount0, team_treasury_amount1,
+            transaction_signature, instruction_index, inner_instruction_index, slot, event_timestamp
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+            pair = EXCLUDED.pair,
+            signer = EXCLUDED.signer,
+            token0 = EXCLUDED.token0,
+            token1 = EXCLUDED.token1,
+            futarchy_treasury_amount0 = EXCLUDED.futarchy_treasury_amount0,
+            futarchy_treasury_amount1 = EXCLUDED.futarchy_treasury_amount1,
+            buybacks_vault_amount0 = EXCLUDED.buybacks_vault_amount0,
+            buybacks_vault_amount1 = EXCLUDED.buybacks_vault_amount1,
+            team_treasury_amount0 = EXCLUDED.team_treasury_amount0,
+            team_treasury_amount1 = EXCLUDED.team_treasury_amount1,
+            slot = EXCLUDED.slot,
+            event_timestamp = EXCLUDED.event_timestamp
+        "#
+    )
+    .bind(event.metadata.pair.to_string())
+    .bind(event.metadata.signer.to_string())
+    .bind(event.token0.to_string())
+    .bind(event.token1.to_string())
+    .bind(bigdecimal::BigDecimal::from(event.futarchy_treasury_amount0))
+    .bind(bigdecimal::BigDecimal::from(event.futarchy_treasury_amount1))
+    .bind(bigdecimal::BigDecimal::from(event.buybacks_vault_amount0))
+    .bind(bigdecimal::BigDecimal::from(event.buybacks_vault_amount1))
+    .bind(bigdecimal::BigDecimal::from(event.team_treasury_amount0))
+    .bind(bigdecimal::BigDecimal::from(event.team_treasury_amount1))
+    .bind(&ctx.signature)
+    .bind(ctx.instruction_index)
+    .bind(ctx.inner_instruction_index)
+    .bind(ctx.slot)
+    .bind(ctx.timestamp)
+    .execute(&mut *conn)
+    .await;
+
+    if let Err(e) = upsert_result {
+        log::error!("Failed to upsert into protocol_fee_claims table: {}", e);
+        return Err(carbon_core::error::Error::Custom(format!("Failed to upsert protocol fee claim: {}", e)));
+    }
+
+    let amounts = [
+        (event.futarchy_treasury_amount0, event.futarchy_treasury_amount1),
+        (event.buybacks_vault_amount0, event.buybacks_vault_amount1),
+        (event.team_treasury_amount0, event.team_treasury_amount1),
+    ];
+
+    for (recipient, (amount0, amount1)) in RECIPIENTS.iter().zip(amounts) {
+        let ledger_result = sqlx::query(
+            r#"
+            INSERT INTO protocol_revenue_ledger (
+                pair, recipient, token0, token1, amount0, amount1,
+                transaction_signature, instruction_index, inner_instruction_index, slot, event_timestamp
+            ) VALUES ($1, $2::revenue_recipient, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index, recipient) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                token0 = EXCLUDED.token0,
+                token1 = EXCLUDED.token1,
+                amount0 = EXCLUDED.amount0,
+                amount1 = EXCLUDED.amount1,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            "#
+        )
+        .bind(event.metadata.pair.to_string())
+        .bind(*recipient)
+        .bind(event.token0.to_string())
+        .bind(event.token1.to_string())
+        .bind(bigdecimal::BigDecimal::from(amount0))
+        .bind(bigdecimal::BigDecimal::from(amount1))
+        .bind(&ctx.signature)
+        .bind(ctx.instruction_index)
+        .bind(ctx.inner_instruction_index)
+        .bind(ctx.slot)
+        .bind(ctx.timestamp)
+        .execute(&mut *conn)
+        .await;
+
+        if let Err(e) = ledger_result {
+            log::error!("Failed to upsert into protocol_revenue_ledger table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to upsert protocol revenue ledger entry: {}", e)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Accrue the fee paid by each swap, in the swap's input token
+pub async fn accrue_swap_fees(
+    conn: &mut PgConnection,
+    swaps: &[(SwapEvent, EventContext)],
+) -> CarbonResult<()> {
+    for chunk in swaps.chunks(INSERT_CHUNK_ROWS) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO swap_fee_accruals (
+                pair, fee0, fee1, transaction_signature, instruction_index, inner_instruction_index, slot, event_timestamp
+            ) "#
+        );
+
+        query.push_values(chunk, |mut row, (swap_event, ctx)| {
+            let (fee0, fee1) = fees::fees_paid(swap_event);
+
+            row.push_bind(swap_event.metadata.pair.to_string())
+                .push_bind(bigdecimal::BigDecimal::from(fee0))
+                .push_bind(bigdecimal::BigDecimal::from(fee1))
+                .push_bind(ctx.signature.clone())
+                .push_bind(ctx.instruction_index)
+                .push_bind(ctx.inner_instruction_index)
+                .push_bind(ctx.slot)
+                .push_bind(ctx.timestamp);
+        });
+
+        query.push(
+            r#"
+            ON CONFLICT (transaction_signature, instruction_index, inner_instruction_index) DO UPDATE SET
+                pair = EXCLUDED.pair,
+                fee0 = EXCLUDED.fee0,
+                fee1 = EXCLUDED.fee1,
+                slot = EXCLUDED.slot,
+                event_timestamp = EXCLUDED.event_timestamp
+            "#
+        );
+
+        if let Err(e) = query.build().execute(&mut *conn).await {
+            log::error!("Failed to upsert into swap_fee_accruals table: {}", e);
+            return Err(carbon_core::error::Error::Custom(format!("Failed to accrue swap fees: {}", e)));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,615 @@
+//! Differences between the rows a reindex dry run would write and the stored ones.
+//!
+//! Every staged row is rendered as the text of the columns its upsert writes, keyed like
+//! the table's conflict target. The stored rows of the same keys are selected with the same
+//! columns cast to text, so a row is either new, changed (with the differing columns) or
+//! identical and left out. Numeric columns are compared by value and JSON columns by
+//! content, so `1.50` and `1.5` or differently spaced JSON do not count as changes.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
+use bigdecimal::BigDecimal;
+use carbon_core::error::{CarbonResult, Error};
+use sqlx::PgConnection;
+
+use crate::{
+    event_context::EventContext,
+    fees, interest,
+    store::{EventKey, InMemoryTables},
+};
+
+/// Column values as text, in the order of the table's `columns`; `None` is NULL
+pub type Values = Vec<Option<String>>;
+
+/// A row a dry run would write
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedRow {
+    /// Identity of the row, as rendered by the table's `key_sql`
+    pub key: String,
+    /// Value of the table's `filter_column`, which selects the stored rows
+    pub filter: String,
+    pub values: Values,
+}
+
+/// Rows a dry run would write to one table
+#[derive(Debug, Clone)]
+pub struct StagedTable {
+    /// Label the rows are printed with
+    pub name: &'static str,
+    pub table: &'static str,
+    /// Indexed column selecting the stored rows of the staged ones
+    pub filter_column: &'static str,
+    /// SQL expression rendering the identity of a stored row like `StagedRow::key`
+    pub key_sql: &'static str,
+    /// Compared columns: name and SQL expression of the stored value
+    pub columns: &'static [(&'static str, &'static str)],
+    pub rows: Vec<StagedRow>,
+}
+
+/// A staged row that differs from the stored rows
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowChange<'a> {
+    /// No row with this identity is stored
+    Insert(&'a StagedRow),
+    /// The stored row differs in these columns
+    Update(&'a StagedRow, Vec<ColumnChange>),
+}
+
+/// A column whose staged value differs from the stored one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnChange {
+    pub column: &'static str,
+    pub stored: Option<String>,
+    pub staged: Option<String>,
+}
+
+/// Identity of a stored event row, rendered like `event_key`
+fn event_key_sql(signature_column: &'static str) -> &'static str {
+    match signature_column {
+        "tx_sig" => "tx_sig || '#' || instruction_index || '.' || inner_instruction_index",
+        _ => "transaction_signature || '#' || instruction_index || '.' || inner_instruction_index",
+    }
+}
+
+/// Identity of an event row, as rendered by `event_key_sql`
+pub fn event_key(ctx: &EventContext) -> String {
+    format!("{}#{}.{}", ctx.signature, ctx.instruction_index, ctx.inner_instruction_index)
+}
+
+fn text(value: impl ToString) -> Option<String> {
+    Some(value.to_string())
+}
+
+fn event_rows<T>(
+    rows: &BTreeMap<EventKey, (T, EventContext)>,
+    values: impl Fn(&T, &EventContext) -> Values,
+) -> Vec<StagedRow> {
+    rows.values()
+        .map(|(row, ctx)| StagedRow { key: event_key(ctx), filter: ctx.signature.clone(), values: values(row, ctx) })
+        .collect()
+}
+
+fn event_table(
+    name: &'static str,
+    table: &'static str,
+    signature_column: &'static str,
+    columns: &'static [(&'static str, &'static str)],
+    rows: Vec<StagedRow>,
+) -> StagedTable {
+    StagedTable { name, table, filter_column: signature_column, key_sql: event_key_sql(signature_column), columns, rows }
+}
+
+// Compared columns of each table, as written by its upsert. Timestamps are compared in
+// epoch seconds, the resolution of block times.
+const SWAP_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("user_address", "user_address"),
+    ("is_token0_in", "is_token0_in"),
+    ("amount_in", "amount_in"),
+    ("amount_out", "amount_out"),
+    ("reserve0", "reserve0"),
+    ("reserve1", "reserve1"),
+    ("timestamp", r#"extract(epoch from "timestamp")"#),
+    ("slot", "slot"),
+    ("fee_paid0", "fee_paid0"),
+    ("fee_paid1", "fee_paid1"),
+    ("fee_value_out", "fee_value_out"),
+    ("price", "price"),
+    ("ema_price", "ema_price"),
+    ("volume_usd", "volume_usd"),
+];
+
+const LIQUIDITY_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("user_address", "user_address"),
+    ("amount0", "amount0"),
+    ("amount1", "amount1"),
+    ("liquidity", "liquidity"),
+    ("timestamp", r#"extract(epoch from "timestamp")"#),
+    ("event_type", "event_type"),
+    ("slot", "slot"),
+];
+
+const ADJUSTMENT_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("signer", "signer"),
+    ("amount0", "amount0"),
+    ("amount1", "amount1"),
+    ("slot", "slot"),
+    ("event_timestamp", "extract(epoch from event_timestamp)"),
+];
+
+const POSITION_UPDATE_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("signer", "signer"),
+    ("position", "position"),
+    ("collateral0", "collateral0"),
+    ("collateral1", "collateral1"),
+    ("debt0_shares", "debt0_shares"),
+    ("debt1_shares", "debt1_shares"),
+    ("collateral0_liquidation_cf_bps", "collateral0_liquidation_cf_bps"),
+    ("collateral1_liquidation_cf_bps", "collateral1_liquidation_cf_bps"),
+    ("collateral0_max_cf_bps", "collateral0_max_cf_bps"),
+    ("collateral1_max_cf_bps", "collateral1_max_cf_bps"),
+    ("slot", "slot"),
+    ("event_timestamp", "extract(epoch from event_timestamp)"),
+];
+
+const LIQUIDATION_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("signer", "signer"),
+    ("position", "position"),
+    ("liquidator", "liquidator"),
+    ("collateral0_liquidated", "collateral0_liquidated"),
+    ("collateral1_liquidated", "collateral1_liquidated"),
+    ("debt0_liquidated", "debt0_liquidated"),
+    ("debt1_liquidated", "debt1_liquidated"),
+    ("collateral_price", "collateral_price"),
+    ("shortfall", "shortfall"),
+    ("liquidation_bonus_applied", "liquidation_bonus_applied"),
+    ("k0", "k0"),
+    ("k1", "k1"),
+    ("slot", "slot"),
+    ("event_timestamp", "extract(epoch from event_timestamp)"),
+];
+
+const POSITION_CREATED_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("owner", "owner"),
+    ("position", "position"),
+    ("slot", "slot"),
+    ("event_timestamp", "extract(epoch from event_timestamp)"),
+];
+
+const LP_POSITION_COLUMNS: &[(&str, &str)] = &[
+    ("pair_address", "pair_address"),
+    ("lp_amount", "lp_amount"),
+    ("amount0", "amount0"),
+    ("amount1", "amount1"),
+    ("signer", "signer"),
+    ("timestamp", r#"extract(epoch from "timestamp")"#),
+    ("slot", "slot"),
+];
+
+const POOL_COLUMNS: &[(&str, &str)] = &[
+    ("token0", "token0"),
+    ("token1", "token1"),
+    ("lp_mint", "lp_mint"),
+    ("rate_model", "rate_model"),
+    ("swap_fee_bps", "swap_fee_bps"),
+    ("half_life", "half_life"),
+    ("fixed_cf_bps", "fixed_cf_bps"),
+    ("params_hash", "encode(params_hash, 'hex')"),
+    ("version", "version"),
+    ("target_util_start_bps", "target_util_start_bps"),
+    ("target_util_end_bps", "target_util_end_bps"),
+    ("rate_half_life_ms", "rate_half_life_ms"),
+    ("min_rate_bps", "min_rate_bps"),
+    ("max_rate_bps", "max_rate_bps"),
+    ("slot", "slot"),
+];
+
+const PAIR_UPDATE_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("signer", "signer"),
+    ("price0_ema", "price0_ema"),
+    ("price1_ema", "price1_ema"),
+    ("rate0", "rate0"),
+    ("rate1", "rate1"),
+    ("accrued_interest0", "accrued_interest0"),
+    ("accrued_interest1", "accrued_interest1"),
+    ("cash_reserve0", "cash_reserve0"),
+    ("cash_reserve1", "cash_reserve1"),
+    ("reserve0_after_interest", "reserve0_after_interest"),
+    ("reserve1_after_interest", "reserve1_after_interest"),
+    ("utilization0", "utilization0"),
+    ("utilization1", "utilization1"),
+    ("borrow_apr0", "borrow_apr0"),
+    ("borrow_apr1", "borrow_apr1"),
+    ("supply_apr0", "supply_apr0"),
+    ("supply_apr1", "supply_apr1"),
+    ("slot", "slot"),
+    ("timestamp", r#"extract(epoch from "timestamp")"#),
+];
+
+const FLASHLOAN_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("signer", "signer"),
+    ("receiver", "receiver"),
+    ("receiver_program", "receiver_program"),
+    ("amount0", "amount0"),
+    ("amount1", "amount1"),
+    ("fee0", "fee0"),
+    ("fee1", "fee1"),
+    ("receiver_instructions", "receiver_instructions"),
+    ("slot", "slot"),
+    ("event_timestamp", "extract(epoch from event_timestamp)"),
+];
+
+const FEE_CLAIM_COLUMNS: &[(&str, &str)] = &[
+    ("pair", "pair"),
+    ("signer", "signer"),
+    ("token0", "token0"),
+    ("token1", "token1"),
+    ("futarchy_treasury_amount0", "futarchy_treasury_amount0"),
+    ("futarchy_treasury_amount1", "futarchy_treasury_amount1"),
+    ("buybacks_vault_amount0", "buybacks_vault_amount0"),
+    ("buybacks_vault_amount1", "buybacks_vault_amount1"),
+    ("team_treasury_amount0", "team_treasury_amount0"),
+    ("team_treasury_amount1", "team_treasury_amount1"),
+    ("slot", "slot"),
+    ("event_timestamp", "extract(epoch from event_timestamp)"),
+];
+
+/// Renders the rows of `tables` as their upserts would write them, per table.
+/// `interest_share_bps` is the protocol interest share the supply APRs are derived with.
+pub fn staged_tables(tables: &InMemoryTables, interest_share_bps: Option<u16>) -> Vec<StagedTable> {
+    let swaps = event_rows(&tables.swaps, |swap, ctx| {
+        let (fee_paid0, fee_paid1) = fees::fees_paid(swap);
+        let pricing = tables
+            .swap_pricing
+            .get(&(ctx.signature.clone(), ctx.instruction_index, ctx.inner_instruction_index))
+            .map(|(pricing, _)| pricing.clone())
+            .unwrap_or_default();
+        vec![
+            text(swap.metadata.pair),
+            text(swap.metadata.signer),
+            text(swap.is_token0_in),
+            text(swap.amount_in),
+            text(swap.amount_out),
+            text(swap.reserve0),
+            text(swap.reserve1),
+            text(ctx.timestamp.timestamp()),
+            text(ctx.slot),
+            text(fee_paid0),
+            text(fee_paid1),
+            fees::fee_value_out(swap).map(|value| value.to_string()),
+            pricing.price.map(|price| price.to_string()),
+            pricing.ema_price.map(|price| price.to_string()),
+            pricing.volume_usd.map(|volume| volume.to_string()),
+        ]
+    });
+
+    let mut liquidity = event_rows(&tables.mints, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.amount0),
+            text(event.amount1),
+            text(event.liquidity),
+            text(ctx.timestamp.timestamp()),
+            text("add"),
+            text(event.metadata.slot),
+        ]
+    });
+    liquidity.extend(event_rows(&tables.burns, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.amount0),
+            text(event.amount1),
+            text(event.liquidity),
+            text(ctx.timestamp.timestamp()),
+            text("remove"),
+            text(event.metadata.slot),
+        ]
+    }));
+
+    let collateral_adjustments = event_rows(&tables.collateral_adjustments, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.amount0),
+            text(event.amount1),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    let debt_adjustments = event_rows(&tables.debt_adjustments, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.amount0),
+            text(event.amount1),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    let position_updates = event_rows(&tables.user_position_updates, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.position),
+            text(event.collateral0),
+            text(event.collateral1),
+            text(event.debt0_shares),
+            text(event.debt1_shares),
+            text(event.collateral0_liquidation_cf_bps),
+            text(event.collateral1_liquidation_cf_bps),
+            text(event.collateral0_max_cf_bps),
+            text(event.collateral1_max_cf_bps),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    let liquidations = event_rows(&tables.user_position_liquidations, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.position),
+            text(event.liquidator),
+            text(event.collateral0_liquidated),
+            text(event.collateral1_liquidated),
+            text(event.debt0_liquidated),
+            text(event.debt1_liquidated),
+            text(event.collateral_price),
+            text(event.shortfall),
+            text(event.liquidation_bonus_applied),
+            text(event.k0),
+            text(event.k1),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    let positions_created = event_rows(&tables.user_positions_created, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.position),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    let lp_positions = event_rows(&tables.user_liquidity_position_updates, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.lp_amount),
+            text(event.token0_amount),
+            text(event.token1_amount),
+            text(event.metadata.signer),
+            text(ctx.timestamp.timestamp()),
+            text(event.metadata.slot),
+        ]
+    });
+
+    // Pools are keyed on their pair: a pair is only created once
+    let pools = tables
+        .pairs_created
+        .values()
+        .map(|(event, ctx)| StagedRow {
+            key: event.metadata.pair.to_string(),
+            filter: event.metadata.pair.to_string(),
+            values: vec![
+                text(event.token0),
+                text(event.token1),
+                text(event.lp_mint),
+                text(event.rate_model),
+                text(event.swap_fee_bps),
+                text(event.half_life),
+                event.fixed_cf_bps.map(|bps| bps.to_string()),
+                text(hex::encode(event.params_hash)),
+                text(event.version),
+                text(event.target_util_start_bps),
+                text(event.target_util_end_bps),
+                text(event.rate_half_life_ms),
+                text(event.min_rate_bps),
+                text(event.max_rate_bps),
+                text(ctx.slot),
+            ],
+        })
+        .collect();
+
+    let pair_updates = event_rows(&tables.pair_updates, |event, ctx| {
+        let (rates0, rates1) = interest::pair_rates(event, interest_share_bps);
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.price0_ema),
+            text(event.price1_ema),
+            text(event.rate0),
+            text(event.rate1),
+            text(event.accrued_interest0),
+            text(event.accrued_interest1),
+            text(event.cash_reserve0),
+            text(event.cash_reserve1),
+            text(event.reserve0_after_interest),
+            text(event.reserve1_after_interest),
+            text(rates0.utilization),
+            text(rates1.utilization),
+            text(rates0.borrow_apr),
+            text(rates1.borrow_apr),
+            rates0.supply_apr.map(|apr| apr.to_string()),
+            rates1.supply_apr.map(|apr| apr.to_string()),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    let flashloans = event_rows(&tables.flashloans, |flashloan, ctx| {
+        vec![
+            text(flashloan.pair),
+            text(flashloan.signer),
+            text(flashloan.receiver),
+            text(flashloan.receiver_program),
+            text(flashloan.amount0),
+            text(flashloan.amount1),
+            flashloan.fee0.map(|fee| fee.to_string()),
+            flashloan.fee1.map(|fee| fee.to_string()),
+            serde_json::to_string(&flashloan.receiver_instructions).ok(),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    let fee_claims = event_rows(&tables.protocol_fee_claims, |event, ctx| {
+        vec![
+            text(event.metadata.pair),
+            text(event.metadata.signer),
+            text(event.token0),
+            text(event.token1),
+            text(event.futarchy_treasury_amount0),
+            text(event.futarchy_treasury_amount1),
+            text(event.buybacks_vault_amount0),
+            text(event.buybacks_vault_amount1),
+            text(event.team_treasury_amount0),
+            text(event.team_treasury_amount1),
+            text(ctx.slot),
+            text(ctx.timestamp.timestamp()),
+        ]
+    });
+
+    vec![
+        event_table("swaps", "swaps", "tx_sig", SWAP_COLUMNS, swaps),
+        event_table("adjust_liquidity", "adjust_liquidity", "tx_sig", LIQUIDITY_COLUMNS, liquidity),
+        event_table(
+            "adjust_collateral_events",
+            "adjust_collateral_events",
+            "transaction_signature",
+            ADJUSTMENT_COLUMNS,
+            collateral_adjustments,
+        ),
+        event_table("adjust_debt_events", "adjust_debt_events", "transaction_signature", ADJUSTMENT_COLUMNS, debt_adjustments),
+        event_table(
+            "user_position_updated_events",
+            "user_position_updated_events",
+            "transaction_signature",
+            POSITION_UPDATE_COLUMNS,
+            position_updates,
+        ),
+        event_table(
+            "user_position_liquidated_events",
+            "user_position_liquidated_events",
+            "transaction_signature",
+            LIQUIDATION_COLUMNS,
+            liquidations,
+        ),
+        event_table(
+            "user_position_created_events",
+            "user_position_created_events",
+            "transaction_signature",
+            POSITION_CREATED_COLUMNS,
+            positions_created,
+        ),
+        event_table(
+            "user_lp_position_updated_events",
+            "user_lp_position_updated_events",
+            "tx_sig",
+            LP_POSITION_COLUMNS,
+            lp_positions,
+        ),
+        StagedTable {
+            name: "pools",
+            table: "pools",
+            filter_column: "pair_address",
+            key_sql: "pair_address",
+            columns: POOL_COLUMNS,
+            rows: pools,
+        },
+        event_table("pair_state_updates", "pair_state_updates", "tx_sig", PAIR_UPDATE_COLUMNS, pair_updates),
+        event_table("flashloans", "flashloans", "transaction_signature", FLASHLOAN_COLUMNS, flashloans),
+        event_table("protocol_fee_claims", "protocol_fee_claims", "transaction_signature", FEE_CLAIM_COLUMNS, fee_claims),
+    ]
+}
+
+/// Whether a stored and a staged column value are the same: numbers by value, JSON by content
+pub fn same_value(stored: Option<&str>, staged: Option<&str>) -> bool {
+    let (stored, staged) = match (stored, staged) {
+        (Some(stored), Some(staged)) => (stored, staged),
+        (stored, staged) => return stored == staged,
+    };
+    if stored == staged {
+        return true;
+    }
+
+    if let (Ok(stored), Ok(staged)) = (BigDecimal::from_str(stored), BigDecimal::from_str(staged)) {
+        return stored == staged;
+    }
+
+    match (serde_json::from_str::<serde_json::Value>(stored), serde_json::from_str::<serde_json::Value>(staged)) {
+        (Ok(stored), Ok(staged)) => stored == staged,
+        _ => false,
+    }
+}
+
+/// Staged rows of `table` that are new or differ from the `stored` values of their key
+pub fn changed_rows<'a>(table: &'a StagedTable, stored: &HashMap<String, Values>) -> Vec<RowChange<'a>> {
+    table
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let Some(stored_values) = stored.get(&row.key) else {
+                return Some(RowChange::Insert(row));
+            };
+
+            let changes: Vec<ColumnChange> = table
+                .columns
+                .iter()
+                .zip(stored_values.iter().zip(&row.values))
+                .filter(|(_, (stored, staged))| !same_value(stored.as_deref(), staged.as_deref()))
+                .map(|((column, _), (stored, staged))| ColumnChange {
+                    column,
+                    stored: stored.clone(),
+                    staged: staged.clone(),
+                })
+                .collect();
+
+            (!changes.is_empty()).then_some(RowChange::Update(row, changes))
+        })
+        .collect()
+}
+
+/// Stored rows of the staged rows of `table`, by key. Only reads.
+pub async fn load_stored_rows(conn: &mut PgConnection, table: &StagedTable) -> CarbonResult<HashMap<String, Values>> {
+    if table.rows.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut filters: Vec<String> = table.rows.iter().map(|row| row.filter.clone()).collect();
+    filters.sort();
+    filters.dedup();
+
+    let columns: Vec<String> = table.columns.iter().map(|(_, sql)| format!("({})::text", sql)).collect();
+    let query = format!(
+        "SELECT ({})::text, ARRAY[{}]::text[] FROM {} WHERE {} = ANY($1)",
+        table.key_sql,
+        columns.join(", "),
+        table.table,
+        table.filter_column
+    );
+
+    let rows: Vec<(String, Values)> = sqlx::query_as(&query)
+        .bind(&filters)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to load stored rows of {}: {}", table.table, e)))?;
+
+    Ok(rows.into_iter().collect())
+}
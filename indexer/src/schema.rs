@@ -0,0 +1,59 @@
+//! Schema checks and the `migrate` subcommand, on top of the migrations embedded by
+//! `omnipair-migrations`.
+
+use carbon_core::error::CarbonResult;
+use clap::Args as ClapArgs;
+
+use crate::database::get_db_pool;
+
+/// Options of the `migrate` subcommand
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct MigrateArgs {
+    /// Only print the migration status, without applying anything
+    #[arg(long, conflicts_with = "baseline")]
+    pub status: bool,
+
+    /// Record migrations up to this version as applied without running them, for a schema
+    /// created with the SQL scripts
+    #[arg(long, value_name = "VERSION")]
+    pub baseline: Option<i64>,
+}
+
+fn schema_error(e: omnipair_migrations::SchemaError) -> carbon_core::error::Error {
+    carbon_core::error::Error::Custom(format!("Database schema: {}", e))
+}
+
+/// Refuses to run against a schema that is behind this build, unless `auto_migrate`
+/// applies the pending migrations first
+pub async fn ensure_schema(auto_migrate: bool) -> CarbonResult<()> {
+    let pool = get_db_pool()?;
+    omnipair_migrations::ensure_schema(pool, auto_migrate).await.map_err(schema_error)
+}
+
+/// Prints the migration status and, unless `--status` is given, applies pending
+/// migrations (or records the baseline)
+pub async fn run_migrate(args: &MigrateArgs) -> CarbonResult<()> {
+    let pool = get_db_pool()?;
+
+    if let Some(version) = args.baseline {
+        let recorded = omnipair_migrations::baseline(pool, version).await.map_err(schema_error)?;
+        log::info!("Baseline recorded {} migrations up to version {}", recorded.len(), version);
+    }
+
+    let status = omnipair_migrations::status(pool).await.map_err(schema_error)?;
+    println!("{}", status);
+
+    if args.status || args.baseline.is_some() {
+        return Ok(());
+    }
+
+    let applied = omnipair_migrations::migrate(pool).await.map_err(schema_error)?;
+    if applied.is_empty() {
+        log::info!("No pending migrations");
+    } else {
+        log::info!("Applied {} migrations", applied.len());
+        print!("{}", omnipair_migrations::status(pool).await.map_err(schema_error)?);
+    }
+
+    Ok(())
+}
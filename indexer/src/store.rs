@@ -0,0 +1,612 @@
+//! Storage behind the processors.
+//!
+//! Processors and the `EventWriter` only talk to an `EventStore`, which is injected when
+//! the pipelines are built. `PostgresEventStore` is the production store backed by the
+//! functions in `database`; `InMemoryEventStore` keeps the same rows in memory with the
+//! same idempotency and slot guards, so processing can be exercised offline against
+//! fixture transactions.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use carbon_core::error::{CarbonResult, Error};
+use chrono::Utc;
+use carbon_omnipair_decoder::{
+    accounts::{
+        futarchy_authority::FutarchyAuthority, pair::Pair, rate_model::RateModel, user_position::UserPosition,
+    },
+    instructions::{
+        adjust_collateral_event::AdjustCollateralEvent,
+        adjust_debt_event::AdjustDebtEvent,
+        burn_event::BurnEvent,
+        claim_protocol_fees_event::ClaimProtocolFeesEvent,
+        mint_event::MintEvent,
+        pair_created_event::PairCreatedEvent,
+        swap_event::SwapEvent,
+        update_pair_event::UpdatePairEvent,
+        user_liquidity_position_updated_event::UserLiquidityPositionUpdatedEvent,
+        user_position_created_event::UserPositionCreatedEvent,
+        user_position_liquidated_event::UserPositionLiquidatedEvent,
+        user_position_updated_event::UserPositionUpdatedEvent,
+    },
+};
+use solana_pubkey::Pubkey;
+
+use crate::{
+    database,
+    dead_letter::{DeadLetter, StoredDeadLetter},
+    event_context::EventContext,
+    event_writer::{EventBatch, ProcessedTransaction},
+    finalizer::{self, Commitment, Settlement},
+    flashloans::FlashloanRecord,
+    notifier::OutboxEntry,
+    pricing::SwapPricing,
+};
+
+/// Persistence used by the indexer pipelines.
+///
+/// Event rows are only ever written through `write_event_batch`, which must apply the
+/// whole batch atomically: the rows of a Solana transaction are either all stored or not
+/// at all. Account upserts never let an older slot overwrite newer state.
+#[async_trait]
+pub trait EventStore: Send + Sync {
+    /// Upserts every row of `batch` (events, latest-state tables, revenue, webhook
+    /// outbox, processed transactions and checkpoint) in one transaction
+    async fn write_event_batch(&self, batch: &EventBatch) -> CarbonResult<()>;
+
+    async fn upsert_pair_account(&self, pubkey: &Pubkey, pair: &Pair, slot: i64) -> CarbonResult<()>;
+
+    async fn upsert_user_position_account(
+        &self,
+        pubkey: &Pubkey,
+        position: &UserPosition,
+        slot: i64,
+    ) -> CarbonResult<()>;
+
+    async fn upsert_rate_model_account(&self, pubkey: &Pubkey, rate_model: &RateModel, slot: i64) -> CarbonResult<()>;
+
+    async fn upsert_futarchy_authority_account(
+        &self,
+        pubkey: &Pubkey,
+        futarchy_authority: &FutarchyAuthority,
+        slot: i64,
+    ) -> CarbonResult<()>;
+
+    /// Ingestion checkpoint of a datasource as (slot, signature)
+    async fn get_ingestion_checkpoint(&self, datasource: &str) -> CarbonResult<Option<(i64, String)>>;
+
+    /// Subset of `signatures` that has not been processed yet, or only in a rolled back
+    /// slot, in the given order
+    async fn filter_unprocessed_signatures(&self, signatures: &[String]) -> CarbonResult<Vec<String>>;
+
+    /// Keeps an update that failed processing until it is replayed
+    async fn write_dead_letter(&self, letter: &DeadLetter) -> CarbonResult<()>;
+
+    /// Dead letters not replayed yet, oldest first, optionally only those of `datasource`
+    async fn load_dead_letters(&self, datasource: Option<&str>, limit: Option<usize>)
+        -> CarbonResult<Vec<StoredDeadLetter>>;
+
+    /// Marks a dead letter as replayed, or records another failed attempt with its error
+    async fn record_dead_letter_replay(&self, id: i64, outcome: Result<(), String>) -> CarbonResult<()>;
+
+    /// Lowest slot from `from_slot` on with rows still at `confirmed` commitment
+    async fn oldest_pending_slot(&self, from_slot: i64) -> CarbonResult<Option<i64>>;
+
+    /// Finalizes the pending rows of `start_slot..=end_slot` whose slot is rooted (every
+    /// slot when `rooted_slots` is `None`) and rolls back the others, undoing what they
+    /// contributed to latest state and candles (see `finalizer`)
+    async fn settle_slots(&self, start_slot: i64, end_slot: i64, rooted_slots: Option<&[i64]>)
+        -> CarbonResult<Settlement>;
+}
+
+/// Production store writing to Postgres through the shared pool (see `init_db_pool`)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PostgresEventStore;
+
+#[async_trait]
+impl EventStore for PostgresEventStore {
+    async fn write_event_batch(&self, batch: &EventBatch) -> CarbonResult<()> {
+        database::write_event_batch(batch).await
+    }
+
+    async fn upsert_pair_account(&self, pubkey: &Pubkey, pair: &Pair, slot: i64) -> CarbonResult<()> {
+        database::upsert_pair_account(pubkey, pair, slot).await
+    }
+
+    async fn upsert_user_position_account(
+        &self,
+        pubkey: &Pubkey,
+        position: &UserPosition,
+        slot: i64,
+    ) -> CarbonResult<()> {
+        database::upsert_user_position_account(pubkey, position, slot).await
+    }
+
+    async fn upsert_rate_model_account(&self, pubkey: &Pubkey, rate_model: &RateModel, slot: i64) -> CarbonResult<()> {
+        database::upsert_rate_model_account(pubkey, rate_model, slot).await
+    }
+
+    async fn upsert_futarchy_authority_account(
+        &self,
+        pubkey: &Pubkey,
+        futarchy_authority: &FutarchyAuthority,
+        slot: i64,
+    ) -> CarbonResult<()> {
+        database::upsert_futarchy_authority_account(pubkey, futarchy_authority, slot).await
+    }
+
+    async fn get_ingestion_checkpoint(&self, datasource: &str) -> CarbonResult<Option<(i64, String)>> {
+        database::get_ingestion_checkpoint(datasource).await
+    }
+
+    async fn filter_unprocessed_signatures(&self, signatures: &[String]) -> CarbonResult<Vec<String>> {
+        database::filter_unprocessed_signatures(signatures).await
+    }
+
+    async fn write_dead_letter(&self, letter: &DeadLetter) -> CarbonResult<()> {
+        database::insert_dead_letter(letter).await
+    }
+
+    async fn load_dead_letters(
+        &self,
+        datasource: Option<&str>,
+        limit: Option<usize>,
+    ) -> CarbonResult<Vec<StoredDeadLetter>> {
+        database::load_dead_letters(datasource, limit).await
+    }
+
+    async fn record_dead_letter_replay(&self, id: i64, outcome: Result<(), String>) -> CarbonResult<()> {
+        database::record_dead_letter_replay(id, outcome).await
+    }
+
+    async fn oldest_pending_slot(&self, from_slot: i64) -> CarbonResult<Option<i64>> {
+        finalizer::oldest_pending_slot(from_slot).await
+    }
+
+    async fn settle_slots(
+        &self,
+        start_slot: i64,
+        end_slot: i64,
+        rooted_slots: Option<&[i64]>,
+    ) -> CarbonResult<Settlement> {
+        finalizer::settle_range(start_slot, end_slot, rooted_slots).await
+    }
+}
+
+/// Idempotency key of every event table: (signature, instruction_index, inner_instruction_index)
+pub type EventKey = (String, i32, i32);
+
+/// Rows held by an `InMemoryEventStore`, one map per table
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryTables {
+    pub swaps: BTreeMap<EventKey, (SwapEvent, EventContext)>,
+    pub swap_pricing: BTreeMap<EventKey, (SwapPricing, EventContext)>,
+    pub mints: BTreeMap<EventKey, (MintEvent, EventContext)>,
+    pub burns: BTreeMap<EventKey, (BurnEvent, EventContext)>,
+    pub collateral_adjustments: BTreeMap<EventKey, (AdjustCollateralEvent, EventContext)>,
+    pub debt_adjustments: BTreeMap<EventKey, (AdjustDebtEvent, EventContext)>,
+    pub user_position_updates: BTreeMap<EventKey, (UserPositionUpdatedEvent, EventContext)>,
+    pub user_position_liquidations: BTreeMap<EventKey, (UserPositionLiquidatedEvent, EventContext)>,
+    pub user_positions_created: BTreeMap<EventKey, (UserPositionCreatedEvent, EventContext)>,
+    pub user_liquidity_position_updates: BTreeMap<EventKey, (UserLiquidityPositionUpdatedEvent, EventContext)>,
+    pub pairs_created: BTreeMap<EventKey, (PairCreatedEvent, EventContext)>,
+    pub pair_updates: BTreeMap<EventKey, (UpdatePairEvent, EventContext)>,
+    pub flashloans: BTreeMap<EventKey, (FlashloanRecord, EventContext)>,
+    pub protocol_fee_claims: BTreeMap<EventKey, (ClaimProtocolFeesEvent, EventContext)>,
+    /// Latest position per (pair, signer), like `user_borrow_positions`
+    pub user_borrow_positions: BTreeMap<(Pubkey, Pubkey), (UserPositionUpdatedEvent, EventContext)>,
+    /// Latest LP position per (pair, signer), like `user_liquidity_positions`
+    pub user_liquidity_positions: BTreeMap<(Pubkey, Pubkey), (UserLiquidityPositionUpdatedEvent, EventContext)>,
+    /// Keyed by (event_key, event_type, endpoint); the first enqueued entry is kept
+    pub outbox: BTreeMap<(String, &'static str, String), OutboxEntry>,
+    /// The first datasource to process a transaction is kept
+    pub processed_transactions: HashMap<String, ProcessedTransaction>,
+    /// (slot, signature) per datasource
+    pub checkpoints: HashMap<&'static str, (i64, String)>,
+    /// Latest account state and its slot
+    pub pair_accounts: HashMap<Pubkey, (Pair, i64)>,
+    pub user_position_accounts: HashMap<Pubkey, (UserPosition, i64)>,
+    pub rate_model_accounts: HashMap<Pubkey, (RateModel, i64)>,
+    pub futarchy_authority_accounts: HashMap<Pubkey, (FutarchyAuthority, i64)>,
+    /// Dead letters by id, including replayed ones
+    pub dead_letters: BTreeMap<i64, StoredDeadLetter>,
+    /// Commitment of settled slots; rows of other slots are `confirmed`
+    pub commitments: BTreeMap<i64, Commitment>,
+    /// Number of successful `write_event_batch` calls
+    pub batches_written: usize,
+}
+
+impl InMemoryTables {
+    /// Commitment of the rows written at `slot`
+    pub fn commitment(&self, slot: i64) -> Commitment {
+        self.commitments.get(&slot).copied().unwrap_or(Commitment::Confirmed)
+    }
+
+    /// Slot of every row that carries a commitment, like `finalizer::COMMITMENT_TABLES`
+    fn row_slots(&self) -> Vec<i64> {
+        let mut slots = Vec::new();
+
+        slots.extend(self.swaps.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.mints.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.burns.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.collateral_adjustments.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.debt_adjustments.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.user_position_updates.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.user_position_liquidations.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.user_positions_created.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.user_liquidity_position_updates.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.pairs_created.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.pair_updates.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.flashloans.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.protocol_fee_claims.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.user_borrow_positions.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.user_liquidity_positions.values().map(|(_, ctx)| ctx.slot));
+        slots.extend(self.processed_transactions.values().map(|transaction| transaction.slot));
+        slots.extend(self.pair_accounts.values().map(|(_, slot)| *slot));
+        slots.extend(self.user_position_accounts.values().map(|(_, slot)| *slot));
+        slots.extend(self.rate_model_accounts.values().map(|(_, slot)| *slot));
+        slots.extend(self.futarchy_authority_accounts.values().map(|(_, slot)| *slot));
+
+        slots
+    }
+
+    /// Pubkeys of the accounts stored at a rolled back slot
+    fn rolled_back_accounts(&self) -> Vec<Pubkey> {
+        let rolled_back = |slot: &i64| self.commitment(*slot) == Commitment::RolledBack;
+        let mut pubkeys = Vec::new();
+
+        pubkeys.extend(self.pair_accounts.iter().filter(|(_, (_, slot))| rolled_back(slot)).map(|(pubkey, _)| *pubkey));
+        pubkeys.extend(
+            self.user_position_accounts.iter().filter(|(_, (_, slot))| rolled_back(slot)).map(|(pubkey, _)| *pubkey),
+        );
+        pubkeys.extend(
+            self.rate_model_accounts.iter().filter(|(_, (_, slot))| rolled_back(slot)).map(|(pubkey, _)| *pubkey),
+        );
+        pubkeys.extend(
+            self.futarchy_authority_accounts
+                .iter()
+                .filter(|(_, (_, slot))| rolled_back(slot))
+                .map(|(pubkey, _)| *pubkey),
+        );
+
+        pubkeys
+    }
+}
+
+/// Store keeping every row in memory, for tests and offline processing
+#[derive(Debug, Default)]
+pub struct InMemoryEventStore {
+    tables: Mutex<InMemoryTables>,
+}
+
+impl InMemoryEventStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of the rows currently stored
+    pub fn snapshot(&self) -> InMemoryTables {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Event rows are upserted on their idempotency key, like the `ON CONFLICT` clauses
+fn upsert_events<T: Clone>(table: &mut BTreeMap<EventKey, (T, EventContext)>, rows: &[(T, EventContext)]) {
+    for (event, ctx) in rows {
+        let key = (ctx.signature.clone(), ctx.instruction_index, ctx.inner_instruction_index);
+        table.insert(key, (event.clone(), ctx.clone()));
+    }
+}
+
+/// A multi-row upsert cannot update the same row twice ("ON CONFLICT DO UPDATE command
+/// cannot affect row a second time"), so a batch fails when it holds a key twice
+fn check_unique_keys<T>(table: &str, rows: &[(T, EventContext)]) -> CarbonResult<()> {
+    let mut keys = HashSet::new();
+    for (_, ctx) in rows {
+        if !keys.insert((&ctx.signature, ctx.instruction_index, ctx.inner_instruction_index)) {
+            return Err(Error::Custom(format!(
+                "Failed to upsert {}: {}#{}.{} is in the batch twice",
+                table, ctx.signature, ctx.instruction_index, ctx.inner_instruction_index
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Latest-state rows are only replaced by rows from the same or a newer slot, like the
+/// `WHERE <table>.slot <= EXCLUDED.slot` guards of their upserts
+fn upsert_latest<T: Clone, K: Ord>(
+    table: &mut BTreeMap<K, (T, EventContext)>,
+    rows: &[(T, EventContext)],
+    key: impl Fn(&T) -> K,
+) {
+    for (row, ctx) in rows {
+        let key = key(row);
+        let newer = table.get(&key).is_none_or(|(_, current)| current.slot <= ctx.slot);
+        if newer {
+            table.insert(key, (row.clone(), ctx.clone()));
+        }
+    }
+}
+
+/// Replaces latest-state rows written at a rolled back slot by the latest event of their
+/// key that was not rolled back, or removes them when there is none. Returns the number
+/// of rows re-derived and removed.
+fn rederive_latest<T: Clone, K: Ord + Clone>(
+    table: &mut BTreeMap<K, (T, EventContext)>,
+    events: &BTreeMap<EventKey, (T, EventContext)>,
+    commitments: &BTreeMap<i64, Commitment>,
+    key: impl Fn(&T) -> K,
+) -> (u64, u64) {
+    let rolled_back = |ctx: &EventContext| commitments.get(&ctx.slot) == Some(&Commitment::RolledBack);
+    let stale: Vec<K> = table.iter().filter(|(_, (_, ctx))| rolled_back(ctx)).map(|(key, _)| key.clone()).collect();
+    let (mut rederived, mut removed) = (0, 0);
+
+    for stale_key in stale {
+        let surviving = events
+            .values()
+            .filter(|(event, ctx)| key(event) == stale_key && !rolled_back(ctx))
+            .max_by_key(|(_, ctx)| (ctx.slot, ctx.instruction_index, ctx.inner_instruction_index));
+
+        match surviving {
+            Some(row) => {
+                table.insert(stale_key, row.clone());
+                rederived += 1;
+            }
+            None => {
+                table.remove(&stale_key);
+                removed += 1;
+            }
+        }
+    }
+
+    (rederived, removed)
+}
+
+/// Account rows are only replaced by state from the same or a newer slot
+fn upsert_account<T: Clone>(table: &mut HashMap<Pubkey, (T, i64)>, pubkey: &Pubkey, account: &T, slot: i64) {
+    let newer = table.get(pubkey).is_none_or(|(_, current_slot)| *current_slot <= slot);
+    if newer {
+        table.insert(*pubkey, (account.clone(), slot));
+    }
+}
+
+#[async_trait]
+impl EventStore for InMemoryEventStore {
+    async fn write_event_batch(&self, batch: &EventBatch) -> CarbonResult<()> {
+        check_unique_keys("swaps", &batch.swaps)?;
+        check_unique_keys("adjust_liquidity (add)", &batch.mints)?;
+        check_unique_keys("adjust_liquidity (remove)", &batch.burns)?;
+        check_unique_keys("adjust_collateral_events", &batch.collateral_adjustments)?;
+        check_unique_keys("adjust_debt_events", &batch.debt_adjustments)?;
+        check_unique_keys("user_position_updated_events", &batch.user_position_updates)?;
+        check_unique_keys("user_position_liquidated_events", &batch.user_position_liquidations)?;
+        check_unique_keys("user_lp_position_updated_events", &batch.user_liquidity_position_updates)?;
+        check_unique_keys("pair_state_updates", &batch.pair_updates)?;
+        check_unique_keys("flashloans", &batch.flashloans)?;
+
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+
+        upsert_events(&mut tables.swaps, &batch.swaps);
+        upsert_events(&mut tables.swap_pricing, &batch.swap_pricing);
+        upsert_events(&mut tables.mints, &batch.mints);
+        upsert_events(&mut tables.burns, &batch.burns);
+        upsert_events(&mut tables.collateral_adjustments, &batch.collateral_adjustments);
+        upsert_events(&mut tables.debt_adjustments, &batch.debt_adjustments);
+        upsert_events(&mut tables.user_position_updates, &batch.user_position_updates);
+        upsert_events(&mut tables.user_position_liquidations, &batch.user_position_liquidations);
+        upsert_events(&mut tables.user_positions_created, &batch.user_positions_created);
+        upsert_events(&mut tables.user_liquidity_position_updates, &batch.user_liquidity_position_updates);
+        upsert_events(&mut tables.pairs_created, &batch.pairs_created);
+        upsert_events(&mut tables.pair_updates, &batch.pair_updates);
+        upsert_events(&mut tables.flashloans, &batch.flashloans);
+        upsert_events(&mut tables.protocol_fee_claims, &batch.protocol_fee_claims);
+        upsert_latest(&mut tables.user_borrow_positions, &batch.user_position_updates, |event| {
+            (event.metadata.pair, event.metadata.signer)
+        });
+        upsert_latest(&mut tables.user_liquidity_positions, &batch.user_liquidity_position_updates, |event| {
+            (event.metadata.pair, event.metadata.signer)
+        });
+
+        for entry in &batch.notifications {
+            tables
+                .outbox
+                .entry((entry.event_key.clone(), entry.event_type, entry.endpoint.clone()))
+                .or_insert_with(|| entry.clone());
+        }
+
+        for transaction in &batch.processed_transactions {
+            let landed_again = tables.processed_transactions.get(&transaction.signature).is_none_or(|processed| {
+                processed.slot != transaction.slot || tables.commitment(processed.slot) == Commitment::RolledBack
+            });
+            if landed_again {
+                tables.processed_transactions.insert(transaction.signature.clone(), transaction.clone());
+            }
+        }
+
+        if let Some(checkpoint) = &batch.checkpoint {
+            let newer = tables
+                .checkpoints
+                .get(checkpoint.datasource)
+                .is_none_or(|(slot, _)| *slot <= checkpoint.slot);
+            if newer {
+                tables
+                    .checkpoints
+                    .insert(checkpoint.datasource, (checkpoint.slot, checkpoint.signature.clone()));
+            }
+        }
+
+        tables.batches_written += 1;
+
+        Ok(())
+    }
+
+    async fn upsert_pair_account(&self, pubkey: &Pubkey, pair: &Pair, slot: i64) -> CarbonResult<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        upsert_account(&mut tables.pair_accounts, pubkey, pair, slot);
+        Ok(())
+    }
+
+    async fn upsert_user_position_account(
+        &self,
+        pubkey: &Pubkey,
+        position: &UserPosition,
+        slot: i64,
+    ) -> CarbonResult<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        upsert_account(&mut tables.user_position_accounts, pubkey, position, slot);
+        Ok(())
+    }
+
+    async fn upsert_rate_model_account(&self, pubkey: &Pubkey, rate_model: &RateModel, slot: i64) -> CarbonResult<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        upsert_account(&mut tables.rate_model_accounts, pubkey, rate_model, slot);
+        Ok(())
+    }
+
+    async fn upsert_futarchy_authority_account(
+        &self,
+        pubkey: &Pubkey,
+        futarchy_authority: &FutarchyAuthority,
+        slot: i64,
+    ) -> CarbonResult<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        upsert_account(&mut tables.futarchy_authority_accounts, pubkey, futarchy_authority, slot);
+        Ok(())
+    }
+
+    async fn get_ingestion_checkpoint(&self, datasource: &str) -> CarbonResult<Option<(i64, String)>> {
+        let tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(tables.checkpoints.get(datasource).cloned())
+    }
+
+    async fn filter_unprocessed_signatures(&self, signatures: &[String]) -> CarbonResult<Vec<String>> {
+        let tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        let processed: HashSet<&String> = tables
+            .processed_transactions
+            .iter()
+            .filter(|(_, transaction)| tables.commitment(transaction.slot) != Commitment::RolledBack)
+            .map(|(signature, _)| signature)
+            .collect();
+
+        Ok(signatures.iter().filter(|signature| !processed.contains(signature)).cloned().collect())
+    }
+
+    async fn write_dead_letter(&self, letter: &DeadLetter) -> CarbonResult<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        let id = tables.dead_letters.keys().next_back().map_or(1, |id| id + 1);
+        tables.dead_letters.insert(
+            id,
+            StoredDeadLetter {
+                id,
+                letter: letter.clone(),
+                replayed_at: None,
+            },
+        );
+        Ok(())
+    }
+
+    async fn load_dead_letters(
+        &self,
+        datasource: Option<&str>,
+        limit: Option<usize>,
+    ) -> CarbonResult<Vec<StoredDeadLetter>> {
+        let tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(tables
+            .dead_letters
+            .values()
+            .filter(|stored| stored.replayed_at.is_none())
+            .filter(|stored| datasource.is_none_or(|datasource| stored.letter.datasource == datasource))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect())
+    }
+
+    async fn record_dead_letter_replay(&self, id: i64, outcome: Result<(), String>) -> CarbonResult<()> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(stored) = tables.dead_letters.get_mut(&id) {
+            match outcome {
+                Ok(()) => stored.replayed_at = Some(Utc::now()),
+                Err(error) => {
+                    stored.letter.attempts += 1;
+                    stored.letter.error = error;
+                    stored.letter.failed_at = Utc::now();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn oldest_pending_slot(&self, from_slot: i64) -> CarbonResult<Option<i64>> {
+        let tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(tables
+            .row_slots()
+            .into_iter()
+            .filter(|slot| *slot >= from_slot && tables.commitment(*slot) == Commitment::Confirmed)
+            .min())
+    }
+
+    async fn settle_slots(
+        &self,
+        start_slot: i64,
+        end_slot: i64,
+        rooted_slots: Option<&[i64]>,
+    ) -> CarbonResult<Settlement> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        let mut settlement = Settlement::default();
+
+        let row_slots = tables.row_slots();
+        let settled: BTreeMap<i64, Commitment> = row_slots
+            .iter()
+            .filter(|slot| (start_slot..=end_slot).contains(*slot) && tables.commitment(**slot) == Commitment::Confirmed)
+            .map(|slot| {
+                let rooted = rooted_slots.is_none_or(|rooted_slots| rooted_slots.contains(slot));
+                (*slot, if rooted { Commitment::Finalized } else { Commitment::RolledBack })
+            })
+            .collect();
+
+        for slot in &row_slots {
+            match settled.get(slot) {
+                Some(Commitment::Finalized) => settlement.finalized += 1,
+                Some(Commitment::RolledBack) => settlement.rolled_back += 1,
+                _ => {}
+            }
+        }
+        tables.commitments.extend(settled);
+
+        if settlement.rolled_back > 0 {
+            let InMemoryTables {
+                user_borrow_positions,
+                user_position_updates,
+                user_liquidity_positions,
+                user_liquidity_position_updates,
+                commitments,
+                ..
+            } = &mut *tables;
+
+            for (rederived, removed) in [
+                rederive_latest(user_borrow_positions, user_position_updates, commitments, |event| {
+                    (event.metadata.pair, event.metadata.signer)
+                }),
+                rederive_latest(user_liquidity_positions, user_liquidity_position_updates, commitments, |event| {
+                    (event.metadata.pair, event.metadata.signer)
+                }),
+            ] {
+                settlement.rederived += rederived;
+                settlement.removed += removed;
+            }
+
+            settlement.rolled_back_accounts = tables.rolled_back_accounts();
+        }
+
+        Ok(settlement)
+    }
+}
@@ -0,0 +1,106 @@
+//! Prometheus metrics.
+//!
+//! The pipelines report their own metrics through `PrometheusMetrics`, registered next to
+//! `LogMetrics` by `register`. Indexer metrics that do not go through a pipeline (database
+//! writes, webhook deliveries, the slot lag) are recorded with the `metrics` macros and
+//! served by the same exporter. Without an installed exporter they are discarded.
+
+use std::{sync::Arc, time::Duration};
+
+use carbon_core::{error::CarbonResult, metrics::Metrics, pipeline::PipelineBuilder};
+use carbon_log_metrics::LogMetrics;
+use carbon_prometheus_metrics::PrometheusMetrics;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+
+use crate::{config::MetricsConfig, event_writer::EventBatch};
+
+/// Rows written to the database, labelled by `event_type`
+pub const EVENTS_PERSISTED: &str = "indexer_events_persisted_total";
+/// Duration of event batch writes, labelled by `outcome` (`ok` or `error`)
+pub const DB_WRITE_DURATION: &str = "indexer_db_write_duration_seconds";
+/// Webhook delivery attempts, labelled by `event_type` and `outcome`
+/// (`delivered`, `retry`, `failed` or `lease_lost`)
+pub const WEBHOOK_DELIVERIES: &str = "indexer_webhook_deliveries_total";
+/// Slots between the chain tip and the slowest streaming datasource
+pub const SLOT_LAG: &str = "indexer_slot_lag";
+/// Confirmed slot of the chain tip
+pub const CHAIN_TIP_SLOT: &str = "indexer_chain_tip_slot";
+/// Swaps whose fee disagrees with the fee rate of their pair
+pub const SWAP_FEE_MISMATCHES: &str = "indexer_swap_fee_mismatches_total";
+
+/// Exporter described by `config`, `None` if disabled
+pub fn exporter(config: &MetricsConfig) -> Option<PrometheusMetrics> {
+    if !config.enabled {
+        return None;
+    }
+
+    let mut exporter = PrometheusMetrics::new_with_address(config.address);
+    if !config.buckets.is_empty() {
+        exporter = exporter.with_buckets(config.buckets.clone());
+    }
+    for (name, buckets) in &config.metric_buckets {
+        exporter = exporter.with_metric_buckets(name, buckets.clone());
+    }
+
+    Some(exporter)
+}
+
+/// Starts serving `/metrics`, before any pipeline runs so that indexer metrics recorded
+/// outside of the pipelines are exported too
+pub async fn install_exporter(config: &MetricsConfig) -> CarbonResult<()> {
+    let Some(exporter) = exporter(config) else {
+        return Ok(());
+    };
+
+    exporter.initialize().await?;
+    describe_metrics();
+
+    Ok(())
+}
+
+/// Registers the log metrics and, if enabled, the Prometheus exporter on a pipeline
+pub fn register(builder: PipelineBuilder, config: &MetricsConfig) -> PipelineBuilder {
+    let builder = builder.metrics(Arc::new(LogMetrics::new()));
+
+    match exporter(config) {
+        Some(exporter) => builder.metrics(Arc::new(exporter)),
+        None => builder,
+    }
+}
+
+fn describe_metrics() {
+    describe_counter!(EVENTS_PERSISTED, "Events written to the database, by event type");
+    describe_histogram!(DB_WRITE_DURATION, Unit::Seconds, "Duration of event batch writes");
+    describe_counter!(WEBHOOK_DELIVERIES, "Webhook delivery attempts, by event type and outcome");
+    describe_gauge!(SLOT_LAG, "Slots between the chain tip and the slowest streaming datasource");
+    describe_gauge!(CHAIN_TIP_SLOT, "Confirmed slot of the chain tip");
+    describe_counter!(SWAP_FEE_MISMATCHES, "Swaps whose fee disagrees with the fee rate of their pair");
+}
+
+/// Counts the rows of a batch written to the database
+pub fn record_events_persisted(batch: &EventBatch) {
+    for (event_type, count) in batch.event_counts() {
+        if count > 0 {
+            counter!(EVENTS_PERSISTED, "event_type" => event_type).increment(count as u64);
+        }
+    }
+}
+
+pub fn record_db_write(duration: Duration, ok: bool) {
+    histogram!(DB_WRITE_DURATION, "outcome" => if ok { "ok" } else { "error" }).record(duration.as_secs_f64());
+}
+
+pub fn record_webhook_delivery(event_type: &str, outcome: &'static str) {
+    counter!(WEBHOOK_DELIVERIES, "event_type" => event_type.to_string(), "outcome" => outcome).increment(1);
+}
+
+pub fn record_swap_fee_mismatches(count: usize) {
+    counter!(SWAP_FEE_MISMATCHES).increment(count as u64);
+}
+
+pub fn set_chain_tip(chain_tip_slot: u64, slot_lag: Option<u64>) {
+    gauge!(CHAIN_TIP_SLOT).set(chain_tip_slot as f64);
+    if let Some(slot_lag) = slot_lag {
+        gauge!(SLOT_LAG).set(slot_lag as f64);
+    }
+}
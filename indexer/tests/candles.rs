@@ -0,0 +1,113 @@
+//! Candle aggregation of swaps.
+
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, TimeZone, Utc};
+use omnipair_carbon_indexer::candles::{aggregate, reserve_price, CandleSwap, Resolution};
+
+const PAIR: &str = "11111111111111111111111111111111";
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(seconds, 0).unwrap()
+}
+
+fn swap(slot: i64, timestamp: DateTime<Utc>, is_token0_in: bool, reserve0: u64, reserve1: u64) -> CandleSwap {
+    CandleSwap {
+        pair: PAIR.to_string(),
+        is_token0_in,
+        amount_in: 100,
+        amount_out: 40,
+        reserve0,
+        reserve1,
+        timestamp,
+        key: (slot, format!("sig{}", slot), 0, 0),
+    }
+}
+
+fn price(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+#[test]
+fn aligns_buckets_on_utc_epoch() {
+    // 2024-01-01T10:37:42Z
+    let timestamp = at(1_704_105_462);
+
+    assert_eq!(Resolution::OneMinute.bucket_start(timestamp), at(1_704_105_420));
+    assert_eq!(Resolution::FiveMinutes.bucket_start(timestamp), at(1_704_105_300));
+    assert_eq!(Resolution::FifteenMinutes.bucket_start(timestamp), at(1_704_105_000));
+    assert_eq!(Resolution::OneHour.bucket_start(timestamp), at(1_704_103_200));
+    assert_eq!(Resolution::FourHours.bucket_start(timestamp), at(1_704_096_000));
+    assert_eq!(Resolution::OneDay.bucket_start(timestamp), at(1_704_067_200));
+
+    // A timestamp on a boundary starts its own bucket
+    assert_eq!(Resolution::OneHour.bucket_start(at(1_704_103_200)), at(1_704_103_200));
+}
+
+#[test]
+fn prices_are_the_exact_reserve_ratio() {
+    assert_eq!(reserve_price(3, 1), Some(price("0.333333333333333333")));
+    assert_eq!(reserve_price(1_000_000, 2_500_000), Some(price("2.5")));
+    assert_eq!(reserve_price(u64::MAX, u64::MAX), Some(price("1")));
+    assert_eq!(reserve_price(0, 1_000), None);
+}
+
+#[test]
+fn folds_swaps_in_trade_order() {
+    let base = 1_704_103_200;
+    // Written out of order: the first trade of the hour arrives last
+    let swaps = [
+        swap(12, at(base + 30), true, 1_000, 3_000),
+        swap(13, at(base + 50), false, 1_000, 1_000),
+        swap(11, at(base + 10), true, 1_000, 2_000),
+    ];
+
+    let candles = aggregate(&swaps);
+    assert_eq!(candles.len(), Resolution::ALL.len());
+
+    let candle = &candles[&(PAIR.to_string(), Resolution::OneMinute, at(base))];
+    assert_eq!(candle.open.as_ref().unwrap().price, price("2"));
+    assert_eq!(candle.open.as_ref().unwrap().key.0, 11);
+    assert_eq!(candle.close.as_ref().unwrap().price, price("1"));
+    assert_eq!(candle.close.as_ref().unwrap().key.0, 13);
+    assert_eq!(candle.high, Some(price("3")));
+    assert_eq!(candle.low, Some(price("1")));
+    assert_eq!(candle.trade_count, 3);
+
+    // Token0 in: (amount_in, amount_out); token1 in: (amount_out, amount_in)
+    assert_eq!(candle.volume0, 100 + 40 + 100);
+    assert_eq!(candle.volume1, 40 + 100 + 40);
+
+    // Every resolution sees the same swaps
+    let daily = &candles[&(PAIR.to_string(), Resolution::OneDay, Resolution::OneDay.bucket_start(at(base)))];
+    assert_eq!(daily.open, candle.open);
+    assert_eq!(daily.close, candle.close);
+}
+
+#[test]
+fn splits_swaps_across_buckets() {
+    let base = 1_704_103_200;
+    let swaps = [swap(1, at(base), true, 1_000, 2_000), swap(2, at(base + 60), true, 1_000, 4_000)];
+
+    let candles = aggregate(&swaps);
+    let first = &candles[&(PAIR.to_string(), Resolution::OneMinute, at(base))];
+    let second = &candles[&(PAIR.to_string(), Resolution::OneMinute, at(base + 60))];
+    assert_eq!((first.trade_count, second.trade_count), (1, 1));
+    assert_eq!(first.close.as_ref().unwrap().price, price("2"));
+    assert_eq!(second.open.as_ref().unwrap().price, price("4"));
+
+    let hourly = &candles[&(PAIR.to_string(), Resolution::OneHour, at(base))];
+    assert_eq!(hourly.trade_count, 2);
+    assert_eq!((hourly.low.clone(), hourly.high.clone()), (Some(price("2")), Some(price("4"))));
+}
+
+#[test]
+fn counts_volume_of_swaps_without_a_price() {
+    let candles = aggregate(&[swap(1, at(0), true, 0, 2_000)]);
+    let candle = &candles[&(PAIR.to_string(), Resolution::OneMinute, at(0))];
+
+    assert_eq!(candle.trade_count, 1);
+    assert_eq!((candle.volume0, candle.volume1), (100, 40));
+    assert!(candle.open.is_none() && candle.high.is_none() && candle.low.is_none() && candle.close.is_none());
+}
@@ -0,0 +1,132 @@
+//! Configuration file loading and validation.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::Parser;
+use omnipair_carbon_indexer::{config::Cluster, Args, Config};
+
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// Loads `file` as the configuration file, with `extra_args` on the command line
+fn load(file: &str, extra_args: &[&str]) -> Result<Config, String> {
+    let path = std::env::temp_dir().join(format!(
+        "omnipair-indexer-{}-{}.toml",
+        std::process::id(),
+        NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, file).unwrap();
+
+    let mut argv = vec!["omnipair-indexer", "--config", path.to_str().unwrap()];
+    argv.extend_from_slice(extra_args);
+    let result = Config::load(Args::parse_from(argv));
+
+    std::fs::remove_file(&path).unwrap();
+    result
+}
+
+#[test]
+fn example_config_is_valid() {
+    let example = std::fs::read_to_string("config.example.toml").unwrap();
+    let config = load(&example, &["--health-port", "9000"]).unwrap();
+
+    assert_eq!(config.cluster, Cluster::MainnetBeta);
+    assert_eq!(config.programs.len(), 1);
+    assert_eq!(config.programs[0].name, "omnipair");
+    assert!(config.processors.events);
+    assert_eq!(config.health.port, 9000);
+    assert_eq!(config.health.max_slot_lag, 150);
+    assert!(config.metrics.enabled);
+    assert_eq!(config.metrics.address.to_string(), "0.0.0.0:9100");
+    assert_eq!(config.metrics.buckets.len(), 12);
+}
+
+#[test]
+fn metrics_settings_are_checked() {
+    let file = r#"
+        [health]
+        port = 9100
+
+        [metrics]
+        buckets = [0.1, 0.05]
+
+        [metrics.metric_buckets]
+        indexer_db_write_duration_seconds = []
+    "#;
+
+    let error = load(file, &[]).unwrap_err();
+    assert!(error.contains("metrics.address: port 9100 is already used"), "{}", error);
+    assert!(error.contains("metrics.buckets: buckets must be finite and strictly increasing"), "{}", error);
+    assert!(error.contains("metrics.metric_buckets.indexer_db_write_duration_seconds: must not be empty"), "{}", error);
+
+    let error = load("", &["--metrics-address", "localhost"]).unwrap_err();
+    assert!(error.contains("metrics.address: 'localhost' is not a socket address"), "{}", error);
+}
+
+#[test]
+fn invalid_settings_are_all_reported() {
+    let file = r#"
+        cluster = "mainnet"
+
+        [[programs]]
+        name = "a"
+        program_id = "not-a-pubkey"
+
+        [[programs]]
+        name = "b"
+        program_id = "omnixgS8fnqHfCcTGKWj6JtKjzpJZ1Y5y9pyFkQDkYE"
+
+        [[programs]]
+        name = "b"
+        program_id = "omnixgS8fnqHfCcTGKWj6JtKjzpJZ1Y5y9pyFkQDkYE"
+
+        [database]
+        max_connections = 0
+
+        [processors]
+        events = false
+        accounts = false
+    "#;
+
+    let error = load(file, &[]).unwrap_err();
+
+    assert!(error.contains("programs[0].program_id: 'not-a-pubkey' is not a valid base58 public key"), "{}", error);
+    assert!(error.contains("programs[2].program_id"), "{}", error);
+    assert!(error.contains("programs[2].name"), "{}", error);
+    assert!(error.contains("database.max_connections"), "{}", error);
+    assert!(error.contains("processors: at least one"), "{}", error);
+}
+
+#[test]
+fn unknown_settings_are_rejected() {
+    let error = load("[datasources.helius]\napi_keys = \"x\"\n", &[]).unwrap_err();
+
+    assert!(error.contains("unknown field `api_keys`"), "{}", error);
+}
+
+#[test]
+fn usd_prices_are_checked() {
+    let file = r#"
+        [pricing.usd_prices]
+        EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v = 1.0
+        So11111111111111111111111111111111111111112 = 152.5
+    "#;
+
+    let config = load(file, &[]).unwrap();
+    let usdc: solana_pubkey::Pubkey = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".parse().unwrap();
+    assert_eq!(config.pricing.usd_prices.len(), 2);
+    assert_eq!(config.pricing.usd_prices[&usdc].to_string(), "1");
+
+    let file = r#"
+        [pricing.usd_prices]
+        not-a-mint = 1.0
+        So11111111111111111111111111111111111111112 = 0.0
+    "#;
+
+    let error = load(file, &[]).unwrap_err();
+    assert!(error.contains("pricing.usd_prices.not-a-mint: 'not-a-mint' is not a valid base58"), "{}", error);
+    assert!(
+        error.contains("pricing.usd_prices.So11111111111111111111111111111111111111112: 0 is not a positive price"),
+        "{}",
+        error
+    );
+}
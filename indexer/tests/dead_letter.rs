@@ -0,0 +1,145 @@
+//! Dead-letter encoding, fallback file import and replay bookkeeping.
+
+use carbon_core::{
+    datasource::{AccountUpdate, DatasourceId, TransactionUpdate, Update},
+    dead_letter::FailedUpdate,
+    pipeline::Pipeline,
+};
+use chrono::Utc;
+use omnipair_carbon_indexer::{
+    dead_letter::{import_fallback_file, replay_dead_letters, DeadLetter, ReplayArgs, ReplaySummary, StoredUpdate},
+    store::{EventStore, InMemoryEventStore},
+};
+use solana_account::Account;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_message::{Message, VersionedMessage};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_status::{InnerInstruction, InnerInstructions, TransactionStatusMeta};
+
+fn account_update() -> Update {
+    Update::Account(AccountUpdate {
+        pubkey: Pubkey::new_from_array([1; 32]),
+        account: Account {
+            lamports: 1_000_000,
+            data: vec![7; 64],
+            owner: Pubkey::new_from_array([2; 32]),
+            executable: false,
+            rent_epoch: u64::MAX,
+        },
+        slot: 312_000_000,
+        transaction_signature: Some(Signature::from([3; 64])),
+    })
+}
+
+fn account_letter(datasource: &str) -> DeadLetter {
+    DeadLetter::new(&FailedUpdate {
+        update: account_update(),
+        datasource_id: DatasourceId::new_named(datasource),
+        error: "Custom(\"database unavailable\")".to_string(),
+    })
+    .unwrap()
+}
+
+#[test]
+fn updates_survive_encoding() {
+    let program_id = Pubkey::new_from_array([4; 32]);
+    let payer = Pubkey::new_from_array([5; 32]);
+    let instruction = Instruction::new_with_bytes(program_id, &[1, 2, 3], vec![AccountMeta::new(payer, true)]);
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::from([6; 64])],
+        message: VersionedMessage::Legacy(Message::new(std::slice::from_ref(&instruction), Some(&payer))),
+    };
+    let meta = TransactionStatusMeta {
+        log_messages: Some(vec!["Program log: swap".to_string()]),
+        inner_instructions: Some(vec![InnerInstructions {
+            index: 0,
+            instructions: vec![InnerInstruction {
+                instruction: transaction.message.instructions()[0].clone(),
+                stack_height: Some(2),
+            }],
+        }]),
+        ..TransactionStatusMeta::default()
+    };
+    let update = Update::Transaction(Box::new(TransactionUpdate {
+        signature: Signature::from([6; 64]),
+        transaction: transaction.clone(),
+        meta: meta.clone(),
+        is_vote: false,
+        slot: 312_000_001,
+        block_time: Some(1_760_000_000),
+        block_hash: None,
+    }));
+
+    let stored = StoredUpdate::encode(&update).unwrap();
+    assert_eq!(stored.kind(), "transaction");
+    assert_eq!(stored.slot(), 312_000_001);
+
+    let json = serde_json::to_string(&stored).unwrap();
+    let Update::Transaction(decoded) = serde_json::from_str::<StoredUpdate>(&json).unwrap().decode().unwrap() else {
+        panic!("expected a transaction update");
+    };
+    assert_eq!(decoded.signature, Signature::from([6; 64]));
+    assert_eq!(decoded.transaction, transaction);
+    assert_eq!(decoded.meta.log_messages, meta.log_messages);
+    assert_eq!(decoded.meta.inner_instructions, meta.inner_instructions);
+    assert_eq!(decoded.block_time, Some(1_760_000_000));
+
+    let stored = StoredUpdate::encode(&account_update()).unwrap();
+    let Update::Account(decoded) = stored.decode().unwrap() else {
+        panic!("expected an account update");
+    };
+    let Update::Account(original) = account_update() else { unreachable!() };
+    assert_eq!(decoded.pubkey, original.pubkey);
+    assert_eq!(decoded.account, original.account);
+    assert_eq!(decoded.transaction_signature, original.transaction_signature);
+}
+
+#[tokio::test]
+async fn fallback_file_is_imported() {
+    let store = InMemoryEventStore::new();
+    let path = std::env::temp_dir().join(format!("omnipair-dead-letters-{}.jsonl", std::process::id()));
+    let letter = account_letter("helius_atlas");
+    let line = serde_json::to_string(&letter).unwrap();
+    std::fs::write(&path, format!("{}\n{}\n", line, line)).unwrap();
+
+    assert_eq!(import_fallback_file(&path, &store).await.unwrap(), 2);
+    assert!(!path.exists());
+    assert_eq!(store.snapshot().dead_letters.len(), 2);
+    assert_eq!(import_fallback_file(&path, &store).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn replay_marks_replayed_and_counts_failed_attempts() {
+    let store = InMemoryEventStore::new();
+    store.write_dead_letter(&account_letter("helius_atlas")).await.unwrap();
+    store.write_dead_letter(&account_letter("rpc_catch_up")).await.unwrap();
+
+    let mut broken = account_letter("helius_atlas");
+    broken.update = StoredUpdate::AccountDeletion {
+        pubkey: "not-a-pubkey".to_string(),
+        slot: 1,
+        transaction_signature: None,
+    };
+    broken.failed_at = Utc::now();
+    store.write_dead_letter(&broken).await.unwrap();
+
+    let mut pipeline = Pipeline::builder().build().unwrap();
+    let args = ReplayArgs {
+        datasource: Some("helius_atlas".to_string()),
+        ..ReplayArgs::default()
+    };
+    let summary = replay_dead_letters(&mut pipeline, &store, &args).await.unwrap();
+    assert_eq!(summary, ReplaySummary { replayed: 1, failed: 1 });
+
+    let tables = store.snapshot();
+    assert!(tables.dead_letters[&1].replayed_at.is_some());
+    assert!(tables.dead_letters[&2].replayed_at.is_none());
+    assert_eq!(tables.dead_letters[&3].letter.attempts, 2);
+    assert!(tables.dead_letters[&3].letter.error.contains("not-a-pubkey"));
+
+    // Only the letters still queued are replayed next time
+    let pending = store.load_dead_letters(None, None).await.unwrap();
+    assert_eq!(pending.iter().map(|stored| stored.id).collect::<Vec<_>>(), vec![2, 3]);
+}
@@ -0,0 +1,65 @@
+//! Exact swap fee accounting.
+
+use carbon_omnipair_decoder::{instructions::swap_event::SwapEvent, types::EventMetadata};
+use omnipair_carbon_indexer::fees::{check_fee, expected_fee, fee_value_out, fees_paid, FeeMismatch};
+use solana_pubkey::Pubkey;
+
+fn swap(is_token0_in: bool, amount_in: u64, amount_in_after_fee: u64, reserve0: u64, reserve1: u64) -> SwapEvent {
+    SwapEvent {
+        reserve0,
+        reserve1,
+        is_token0_in,
+        amount_in,
+        amount_out: 0,
+        amount_in_after_fee,
+        metadata: EventMetadata { signer: Pubkey::new_unique(), pair: Pubkey::new_unique(), slot: 1 },
+    }
+}
+
+#[test]
+fn fees_are_paid_in_the_input_token() {
+    assert_eq!(fees_paid(&swap(true, 1_000_000, 997_000, 1, 1)), (3_000, 0));
+    assert_eq!(fees_paid(&swap(false, 1_000_000, 997_000, 1, 1)), (0, 3_000));
+
+    // A fee larger than the input is not a fee
+    assert_eq!(fees_paid(&swap(true, 10, 20, 1, 1)), (0, 0));
+}
+
+#[test]
+fn fee_values_use_exact_integer_ratios() {
+    // Reserves whose ratio is not representable as an f64
+    let reserve0 = u64::MAX - 1;
+    let reserve1 = u64::MAX;
+    assert_eq!(fee_value_out(&swap(false, u64::MAX, 0, reserve0, reserve1)), Some((u64::MAX - 1) as u128));
+
+    assert_eq!(fee_value_out(&swap(true, 1_000, 997, 3, 10)), Some(10));
+    assert_eq!(fee_value_out(&swap(false, 1_000, 997, 3, 10)), Some(0));
+
+    // Empty input reserve
+    assert_eq!(fee_value_out(&swap(true, 1_000, 997, 0, 10)), None);
+    assert_eq!(fee_value_out(&swap(false, 1_000, 997, 3, 0)), None);
+}
+
+#[test]
+fn expected_fees_allow_rounding_either_way() {
+    assert_eq!(expected_fee(1_000_000, 30), 3_000..=3_000);
+    assert_eq!(expected_fee(1_001, 30), 3..=4);
+    assert_eq!(expected_fee(u64::MAX, 10_000), u64::MAX..=u64::MAX);
+    assert_eq!(expected_fee(1_000, 0), 0..=0);
+}
+
+#[test]
+fn flags_fees_that_disagree_with_the_pair_rate() {
+    assert_eq!(check_fee(&swap(true, 1_000_000, 997_000, 1, 1), 30), None);
+    assert_eq!(check_fee(&swap(true, 1_001, 997, 1, 1), 30), None);
+    assert_eq!(check_fee(&swap(true, 1_001, 998, 1, 1), 30), None);
+
+    assert_eq!(
+        check_fee(&swap(false, 1_000_000, 990_000, 1, 1), 30),
+        Some(FeeMismatch { fee: 10_000, expected: 3_000..=3_000, swap_fee_bps: 30 })
+    );
+    assert_eq!(
+        check_fee(&swap(true, 1_000_000, 1_000_000, 1, 1), 25),
+        Some(FeeMismatch { fee: 0, expected: 2_500..=2_500, swap_fee_bps: 25 })
+    );
+}
@@ -0,0 +1,99 @@
+//! Readiness of the health state as the pipeline reports its progress.
+
+use std::time::Duration;
+
+use carbon_core::metrics::Metrics;
+use omnipair_carbon_indexer::{config::HealthConfig, datasources::program_subscribe_name, health::HealthState};
+use solana_pubkey::Pubkey;
+
+fn health_state() -> HealthState {
+    let config = HealthConfig {
+        port: 0,
+        max_slot_lag: 100,
+        max_update_age: Duration::ZERO,
+    };
+    HealthState::new(config, &["helius_atlas_ws"])
+}
+
+#[tokio::test]
+async fn ready_once_datasources_are_connected_and_current() {
+    let health = health_state();
+
+    let (failures, report) = health.readiness(Ok(()));
+    assert_eq!(failures, vec!["datasource helius_atlas_ws: disconnected".to_string()]);
+    assert_eq!(report["status"], "not_ready");
+
+    health.update_gauge("helius_atlas_ws_connected", 1.0).await.unwrap();
+    health.update_gauge("helius_atlas_ws_slot", 1_000.0).await.unwrap();
+    health.update_gauge("updates_queued", 3.0).await.unwrap();
+    health.set_chain_tip_slot(1_050);
+    health.record_transaction(990, "sig");
+
+    let (failures, report) = health.readiness(Ok(()));
+    assert!(failures.is_empty(), "{:?}", failures);
+    assert_eq!(report["status"], "ready");
+    assert_eq!(report["slot_lag"], 50);
+    assert_eq!(report["queue_depth"], 3);
+    assert_eq!(report["last_processed"]["slot"], 990);
+    assert_eq!(report["last_processed"]["signature"], "sig");
+}
+
+#[tokio::test]
+async fn stale_stream_or_database_error_flips_readiness() {
+    let health = health_state();
+    health.update_gauge("helius_atlas_ws_connected", 1.0).await.unwrap();
+    health.update_gauge("helius_atlas_ws_slot", 1_000.0).await.unwrap();
+    health.set_chain_tip_slot(1_200);
+
+    let (failures, _) = health.readiness(Err("connection refused".to_string()));
+    assert_eq!(
+        failures,
+        vec!["database: connection refused".to_string(), "slot lag 200 exceeds 100".to_string()]
+    );
+
+    health.update_gauge("helius_atlas_ws_connected", 0.0).await.unwrap();
+    health.reset_datasources();
+    let (failures, report) = health.readiness(Ok(()));
+    assert!(failures.contains(&"datasource helius_atlas_ws: disconnected".to_string()));
+    assert_eq!(report["datasources"]["helius_atlas_ws"]["connected"], false);
+}
+
+#[tokio::test]
+async fn every_program_subscription_must_be_connected() {
+    let program_id = Pubkey::new_unique();
+    let subscriptions = [program_subscribe_name(&program_id, Some(8)), program_subscribe_name(&program_id, Some(16))];
+    let config = HealthConfig {
+        port: 0,
+        max_slot_lag: 0,
+        max_update_age: Duration::ZERO,
+    };
+    let health = HealthState::new(config, &subscriptions);
+
+    for subscription in &subscriptions {
+        let (failures, _) = health.readiness(Ok(()));
+        assert!(failures.contains(&format!("datasource {}: disconnected", subscription)));
+    }
+
+    // One connected subscription does not make the others connected
+    health.update_gauge(&format!("{}_connected", subscriptions[0]), 1.0).await.unwrap();
+    let (failures, _) = health.readiness(Ok(()));
+    assert_eq!(failures, vec![format!("datasource {}: disconnected", subscriptions[1])]);
+
+    health.update_gauge(&format!("{}_connected", subscriptions[1]), 1.0).await.unwrap();
+    let (failures, report) = health.readiness(Ok(()));
+    assert!(failures.is_empty(), "{:?}", failures);
+    assert_eq!(report["datasources"][&subscriptions[0]]["connected"], true);
+}
+
+#[tokio::test]
+async fn plain_status_keeps_its_original_fields() {
+    let health = health_state();
+
+    let status = health.status();
+    let mut fields: Vec<_> = status.as_object().unwrap().keys().cloned().collect();
+    fields.sort();
+    assert_eq!(fields, vec!["service", "status", "timestamp", "uptime"]);
+    assert_eq!(status["status"], "healthy");
+    assert_eq!(status["service"], "omnipair-indexer");
+    assert!(status["uptime"].as_str().unwrap().ends_with('s'));
+}
@@ -0,0 +1,100 @@
+//! Utilization, borrow and supply APRs of pair updates.
+
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use carbon_omnipair_decoder::{
+    accounts::rate_model::RateModel,
+    instructions::update_pair_event::UpdatePairEvent,
+    types::EventMetadata,
+};
+use omnipair_carbon_indexer::interest::{pair_rates, token_rates, RateModelParams, TokenRates};
+use solana_pubkey::Pubkey;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+#[test]
+fn utilization_is_the_lent_out_share_of_the_reserve() {
+    // 600 of 1,000 lent out, borrowed at 12.5% a year, 10% of the interest to the protocol
+    let rates = token_rates(1_000, 400, 125_000_000, Some(1_000));
+
+    assert_eq!(
+        rates,
+        TokenRates {
+            utilization: decimal("0.6"),
+            borrow_apr: decimal("0.125"),
+            supply_apr: Some(decimal("0.0675")),
+        }
+    );
+
+    // Unknown protocol share
+    assert_eq!(token_rates(1_000, 400, 125_000_000, None).supply_apr, None);
+
+    // Empty reserve, and cash above the reserve
+    assert_eq!(token_rates(0, 0, 50_000_000, Some(0)).utilization, decimal("0"));
+    assert_eq!(token_rates(1_000, 2_000, 50_000_000, Some(0)).supply_apr, Some(decimal("0")));
+
+    // Rounded down to 18 decimals
+    assert_eq!(token_rates(3, 2, 0, Some(0)).utilization, decimal("0.333333333333333333"));
+}
+
+#[test]
+fn borrow_apr_matches_the_rates_reported_by_the_api() {
+    // The API reports the program's getRates as `floor(rate / 1e7 * 100) / 100` percent a year
+    // (digits past the scale are dropped, as the API floors)
+    for (rate, api_percent) in [(125_000_000u64, "12.5"), (1_000_000_000, "100"), (38_123_456, "3.81")] {
+        let borrow_apr = token_rates(1_000, 1_000, rate, None).borrow_apr;
+        let percent = (borrow_apr * BigDecimal::from(100)).with_scale(2);
+        assert_eq!(percent, decimal(api_percent), "rate {}", rate);
+    }
+}
+
+#[test]
+fn pair_updates_are_rated_per_token() {
+    let event = UpdatePairEvent {
+        price0_ema: 0,
+        price1_ema: 0,
+        rate0: 200_000_000,
+        rate1: 40_000_000,
+        accrued_interest0: 0,
+        accrued_interest1: 0,
+        cash_reserve0: 750,
+        cash_reserve1: 5_000,
+        reserve0_after_interest: 1_000,
+        reserve1_after_interest: 5_000,
+        metadata: EventMetadata { signer: Pubkey::new_unique(), pair: Pubkey::new_unique(), slot: 1 },
+    };
+
+    let (rates0, rates1) = pair_rates(&event, Some(0));
+    assert_eq!(rates0.utilization, decimal("0.25"));
+    assert_eq!(rates0.supply_apr, Some(decimal("0.05")));
+    assert_eq!(rates1.utilization, decimal("0"));
+    assert_eq!(rates1.borrow_apr, decimal("0.04"));
+    assert_eq!(rates1.supply_apr, Some(decimal("0")));
+}
+
+#[test]
+fn rate_model_parameters_are_converted_to_bps() {
+    let rate_model = RateModel {
+        exp_rate: 1,
+        target_util_start: 500_000_000,
+        target_util_end: 850_000_000,
+        half_life_ms: 3_600_000,
+        min_rate: 10_000_000,
+        max_rate: 2_000_000_000,
+        initial_rate: 0,
+    };
+
+    assert_eq!(
+        RateModelParams::from(&rate_model),
+        RateModelParams {
+            target_util_start_bps: 5_000,
+            target_util_end_bps: 8_500,
+            rate_half_life_ms: 3_600_000,
+            min_rate_bps: 100,
+            max_rate_bps: 20_000,
+        }
+    );
+}
@@ -0,0 +1,41 @@
+//! Webhook signatures and retry backoff.
+
+use std::time::Duration;
+
+use omnipair_carbon_indexer::notifier::{backoff, sign};
+
+#[test]
+fn signs_the_timestamp_and_body() {
+    let body = br#"{"event_type":"swap"}"#;
+
+    // HMAC-SHA256 of `1760000000.{"event_type":"swap"}` keyed with `whsec_test`
+    assert_eq!(
+        sign("whsec_test", "1760000000", body),
+        "sha256=068fa7c5ac00881e1875c65c30c5a0af180083ca11cde386ce958c61e8fcc316"
+    );
+
+    // The timestamp and body are joined with a dot
+    assert_eq!(sign("whsec_test", "1760000000", b"1.2"), sign("whsec_test", "1760000000.1", b"2"));
+
+    // Either part changes the signature
+    assert_ne!(sign("whsec_test", "1760000001", body), sign("whsec_test", "1760000000", body));
+    assert_ne!(sign("whsec_other", "1760000000", body), sign("whsec_test", "1760000000", body));
+}
+
+#[test]
+fn backoff_doubles_up_to_one_hour() {
+    let initial = Duration::from_secs(5);
+
+    assert_eq!(backoff(initial, 1), Duration::from_secs(5));
+    assert_eq!(backoff(initial, 2), Duration::from_secs(10));
+    assert_eq!(backoff(initial, 5), Duration::from_secs(80));
+    assert_eq!(backoff(initial, 10), Duration::from_secs(2_560));
+
+    // Capped
+    assert_eq!(backoff(initial, 11), Duration::from_secs(3_600));
+    assert_eq!(backoff(initial, i32::MAX), Duration::from_secs(3_600));
+    assert_eq!(backoff(Duration::from_secs(u64::MAX), 2), Duration::from_secs(3_600));
+
+    // A first attempt, or a count that never went up, waits the initial backoff
+    assert_eq!(backoff(initial, 0), initial);
+}
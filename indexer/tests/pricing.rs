@@ -0,0 +1,97 @@
+//! Decimal-aware swap prices and USD volume.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use bigdecimal::BigDecimal;
+use carbon_omnipair_decoder::{instructions::swap_event::SwapEvent, types::EventMetadata};
+use chrono::Utc;
+use omnipair_carbon_indexer::pricing::{
+    ema_price, spot_price, usd_value, PairTokens, StaticPriceSource, SwapPricer, SwapPricing,
+};
+use solana_pubkey::Pubkey;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+/// SOL (9 decimals) priced in USDC (6 decimals)
+fn sol_usdc() -> PairTokens {
+    PairTokens {
+        token0: Pubkey::new_unique(),
+        token1: Pubkey::new_unique(),
+        token0_decimals: 9,
+        token1_decimals: 6,
+    }
+}
+
+fn swap(pair: Pubkey, is_token0_in: bool, amount_in: u64, amount_out: u64) -> SwapEvent {
+    SwapEvent {
+        // 1,000 SOL against 150,000 USDC
+        reserve0: 1_000_000_000_000,
+        reserve1: 150_000_000_000,
+        is_token0_in,
+        amount_in,
+        amount_out,
+        amount_in_after_fee: amount_in,
+        metadata: EventMetadata { signer: Pubkey::new_unique(), pair, slot: 1 },
+    }
+}
+
+#[test]
+fn prices_are_scaled_by_decimals() {
+    let tokens = sol_usdc();
+
+    assert_eq!(spot_price(1_000_000_000_000, 150_000_000_000, &tokens), Some(decimal("150")));
+    assert_eq!(spot_price(3_000_000_000, 1_000_000, &tokens), Some(decimal("0.333333333333333333")));
+    assert_eq!(spot_price(0, 150_000_000_000, &tokens), None);
+
+    // The on-chain EMA is the NAD-scaled raw ratio: 150 USDC per SOL is 0.15 raw
+    assert_eq!(ema_price(150_000_000, &tokens), Some(decimal("150")));
+}
+
+#[test]
+fn usd_values_use_whole_tokens() {
+    assert_eq!(usd_value(2_500_000_000, 9, &decimal("150.25")), decimal("375.625"));
+    assert_eq!(usd_value(1, 6, &decimal("1")), decimal("0.000001"));
+    // Truncated to micro-dollars
+    assert_eq!(usd_value(1, 9, &decimal("1")), decimal("0"));
+}
+
+#[tokio::test]
+async fn swaps_are_priced_from_the_input_token_first() {
+    let tokens = sol_usdc();
+    let pair = Pubkey::new_unique();
+    let usd_prices = HashMap::from([(tokens.token1, decimal("1"))]);
+    let pricer = SwapPricer::new("http://127.0.0.1:1".to_string(), Arc::new(StaticPriceSource::new(usd_prices)));
+    pricer.remember_pair(pair, tokens);
+
+    // USDC in: valued with the input token
+    let pricing = pricer
+        .price_swap(&swap(pair, false, 300_000_000, 1_990_000_000), Utc::now(), Some(150_000_000))
+        .await
+        .unwrap();
+    assert_eq!(
+        pricing,
+        SwapPricing {
+            price: Some(decimal("150")),
+            ema_price: Some(decimal("150")),
+            volume_usd: Some(decimal("300")),
+        }
+    );
+
+    // SOL in has no USD price: valued with the USDC received
+    let pricing = pricer.price_swap(&swap(pair, true, 2_000_000_000, 298_500_000), Utc::now(), None).await.unwrap();
+    assert_eq!(pricing.volume_usd, Some(decimal("298.5")));
+    assert_eq!(pricing.ema_price, None);
+}
+
+#[tokio::test]
+async fn swaps_without_usd_prices_have_no_volume() {
+    let pair = Pubkey::new_unique();
+    let pricer = SwapPricer::new("http://127.0.0.1:1".to_string(), Arc::new(StaticPriceSource::default()));
+    pricer.remember_pair(pair, sol_usdc());
+
+    let pricing = pricer.price_swap(&swap(pair, true, 1_000_000_000, 149_000_000), Utc::now(), None).await.unwrap();
+    assert_eq!(pricing.price, Some(decimal("150")));
+    assert_eq!(pricing.volume_usd, None);
+}
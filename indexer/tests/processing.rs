@@ -0,0 +1,626 @@
+//! Offline processing tests: fixture instructions go through the decoder and the
+//! processors, and the resulting rows are checked on an `InMemoryEventStore`.
+
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use carbon_core::{
+    instruction::{DecodedInstruction, InstructionDecoder, InstructionMetadata, NestedInstructions, MAX_INSTRUCTION_STACK_DEPTH},
+    metrics::MetricsCollection,
+    processor::Processor,
+    transaction::TransactionMetadata,
+};
+use carbon_omnipair_decoder::{
+    accounts::user_position::UserPosition,
+    instructions::OmnipairInstruction,
+    OmnipairDecoder,
+};
+use carbon_test_utils::read_instruction;
+use omnipair_carbon_indexer::{
+    checkpoints::{CheckpointProcessor, BACKFILL_DATASOURCE, LIVE_DATASOURCE},
+    event_writer::{EventWriter, WriteMode},
+    finalizer::Commitment,
+    notifier::{Notifier, NotifierConfig, SWAP},
+    pricing::{PairTokens, StaticPriceSource, SwapPricer},
+    store::{EventStore, InMemoryEventStore},
+    BlockTimeResolver, OmnipairInstructionProcessor,
+};
+use solana_instruction::Instruction;
+use solana_message::compiled_instruction::CompiledInstruction;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_transaction_status::{InnerInstruction, InnerInstructions, TransactionStatusMeta};
+
+const SLOT: u64 = 312_000_000;
+const BLOCK_TIME: i64 = 1_760_000_000;
+const WEBHOOK_URL: &str = "https://example.com/webhooks/swap";
+
+/// Accounts of the flashloan fixture
+const FLASHLOAN_PAIR: Pubkey = Pubkey::new_from_array([9; 32]);
+const FLASHLOAN_USER: Pubkey = Pubkey::new_from_array([7; 32]);
+const RECEIVER_PROGRAM: Pubkey = Pubkey::new_from_array([11; 32]);
+const TOKEN_PROGRAM: Pubkey = Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+type Input = (
+    InstructionMetadata,
+    DecodedInstruction<OmnipairInstruction>,
+    NestedInstructions,
+    solana_instruction::Instruction,
+);
+
+/// Processors of one pipeline, sharing a writer on `store`
+struct Harness {
+    instructions: OmnipairInstructionProcessor,
+    checkpoints: CheckpointProcessor,
+    writer: Arc<EventWriter>,
+    metrics: Arc<MetricsCollection>,
+}
+
+impl Harness {
+    fn new(
+        store: Arc<InMemoryEventStore>,
+        mode: WriteMode,
+        checkpoints: impl FnOnce(Arc<EventWriter>) -> CheckpointProcessor,
+    ) -> Self {
+        let endpoints = HashMap::from([(SWAP.to_string(), vec![WEBHOOK_URL.to_string()])]);
+        let notifier = Arc::new(Notifier::new(NotifierConfig {
+            endpoints,
+            ..NotifierConfig::default()
+        }));
+        // Fixture transactions carry their block time, so the RPC is never called
+        let block_times = Arc::new(BlockTimeResolver::new("http://127.0.0.1:8899".to_string()));
+        let writer = Arc::new(EventWriter::new(store, mode));
+
+        Self {
+            instructions: OmnipairInstructionProcessor::new(block_times, notifier, writer.clone()),
+            checkpoints: checkpoints(writer.clone()),
+            writer,
+            metrics: Arc::new(MetricsCollection::new(vec![])),
+        }
+    }
+
+    /// Prices swaps with `pricer`
+    fn with_pricing(self, pricer: Arc<SwapPricer>) -> Self {
+        Self { instructions: self.instructions.with_pricing(pricer), ..self }
+    }
+
+    /// Runs every instruction of a transaction through the processors in pipeline order:
+    /// the instruction processor over all instructions, then the checkpoint processor
+    async fn process(&mut self, transaction: &[Input]) {
+        self.stage(transaction).await;
+
+        for input in transaction {
+            self.checkpoints.process(input.clone(), self.metrics.clone()).await.unwrap();
+        }
+    }
+
+    /// Only runs the instruction processor, as when a later pipe fails the transaction
+    async fn stage(&mut self, transaction: &[Input]) {
+        for input in transaction {
+            self.instructions.process(input.clone(), self.metrics.clone()).await.unwrap();
+        }
+    }
+}
+
+/// Decodes the fixture instructions into the inputs of one transaction
+fn fixture_transaction(signature_byte: u8, fixtures: &[&str]) -> Vec<Input> {
+    fixture_transaction_at(SLOT, signature_byte, fixtures)
+}
+
+/// Same as `fixture_transaction`, for a transaction landed at `slot`
+fn fixture_transaction_at(slot: u64, signature_byte: u8, fixtures: &[&str]) -> Vec<Input> {
+    let transaction_metadata = Arc::new(TransactionMetadata {
+        slot,
+        signature: Signature::from([signature_byte; 64]),
+        block_time: Some(BLOCK_TIME),
+        ..TransactionMetadata::default()
+    });
+
+    fixtures
+        .iter()
+        .enumerate()
+        .map(|(index, fixture)| {
+            let instruction = read_fixture(fixture);
+            let decoded = OmnipairDecoder.decode_instruction(&instruction).expect("decode fixture");
+            let metadata = InstructionMetadata {
+                transaction_metadata: transaction_metadata.clone(),
+                stack_height: 1,
+                index: index as u32,
+                absolute_path: vec![index as u8],
+            };
+
+            (metadata, decoded, NestedInstructions::default(), instruction)
+        })
+        .collect()
+}
+
+fn signature(signature_byte: u8) -> String {
+    Signature::from([signature_byte; 64]).to_string()
+}
+
+fn read_fixture(fixture: &str) -> Instruction {
+    read_instruction(format!("tests/fixtures/{}.json", fixture)).expect("read fixture")
+}
+
+fn program_instruction(program_id: Pubkey, data: &[u8]) -> Instruction {
+    Instruction { program_id, accounts: vec![], data: data.to_vec() }
+}
+
+/// A transaction with the `flashloan` fixture as its only instruction, invoking `inner`
+/// (stack height and instruction, in execution order) nested as the pipeline does
+fn flashloan_transaction(signature_byte: u8, inner: Vec<(u32, Instruction)>) -> Vec<Input> {
+    let inner_instructions = InnerInstructions {
+        index: 0,
+        instructions: inner
+            .iter()
+            .map(|(stack_height, _)| InnerInstruction {
+                instruction: CompiledInstruction { program_id_index: 0, accounts: vec![], data: vec![] },
+                stack_height: Some(*stack_height),
+            })
+            .collect(),
+    };
+    let transaction_metadata = Arc::new(TransactionMetadata {
+        slot: SLOT,
+        signature: Signature::from([signature_byte; 64]),
+        meta: TransactionStatusMeta { inner_instructions: Some(vec![inner_instructions]), ..TransactionStatusMeta::default() },
+        block_time: Some(BLOCK_TIME),
+        ..TransactionMetadata::default()
+    });
+    let metadata = |stack_height: u32, absolute_path: &[u8]| InstructionMetadata {
+        transaction_metadata: transaction_metadata.clone(),
+        stack_height,
+        index: 0,
+        absolute_path: absolute_path.to_vec(),
+    };
+
+    let mut instructions = vec![(metadata(1, &[0]), read_fixture("flashloan"))];
+    let mut path = [0u8; MAX_INSTRUCTION_STACK_DEPTH];
+    let mut previous_height = 1;
+    for (stack_height, instruction) in inner {
+        let height = stack_height as usize;
+        if height > previous_height {
+            path[height - 1] = 0;
+        } else {
+            path[height - 1] += 1;
+        }
+        previous_height = height;
+        instructions.push((metadata(stack_height, &path[..height]), instruction));
+    }
+
+    let flashloan = NestedInstructions::from(instructions).into_iter().next().unwrap();
+    let decoded = OmnipairDecoder.decode_instruction(&flashloan.instruction).expect("decode fixture");
+
+    vec![(flashloan.metadata, decoded, flashloan.inner_instructions, flashloan.instruction)]
+}
+
+#[tokio::test]
+async fn processes_fixture_transaction_into_store() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+
+    harness.process(&fixture_transaction(1, &["swap_event", "update_pair_event"])).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.batches_written, 1);
+
+    assert_eq!(tables.swaps.len(), 1);
+    let (swap, ctx) = tables.swaps.values().next().unwrap();
+    assert!(swap.is_token0_in);
+    assert_eq!(swap.amount_in, 1_000_000);
+    assert_eq!(swap.amount_out, 1_990_000);
+    assert_eq!(swap.amount_in_after_fee, 997_000);
+    assert_eq!(ctx.signature, signature(1));
+    assert_eq!(ctx.slot, SLOT as i64);
+    assert_eq!((ctx.instruction_index, ctx.inner_instruction_index), (0, 0));
+    assert_eq!(ctx.timestamp.timestamp(), BLOCK_TIME);
+
+    assert_eq!(tables.pair_updates.len(), 1);
+    let (update, ctx) = tables.pair_updates.values().next().unwrap();
+    assert_eq!(update.accrued_interest0, 42);
+    assert_eq!(update.reserve1_after_interest, 2_000_000_017);
+    assert_eq!(ctx.instruction_index, 1);
+
+    assert_eq!(tables.outbox.len(), 1);
+    let entry = tables.outbox.values().next().unwrap();
+    assert_eq!(entry.endpoint, WEBHOOK_URL);
+    assert_eq!(entry.payload["amount_in"], "1000000");
+    assert_eq!(entry.payload["signature"], signature(1).as_str());
+
+    let processed = &tables.processed_transactions[&signature(1)];
+    assert_eq!(processed.datasource, BACKFILL_DATASOURCE);
+    assert!(tables.checkpoints.is_empty());
+}
+
+#[tokio::test]
+async fn swaps_that_cannot_be_priced_are_stored_without_price() {
+    let store = Arc::new(InMemoryEventStore::new());
+    // Nothing listens on the RPC, so the pair tokens cannot be fetched
+    let pricer = Arc::new(SwapPricer::new("http://127.0.0.1:1".to_string(), Arc::new(StaticPriceSource::default())));
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    })
+    .with_pricing(pricer.clone());
+
+    harness.process(&fixture_transaction(1, &["swap_event"])).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.swaps.len(), 1);
+    assert!(tables.swap_pricing.is_empty());
+    assert!(tables.processed_transactions.contains_key(&signature(1)));
+
+    // Once the pair is known, e.g. from its account update, its swaps are priced
+    let (swap, _) = tables.swaps.values().next().unwrap();
+    pricer.remember_pair(
+        swap.metadata.pair,
+        PairTokens {
+            token0: Pubkey::new_unique(),
+            token1: Pubkey::new_unique(),
+            token0_decimals: 6,
+            token1_decimals: 6,
+        },
+    );
+    harness.process(&fixture_transaction(2, &["swap_event"])).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.swaps.len(), 2);
+    assert_eq!(tables.swap_pricing.len(), 1);
+    let (pricing, ctx) = tables.swap_pricing.values().next().unwrap();
+    assert_eq!(ctx.signature, signature(2));
+    assert!(pricing.price.is_some());
+}
+
+#[tokio::test]
+async fn retried_transactions_are_staged_from_scratch() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+
+    // A later pipe failed the whole transaction, then it is retried
+    let single = fixture_transaction(1, &["swap_event"]);
+    harness.stage(&single).await;
+    harness.process(&single).await;
+
+    // Processing stopped after the first instruction, then the transaction is retried
+    let transaction = fixture_transaction(2, &["swap_event", "mint_event"]);
+    harness.stage(&transaction[..1]).await;
+    harness.process(&transaction).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.batches_written, 2);
+    assert_eq!(tables.swaps.len(), 2);
+    assert_eq!(tables.mints.len(), 1);
+    assert_eq!(tables.outbox.len(), 2);
+    assert!(tables.processed_transactions.contains_key(&signature(1)));
+    assert!(tables.processed_transactions.contains_key(&signature(2)));
+}
+
+#[tokio::test]
+async fn reprocessing_a_transaction_is_idempotent() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+    let transaction = fixture_transaction(1, &["swap_event", "mint_event"]);
+
+    harness.process(&transaction).await;
+    // A second processor sees the same transaction again, e.g. a gap fill after a restart
+    let mut replay = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+    replay.process(&transaction).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.batches_written, 2);
+    assert_eq!(tables.swaps.len(), 1);
+    assert_eq!(tables.mints.len(), 1);
+    assert_eq!(tables.outbox.len(), 1);
+    assert_eq!(tables.processed_transactions.len(), 1);
+}
+
+#[tokio::test]
+async fn uncommitted_transaction_is_not_written() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+
+    harness.stage(&fixture_transaction(1, &["swap_event", "update_pair_event"])).await;
+    harness.process(&fixture_transaction(2, &["mint_event"])).await;
+
+    let tables = store.snapshot();
+    assert!(tables.swaps.is_empty());
+    assert!(tables.pair_updates.is_empty());
+    assert_eq!(tables.mints.len(), 1);
+    assert_eq!(tables.mints.values().next().unwrap().1.signature, signature(2));
+    assert!(!tables.processed_transactions.contains_key(&signature(1)));
+
+    let unprocessed = store
+        .filter_unprocessed_signatures(&[signature(1), signature(2)])
+        .await
+        .unwrap();
+    assert_eq!(unprocessed, vec![signature(1)]);
+}
+
+#[tokio::test]
+async fn batched_writer_writes_transactions_together() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mode = WriteMode::Batched {
+        max_transactions: 2,
+        max_delay: Duration::from_secs(3600),
+    };
+    let mut harness = Harness::new(store.clone(), mode, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+
+    harness.process(&fixture_transaction(1, &["swap_event"])).await;
+    assert_eq!(store.snapshot().batches_written, 0);
+
+    harness.process(&fixture_transaction(2, &["mint_event"])).await;
+    harness.process(&fixture_transaction(3, &["update_pair_event"])).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.batches_written, 1);
+    assert_eq!(tables.processed_transactions.len(), 2);
+    assert!(tables.pair_updates.is_empty());
+
+    harness.writer.flush().await.unwrap();
+
+    let tables = store.snapshot();
+    assert_eq!(tables.batches_written, 2);
+    assert_eq!(tables.processed_transactions.len(), 3);
+    assert_eq!(tables.pair_updates.len(), 1);
+}
+
+#[tokio::test]
+async fn live_checkpoint_advances_once_caught_up() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let caught_up = Arc::new(AtomicBool::new(false));
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::new(writer, LIVE_DATASOURCE, caught_up.clone())
+    });
+
+    harness.process(&fixture_transaction(1, &["swap_event"])).await;
+    assert_eq!(store.get_ingestion_checkpoint(LIVE_DATASOURCE).await.unwrap(), None);
+
+    caught_up.store(true, std::sync::atomic::Ordering::Release);
+    harness.process(&fixture_transaction(2, &["swap_event"])).await;
+
+    assert_eq!(
+        store.get_ingestion_checkpoint(LIVE_DATASOURCE).await.unwrap(),
+        Some((SLOT as i64, signature(2)))
+    );
+    assert_eq!(store.snapshot().processed_transactions.len(), 2);
+}
+
+#[tokio::test]
+async fn older_slots_do_not_overwrite_latest_positions() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+    let fixtures = ["user_position_updated_event", "user_liquidity_position_updated_event"];
+
+    // The live stream writes the newer slot, then a catch-up crawl reaches an older one
+    harness.process(&fixture_transaction_at(SLOT + 10, 1, &fixtures)).await;
+    harness.process(&fixture_transaction_at(SLOT, 2, &fixtures)).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.user_position_updates.len(), 2);
+    assert_eq!(tables.user_liquidity_position_updates.len(), 2);
+
+    assert_eq!(tables.user_borrow_positions.len(), 1);
+    let (position, ctx) = tables.user_borrow_positions.values().next().unwrap();
+    assert_eq!(position.collateral0, 2_000_000);
+    assert_eq!((ctx.slot, ctx.signature.clone()), ((SLOT + 10) as i64, signature(1)));
+
+    assert_eq!(tables.user_liquidity_positions.len(), 1);
+    let (position, ctx) = tables.user_liquidity_positions.values().next().unwrap();
+    assert_eq!(position.lp_amount, 1_414_213);
+    assert_eq!((ctx.slot, ctx.signature.clone()), ((SLOT + 10) as i64, signature(1)));
+
+    // The same slot replaces the row
+    harness.process(&fixture_transaction_at(SLOT + 10, 3, &fixtures)).await;
+    let tables = store.snapshot();
+    assert_eq!(tables.user_borrow_positions.values().next().unwrap().1.signature, signature(3));
+}
+
+#[tokio::test]
+async fn rolled_back_slots_fall_back_to_surviving_positions() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+    let fixtures = ["user_position_updated_event", "user_liquidity_position_updated_event"];
+    let (slot, skipped_slot) = (SLOT as i64, (SLOT + 10) as i64);
+
+    harness.process(&fixture_transaction_at(SLOT, 1, &fixtures)).await;
+    harness.process(&fixture_transaction_at(SLOT + 10, 2, &fixtures)).await;
+
+    let position_account = Pubkey::new_from_array([5; 32]);
+    let position = UserPosition {
+        owner: Pubkey::new_from_array([7; 32]),
+        pair: Pubkey::new_from_array([9; 32]),
+        collateral0_liquidation_cf_bps: 8_500,
+        collateral1_liquidation_cf_bps: 8_500,
+        collateral0: 2_000_000,
+        collateral1: 0,
+        debt0_shares: 750_000,
+        debt1_shares: 0,
+        bump: 255,
+    };
+    store.upsert_user_position_account(&position_account, &position, skipped_slot).await.unwrap();
+
+    assert_eq!(store.oldest_pending_slot(0).await.unwrap(), Some(slot));
+    assert_eq!(store.oldest_pending_slot(slot + 1).await.unwrap(), Some(skipped_slot));
+
+    // Only the first slot was rooted
+    let settlement = store.settle_slots(slot, skipped_slot, Some(&[slot])).await.unwrap();
+
+    // Two events and the processed transaction at the first slot; two events, two latest
+    // positions, the processed transaction and the account at the skipped one
+    assert_eq!(settlement.finalized, 3);
+    assert_eq!(settlement.rolled_back, 6);
+    assert_eq!(settlement.rederived, 2);
+    assert_eq!(settlement.removed, 0);
+    assert_eq!(settlement.rolled_back_accounts, vec![position_account]);
+
+    let tables = store.snapshot();
+    assert_eq!(tables.commitment(slot), Commitment::Finalized);
+    assert_eq!(tables.commitment(skipped_slot), Commitment::RolledBack);
+    assert_eq!(store.oldest_pending_slot(0).await.unwrap(), None);
+
+    // The latest positions are back to the surviving events
+    let (_, ctx) = tables.user_borrow_positions.values().next().unwrap();
+    assert_eq!((ctx.slot, ctx.signature.clone()), (slot, signature(1)));
+    let (_, ctx) = tables.user_liquidity_positions.values().next().unwrap();
+    assert_eq!((ctx.slot, ctx.signature.clone()), (slot, signature(1)));
+
+    // Event rows stay, rolled back
+    assert_eq!(tables.user_position_updates.len(), 2);
+
+    // Settled slots are never settled again
+    assert_eq!(store.settle_slots(slot, skipped_slot, None).await.unwrap().finalized, 0);
+
+    // Positions without a surviving event are removed
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+    harness.process(&fixture_transaction_at(SLOT + 10, 2, &fixtures)).await;
+
+    let settlement = store.settle_slots(slot, skipped_slot, Some(&[])).await.unwrap();
+    assert_eq!(settlement.finalized, 0);
+    assert_eq!((settlement.rederived, settlement.removed), (0, 2));
+
+    let tables = store.snapshot();
+    assert!(tables.user_borrow_positions.is_empty());
+    assert!(tables.user_liquidity_positions.is_empty());
+}
+
+#[tokio::test]
+async fn rolled_back_transactions_are_indexed_again_when_they_land() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+    let fixtures = ["swap_event"];
+    let (slot, landed_slot) = (SLOT as i64, (SLOT + 5) as i64);
+
+    harness.process(&fixture_transaction_at(SLOT, 1, &fixtures)).await;
+    store.settle_slots(slot, slot, Some(&[])).await.unwrap();
+
+    // A gap fill no longer skips the rolled back transaction
+    let unprocessed = store.filter_unprocessed_signatures(&[signature(1)]).await.unwrap();
+    assert_eq!(unprocessed, vec![signature(1)]);
+
+    // Landed again in a later slot
+    harness.process(&fixture_transaction_at(SLOT + 5, 1, &fixtures)).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.processed_transactions[&signature(1)].slot, landed_slot);
+    assert_eq!(tables.commitment(landed_slot), Commitment::Confirmed);
+    let (_, ctx) = tables.swaps.values().next().unwrap();
+    assert_eq!(ctx.slot, landed_slot);
+    assert!(store.filter_unprocessed_signatures(&[signature(1)]).await.unwrap().is_empty());
+    assert_eq!(store.oldest_pending_slot(0).await.unwrap(), Some(landed_slot));
+}
+
+#[tokio::test]
+async fn flashloans_take_amounts_and_fees_from_their_event() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+
+    let inner = vec![
+        (2, program_instruction(TOKEN_PROGRAM, &[3])),
+        (2, program_instruction(RECEIVER_PROGRAM, &[1, 2, 3])),
+        (2, read_fixture("flashloan_event")),
+    ];
+    harness.process(&flashloan_transaction(1, inner)).await;
+
+    let tables = store.snapshot();
+    assert_eq!(tables.flashloans.len(), 1);
+    let (flashloan, ctx) = tables.flashloans.values().next().unwrap();
+    assert_eq!((flashloan.pair, flashloan.signer), (FLASHLOAN_PAIR, FLASHLOAN_USER));
+    assert_eq!((flashloan.receiver, flashloan.receiver_program), (RECEIVER_PROGRAM, RECEIVER_PROGRAM));
+    assert_eq!((flashloan.amount0, flashloan.amount1), (5_000_000, 0));
+    assert_eq!((flashloan.fee0, flashloan.fee1), (Some(4_500), Some(0)));
+    assert_eq!((ctx.instruction_index, ctx.inner_instruction_index), (0, 0));
+
+    // Only the receiver's instructions, not the loan transfer or the event
+    assert_eq!(flashloan.receiver_instructions.len(), 1);
+    let receiver = &flashloan.receiver_instructions[0];
+    assert_eq!(receiver.program_id, RECEIVER_PROGRAM.to_string());
+    assert_eq!((receiver.inner_instruction_index, receiver.stack_height), (2, 2));
+    assert_eq!(receiver.data, bs58::encode([1, 2, 3]).into_string());
+}
+
+#[tokio::test]
+async fn flashloans_without_event_have_no_fees() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+
+    let inner = vec![
+        (2, program_instruction(TOKEN_PROGRAM, &[3])),
+        (2, program_instruction(RECEIVER_PROGRAM, &[1, 2, 3])),
+    ];
+    harness.process(&flashloan_transaction(1, inner)).await;
+
+    let tables = store.snapshot();
+    let (flashloan, _) = tables.flashloans.values().next().unwrap();
+    // Amounts of the instruction args, the receiver program as the receiver
+    assert_eq!((flashloan.pair, flashloan.signer), (FLASHLOAN_PAIR, FLASHLOAN_USER));
+    assert_eq!((flashloan.receiver, flashloan.receiver_program), (RECEIVER_PROGRAM, RECEIVER_PROGRAM));
+    assert_eq!((flashloan.amount0, flashloan.amount1), (5_000_000, 0));
+    assert_eq!((flashloan.fee0, flashloan.fee1), (None, None));
+    assert_eq!(flashloan.receiver_instructions.len(), 1);
+}
+
+#[tokio::test]
+async fn flashloans_record_everything_the_receiver_invokes() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let mut harness = Harness::new(store.clone(), WriteMode::PerTransaction, |writer| {
+        CheckpointProcessor::without_checkpoint(writer, BACKFILL_DATASOURCE)
+    });
+    let dex_program = Pubkey::new_from_array([12; 32]);
+
+    // Loan transfer, then the receiver swapping on a DEX and repaying, then the event
+    let inner = vec![
+        (2, program_instruction(TOKEN_PROGRAM, &[3])),
+        (2, program_instruction(RECEIVER_PROGRAM, &[1])),
+        (3, program_instruction(dex_program, &[2])),
+        (4, program_instruction(TOKEN_PROGRAM, &[3, 1])),
+        (3, program_instruction(TOKEN_PROGRAM, &[3, 2])),
+        (2, read_fixture("flashloan_event")),
+    ];
+    harness.process(&flashloan_transaction(1, inner)).await;
+
+    let tables = store.snapshot();
+    let (flashloan, _) = tables.flashloans.values().next().unwrap();
+    assert_eq!((flashloan.fee0, flashloan.fee1), (Some(4_500), Some(0)));
+
+    let receiver_instructions: Vec<_> = flashloan
+        .receiver_instructions
+        .iter()
+        .map(|instruction| (instruction.inner_instruction_index, instruction.stack_height, instruction.program_id.clone()))
+        .collect();
+    assert_eq!(
+        receiver_instructions,
+        vec![
+            (2, 2, RECEIVER_PROGRAM.to_string()),
+            (3, 3, dex_program.to_string()),
+            (4, 4, TOKEN_PROGRAM.to_string()),
+            (5, 3, TOKEN_PROGRAM.to_string()),
+        ]
+    );
+    assert_eq!(flashloan.receiver_instructions[3].data, bs58::encode([3, 2]).into_string());
+}
@@ -0,0 +1,144 @@
+//! Transaction selection of the `reindex` command and the rows its dry runs print.
+
+use std::collections::HashMap;
+
+use carbon_omnipair_decoder::{instructions::swap_event::SwapEvent, types::EventMetadata};
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use omnipair_carbon_indexer::{
+    config::Command,
+    event_context::EventContext,
+    reindex::{collect_signatures, read_signatures_file, ReindexArgs},
+    row_diff::{changed_rows, same_value, staged_tables, ColumnChange, RowChange},
+    store::InMemoryTables,
+    Args,
+};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+
+fn reindex_args(argv: &[&str]) -> Result<ReindexArgs, clap::Error> {
+    let mut full = vec!["omnipair-indexer", "reindex"];
+    full.extend_from_slice(argv);
+
+    match Args::try_parse_from(full)?.command {
+        Some(Command::Reindex(args)) => Ok(args),
+        command => panic!("expected the reindex command, got {:?}", command),
+    }
+}
+
+#[test]
+fn requires_a_transaction_selection() {
+    assert!(reindex_args(&[]).is_err());
+    assert!(reindex_args(&["--dry-run"]).is_err());
+
+    // A pair needs both ends of its slot range
+    assert!(reindex_args(&["--pair", "11111111111111111111111111111111", "--start-slot", "1"]).is_err());
+    assert!(reindex_args(&["--start-slot", "1", "--end-slot", "2"]).is_err());
+
+    let args = reindex_args(&[
+        "--pair",
+        "11111111111111111111111111111111",
+        "--start-slot",
+        "1",
+        "--end-slot",
+        "2",
+        "--dry-run",
+    ])
+    .unwrap();
+    assert_eq!(args.start_slot, Some(1));
+    assert!(args.dry_run);
+}
+
+#[tokio::test]
+async fn combines_listed_signatures_once() {
+    let first = Signature::from([1; 64]);
+    let second = Signature::from([2; 64]);
+    let path = std::env::temp_dir().join(format!("omnipair-reindex-{}.txt", std::process::id()));
+    std::fs::write(&path, format!("# after the processor fix\n{}\n\n  {}  \n", second, first)).unwrap();
+
+    assert_eq!(read_signatures_file(&path).unwrap(), vec![second, first]);
+
+    let first_string = first.to_string();
+    let path_string = path.to_str().unwrap().to_string();
+    let args = reindex_args(&["--signature", &first_string, "--signatures-file", &path_string]).unwrap();
+    // Without a pair range the RPC is never called
+    let signatures = collect_signatures("http://127.0.0.1:8899", &args).await.unwrap();
+    assert_eq!(signatures, vec![first, second]);
+
+    std::fs::remove_file(&path).unwrap();
+
+    let args = reindex_args(&["--signature", "not-a-signature"]).unwrap();
+    assert!(collect_signatures("http://127.0.0.1:8899", &args).await.is_err());
+}
+
+#[test]
+fn dry_runs_only_print_new_and_changed_rows() {
+    let ctx = |signature_byte: u8| EventContext {
+        signature: Signature::from([signature_byte; 64]).to_string(),
+        slot: 312_000_000,
+        instruction_index: 2,
+        inner_instruction_index: 1,
+        timestamp: Utc.timestamp_opt(1_760_000_000, 0).unwrap(),
+    };
+    let swap = SwapEvent {
+        reserve0: 1_000_000_000,
+        reserve1: 2_000_000_000,
+        is_token0_in: true,
+        amount_in: 1_000_000,
+        amount_out: 1_990_000,
+        amount_in_after_fee: 997_000,
+        metadata: EventMetadata { signer: Pubkey::new_unique(), pair: Pubkey::new_unique(), slot: 312_000_000 },
+    };
+
+    let mut tables = InMemoryTables::default();
+    for signature_byte in [1, 2, 3] {
+        let ctx = ctx(signature_byte);
+        tables
+            .swaps
+            .insert((ctx.signature.clone(), ctx.instruction_index, ctx.inner_instruction_index), (swap.clone(), ctx));
+    }
+
+    let staged = staged_tables(&tables, None);
+    let swaps = staged.iter().find(|table| table.table == "swaps").unwrap();
+    let [unchanged, changed, new] = [&swaps.rows[0], &swaps.rows[1], &swaps.rows[2]];
+    assert_eq!(unchanged.key, format!("{}#2.1", ctx(1).signature));
+
+    // Stored values as Postgres renders them: numerics with a scale, epochs with fractions
+    let stored_values = |amount_out: &str| {
+        let mut values = unchanged.values.clone();
+        let column = |name: &str| swaps.columns.iter().position(|(column, _)| *column == name).unwrap();
+        values[column("amount_out")] = Some(amount_out.to_string());
+        values[column("timestamp")] = Some("1760000000.000000".to_string());
+        values[column("fee_paid0")] = Some("3000.000000000".to_string());
+        values
+    };
+    let stored = HashMap::from([
+        (unchanged.key.clone(), stored_values("1990000")),
+        (changed.key.clone(), stored_values("1990001")),
+    ]);
+
+    assert_eq!(
+        changed_rows(swaps, &stored),
+        vec![
+            RowChange::Update(
+                changed,
+                vec![ColumnChange {
+                    column: "amount_out",
+                    stored: Some("1990001".to_string()),
+                    staged: Some("1990000".to_string()),
+                }]
+            ),
+            RowChange::Insert(new),
+        ]
+    );
+}
+
+#[test]
+fn compares_numbers_by_value_and_json_by_content() {
+    assert!(same_value(Some("150.500000"), Some("150.5")));
+    assert!(!same_value(Some("150.5"), Some("150.51")));
+    assert!(same_value(None, None));
+    assert!(!same_value(None, Some("0")));
+    assert!(same_value(Some(r#"[{"data": "ab", "stack_height": 2}]"#), Some(r#"[{"stack_height":2,"data":"ab"}]"#)));
+    assert!(!same_value(Some("11111111111111111111111111111111"), Some("So11111111111111111111111111111111111111112")));
+}